@@ -0,0 +1,95 @@
+/**
+ * Analogy module holds the concept mappings that explain one domain through
+ * another (e.g., the immune system as a castle's defenses), plus the JSON
+ * schema the output is checked against.
+ */
+
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+/**
+ * A concept in the source domain paired with its counterpart in the target
+ * domain, and why they correspond.
+ */
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct AnalogyMapping {
+    pub source_concept: String,
+    pub target_concept: String,
+    pub explanation: String,
+}
+
+/**
+ * JSON schema analogy output must match.
+ */
+pub fn analogy_schema() -> Value {
+    json!({
+        "type": "object",
+        "properties": {
+            "mappings": {
+                "type": "array",
+                "minItems": 1,
+                "items": {
+                    "type": "object",
+                    "properties": {
+                        "source_concept": { "type": "string", "minLength": 1 },
+                        "target_concept": { "type": "string", "minLength": 1 },
+                        "explanation": { "type": "string", "minLength": 1 }
+                    },
+                    "required": ["source_concept", "target_concept", "explanation"]
+                }
+            }
+        },
+        "required": ["mappings"]
+    })
+}
+
+/**
+ * Trims mappings and drops those with a blank concept or explanation.
+ */
+pub fn cleaned(mappings: Vec<AnalogyMapping>) -> Vec<AnalogyMapping> {
+    mappings
+        .into_iter()
+        .map(|mapping| AnalogyMapping {
+            source_concept: mapping.source_concept.trim().to_string(),
+            target_concept: mapping.target_concept.trim().to_string(),
+            explanation: mapping.explanation.trim().to_string(),
+        })
+        .filter(|mapping| {
+            !mapping.source_concept.is_empty() && !mapping.target_concept.is_empty() && !mapping.explanation.is_empty()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{analogy_schema, cleaned, AnalogyMapping};
+    use crate::extract::conforms;
+    use serde_json::json;
+
+    fn mapping(source: &str, target: &str, explanation: &str) -> AnalogyMapping {
+        AnalogyMapping {
+            source_concept: source.to_string(),
+            target_concept: target.to_string(),
+            explanation: explanation.to_string(),
+        }
+    }
+
+    #[test]
+    fn schema_requires_every_field() {
+        let schema = analogy_schema();
+        let valid = json!({ "source_concept": "Antibody", "target_concept": "Guard", "explanation": "Both identify intruders." });
+
+        assert!(conforms(&schema, &json!({ "mappings": [valid] })));
+        assert!(!conforms(&schema, &json!({ "mappings": [] })));
+        assert!(!conforms(&schema, &json!({ "mappings": [{ "source_concept": "Antibody", "target_concept": "Guard" }] })));
+    }
+
+    #[test]
+    fn drops_mappings_with_blank_fields() {
+        let mappings = vec![
+            mapping(" Antibody ", "Guard", " Both identify intruders. "),
+            mapping("Fever", " ", "Raises the cost of staying."),
+        ];
+        assert_eq!(cleaned(mappings), vec![mapping("Antibody", "Guard", "Both identify intruders.")]);
+    }
+}
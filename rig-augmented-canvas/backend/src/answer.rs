@@ -0,0 +1,119 @@
+/**
+ * Answer module holds the answers to generated questions, taken strictly
+ * from the note they were asked about, plus the JSON schema the output is
+ * checked against. Answers are returned in question order, so a self-quiz
+ * can pair each question with its answer by position.
+ */
+
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+/// Answer given when the content doesn't answer a question.
+pub const NOT_STATED: &str = "Not stated in the content";
+
+/**
+ * The answer to one numbered question, as returned by the model.
+ */
+#[derive(Deserialize, Debug, Clone)]
+pub struct NumberedAnswer {
+    pub number: usize,
+    pub answer: String,
+}
+
+/**
+ * JSON schema answer output must match.
+ */
+pub fn answers_schema() -> Value {
+    json!({
+        "type": "object",
+        "properties": {
+            "answers": {
+                "type": "array",
+                "items": {
+                    "type": "object",
+                    "properties": {
+                        "number": { "type": "integer", "minimum": 1 },
+                        "answer": { "type": "string" }
+                    },
+                    "required": ["number", "answer"]
+                }
+            }
+        },
+        "required": ["answers"]
+    })
+}
+
+/**
+ * Lists the questions numbered from 1, one per line, for the prompt.
+ *
+ * @param questions The questions to answer
+ * @return The numbered list
+ */
+pub fn numbered(questions: &[String]) -> String {
+    questions
+        .iter()
+        .enumerate()
+        .map(|(index, question)| format!("{}. {}", index + 1, question.trim()))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/**
+ * Puts the model's answers in question order. Questions the model skipped
+ * or left blank are answered with `NOT_STATED`; answers to numbers that
+ * weren't asked are dropped, and only the first non-blank answer to a
+ * number counts.
+ *
+ * @param answers The answers returned by the model
+ * @param count The number of questions asked
+ * @return One answer per question, in question order
+ */
+pub fn aligned(answers: Vec<NumberedAnswer>, count: usize) -> Vec<String> {
+    let mut aligned: Vec<Option<String>> = vec![None; count];
+    for answer in answers {
+        let text = answer.answer.trim();
+        if let Some(slot @ None) = answer.number.checked_sub(1).and_then(|index| aligned.get_mut(index)) {
+            if !text.is_empty() {
+                *slot = Some(text.to_string());
+            }
+        }
+    }
+    aligned
+        .into_iter()
+        .map(|answer| answer.unwrap_or_else(|| NOT_STATED.to_string()))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{aligned, numbered, NumberedAnswer, NOT_STATED};
+
+    fn answer(number: usize, answer: &str) -> NumberedAnswer {
+        NumberedAnswer {
+            number,
+            answer: answer.to_string(),
+        }
+    }
+
+    #[test]
+    fn numbers_questions_from_one() {
+        let questions = ["What is ownership? ".to_string(), "What is borrowing?".to_string()];
+        assert_eq!(numbered(&questions), "1. What is ownership?\n2. What is borrowing?");
+    }
+
+    #[test]
+    fn aligns_answers_with_questions() {
+        let answers = vec![
+            answer(3, "Lifetimes bound references."),
+            answer(1, " Each value has one owner. "),
+            answer(1, "A later duplicate."),
+            answer(0, "Not a question number."),
+            answer(7, "Out of range."),
+        ];
+        assert_eq!(
+            aligned(answers, 4),
+            vec!["Each value has one owner.", NOT_STATED, "Lifetimes bound references.", NOT_STATED]
+        );
+        assert!(aligned(Vec::new(), 0).is_empty());
+    }
+}
@@ -0,0 +1,164 @@
+/**
+ * API error module defines the body every endpoint returns on failure,
+ * `{ "code": ..., "message": ... }`, sent with a 4xx/5xx status. Clients
+ * branch on `code`, which is stable, rather than on the human-readable
+ * `message`.
+ */
+
+use axum::{
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::Serialize;
+use std::error::Error;
+
+use crate::error::RigError;
+use crate::telemetry;
+
+/**
+ * Error body shared by all endpoints. `details` lists the individual
+ * problems when there are several (e.g., schema violations).
+ */
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct ErrorResponse {
+    pub code: String,
+    pub message: String,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub details: Vec<String>,
+}
+
+/**
+ * An error response and the status it is sent with.
+ */
+#[derive(Debug)]
+pub struct ApiError {
+    pub status: StatusCode,
+    pub body: ErrorResponse,
+}
+
+impl ApiError {
+    pub fn new(status: StatusCode, code: &str, message: impl Into<String>) -> Self {
+        Self {
+            status,
+            body: ErrorResponse {
+                code: code.to_string(),
+                message: message.into(),
+                details: Vec::new(),
+            },
+        }
+    }
+
+    /**
+     * A `400 Bad Request` for input the handler rejected before doing any work.
+     */
+    pub fn bad_request(message: impl Into<String>) -> Self {
+        Self::new(StatusCode::BAD_REQUEST, "bad_request", message)
+    }
+
+    /**
+     * Maps a service error to its status and code. Errors that aren't a
+     * `RigError` are internal failures.
+     *
+     * @param error The error returned by the service
+     * @return The response to send
+     */
+    pub fn from_error(error: &(dyn Error + 'static)) -> Self {
+        let message = error.to_string();
+        let Some(error) = error.downcast_ref::<RigError>() else {
+            return Self::new(StatusCode::INTERNAL_SERVER_ERROR, "internal_error", message);
+        };
+        let (status, code) = match error {
+            RigError::MissingApiKey { .. } => (StatusCode::UNAUTHORIZED, "missing_api_key"),
+            RigError::RateLimited { .. } => (StatusCode::TOO_MANY_REQUESTS, "rate_limited"),
+            RigError::Timeout { .. } => (StatusCode::GATEWAY_TIMEOUT, "timeout"),
+            RigError::ParseError { .. } => (StatusCode::BAD_GATEWAY, "parse_error"),
+            RigError::ProviderError { .. } => (StatusCode::BAD_GATEWAY, "provider_error"),
+            RigError::ProviderUnreachable { .. } => (StatusCode::SERVICE_UNAVAILABLE, "provider_unreachable"),
+            RigError::Blocked { .. } => (StatusCode::FORBIDDEN, "blocked"),
+            RigError::InvalidSchema { .. } => (StatusCode::BAD_REQUEST, "invalid_schema"),
+            RigError::SchemaValidation { .. } => (StatusCode::UNPROCESSABLE_ENTITY, "schema_validation"),
+            RigError::EmbeddingsUnsupported { .. } => (StatusCode::NOT_IMPLEMENTED, "embeddings_unsupported"),
+            RigError::InputTooLarge { .. } => (StatusCode::PAYLOAD_TOO_LARGE, "input_too_large"),
+        };
+        let mut api_error = Self::new(status, code, message);
+        if let RigError::SchemaValidation { errors } = error {
+            api_error.body.details = errors.clone();
+        }
+        api_error
+    }
+
+    /**
+     * Replaces the message with a user-facing fallback, keeping the
+     * original message in `details` for logs.
+     *
+     * @param fallback The message to show instead, if any
+     * @return The error, with the fallback applied
+     */
+    pub fn with_fallback(mut self, fallback: Option<String>) -> Self {
+        if let Some(fallback) = fallback {
+            let message = std::mem::replace(&mut self.body.message, fallback);
+            self.body.details.push(message);
+        }
+        self
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        telemetry::record_error(&self.body.code);
+        (self.status, Json(self.body)).into_response()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ApiError;
+    use crate::error::RigError;
+    use axum::http::StatusCode;
+    use std::error::Error;
+
+    fn mapped(error: Box<dyn Error>) -> (StatusCode, String) {
+        let api_error = ApiError::from_error(error.as_ref());
+        (api_error.status, api_error.body.code)
+    }
+
+    #[test]
+    fn maps_errors_to_status_and_code() {
+        let cases = [
+            (RigError::MissingApiKey { env_var: None }, StatusCode::UNAUTHORIZED, "missing_api_key"),
+            (RigError::RateLimited { message: "slow down".to_string() }, StatusCode::TOO_MANY_REQUESTS, "rate_limited"),
+            (RigError::Timeout { secs: 60 }, StatusCode::GATEWAY_TIMEOUT, "timeout"),
+            (RigError::ParseError { message: "bad JSON".to_string() }, StatusCode::BAD_GATEWAY, "parse_error"),
+        ];
+        for (error, status, code) in cases {
+            assert_eq!(mapped(Box::new(error)), (status, code.to_string()));
+        }
+        assert_eq!(
+            mapped("something broke".into()),
+            (StatusCode::INTERNAL_SERVER_ERROR, "internal_error".to_string())
+        );
+    }
+
+    #[test]
+    fn keeps_messages_and_validation_details() {
+        let error: Box<dyn Error> = Box::new(RigError::SchemaValidation { errors: vec!["/name: required".to_string()] });
+        let api_error = ApiError::from_error(error.as_ref());
+        assert_eq!(api_error.body.message, error.to_string());
+        assert_eq!(api_error.body.details, vec!["/name: required"]);
+    }
+
+    #[test]
+    fn fallbacks_keep_the_original_message() {
+        let error: Box<dyn Error> = Box::new(RigError::ProviderUnreachable { message: "connection refused".to_string() });
+        let api_error = ApiError::from_error(error.as_ref()).with_fallback(Some("Offline, try again later".to_string()));
+        assert_eq!(api_error.status, StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(api_error.body.code, "provider_unreachable");
+        assert_eq!(api_error.body.message, "Offline, try again later");
+        assert_eq!(api_error.body.details, vec![error.to_string()]);
+
+        let api_error = ApiError::from_error(error.as_ref()).with_fallback(None);
+        assert_eq!(api_error.body.message, error.to_string());
+        assert!(api_error.body.details.is_empty());
+    }
+}
@@ -0,0 +1,114 @@
+/**
+ * Auth module optionally protects the backend with a shared bearer token.
+ * The backend holds the provider API key, so without it anything that can
+ * reach the port can spend it. When `RIG_AUTH_TOKEN` is set, every request
+ * except the health check must send `Authorization: Bearer <token>`; when it
+ * is unset, requests pass through unchanged.
+ */
+
+use axum::{
+    extract::{Request, State},
+    http::{header, HeaderValue, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use std::sync::Arc;
+
+use crate::api_error::ApiError;
+
+/// Environment variable holding the token clients must send.
+pub const AUTH_TOKEN_ENV: &str = "RIG_AUTH_TOKEN";
+
+/// Paths that never require the token, so liveness checks keep working.
+const UNAUTHENTICATED_PATHS: [&str; 1] = ["/health"];
+
+/**
+ * The token requests must carry, or None if auth is off.
+ */
+#[derive(Clone)]
+pub struct AuthToken(Option<Arc<str>>);
+
+impl AuthToken {
+    /**
+     * Reads the token from `RIG_AUTH_TOKEN`. Unset or blank turns auth off.
+     */
+    pub fn from_env() -> Self {
+        Self::new(std::env::var(AUTH_TOKEN_ENV).ok().as_deref())
+    }
+
+    fn new(token: Option<&str>) -> Self {
+        Self(token.map(str::trim).filter(|token| !token.is_empty()).map(Arc::from))
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.0.is_some()
+    }
+
+    /**
+     * Whether an `Authorization` header value carries the token.
+     * The comparison takes the same time wherever the first mismatch is.
+     */
+    fn accepts(&self, authorization: Option<&str>) -> bool {
+        let Some(expected) = &self.0 else {
+            return true;
+        };
+        let Some(token) = authorization.and_then(|value| value.strip_prefix("Bearer ")) else {
+            return false;
+        };
+        let (token, expected) = (token.trim().as_bytes(), expected.as_bytes());
+        token.len() == expected.len() && token.iter().zip(expected).fold(0, |diff, (a, b)| diff | (a ^ b)) == 0
+    }
+}
+
+/**
+ * Middleware rejecting requests without the configured bearer token with
+ * `401 Unauthorized`. A no-op when auth is off.
+ *
+ * @param token The token requests must carry
+ * @param request The incoming request
+ * @param next The rest of the middleware stack and the handler
+ * @return The handler's response, or 401 if the token is missing or wrong
+ */
+pub async fn require_token(State(token): State<AuthToken>, request: Request, next: Next) -> Response {
+    if UNAUTHENTICATED_PATHS.contains(&request.uri().path()) {
+        return next.run(request).await;
+    }
+    let authorization = request.headers().get(header::AUTHORIZATION).and_then(|value| value.to_str().ok());
+    if token.accepts(authorization) {
+        return next.run(request).await;
+    }
+
+    tracing::warn!("Rejected request without a valid bearer token");
+    let mut response = ApiError::new(
+        StatusCode::UNAUTHORIZED,
+        "unauthorized",
+        format!("Missing or invalid bearer token; send the value of {} as 'Authorization: Bearer <token>'", AUTH_TOKEN_ENV),
+    )
+    .into_response();
+    response.headers_mut().insert(header::WWW_AUTHENTICATE, HeaderValue::from_static("Bearer"));
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::AuthToken;
+
+    #[test]
+    fn accepts_only_the_matching_bearer_token() {
+        let token = AuthToken::new(Some(" s3cret "));
+        assert!(token.is_enabled());
+        assert!(token.accepts(Some("Bearer s3cret")));
+        assert!(!token.accepts(Some("Bearer s3cre")));
+        assert!(!token.accepts(Some("Bearer s3cret2")));
+        assert!(!token.accepts(Some("Basic s3cret")));
+        assert!(!token.accepts(None));
+    }
+
+    #[test]
+    fn unset_or_blank_token_allows_everything() {
+        for token in [AuthToken::new(None), AuthToken::new(Some("  "))] {
+            assert!(!token.is_enabled());
+            assert!(token.accepts(None));
+        }
+    }
+}
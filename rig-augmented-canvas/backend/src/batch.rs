@@ -0,0 +1,124 @@
+/**
+ * Batch module runs many independent prompts against the RigService with
+ * bounded concurrency. Each item succeeds or fails on its own, so one bad
+ * node never sinks the rest of the batch.
+ */
+
+use futures::stream::{self, Stream, StreamExt};
+use serde::{Deserialize, Serialize};
+
+use crate::rig_service::{PromptOptions, RigService};
+
+/// Default number of items processed at the same time when the request doesn't say.
+pub const DEFAULT_BATCH_CONCURRENCY: usize = 4;
+
+/// Upper bound on the per-request concurrency to avoid hammering the provider.
+pub const MAX_BATCH_CONCURRENCY: usize = 16;
+
+/**
+ * A single prompt within a batch, identified by the caller-supplied id
+ * (usually the canvas node id).
+ */
+#[derive(Deserialize, Clone)]
+pub struct BatchItem {
+    pub id: String,
+    pub content: String,
+    pub system_prompt: Option<String>,
+}
+
+/**
+ * Outcome of a single batch item. Exactly one of `response` or `error` is set.
+ */
+#[derive(Serialize, Clone)]
+pub struct BatchItemResult {
+    #[serde(skip)]
+    pub index: usize,
+    pub id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub response: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl BatchItemResult {
+    pub fn is_success(&self) -> bool {
+        self.error.is_none()
+    }
+}
+
+/**
+ * Totals reported once every item in a batch has finished.
+ */
+#[derive(Serialize, Default, Clone)]
+pub struct BatchSummary {
+    pub total: usize,
+    pub succeeded: usize,
+    pub failed: usize,
+}
+
+impl BatchSummary {
+    pub fn record(&mut self, result: &BatchItemResult) {
+        self.total += 1;
+        if result.is_success() {
+            self.succeeded += 1;
+        } else {
+            self.failed += 1;
+        }
+    }
+}
+
+/**
+ * Clamps a requested concurrency into the supported range.
+ *
+ * @param requested The concurrency asked for by the client, if any
+ * @return A concurrency between 1 and MAX_BATCH_CONCURRENCY
+ */
+pub fn effective_concurrency(requested: Option<usize>) -> usize {
+    requested
+        .unwrap_or(DEFAULT_BATCH_CONCURRENCY)
+        .clamp(1, MAX_BATCH_CONCURRENCY)
+}
+
+/**
+ * Runs every item through `generate_response_with_options` with at most `concurrency`
+ * calls in flight, yielding results in completion order.
+ *
+ * @param service The service used to generate each response
+ * @param items The prompts to run
+ * @param api_key Optional API key applied to every item
+ * @param options Per-request options applied to every item
+ * @param concurrency Maximum number of items processed at once
+ * @return A stream of per-item results as they finish
+ */
+pub fn run_batch<'a>(
+    service: &'a RigService,
+    items: Vec<BatchItem>,
+    api_key: Option<&'a str>,
+    options: &'a PromptOptions,
+    concurrency: usize,
+) -> impl Stream<Item = BatchItemResult> + 'a {
+    stream::iter(items.into_iter().enumerate())
+        .map(move |(index, item)| async move {
+            match service
+                .generate_response_with_options(&item.content, item.system_prompt.as_deref(), api_key, options)
+                .await
+            {
+                Ok(response) => BatchItemResult {
+                    index,
+                    id: item.id,
+                    response: Some(response),
+                    error: None,
+                },
+                Err(e) => {
+                    tracing::error!("Error generating batch item {}: {}", item.id, e);
+                    BatchItemResult {
+                        index,
+                        id: item.id,
+                        response: None,
+                        error: Some(service.fallback_message(e.as_ref()).unwrap_or_else(|| e.to_string())),
+                    }
+                }
+            }
+        })
+        .buffer_unordered(concurrency.max(1))
+}
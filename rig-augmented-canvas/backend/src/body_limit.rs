@@ -0,0 +1,88 @@
+/**
+ * Body limit module caps the size of request bodies. Bodies may be
+ * gzip-encoded (`Content-Encoding: gzip`); the limit applies to the
+ * decompressed body and is enforced while it is decoded, so a small
+ * compressed payload can't expand without bound. Oversized bodies are
+ * rejected with `413 Payload Too Large`.
+ */
+
+use axum::{extract::DefaultBodyLimit, Router};
+use tower_http::decompression::RequestDecompressionLayer;
+
+/// Environment variable holding the largest request body accepted, in bytes.
+pub const MAX_BODY_BYTES_ENV: &str = "RIG_MAX_BODY_BYTES";
+
+/// Largest request body accepted unless configured otherwise, in bytes.
+pub const DEFAULT_MAX_BODY_BYTES: usize = 8 * 1024 * 1024;
+
+/**
+ * Reads the limit from `RIG_MAX_BODY_BYTES`. Unset, zero or unparsable
+ * values keep the default.
+ */
+pub fn max_body_bytes_from_env() -> usize {
+    std::env::var(MAX_BODY_BYTES_ENV)
+        .ok()
+        .and_then(|value| value.trim().parse().ok())
+        .filter(|limit| *limit > 0)
+        .unwrap_or(DEFAULT_MAX_BODY_BYTES)
+}
+
+/**
+ * Accepts gzip-encoded bodies on every route and limits bodies to
+ * `max_bytes` after decompression.
+ *
+ * @param router The routes to protect
+ * @param max_bytes The largest decompressed body accepted
+ * @return The router with decompression and the limit applied
+ */
+pub fn limit_bodies<S>(router: Router<S>, max_bytes: usize) -> Router<S>
+where
+    S: Clone + Send + Sync + 'static,
+{
+    // The limit sits outside decompression so extractors count decoded bytes
+    router
+        .layer(RequestDecompressionLayer::new())
+        .layer(DefaultBodyLimit::max(max_bytes))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::limit_bodies;
+    use axum::{
+        body::Body,
+        http::{header, Request, StatusCode},
+        routing::post,
+        Router,
+    };
+    use flate2::{write::GzEncoder, Compression};
+    use std::io::Write;
+    use tower::ServiceExt;
+
+    fn gzip(bytes: &[u8]) -> Vec<u8> {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::best());
+        encoder.write_all(bytes).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    async fn post_gzip(body: Vec<u8>) -> StatusCode {
+        let app = limit_bodies(Router::new().route("/", post(|body: String| async move { body.len().to_string() })), 1024);
+        let request = Request::post("/")
+            .header(header::CONTENT_ENCODING, "gzip")
+            .body(Body::from(body))
+            .unwrap();
+        app.oneshot(request).await.unwrap().status()
+    }
+
+    #[tokio::test]
+    async fn accepts_compressed_bodies_within_the_limit() {
+        assert_eq!(post_gzip(gzip(&[b'a'; 1000])).await, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn cuts_off_bodies_that_decompress_past_the_limit() {
+        // 16 MiB of zeros compresses to a few KiB; decoding must stop at the limit
+        let bomb = gzip(&vec![0; 16 * 1024 * 1024]);
+        assert!(bomb.len() < 1024 * 32);
+        assert_eq!(post_gzip(bomb).await, StatusCode::PAYLOAD_TOO_LARGE);
+    }
+}
@@ -0,0 +1,60 @@
+/**
+ * Budget module splits the prompt budget of a combined multi-node prompt
+ * between the instruction (system prompt and user prompt) and the note
+ * content. Only content is ever cut to fit, so the instruction reaches the
+ * model intact; reserving at most a fixed share for it keeps a huge system
+ * prompt from leaving no room for the notes.
+ */
+
+/**
+ * Checks that a ratio leaves room for both instruction and content.
+ *
+ * @param ratio The share of the budget reserved for the instruction
+ * @return An error describing the problem, if any
+ */
+pub fn validate_instruction_budget_ratio(ratio: f64) -> Result<(), String> {
+    if !(ratio.is_finite() && ratio > 0.0 && ratio < 1.0) {
+        return Err("instruction_budget_ratio must be between 0 and 1 (exclusive)".to_string());
+    }
+    Ok(())
+}
+
+/**
+ * Computes how many characters of note content fit next to an instruction.
+ * The instruction takes what it needs up to `ratio` of the budget; content
+ * gets the rest, so it is never squeezed below `1 - ratio` of the budget.
+ *
+ * @param total_chars The budget for the whole prompt
+ * @param instruction_chars The length of the instruction, which is never cut
+ * @param ratio The share of the budget reserved for the instruction
+ * @return The budget for node content
+ */
+pub fn content_budget(total_chars: usize, instruction_chars: usize, ratio: f64) -> usize {
+    let reserved = (total_chars as f64 * ratio).floor() as usize;
+    total_chars - instruction_chars.min(reserved)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{content_budget, validate_instruction_budget_ratio};
+
+    #[test]
+    fn validates_ratio_range() {
+        assert!(validate_instruction_budget_ratio(0.25).is_ok());
+        assert!(validate_instruction_budget_ratio(0.0).is_err());
+        assert!(validate_instruction_budget_ratio(1.0).is_err());
+        assert!(validate_instruction_budget_ratio(f64::NAN).is_err());
+    }
+
+    #[test]
+    fn short_instructions_leave_the_rest_to_content() {
+        assert_eq!(content_budget(10_000, 300, 0.25), 9_700);
+        assert_eq!(content_budget(10_000, 0, 0.25), 10_000);
+    }
+
+    #[test]
+    fn huge_instructions_cannot_starve_content() {
+        assert_eq!(content_budget(10_000, 50_000, 0.25), 7_500);
+        assert_eq!(content_budget(10_000, 2_500, 0.25), 7_500);
+    }
+}
@@ -0,0 +1,155 @@
+/**
+ * Cache module provides small bounded LRU caches keyed by a hash of
+ * everything that influences the cached value.
+ */
+
+use lru::LruCache;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::num::NonZeroUsize;
+use std::sync::Mutex;
+
+use crate::truncate::LimitedResponse;
+
+/// Number of responses kept before the least recently used one is evicted.
+pub const DEFAULT_RESPONSE_CACHE_SIZE: usize = 128;
+
+/// Number of built agents kept before the least recently used one is evicted.
+pub const DEFAULT_AGENT_CACHE_SIZE: usize = 32;
+
+/// Number of compiled blocked-pattern sets kept (the default configuration's and each profile's).
+pub const DEFAULT_PATTERN_CACHE_SIZE: usize = 16;
+
+/**
+ * Hashes the given parts into a stable hexadecimal key.
+ * Parts are length-prefixed so ("ab", "c") and ("a", "bc") differ.
+ *
+ * @param parts The values that determine the cached output
+ * @return A 16-character hexadecimal key
+ */
+pub fn cache_key(parts: &[&str]) -> String {
+    let mut hasher = DefaultHasher::new();
+    for part in parts {
+        part.len().hash(&mut hasher);
+        part.hash(&mut hasher);
+    }
+    format!("{:016x}", hasher.finish())
+}
+
+/**
+ * Computes the ETag of a response from its text, so the tag changes
+ * whenever the stored response does, even if the request stays the same.
+ *
+ * @param response The response as stored in the cache
+ * @return A 16-character hexadecimal tag
+ */
+pub fn response_etag(response: &str) -> String {
+    cache_key(&[response])
+}
+
+/**
+ * How a request uses the response cache.
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CachePolicy {
+    /// Neither read nor store (e.g., structured endpoints that parse the response).
+    #[default]
+    Skip,
+    /// Answer from the cache when possible, and store fresh responses.
+    Use,
+    /// Always ask the model, storing the fresh response over any cached one.
+    Refresh,
+}
+
+impl CachePolicy {
+    /**
+     * The policy for a client's `cache` flag: unset or true uses the
+     * cache, false asks for a fresh answer.
+     */
+    pub fn from_flag(cache: Option<bool>) -> Self {
+        if cache.unwrap_or(true) {
+            CachePolicy::Use
+        } else {
+            CachePolicy::Refresh
+        }
+    }
+}
+
+/**
+ * Thread-safe LRU cache from string keys to cheaply cloned values.
+ */
+pub struct BoundedCache<V> {
+    entries: Mutex<LruCache<String, V>>,
+}
+
+/// Generated responses, keyed by the request that produced them.
+pub type ResponseCache = BoundedCache<LimitedResponse>;
+
+impl<V: Clone> BoundedCache<V> {
+    pub fn new(capacity: usize) -> Self {
+        let capacity = NonZeroUsize::new(capacity).unwrap_or(NonZeroUsize::MIN);
+        Self {
+            entries: Mutex::new(LruCache::new(capacity)),
+        }
+    }
+
+    pub fn get(&self, key: &str) -> Option<V> {
+        self.entries.lock().ok()?.get(key).cloned()
+    }
+
+    pub fn insert(&self, key: String, value: V) {
+        if let Ok(mut entries) = self.entries.lock() {
+            entries.put(key, value);
+        }
+    }
+
+    /**
+     * Removes every entry.
+     *
+     * @return The number of entries removed
+     */
+    pub fn clear(&self) -> usize {
+        match self.entries.lock() {
+            Ok(mut entries) => {
+                let count = entries.len();
+                entries.clear();
+                count
+            }
+            Err(_) => 0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{cache_key, response_etag, BoundedCache, CachePolicy};
+
+    #[test]
+    fn keys_differ_by_part_boundaries() {
+        assert_eq!(cache_key(&["ab", "c"]), cache_key(&["ab", "c"]));
+        assert_ne!(cache_key(&["ab", "c"]), cache_key(&["a", "bc"]));
+    }
+
+    #[test]
+    fn etags_follow_the_response() {
+        assert_eq!(response_etag("Paris"), response_etag("Paris"));
+        assert_ne!(response_etag("Paris"), response_etag("Paris."));
+    }
+
+    #[test]
+    fn clears_all_entries() {
+        let cache = BoundedCache::new(2);
+        cache.insert("a".to_string(), "1".to_string());
+        cache.insert("b".to_string(), "2".to_string());
+        assert_eq!(cache.clear(), 2);
+        assert_eq!(cache.get("a"), None);
+        assert_eq!(cache.clear(), 0);
+    }
+
+    #[test]
+    fn maps_the_cache_flag_to_a_policy() {
+        assert_eq!(CachePolicy::from_flag(None), CachePolicy::Use);
+        assert_eq!(CachePolicy::from_flag(Some(true)), CachePolicy::Use);
+        assert_eq!(CachePolicy::from_flag(Some(false)), CachePolicy::Refresh);
+    }
+}
@@ -0,0 +1,103 @@
+/**
+ * Canvas module defines serde types matching Obsidian's `.canvas` JSON format,
+ * so the backend can hand the plugin a ready-made canvas document.
+ */
+
+use serde::{Deserialize, Serialize};
+
+/**
+ * A complete Obsidian canvas document.
+ */
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct CanvasDoc {
+    pub nodes: Vec<CanvasNode>,
+    pub edges: Vec<CanvasEdge>,
+}
+
+/**
+ * A text node on the canvas. Obsidian also supports file, link and group
+ * nodes, but generated canvases only ever contain text.
+ */
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CanvasNode {
+    pub id: String,
+    #[serde(rename = "type")]
+    pub node_type: String,
+    pub text: String,
+    pub x: i64,
+    pub y: i64,
+    pub width: i64,
+    pub height: i64,
+}
+
+/**
+ * A connection between two canvas nodes.
+ */
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CanvasEdge {
+    pub id: String,
+    pub from_node: String,
+    pub from_side: String,
+    pub to_node: String,
+    pub to_side: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+}
+
+impl CanvasNode {
+    pub fn text(id: String, text: String, x: i64, y: i64, width: i64, height: i64) -> Self {
+        Self {
+            id,
+            node_type: "text".to_string(),
+            text,
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+}
+
+impl CanvasEdge {
+    /**
+     * Creates a top-to-bottom edge, matching the layered layout produced by `layout.rs`.
+     */
+    pub fn downward(id: String, from_node: String, to_node: String, label: Option<String>) -> Self {
+        Self {
+            id,
+            from_node,
+            from_side: "bottom".to_string(),
+            to_node,
+            to_side: "top".to_string(),
+            label,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CanvasDoc, CanvasEdge, CanvasNode};
+    use serde_json::json;
+
+    #[test]
+    fn serializes_in_the_obsidian_format() {
+        let doc = CanvasDoc {
+            nodes: vec![CanvasNode::text("a".to_string(), "Ownership".to_string(), -200, 0, 400, 200)],
+            edges: vec![
+                CanvasEdge::downward("e1".to_string(), "a".to_string(), "b".to_string(), Some("moves".to_string())),
+                CanvasEdge::downward("e2".to_string(), "a".to_string(), "c".to_string(), None),
+            ],
+        };
+        assert_eq!(
+            serde_json::to_value(&doc).unwrap(),
+            json!({
+                "nodes": [{ "id": "a", "type": "text", "text": "Ownership", "x": -200, "y": 0, "width": 400, "height": 200 }],
+                "edges": [
+                    { "id": "e1", "fromNode": "a", "fromSide": "bottom", "toNode": "b", "toSide": "top", "label": "moves" },
+                    { "id": "e2", "fromNode": "a", "fromSide": "bottom", "toNode": "c", "toSide": "top" }
+                ]
+            })
+        );
+    }
+}
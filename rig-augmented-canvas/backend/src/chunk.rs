@@ -0,0 +1,134 @@
+/**
+ * Chunk module splits long text into overlapping windows for features that
+ * process a note piece by piece, such as map-reduce summarization. Windows
+ * are measured in estimated tokens (about four characters per token, as in
+ * `throttle::estimate_tokens`) and always break between words. Consecutive
+ * windows share up to `overlap` tokens so context at a boundary appears in
+ * both.
+ */
+
+/// Default window size in estimated tokens.
+pub const DEFAULT_CHUNK_SIZE_TOKENS: usize = 2000;
+
+/// Default overlap between consecutive windows in estimated tokens.
+pub const DEFAULT_CHUNK_OVERLAP_TOKENS: usize = 200;
+
+/**
+ * Checks a chunking configuration: the size must be positive and the overlap
+ * smaller than the size, or the windows would never advance.
+ *
+ * @param size_tokens The window size
+ * @param overlap_tokens The overlap between windows
+ * @return An error describing the problem, if any
+ */
+pub fn validate_chunking(size_tokens: usize, overlap_tokens: usize) -> Result<(), String> {
+    if size_tokens == 0 {
+        return Err("chunk_size_tokens must be greater than 0".to_string());
+    }
+    if overlap_tokens >= size_tokens {
+        return Err("chunk_overlap_tokens must be smaller than chunk_size_tokens".to_string());
+    }
+    Ok(())
+}
+
+/**
+ * Estimated token cost of a word, never less than one.
+ */
+fn word_tokens(word: &str) -> usize {
+    word.chars().count().div_ceil(4).max(1)
+}
+
+/**
+ * Splits text into windows of at most `size_tokens` estimated tokens, each
+ * starting up to `overlap_tokens` before the previous one ended. A single
+ * word larger than the window gets a window of its own. Whitespace inside a
+ * window is kept as written.
+ *
+ * @param text The text to split
+ * @param size_tokens The window size
+ * @param overlap_tokens The overlap between consecutive windows
+ * @return The windows in order; empty for blank text
+ */
+pub fn chunk_text(text: &str, size_tokens: usize, overlap_tokens: usize) -> Vec<String> {
+    let words: Vec<(usize, usize)> = text
+        .split_whitespace()
+        .map(|word| {
+            let start = word.as_ptr() as usize - text.as_ptr() as usize;
+            (start, start + word.len())
+        })
+        .collect();
+    let cost = |index: usize| word_tokens(&text[words[index].0..words[index].1]);
+
+    let mut chunks = Vec::new();
+    let mut start = 0;
+    while start < words.len() {
+        // Take words until the window is full, always at least one
+        let mut end = start + 1;
+        let mut used = cost(start);
+        while end < words.len() && used + cost(end) <= size_tokens {
+            used += cost(end);
+            end += 1;
+        }
+        chunks.push(text[words[start].0..words[end - 1].1].to_string());
+        if end == words.len() {
+            break;
+        }
+
+        // Step back over the words shared with the next window, but always advance
+        let mut next = end;
+        let mut shared = 0;
+        while next > start + 1 && shared + cost(next - 1) <= overlap_tokens {
+            shared += cost(next - 1);
+            next -= 1;
+        }
+        start = next;
+    }
+    chunks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{chunk_text, validate_chunking, word_tokens};
+
+    #[test]
+    fn rejects_overlap_not_smaller_than_size() {
+        assert!(validate_chunking(2000, 200).is_ok());
+        assert!(validate_chunking(200, 200).is_err());
+        assert!(validate_chunking(0, 0).is_err());
+    }
+
+    #[test]
+    fn overlapping_windows_reconstruct_the_text() {
+        let words: Vec<String> = (0..500).map(|i| format!("word{}", i % 37)).collect();
+        let text = words.join(" ");
+        let chunks = chunk_text(&text, 50, 10);
+        assert!(chunks.len() > 1);
+
+        // Each window fits the budget
+        for chunk in &chunks {
+            assert!(chunk.split_whitespace().map(word_tokens).sum::<usize>() <= 50);
+        }
+
+        // Dropping each window's overlap with the previous one gives back every word once
+        let mut rebuilt: Vec<&str> = chunks[0].split_whitespace().collect();
+        for pair in chunks.windows(2) {
+            let previous: Vec<&str> = pair[0].split_whitespace().collect();
+            let current: Vec<&str> = pair[1].split_whitespace().collect();
+            let overlap = (1..previous.len().min(current.len() + 1))
+                .rev()
+                .find(|&len| previous[previous.len() - len..] == current[..len])
+                .unwrap_or(0);
+            assert!(overlap > 0);
+            assert!(current[..overlap].iter().map(|word| word_tokens(word)).sum::<usize>() <= 10);
+            rebuilt.extend(&current[overlap..]);
+        }
+        assert_eq!(rebuilt, words);
+    }
+
+    #[test]
+    fn handles_short_blank_and_oversized_text() {
+        assert_eq!(chunk_text("A short note.", 2000, 200), vec!["A short note."]);
+        assert!(chunk_text("  \n ", 2000, 200).is_empty());
+        assert_eq!(chunk_text("tiny enormousword tiny", 2, 1), vec!["tiny", "enormousword", "tiny"]);
+    }
+}
@@ -0,0 +1,89 @@
+/**
+ * Cite module formats extracted references as citations in a chosen style
+ * and holds the JSON schema the output is checked against. The schema asks
+ * for exactly one citation per reference so they can be matched by position.
+ */
+
+use serde_json::{json, Value};
+
+/**
+ * A supported citation style.
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CitationStyle {
+    Apa,
+    Mla,
+    Chicago,
+}
+
+impl CitationStyle {
+    /**
+     * Parses a style name as sent by the plugin ("apa", "mla" or "chicago").
+     *
+     * @param style The style name, case-insensitive
+     * @return The style, or None if it isn't supported
+     */
+    pub fn parse(style: &str) -> Option<Self> {
+        match style.trim().to_lowercase().as_str() {
+            "apa" => Some(Self::Apa),
+            "mla" => Some(Self::Mla),
+            "chicago" => Some(Self::Chicago),
+            _ => None,
+        }
+    }
+
+    /**
+     * The style guide the model is asked to follow.
+     */
+    pub fn guide(self) -> &'static str {
+        match self {
+            Self::Apa => "APA (7th edition)",
+            Self::Mla => "MLA (9th edition)",
+            Self::Chicago => "Chicago (17th edition, bibliography style)",
+        }
+    }
+}
+
+/**
+ * Builds the JSON schema citation output must match: exactly `count`
+ * non-empty citations, one per reference in order.
+ *
+ * @param count The number of references sent
+ * @return The schema
+ */
+pub fn citation_schema(count: usize) -> Value {
+    json!({
+        "type": "object",
+        "properties": {
+            "citations": {
+                "type": "array",
+                "minItems": count,
+                "maxItems": count,
+                "items": { "type": "string", "minLength": 1 }
+            }
+        },
+        "required": ["citations"]
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{citation_schema, CitationStyle};
+    use crate::extract::conforms;
+    use serde_json::json;
+
+    #[test]
+    fn parses_supported_styles() {
+        assert_eq!(CitationStyle::parse(" APA "), Some(CitationStyle::Apa));
+        assert_eq!(CitationStyle::parse("chicago"), Some(CitationStyle::Chicago));
+        assert_eq!(CitationStyle::parse("harvard"), None);
+    }
+
+    #[test]
+    fn schema_requires_one_citation_per_reference() {
+        let schema = citation_schema(2);
+        assert!(conforms(&schema, &json!({ "citations": ["A.", "B."] })));
+        assert!(!conforms(&schema, &json!({ "citations": ["A."] })));
+        assert!(!conforms(&schema, &json!({ "citations": ["A.", ""] })));
+    }
+}
@@ -0,0 +1,60 @@
+/**
+ * Cloze module handles cloze deletion flashcards in Anki's format: the
+ * front is a sentence with parts hidden as `{{c1::answer}}` (optionally
+ * `{{c1::answer::hint}}`), numbered `c1`, `c2`, ... and the back holds the
+ * full sentence.
+ */
+
+use regex::Regex;
+use std::sync::OnceLock;
+
+/**
+ * Matches one deletion, capturing the hidden text without its hint.
+ */
+fn deletion() -> &'static Regex {
+    static DELETION: OnceLock<Regex> = OnceLock::new();
+    DELETION.get_or_init(|| Regex::new(r"\{\{c\d+::([^}]+?)(?:::[^}]*)?\}\}").unwrap())
+}
+
+/**
+ * Whether a text has at least one cloze deletion.
+ *
+ * @param text The front of a card
+ * @return true if the card has something to recall
+ */
+pub fn has_deletion(text: &str) -> bool {
+    deletion().is_match(text)
+}
+
+/**
+ * The text with every deletion revealed, i.e. the full sentence.
+ *
+ * @param text The front of a card
+ * @return The text without cloze markers or hints
+ */
+pub fn revealed(text: &str) -> String {
+    deletion().replace_all(text, "$1").into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{has_deletion, revealed};
+
+    #[test]
+    fn detects_deletions() {
+        assert!(has_deletion("The {{c1::mitochondria}} produce energy."));
+        assert!(has_deletion("{{c12::Rust}} was released in {{c13::2015::year}}."));
+        assert!(!has_deletion("What produces most of the cell's energy?"));
+        assert!(!has_deletion("An empty {{c1::}} deletion."));
+    }
+
+    #[test]
+    fn reveals_deletions_without_hints() {
+        assert_eq!(
+            revealed("{{c1::Rust}} 1.0 was released in {{c2::2015::year}}."),
+            "Rust 1.0 was released in 2015."
+        );
+        assert_eq!(revealed("No deletions here."), "No deletions here.");
+        assert_eq!(revealed("{{c1::}} then {{c2::x}}"), "{{c1::}} then x");
+    }
+}
@@ -0,0 +1,59 @@
+/**
+ * Compare module supports running one prompt against several models side by
+ * side. Models are named as in the configuration (a model name or alias),
+ * optionally prefixed with their provider, e.g. "openai/gpt-4o".
+ */
+
+use serde::Serialize;
+
+/// Number of models prompted at the same time.
+pub const COMPARE_CONCURRENCY: usize = 4;
+
+/// Maximum number of models compared in one request.
+pub const MAX_COMPARE_MODELS: usize = 8;
+
+/**
+ * One model's answer, or the error it failed with, and how long it took.
+ */
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct ModelComparison {
+    pub model: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub response: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    pub latency_ms: u64,
+}
+
+/**
+ * Splits a "provider/model" name into the model to request. Names without
+ * a provider use the configured one; only OpenAI is supported for now.
+ *
+ * @param spec The model as named by the client
+ * @return The model name or alias, or why it can't be used
+ */
+pub fn parse_model_spec(spec: &str) -> Result<String, String> {
+    let spec = spec.trim();
+    let model = match spec.split_once('/') {
+        Some((provider, model)) if provider.eq_ignore_ascii_case("openai") => model.trim(),
+        Some((provider, _)) => return Err(format!("Unsupported provider '{}'", provider)),
+        None => spec,
+    };
+    if model.is_empty() {
+        return Err("Model name is empty".to_string());
+    }
+    Ok(model.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse_model_spec;
+
+    #[test]
+    fn parses_model_names_with_optional_provider() {
+        assert_eq!(parse_model_spec(" gpt-4o "), Ok("gpt-4o".to_string()));
+        assert_eq!(parse_model_spec("OpenAI/o3-mini"), Ok("o3-mini".to_string()));
+        assert!(parse_model_spec("anthropic/some-model").is_err());
+        assert!(parse_model_spec("openai/ ").is_err());
+    }
+}
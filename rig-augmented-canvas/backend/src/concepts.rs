@@ -0,0 +1,127 @@
+/**
+ * Concepts module holds the concept map returned by the model and checks
+ * that its relations only connect declared concepts.
+ */
+
+use serde::{Deserialize, Serialize};
+
+/**
+ * A typed, directed relation between two concepts, where `type` is a verb
+ * phrase such as "causes" or "is part of".
+ */
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ConceptRelation {
+    pub from: String,
+    pub to: String,
+    #[serde(rename = "type")]
+    pub relation_type: String,
+}
+
+/**
+ * Concepts (rendered as nodes) and the typed relations between them
+ * (rendered as labeled edges).
+ */
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct ConceptMap {
+    #[serde(default)]
+    pub concepts: Vec<String>,
+    #[serde(default)]
+    pub relations: Vec<ConceptRelation>,
+}
+
+impl ConceptMap {
+    /**
+     * Cleans up a concept map: trims and dedupes concepts (case-insensitively),
+     * resolves relation endpoints to the declared spelling, and drops relations
+     * that reference undeclared concepts, point at themselves, lack a type or
+     * repeat an earlier relation.
+     *
+     * @return The validated map and the number of relations dropped
+     */
+    pub fn validated(self) -> (Self, usize) {
+        let mut concepts: Vec<String> = Vec::new();
+        for concept in self.concepts {
+            let concept = concept.trim();
+            if !concept.is_empty() && find(&concepts, concept).is_none() {
+                concepts.push(concept.to_string());
+            }
+        }
+        
+        let total = self.relations.len();
+        let mut relations: Vec<ConceptRelation> = Vec::new();
+        for relation in self.relations {
+            let (Some(from), Some(to)) = (find(&concepts, &relation.from), find(&concepts, &relation.to)) else {
+                continue;
+            };
+            let relation_type = relation.relation_type.trim();
+            if from == to || relation_type.is_empty() {
+                continue;
+            }
+            
+            let relation = ConceptRelation {
+                from: from.to_string(),
+                to: to.to_string(),
+                relation_type: relation_type.to_string(),
+            };
+            if !relations.contains(&relation) {
+                relations.push(relation);
+            }
+        }
+        
+        let dropped = total - relations.len();
+        (ConceptMap { concepts, relations }, dropped)
+    }
+}
+
+/**
+ * Finds the declared spelling of a concept, ignoring case and surrounding whitespace.
+ */
+fn find<'a>(concepts: &'a [String], name: &str) -> Option<&'a str> {
+    let name = name.trim().to_lowercase();
+    concepts
+        .iter()
+        .find(|concept| concept.to_lowercase() == name)
+        .map(String::as_str)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ConceptMap, ConceptRelation};
+
+    fn relation(from: &str, to: &str, relation_type: &str) -> ConceptRelation {
+        ConceptRelation {
+            from: from.to_string(),
+            to: to.to_string(),
+            relation_type: relation_type.to_string(),
+        }
+    }
+
+    #[test]
+    fn keeps_relations_between_declared_concepts() {
+        let map = ConceptMap {
+            concepts: vec!["Heat".to_string(), " Evaporation ".to_string(), "heat".to_string()],
+            relations: vec![relation("heat", "evaporation", " causes ")],
+        };
+        let (map, dropped) = map.validated();
+        assert_eq!(map.concepts, vec!["Heat", "Evaporation"]);
+        assert_eq!(map.relations, vec![relation("Heat", "Evaporation", "causes")]);
+        assert_eq!(dropped, 0);
+    }
+
+    #[test]
+    fn drops_invalid_relations() {
+        let map = ConceptMap {
+            concepts: vec!["Cell".to_string(), "Nucleus".to_string()],
+            relations: vec![
+                relation("Nucleus", "Cell", "is part of"),
+                relation("Nucleus", "Cell", "is part of"),
+                relation("Nucleus", "Ribosome", "contains"),
+                relation("Cell", "Cell", "is"),
+                relation("Cell", "Nucleus", ""),
+            ],
+        };
+        let (map, dropped) = map.validated();
+        assert_eq!(map.relations, vec![relation("Nucleus", "Cell", "is part of")]);
+        assert_eq!(dropped, 4);
+    }
+}
@@ -0,0 +1,133 @@
+/**
+ * Config file module keeps the model configuration across restarts. On
+ * startup the configuration is read from `rig-canvas.toml` in the working
+ * directory (or the file named by `RIG_CONFIG`), and configuration updates
+ * can be written back to it so the plugin's settings and the backend agree.
+ * A missing or malformed file is logged and the defaults are used instead.
+ */
+
+use std::error::Error;
+use std::path::{Path, PathBuf};
+
+use crate::models::ModelConfig;
+
+/// Configuration file looked for in the working directory.
+pub const CONFIG_FILE_NAME: &str = "rig-canvas.toml";
+
+/// Path of the configuration file, overriding `CONFIG_FILE_NAME`.
+pub const CONFIG_PATH_ENV: &str = "RIG_CONFIG";
+
+/**
+ * The configuration file's path: `RIG_CONFIG` if set, otherwise
+ * `rig-canvas.toml` in the working directory.
+ */
+pub fn config_path() -> PathBuf {
+    std::env::var(CONFIG_PATH_ENV)
+        .ok()
+        .filter(|path| !path.is_empty())
+        .map_or_else(|| PathBuf::from(CONFIG_FILE_NAME), PathBuf::from)
+}
+
+/**
+ * Reads the configuration from a file. Fields the file leaves out keep
+ * their defaults.
+ *
+ * @param path The configuration file
+ * @return The configuration, or None if the file is missing or can't be parsed
+ */
+pub fn load(path: &Path) -> Option<ModelConfig> {
+    let text = match std::fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            // Only worth a warning when the file was asked for explicitly
+            if std::env::var_os(CONFIG_PATH_ENV).is_some() {
+                tracing::warn!("Config file {} not found; using the default configuration", path.display());
+            }
+            return None;
+        }
+        Err(e) => {
+            tracing::warn!("Could not read config file {}: {}; using the default configuration", path.display(), e);
+            return None;
+        }
+    };
+    match parse(&text) {
+        Ok(config) => {
+            tracing::info!("Loaded configuration from {}", path.display());
+            Some(config)
+        }
+        Err(e) => {
+            tracing::warn!("Malformed config file {}: {}; using the default configuration", path.display(), e);
+            None
+        }
+    }
+}
+
+/**
+ * Parses a configuration file, laying its top-level settings over the
+ * defaults so a file only needs the settings it changes.
+ */
+fn parse(text: &str) -> Result<ModelConfig, Box<dyn Error>> {
+    let toml::Value::Table(mut config) = toml::Value::try_from(ModelConfig::default())? else {
+        return Err("The default configuration is not a table".into());
+    };
+    config.extend(toml::from_str::<toml::Table>(text)?);
+    Ok(toml::Value::Table(config).try_into()?)
+}
+
+/**
+ * Writes the configuration to a file, replacing its contents.
+ *
+ * @param path The configuration file
+ * @param config The configuration to write
+ */
+pub fn save(path: &Path, config: &ModelConfig) -> Result<(), Box<dyn Error>> {
+    let text = toml::to_string_pretty(config).map_err(|e| format!("Could not serialize config: {}", e))?;
+    std::fs::write(path, text).map_err(|e| format!("Could not write config file {}: {}", path.display(), e))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{load, parse, save};
+    use crate::models::ModelConfig;
+
+    fn temp_path(name: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("rig-canvas-{}-{}.toml", name, std::process::id()))
+    }
+
+    #[test]
+    fn saved_config_round_trips() {
+        let path = temp_path("round-trip");
+        let config = ModelConfig {
+            model_name: "gpt-4o-mini".to_string(),
+            max_input_tokens: Some(2_000),
+            ..ModelConfig::default()
+        };
+        save(&path, &config).unwrap();
+        let loaded = load(&path).unwrap();
+        assert_eq!(loaded.model_name, "gpt-4o-mini");
+        assert_eq!(loaded.max_input_tokens, Some(2_000));
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn partial_files_keep_defaults() {
+        let config = parse("model_name = \"gpt-4o\"\n[model_aliases]\nfast = \"gpt-4o-mini\"\n").unwrap();
+        assert_eq!(config.model_name, "gpt-4o");
+        assert_eq!(config.model_aliases.get("fast").map(String::as_str), Some("gpt-4o-mini"));
+        assert_eq!(config.request_timeout_secs, ModelConfig::default().request_timeout_secs);
+    }
+
+    #[test]
+    fn missing_or_malformed_files_load_as_none() {
+        let path = temp_path("malformed");
+        assert!(load(&path).is_none());
+
+        std::fs::write(&path, "model_name = [").unwrap();
+        assert!(load(&path).is_none());
+
+        std::fs::write(&path, "max_history_turns = \"many\"").unwrap();
+        assert!(load(&path).is_none());
+        let _ = std::fs::remove_file(&path);
+    }
+}
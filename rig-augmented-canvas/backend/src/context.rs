@@ -0,0 +1,157 @@
+/**
+ * Context module keeps combined multi-node prompts within the model's
+ * context window. When the nodes don't fit, they are either truncated (the
+ * least relevant nodes, by weight, are dropped) or summarized one by one
+ * before the final prompt, and the response reports which nodes were
+ * affected. Limits come from `context_limits` in the configuration, falling
+ * back to known defaults for OpenAI models.
+ */
+
+use serde::{Deserialize, Serialize};
+
+use crate::weighting;
+
+/// Context window assumed for models without a known or configured limit.
+pub const DEFAULT_CONTEXT_LIMIT: usize = 8_192;
+
+/// Tokens kept free for the response when `max_tokens` isn't set.
+pub const DEFAULT_RESPONSE_RESERVE_TOKENS: usize = 4_096;
+
+/// Tokens allowed for the "Node N (...):" label around each node's content.
+pub const NODE_LABEL_TOKENS: usize = 16;
+
+/**
+ * A canvas node sent with a multi-node prompt: its content, an optional
+ * instruction describing its role (e.g., "treat this as the source") and an
+ * optional weight signalling how much it matters.
+ */
+#[derive(Deserialize, Debug, Clone)]
+pub struct NodeContent {
+    pub id: String,
+    pub content: String,
+    #[serde(default)]
+    pub instruction: Option<String>,
+    #[serde(default)]
+    pub weight: Option<f32>,
+}
+
+impl NodeContent {
+    /**
+     * The node's instruction, if it has a non-blank one.
+     */
+    pub fn instruction(&self) -> Option<&str> {
+        self.instruction.as_deref().map(str::trim).filter(|instruction| !instruction.is_empty())
+    }
+
+    /**
+     * The content sent for this node alone in per-node mode.
+     */
+    pub fn prompt_content(&self, prompt: &str) -> String {
+        match self.instruction() {
+            Some(instruction) => format!("Instruction: {}\n\n{}\n\nPrompt: {}", instruction, self.content, prompt),
+            None => format!("{}\n\nPrompt: {}", self.content, prompt),
+        }
+    }
+}
+
+/**
+ * What to do when combined nodes don't fit the context window.
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ContextOverflow {
+    /// Drop the least relevant nodes until the rest fit.
+    #[default]
+    Truncate,
+    /// Summarize each node first, dropping nodes only if the summaries still don't fit.
+    Summarize,
+}
+
+/**
+ * The nodes changed to fit the context window, by id.
+ */
+#[derive(Serialize, Debug, Clone, Default, PartialEq)]
+pub struct ContextReport {
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub dropped: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub summarized: Vec<String>,
+}
+
+impl ContextReport {
+    pub fn is_empty(&self) -> bool {
+        self.dropped.is_empty() && self.summarized.is_empty()
+    }
+}
+
+/**
+ * The context window of a model, by the prefix of its name.
+ *
+ * @param model The concrete model name (aliases already resolved)
+ * @return The limit in tokens
+ */
+pub fn default_context_limit(model: &str) -> usize {
+    let model = model.trim().to_lowercase();
+    let model = model.strip_prefix("openai/").unwrap_or(&model);
+    // More specific prefixes come first
+    let limits: [(&str, usize); 14] = [
+        ("o1-mini", 128_000),
+        ("o1", 200_000),
+        ("o3", 200_000),
+        ("o4", 200_000),
+        ("gpt-5", 400_000),
+        ("gpt-4.1", 1_047_576),
+        ("gpt-4.5", 128_000),
+        ("gpt-4o", 128_000),
+        ("chatgpt-4o", 128_000),
+        ("gpt-4-turbo", 128_000),
+        ("gpt-4-32k", 32_768),
+        ("gpt-4", 8_192),
+        ("gpt-3.5-turbo-instruct", 4_096),
+        ("gpt-3.5", 16_385),
+    ];
+    limits
+        .iter()
+        .find(|(prefix, _)| model.starts_with(prefix))
+        .map_or(DEFAULT_CONTEXT_LIMIT, |(_, limit)| *limit)
+}
+
+/**
+ * Picks the nodes to drop so the rest fit a token budget: the lightest
+ * first, and among equal weights the later ones, as in `weighting::arrange`.
+ * The heaviest node is always kept.
+ *
+ * @param weights Each node's weight, if set
+ * @param tokens Each node's token count, including its label
+ * @param budget The tokens available for node content
+ * @return The positions of the nodes to drop, in request order
+ */
+pub fn nodes_to_drop(weights: &[Option<f32>], tokens: &[usize], budget: usize) -> Vec<usize> {
+    let kept: Vec<usize> = weighting::arrange(weights, tokens, Some(budget))
+        .into_iter()
+        .map(|node| node.index)
+        .collect();
+    (0..weights.len()).filter(|index| !kept.contains(index)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{default_context_limit, nodes_to_drop, DEFAULT_CONTEXT_LIMIT};
+
+    #[test]
+    fn looks_up_limits_by_model() {
+        assert_eq!(default_context_limit("o3-mini"), 200_000);
+        assert_eq!(default_context_limit("o1-mini"), 128_000);
+        assert_eq!(default_context_limit("openai/gpt-4o-mini"), 128_000);
+        assert_eq!(default_context_limit("gpt-4-0613"), 8_192);
+        assert_eq!(default_context_limit("gpt-3.5-turbo-instruct"), 4_096);
+        assert_eq!(default_context_limit("llama3"), DEFAULT_CONTEXT_LIMIT);
+    }
+
+    #[test]
+    fn drops_least_relevant_nodes() {
+        assert!(nodes_to_drop(&[None, None], &[100, 100], 200).is_empty());
+        assert_eq!(nodes_to_drop(&[Some(0.5), None, Some(2.0)], &[100, 100, 100], 250), vec![0]);
+        assert_eq!(nodes_to_drop(&[None, None, None], &[100, 100, 100], 150), vec![1, 2]);
+    }
+}
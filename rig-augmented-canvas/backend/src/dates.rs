@@ -0,0 +1,221 @@
+/**
+ * Dates module holds the dates and times mentioned in a note, normalized to
+ * ISO-8601 for task and calendar workflows. Absolute dates ("July 20, 1969")
+ * and simple relative ones ("tomorrow", "next Friday", "in 3 days") are
+ * resolved here against a reference date; the model's resolution is only
+ * used for fuzzier expressions ("the week after the launch").
+ */
+
+use crate::timeline::normalize_date;
+use chrono::{Datelike, Duration, NaiveDate, NaiveDateTime, Weekday};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::sync::OnceLock;
+
+/**
+ * Whether a mention names a date outright or relative to the reference date.
+ */
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum DateKind {
+    Absolute,
+    Relative,
+}
+
+/**
+ * A date or time mentioned in a note. `text` is the wording in the note and
+ * `iso` is `YYYY-MM-DD` (or `YYYY-MM`, `YYYY`, `YYYY-MM-DDTHH:MM` at the
+ * precision given).
+ */
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct MentionedDate {
+    pub text: String,
+    pub iso: String,
+    pub kind: DateKind,
+}
+
+/**
+ * JSON schema date output must match.
+ */
+pub fn dates_schema() -> Value {
+    json!({
+        "type": "object",
+        "properties": {
+            "dates": {
+                "type": "array",
+                "items": {
+                    "type": "object",
+                    "properties": {
+                        "text": { "type": "string", "minLength": 1 },
+                        "iso": { "type": "string", "minLength": 1 },
+                        "kind": { "type": "string", "enum": ["absolute", "relative"] }
+                    },
+                    "required": ["text", "iso", "kind"]
+                }
+            }
+        },
+        "required": ["dates"]
+    })
+}
+
+/**
+ * Parses a reference date sent by the plugin.
+ *
+ * @param reference_date The date as `YYYY-MM-DD`
+ * @return The date, or an error describing the expected format
+ */
+pub fn parse_reference_date(reference_date: &str) -> Result<NaiveDate, String> {
+    NaiveDate::parse_from_str(reference_date.trim(), "%Y-%m-%d")
+        .map_err(|_| format!("Invalid reference_date '{}': expected YYYY-MM-DD", reference_date))
+}
+
+/**
+ * Checks that a value is ISO-8601 at one of the precisions this module returns.
+ */
+fn is_iso(value: &str) -> bool {
+    static PARTIAL: OnceLock<Regex> = OnceLock::new();
+    let partial = PARTIAL.get_or_init(|| Regex::new(r"^\d{4}(-(0[1-9]|1[0-2]))?$").unwrap());
+
+    partial.is_match(value)
+        || NaiveDate::parse_from_str(value, "%Y-%m-%d").is_ok()
+        || NaiveDateTime::parse_from_str(value, "%Y-%m-%dT%H:%M").is_ok()
+        || NaiveDateTime::parse_from_str(value, "%Y-%m-%dT%H:%M:%S").is_ok()
+}
+
+/**
+ * Resolves the common relative expressions against a reference date:
+ * today/tomorrow/yesterday, "next <weekday>" and "last <weekday>" (the
+ * nearest such day after or before the reference), "in N days/weeks" and
+ * "N days/weeks ago".
+ *
+ * @param text The expression as written in the note
+ * @param reference The date relative expressions are resolved against
+ * @return The date, or None if the expression isn't one of these forms
+ */
+pub fn resolve_relative(text: &str, reference: NaiveDate) -> Option<NaiveDate> {
+    static WEEKDAY: OnceLock<Regex> = OnceLock::new();
+    static OFFSET: OnceLock<Regex> = OnceLock::new();
+    let weekday = WEEKDAY.get_or_init(|| Regex::new(r"^(next|last) ([a-z]+)$").unwrap());
+    let offset = OFFSET.get_or_init(|| Regex::new(r"^(?:in )?(\d{1,3}) (day|week)s?( ago)?$").unwrap());
+
+    let text = text.trim().to_lowercase().split_whitespace().collect::<Vec<&str>>().join(" ");
+    match text.as_str() {
+        "today" => return Some(reference),
+        "tomorrow" => return Some(reference + Duration::days(1)),
+        "yesterday" => return Some(reference - Duration::days(1)),
+        _ => {}
+    }
+
+    if let Some(captures) = weekday.captures(&text) {
+        let target: Weekday = captures[2].parse().ok()?;
+        let ahead = (7 + target.num_days_from_monday() as i64 - reference.weekday().num_days_from_monday() as i64) % 7;
+        return Some(if &captures[1] == "next" {
+            reference + Duration::days(if ahead == 0 { 7 } else { ahead })
+        } else {
+            reference - Duration::days(if ahead == 0 { 7 } else { 7 - ahead })
+        });
+    }
+
+    let captures = offset.captures(&text)?;
+    let ago = captures.get(3).is_some();
+    // "3 days" alone is a duration, not a date
+    if !ago && !text.starts_with("in ") {
+        return None;
+    }
+    let days = captures[1].parse::<i64>().ok()? * if &captures[2] == "week" { 7 } else { 1 };
+    Some(if ago { reference - Duration::days(days) } else { reference + Duration::days(days) })
+}
+
+/**
+ * Normalizes the dates returned by the model. Relative expressions this
+ * module understands and absolute dates it can parse are resolved here,
+ * overriding the model; otherwise the model's `iso` is kept if it is valid
+ * ISO-8601. Mentions left without a valid date are dropped.
+ *
+ * @param dates The dates returned by the model
+ * @param reference The date relative expressions are resolved against
+ * @return The normalized dates in the note's order
+ */
+pub fn resolved(dates: Vec<MentionedDate>, reference: NaiveDate) -> Vec<MentionedDate> {
+    dates
+        .into_iter()
+        .filter_map(|date| {
+            let text = date.text.trim().to_string();
+            if text.is_empty() {
+                return None;
+            }
+            if let Some(day) = resolve_relative(&text, reference) {
+                let iso = day.format("%Y-%m-%d").to_string();
+                return Some(MentionedDate { text, iso, kind: DateKind::Relative });
+            }
+            let normalized = normalize_date(&text);
+            if is_iso(&normalized) {
+                return Some(MentionedDate { text, iso: normalized, kind: DateKind::Absolute });
+            }
+            let iso = date.iso.trim().to_string();
+            is_iso(&iso).then_some(MentionedDate { text, iso, kind: date.kind })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_reference_date, resolve_relative, resolved, DateKind, MentionedDate};
+    use chrono::NaiveDate;
+
+    fn day(iso: &str) -> NaiveDate {
+        NaiveDate::parse_from_str(iso, "%Y-%m-%d").unwrap()
+    }
+
+    fn mention(text: &str, iso: &str, kind: DateKind) -> MentionedDate {
+        MentionedDate {
+            text: text.to_string(),
+            iso: iso.to_string(),
+            kind,
+        }
+    }
+
+    #[test]
+    fn resolves_relative_expressions() {
+        // 2024-05-15 is a Wednesday
+        let reference = day("2024-05-15");
+        assert_eq!(resolve_relative("Tomorrow", reference), Some(day("2024-05-16")));
+        assert_eq!(resolve_relative("next Friday", reference), Some(day("2024-05-17")));
+        assert_eq!(resolve_relative("next wednesday", reference), Some(day("2024-05-22")));
+        assert_eq!(resolve_relative("last Monday", reference), Some(day("2024-05-13")));
+        assert_eq!(resolve_relative("in 2 weeks", reference), Some(day("2024-05-29")));
+        assert_eq!(resolve_relative("3 days ago", reference), Some(day("2024-05-12")));
+        assert_eq!(resolve_relative("3 days", reference), None);
+        assert_eq!(resolve_relative("after the launch", reference), None);
+    }
+
+    #[test]
+    fn prefers_local_resolution_over_the_model() {
+        let reference = day("2024-05-15");
+        let dates = resolved(
+            vec![
+                mention("next Friday", "2024-05-24", DateKind::Relative),
+                mention("July 20th, 1969", "1969-07-21", DateKind::Absolute),
+                mention("the week after launch", "2024-06-03", DateKind::Relative),
+                mention("sometime", "soon", DateKind::Relative),
+                mention(" ", "2024-01-01", DateKind::Absolute),
+            ],
+            reference,
+        );
+        assert_eq!(
+            dates,
+            vec![
+                mention("next Friday", "2024-05-17", DateKind::Relative),
+                mention("July 20th, 1969", "1969-07-20", DateKind::Absolute),
+                mention("the week after launch", "2024-06-03", DateKind::Relative),
+            ]
+        );
+    }
+
+    #[test]
+    fn rejects_malformed_reference_dates() {
+        assert_eq!(parse_reference_date(" 2024-05-15 "), Ok(day("2024-05-15")));
+        assert!(parse_reference_date("15/05/2024").is_err());
+    }
+}
@@ -0,0 +1,113 @@
+/**
+ * Deadline module lets the client bound how long the server works on a
+ * request. When the plugin gives up waiting (its own UI timeout), there is
+ * no point in finishing a paid model call whose result will be discarded.
+ */
+
+use axum::{
+    extract::Request,
+    http::StatusCode,
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use std::time::Duration;
+
+use crate::api_error::ApiError;
+
+/// Header carrying the client's deadline, in milliseconds from receipt.
+pub const DEADLINE_HEADER: &str = "x-deadline-ms";
+
+/**
+ * Middleware aborting the handler once the `X-Deadline-Ms` budget is spent.
+ * Dropping the handler future cancels any in-flight model call. Requests
+ * without the header (or with an unparsable value) run without a deadline.
+ * For streaming endpoints the deadline covers the time until the stream starts.
+ *
+ * @param request The incoming request
+ * @param next The rest of the middleware stack and the handler
+ * @return The handler's response, or 504 Gateway Timeout if the deadline passed
+ */
+pub async fn enforce_deadline(request: Request, next: Next) -> Response {
+    let Some(deadline_ms) = request
+        .headers()
+        .get(DEADLINE_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.trim().parse::<u64>().ok())
+    else {
+        return next.run(request).await;
+    };
+    
+    match tokio::time::timeout(Duration::from_millis(deadline_ms), next.run(request)).await {
+        Ok(response) => response,
+        Err(_) => {
+            tracing::warn!("Request aborted after client deadline of {} ms", deadline_ms);
+            ApiError::new(
+                StatusCode::GATEWAY_TIMEOUT,
+                "deadline_exceeded",
+                format!(
+                    "The request could not be completed within the client deadline of {} ms and was aborted",
+                    deadline_ms
+                ),
+            )
+            .into_response()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{enforce_deadline, DEADLINE_HEADER};
+    use axum::{
+        body::{self, Body},
+        http::{Request, StatusCode},
+        middleware,
+        routing::get,
+        Router,
+    };
+    use std::time::Duration;
+    use tower::ServiceExt;
+
+    /// Sends a request to a handler that takes 100 ms, with the given deadline header.
+    async fn slow_request(deadline: Option<&str>) -> (StatusCode, serde_json::Value) {
+        let app = Router::new()
+            .route(
+                "/",
+                get(|| async {
+                    tokio::time::sleep(Duration::from_millis(100)).await;
+                    axum::Json(serde_json::json!({ "done": true }))
+                }),
+            )
+            .layer(middleware::from_fn(enforce_deadline));
+        let mut request = Request::get("/");
+        if let Some(deadline) = deadline {
+            request = request.header(DEADLINE_HEADER, deadline);
+        }
+        let response = app.oneshot(request.body(Body::empty()).unwrap()).await.unwrap();
+        let status = response.status();
+        let bytes = body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, serde_json::from_slice(&bytes).unwrap())
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn aborts_requests_past_the_deadline() {
+        let (status, body) = slow_request(Some("50")).await;
+        assert_eq!(status, StatusCode::GATEWAY_TIMEOUT);
+        assert_eq!(body["code"], "deadline_exceeded");
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn finishes_requests_within_the_deadline() {
+        let (status, body) = slow_request(Some(" 500 ")).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["done"], true);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn runs_without_a_deadline_when_the_header_is_missing_or_unparsable() {
+        for deadline in [None, Some("soon"), Some("-5"), Some("")] {
+            let (status, body) = slow_request(deadline).await;
+            assert_eq!(status, StatusCode::OK);
+            assert_eq!(body["done"], true);
+        }
+    }
+}
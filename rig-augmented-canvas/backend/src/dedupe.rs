@@ -0,0 +1,174 @@
+/**
+ * Dedupe module provides string-similarity helpers and the merge logic used
+ * to collapse near-duplicate flashcards generated from overlapping notes and
+ * near-identical generated questions.
+ */
+
+use crate::rig_service::Flashcard;
+
+/// Default similarity above which two flashcard fronts are treated as duplicates.
+pub const DEFAULT_DEDUPE_THRESHOLD: f64 = 0.85;
+
+/**
+ * Normalizes text for comparison: lowercases, drops punctuation and
+ * collapses whitespace.
+ *
+ * @param text The text to normalize
+ * @return The normalized text
+ */
+pub fn normalize(text: &str) -> String {
+    text.to_lowercase()
+        .chars()
+        .map(|c| if c.is_alphanumeric() { c } else { ' ' })
+        .collect::<String>()
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/**
+ * Computes the Levenshtein edit distance between two strings, by character.
+ *
+ * @param a The first string
+ * @param b The second string
+ * @return The minimum number of single-character edits turning a into b
+ */
+pub fn levenshtein(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    let mut current = vec![0; b.len() + 1];
+
+    for (i, ca) in a.iter().enumerate() {
+        current[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != cb);
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        std::mem::swap(&mut previous, &mut current);
+    }
+
+    previous[b.len()]
+}
+
+/**
+ * Similarity between two strings in 0.0..=1.0 based on the Levenshtein
+ * distance of their normalized forms. 1.0 means identical after normalization.
+ *
+ * @param a The first string
+ * @param b The second string
+ * @return The normalized similarity score
+ */
+pub fn similarity(a: &str, b: &str) -> f64 {
+    let a = normalize(a);
+    let b = normalize(b);
+    let longest = a.chars().count().max(b.chars().count());
+    if longest == 0 {
+        return 1.0;
+    }
+    1.0 - levenshtein(&a, &b) as f64 / longest as f64
+}
+
+/**
+ * Merges flashcards whose fronts are near-duplicates, preserving the order in
+ * which each distinct card first appeared. When two cards collide, the one
+ * with the more complete answer (longer back) is kept.
+ *
+ * @param flashcards The flashcards to deduplicate
+ * @param threshold Similarity at or above which fronts are considered duplicates
+ * @return The deduplicated flashcards
+ */
+pub fn dedupe_flashcards(flashcards: Vec<Flashcard>, threshold: f64) -> Vec<Flashcard> {
+    let mut kept: Vec<Flashcard> = Vec::new();
+
+    for card in flashcards {
+        match kept
+            .iter_mut()
+            .find(|existing| similarity(&existing.front, &card.front) >= threshold)
+        {
+            Some(existing) => {
+                if card.back.trim().len() > existing.back.trim().len() {
+                    *existing = card;
+                }
+            }
+            None => kept.push(card),
+        }
+    }
+
+    kept
+}
+
+/**
+ * Removes near-duplicate questions, keeping the first of each group. When
+ * fewer than `count` distinct questions remain, the earliest dropped ones
+ * are added back (in their original positions) until `count` is reached,
+ * so deduplication never leaves the caller short of questions it asked for.
+ *
+ * @param questions The questions as generated
+ * @param threshold Similarity at or above which questions are considered duplicates
+ * @param count The number of questions requested
+ * @return The questions to return, in their original order
+ */
+pub fn dedupe_questions(questions: Vec<String>, threshold: f64, count: usize) -> Vec<String> {
+    let mut keep = vec![false; questions.len()];
+    for (index, question) in questions.iter().enumerate() {
+        keep[index] = !questions[..index]
+            .iter()
+            .zip(&keep)
+            .any(|(earlier, kept)| *kept && similarity(earlier, question) >= threshold);
+    }
+
+    let mut missing = count.saturating_sub(keep.iter().filter(|kept| **kept).count());
+    for kept in keep.iter_mut().filter(|kept| !**kept) {
+        if missing == 0 {
+            break;
+        }
+        *kept = true;
+        missing -= 1;
+    }
+
+    questions
+        .into_iter()
+        .zip(keep)
+        .filter_map(|(question, kept)| kept.then_some(question))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::dedupe_questions;
+
+    fn questions() -> Vec<String> {
+        [
+            "What is ownership in Rust?",
+            "What is ownership in Rust",
+            "How does the borrow checker work?",
+            "What is Ownership in Rust?!",
+            "Why are lifetimes needed?",
+        ]
+        .map(str::to_string)
+        .to_vec()
+    }
+
+    #[test]
+    fn removes_near_duplicate_questions() {
+        assert_eq!(
+            dedupe_questions(questions(), 0.85, 3),
+            vec!["What is ownership in Rust?", "How does the borrow checker work?", "Why are lifetimes needed?"]
+        );
+    }
+
+    #[test]
+    fn tops_up_to_the_requested_count() {
+        let deduped = dedupe_questions(questions(), 0.85, 4);
+        assert_eq!(
+            deduped,
+            vec![
+                "What is ownership in Rust?",
+                "What is ownership in Rust",
+                "How does the borrow checker work?",
+                "Why are lifetimes needed?",
+            ]
+        );
+    }
+}
@@ -0,0 +1,46 @@
+/**
+ * Diff module computes textual differences between note versions so the
+ * model only has to summarize what actually changed.
+ */
+
+use similar::TextDiff;
+
+/**
+ * Builds a unified line diff between two versions of a note.
+ *
+ * @param old_label Label of the earlier version
+ * @param old The earlier content
+ * @param new_label Label of the later version
+ * @param new The later content
+ * @return The unified diff, or an empty string when the versions are identical
+ */
+pub fn unified_diff(old_label: &str, old: &str, new_label: &str, new: &str) -> String {
+    if old == new {
+        return String::new();
+    }
+
+    TextDiff::from_lines(old, new)
+        .unified_diff()
+        .context_radius(2)
+        .header(old_label, new_label)
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::unified_diff;
+
+    #[test]
+    fn identical_versions_have_no_diff() {
+        assert_eq!(unified_diff("v1", "# Rust\nOwnership\n", "v2", "# Rust\nOwnership\n"), "");
+    }
+
+    #[test]
+    fn changed_lines_appear_under_the_labels() {
+        let diff = unified_diff("v1", "# Rust\nOwnership\nTraits\n", "v2", "# Rust\nBorrowing\nTraits\n");
+        assert!(diff.starts_with("--- v1\n+++ v2\n"));
+        assert!(diff.contains("-Ownership\n"));
+        assert!(diff.contains("+Borrowing\n"));
+        assert!(diff.contains(" Traits\n"));
+    }
+}
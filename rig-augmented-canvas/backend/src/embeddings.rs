@@ -0,0 +1,264 @@
+/**
+ * Embeddings module computes embedding vectors for text, with a
+ * micro-batching layer for callers embedding one text at a time: requests
+ * arriving within a short window are sent to the provider in a single call
+ * and the vectors are handed back to each caller. Embeddings are requested
+ * from the configured `base_url`, like completions.
+ */
+
+use rig::embeddings::EmbeddingModel;
+use std::sync::OnceLock;
+use std::time::Duration;
+use tokio::sync::{mpsc, oneshot};
+use tokio::time::Instant;
+
+use crate::models::openai_client;
+use crate::pricing::UsageReport;
+use crate::telemetry;
+use crate::tokens;
+
+/// Embedding model used when the configuration doesn't name one.
+pub const DEFAULT_EMBEDDING_MODEL: &str = "text-embedding-3-small";
+
+/// Default time to wait for more texts before sending a batch.
+pub const DEFAULT_EMBEDDING_BATCH_WINDOW_MS: u64 = 50;
+
+/// Maximum number of texts sent to the provider in one call.
+pub const MAX_EMBEDDING_BATCH: usize = 96;
+
+/// Fragments of provider errors meaning embeddings can't be produced at all, as opposed to a failed call.
+const UNSUPPORTED_ERROR_HINTS: [&str; 6] = [
+    "404",
+    "not found",
+    "does not exist",
+    "not supported",
+    "unsupported",
+    "does not support",
+];
+
+/**
+ * Tells whether a provider error means embeddings aren't offered (an
+ * unknown endpoint or model) rather than a transient failure.
+ *
+ * @param message The error returned by `embed_texts`
+ * @return true if retrying won't help
+ */
+pub fn is_unsupported_error(message: &str) -> bool {
+    let message = message.to_lowercase();
+    UNSUPPORTED_ERROR_HINTS.iter().any(|hint| message.contains(hint))
+}
+
+/**
+ * The model, key and provider endpoint embeddings are computed with.
+ */
+#[derive(Clone, Debug, PartialEq)]
+pub struct EmbeddingTarget {
+    pub model: String,
+    pub api_key: String,
+    pub base_url: Option<String>, // The configured base_url; None for OpenAI itself
+}
+
+/**
+ * Embeds texts in a single provider call.
+ * Errors are returned as strings so results can cross task boundaries.
+ *
+ * @param target The model, key and endpoint to use
+ * @param texts The texts to embed
+ * @return One vector per text, in order
+ */
+pub async fn embed_texts(target: &EmbeddingTarget, texts: Vec<String>) -> Result<Vec<Vec<f64>>, String> {
+    let expected = texts.len();
+    let prompt_tokens: usize = texts.iter().map(|text| tokens::count_tokens(&target.model, text)).sum();
+    let client = openai_client(target.base_url.as_deref(), &target.api_key);
+    let started = Instant::now();
+    let embeddings = client.embedding_model(&target.model).embed_texts(texts).await;
+    telemetry::record_provider_call(&target.model, started.elapsed());
+    let embeddings = embeddings.map_err(|e| format!("Failed to compute embeddings: {}", e))?;
+    
+    // The provider's usage isn't passed through, so the input is counted locally; embeddings have no completion
+    let usage = UsageReport { prompt_tokens: prompt_tokens as u32, completion_tokens: 0, estimated_cost_usd: None };
+    telemetry::record_tokens(&target.model, &usage);
+    
+    if embeddings.len() != expected {
+        return Err(format!("Expected {} embeddings, got {}", expected, embeddings.len()));
+    }
+    Ok(embeddings.into_iter().map(|embedding| embedding.vec).collect())
+}
+
+/**
+ * A single text waiting to be embedded as part of a batch.
+ */
+struct EmbeddingJob {
+    target: EmbeddingTarget,
+    text: String,
+    window: Duration,
+    reply: oneshot::Sender<Result<Vec<f64>, String>>,
+}
+
+/**
+ * Collects single-text embedding requests into batches. The background task
+ * is started on first use, so the batcher can be created outside a runtime.
+ */
+#[derive(Default)]
+pub struct EmbeddingBatcher {
+    sender: OnceLock<mpsc::UnboundedSender<EmbeddingJob>>,
+}
+
+impl EmbeddingBatcher {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /**
+     * Embeds one text, sharing the provider call with other texts submitted
+     * within `window` for the same model, key and endpoint.
+     *
+     * @param target The model, key and endpoint to use
+     * @param text The text to embed
+     * @param window How long the batch waits for more texts
+     * @return The embedding vector
+     */
+    pub async fn embed(&self, target: &EmbeddingTarget, text: String, window: Duration) -> Result<Vec<f64>, String> {
+        let sender = self.sender.get_or_init(|| {
+            let (sender, receiver) = mpsc::unbounded_channel();
+            tokio::spawn(run_batches(receiver));
+            sender
+        });
+        
+        let (reply, response) = oneshot::channel();
+        sender
+            .send(EmbeddingJob {
+                target: target.clone(),
+                text,
+                window,
+                reply,
+            })
+            .map_err(|_| "Embedding batcher has stopped".to_string())?;
+        
+        response
+            .await
+            .map_err(|_| "Embedding batch was dropped".to_string())?
+    }
+}
+
+/**
+ * Gathers jobs for one window at a time (starting at each window's first
+ * job), then sends one provider call per model, key and endpoint.
+ */
+async fn run_batches(mut receiver: mpsc::UnboundedReceiver<EmbeddingJob>) {
+    while let Some(first) = receiver.recv().await {
+        let pending = collect_window(&mut receiver, first).await;
+        for group in group_by_target(pending) {
+            tokio::spawn(send_batch(group));
+        }
+    }
+}
+
+/**
+ * Collects the jobs arriving within the first job's window, up to
+ * MAX_EMBEDDING_BATCH.
+ */
+async fn collect_window(receiver: &mut mpsc::UnboundedReceiver<EmbeddingJob>, first: EmbeddingJob) -> Vec<EmbeddingJob> {
+    let deadline = Instant::now() + first.window;
+    let mut pending = vec![first];
+    while pending.len() < MAX_EMBEDDING_BATCH {
+        match tokio::time::timeout_at(deadline, receiver.recv()).await {
+            Ok(Some(job)) => pending.push(job),
+            // Window elapsed or every sender is gone
+            _ => break,
+        }
+    }
+    pending
+}
+
+/**
+ * Splits jobs into groups that can share a call: texts for different
+ * models, keys or endpoints can't.
+ */
+fn group_by_target(jobs: Vec<EmbeddingJob>) -> Vec<Vec<EmbeddingJob>> {
+    let mut groups: Vec<Vec<EmbeddingJob>> = Vec::new();
+    for job in jobs {
+        match groups.iter_mut().find(|group| group[0].target == job.target) {
+            Some(group) => group.push(job),
+            None => groups.push(vec![job]),
+        }
+    }
+    groups
+}
+
+/**
+ * Embeds a group of jobs in one call and replies to each caller.
+ */
+async fn send_batch(jobs: Vec<EmbeddingJob>) {
+    let target = jobs[0].target.clone();
+    let (texts, replies): (Vec<String>, Vec<_>) = jobs.into_iter().map(|job| (job.text, job.reply)).unzip();
+    tracing::debug!("Embedding batch of {} texts with '{}'", texts.len(), target.model);
+    
+    match embed_texts(&target, texts).await {
+        Ok(vectors) => {
+            for (reply, vector) in replies.into_iter().zip(vectors) {
+                let _ = reply.send(Ok(vector));
+            }
+        }
+        Err(e) => {
+            for reply in replies {
+                let _ = reply.send(Err(e.clone()));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{collect_window, group_by_target, is_unsupported_error, EmbeddingJob, EmbeddingTarget};
+    use std::time::Duration;
+    use tokio::sync::{mpsc, oneshot};
+
+    fn job(model: &str, text: &str) -> EmbeddingJob {
+        EmbeddingJob {
+            target: EmbeddingTarget {
+                model: model.to_string(),
+                api_key: "key".to_string(),
+                base_url: None,
+            },
+            text: text.to_string(),
+            window: Duration::from_millis(50),
+            reply: oneshot::channel().0,
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn batches_texts_arriving_within_the_window() {
+        let (sender, mut receiver) = mpsc::unbounded_channel();
+        sender.send(job("small", "a")).unwrap();
+        let late_sender = sender.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(30)).await;
+            sender.send(job("small", "b")).unwrap();
+            tokio::time::sleep(Duration::from_millis(40)).await;
+            late_sender.send(job("small", "c")).unwrap();
+        });
+
+        let first = receiver.recv().await.unwrap();
+        let batch: Vec<String> = collect_window(&mut receiver, first).await.into_iter().map(|job| job.text).collect();
+        assert_eq!(batch, ["a", "b"]);
+
+        // The text sent after the window starts the next batch
+        let next = receiver.recv().await.unwrap();
+        assert_eq!(next.text, "c");
+    }
+
+    #[test]
+    fn groups_jobs_by_target() {
+        let groups = group_by_target(vec![job("small", "a"), job("large", "b"), job("small", "c")]);
+        let texts: Vec<Vec<String>> = groups.into_iter().map(|group| group.into_iter().map(|job| job.text).collect()).collect();
+        assert_eq!(texts, [vec!["a", "c"], vec!["b"]]);
+    }
+
+    #[test]
+    fn recognizes_unsupported_embedding_errors() {
+        assert!(is_unsupported_error("Failed to compute embeddings: HttpError: 404 Not Found"));
+        assert!(is_unsupported_error("Failed to compute embeddings: The model `nomic` does not exist"));
+        assert!(!is_unsupported_error("Failed to compute embeddings: rate limit exceeded"));
+    }
+}
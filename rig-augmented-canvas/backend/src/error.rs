@@ -0,0 +1,75 @@
+/**
+ * Error module defines errors callers may want to tell apart from ordinary
+ * failures. They travel as `Box<dyn Error>` like every other error in the
+ * service; handlers recover them with `downcast_ref` and match on the
+ * variant instead of inspecting messages.
+ */
+
+use std::error::Error;
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum RigError {
+    /// The content matched one of the configured `blocked_patterns`.
+    Blocked { pattern: String },
+    /// A client-supplied JSON schema could not be compiled.
+    InvalidSchema { message: String },
+    /// The model's output still failed schema validation after a retry.
+    SchemaValidation { errors: Vec<String> },
+    /// The model provider couldn't be reached at all (connection failure or timeout).
+    ProviderUnreachable { message: String },
+    /// Embeddings are disabled or the provider can't produce them.
+    EmbeddingsUnsupported { message: String },
+    /// The model didn't answer within `request_timeout_secs`.
+    Timeout { secs: u64 },
+    /// No API key was sent and none is set in the configured environment variable.
+    MissingApiKey { env_var: Option<String> },
+    /// The provider rejected the request because of its rate limits.
+    RateLimited { message: String },
+    /// The provider returned an error for the request.
+    ProviderError { message: String },
+    /// The model's output couldn't be parsed into the expected structure.
+    ParseError { message: String },
+    /// The prompt has more tokens than `max_input_tokens` allows.
+    InputTooLarge { tokens: usize, limit: usize },
+}
+
+impl fmt::Display for RigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RigError::Blocked { pattern } => {
+                write!(f, "Request blocked by content policy (matched pattern '{}')", pattern)
+            }
+            RigError::InvalidSchema { message } => write!(f, "{}", message),
+            RigError::SchemaValidation { errors } => {
+                write!(f, "Model output does not match the schema: {}", errors.join("; "))
+            }
+            RigError::ProviderUnreachable { message } => {
+                write!(f, "Model provider is unreachable: {}", message)
+            }
+            RigError::EmbeddingsUnsupported { message } => {
+                write!(f, "Embeddings are not available: {}", message)
+            }
+            RigError::Timeout { secs } => write!(f, "Request timed out after {} seconds", secs),
+            RigError::MissingApiKey { env_var: Some(env_var) } => write!(
+                f,
+                "OpenAI API key not found. Please enter your API key in the plugin settings or set the {} environment variable before starting the backend.",
+                env_var
+            ),
+            RigError::MissingApiKey { env_var: None } => write!(
+                f,
+                "OpenAI API key not found. Please enter your API key in the plugin settings; no API key environment variable is configured."
+            ),
+            RigError::RateLimited { message } => write!(f, "Rate limited by the model provider: {}", message),
+            RigError::ProviderError { message } => write!(f, "Model provider error: {}", message),
+            RigError::ParseError { message } => write!(f, "{}", message),
+            RigError::InputTooLarge { tokens, limit } => write!(
+                f,
+                "Prompt is too large: {} tokens exceeds the limit of {} (max_input_tokens)",
+                tokens, limit
+            ),
+        }
+    }
+}
+
+impl Error for RigError {}
@@ -0,0 +1,118 @@
+/**
+ * Export module writes flashcards as delimited text that Anki (and
+ * spreadsheets) can import: one card per line, `front` then `back`. Fields
+ * containing the separator, a quote or a line break are wrapped in double
+ * quotes with inner quotes doubled, which is how Anki's importer reads them.
+ */
+
+use serde::Deserialize;
+
+use crate::rig_service::Flashcard;
+
+/**
+ * Delimited text formats cards can be exported as.
+ */
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    /// Tab-separated values, Anki's default import format.
+    #[default]
+    Tsv,
+    /// Comma-separated values.
+    Csv,
+}
+
+impl ExportFormat {
+    fn separator(self) -> char {
+        match self {
+            ExportFormat::Tsv => '\t',
+            ExportFormat::Csv => ',',
+        }
+    }
+
+    pub fn content_type(self) -> &'static str {
+        match self {
+            ExportFormat::Tsv => "text/tab-separated-values; charset=utf-8",
+            ExportFormat::Csv => "text/csv; charset=utf-8",
+        }
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            ExportFormat::Tsv => "tsv",
+            ExportFormat::Csv => "csv",
+        }
+    }
+}
+
+/**
+ * Quotes a field if it contains the separator, a quote or a line break.
+ *
+ * @param field The field's text
+ * @param separator The separator between fields
+ * @return The field as written to the file
+ */
+fn escape_field(field: &str, separator: char) -> String {
+    if field.contains([separator, '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+/**
+ * Writes rows of fields as delimited text, one row per line.
+ *
+ * @param rows The rows to write
+ * @param format The format to write them in
+ * @return The text, ending with a line break unless there are no rows
+ */
+pub fn delimited<'a>(rows: impl IntoIterator<Item = Vec<&'a str>>, format: ExportFormat) -> String {
+    let separator = format.separator();
+    rows.into_iter()
+        .map(|row| {
+            let fields: Vec<String> = row.iter().map(|field| escape_field(field, separator)).collect();
+            fields.join(&separator.to_string()) + "\n"
+        })
+        .collect()
+}
+
+/**
+ * Writes flashcards as delimited text, `front` then `back`.
+ *
+ * @param cards The cards to export
+ * @param format The format to write them in
+ * @return The text
+ */
+pub fn export_flashcards(cards: &[Flashcard], format: ExportFormat) -> String {
+    delimited(cards.iter().map(|card| vec![card.front.as_str(), card.back.as_str()]), format)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{delimited, ExportFormat};
+
+    #[test]
+    fn writes_one_row_per_line() {
+        let rows = vec![vec!["What is Rust?", "A systems language."], vec!["Year?", "2015"]];
+        assert_eq!(
+            delimited(rows.clone(), ExportFormat::Tsv),
+            "What is Rust?\tA systems language.\nYear?\t2015\n"
+        );
+        assert_eq!(delimited(rows, ExportFormat::Csv), "What is Rust?,A systems language.\nYear?,2015\n");
+        assert_eq!(delimited(Vec::<Vec<&str>>::new(), ExportFormat::Tsv), "");
+    }
+
+    #[test]
+    fn quotes_fields_with_separators_quotes_and_line_breaks() {
+        let rows = vec![vec!["Tab\there", "Line one\nline two"], vec!["Say \"hi\"", "a, b"]];
+        assert_eq!(
+            delimited(rows.clone(), ExportFormat::Tsv),
+            "\"Tab\there\"\t\"Line one\nline two\"\n\"Say \"\"hi\"\"\"\ta, b\n"
+        );
+        assert_eq!(
+            delimited(rows, ExportFormat::Csv),
+            "Tab\there,\"Line one\nline two\"\n\"Say \"\"hi\"\"\",\"a, b\"\n"
+        );
+    }
+}
@@ -0,0 +1,298 @@
+/**
+ * Extract module validates model output against a user-supplied JSON schema
+ * for the `/api/extract` endpoint, and holds the helpers that pull JSON out
+ * of responses wrapped in code fences or prose.
+ */
+
+use jsonschema::JSONSchema;
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+
+/// Keywords whose value is a reference the validator would resolve.
+const REFERENCE_KEYWORDS: [&str; 3] = ["$ref", "$dynamicRef", "$recursiveRef"];
+
+/**
+ * Compiles a user-supplied JSON schema. Only references within the schema
+ * itself (`#...`) are allowed, so a schema can't make the backend fetch URLs
+ * or read local files while resolving `$ref`.
+ *
+ * @param schema The schema as sent by the client
+ * @return The compiled schema, or a description of why it is invalid
+ */
+pub fn compile_schema(schema: &Value) -> Result<JSONSchema, String> {
+    if let Some(reference) = first_external_ref(schema) {
+        return Err(format!("Invalid JSON schema: only local references (\"#...\") are allowed, got \"{}\"", reference));
+    }
+    JSONSchema::compile(schema).map_err(|e| format!("Invalid JSON schema: {}", e))
+}
+
+/**
+ * Finds the first reference in a schema that points outside it.
+ */
+fn first_external_ref(schema: &Value) -> Option<&str> {
+    match schema {
+        Value::Object(map) => map.iter().find_map(|(key, value)| match value {
+            Value::String(reference) if REFERENCE_KEYWORDS.contains(&key.as_str()) && !reference.starts_with('#') => {
+                Some(reference.as_str())
+            }
+            _ => first_external_ref(value),
+        }),
+        Value::Array(items) => items.iter().find_map(first_external_ref),
+        _ => None,
+    }
+}
+
+/**
+ * Validates a value against a compiled schema.
+ *
+ * @return One message per violation, prefixed with the offending location
+ */
+pub fn validate(schema: &JSONSchema, instance: &Value) -> Vec<String> {
+    match schema.validate(instance) {
+        Ok(()) => Vec::new(),
+        Err(errors) => errors
+            .map(|error| {
+                let path = error.instance_path.to_string();
+                let path = if path.is_empty() { "/".to_string() } else { path };
+                format!("{}: {}", path, error)
+            })
+            .collect(),
+    }
+}
+
+/**
+ * Parses the JSON value in a model response. Models sometimes wrap the JSON
+ * in code fences or prose, so when the whole response doesn't parse, the
+ * fenced content is tried, then the first balanced object in it that parses,
+ * then the first such array. This is the one lenient parser every
+ * structured endpoint goes through.
+ *
+ * @param response The response text
+ * @return The value, or a description of why the response couldn't be parsed
+ */
+pub fn parse_json(response: &str) -> Result<Value, String> {
+    let trimmed = response.trim();
+    let unfenced = strip_code_fences(trimmed);
+    let error = match serde_json::from_str(trimmed).or_else(|_| serde_json::from_str(unfenced)) {
+        Ok(value) => return Ok(value),
+        Err(e) => e,
+    };
+    
+    // Prefer the fenced block, but the JSON may also sit in prose outside it
+    let embedded = first_json_value(unfenced, '{', '}')
+        .or_else(|| first_json_value(trimmed, '{', '}'))
+        .or_else(|| first_json_value(unfenced, '[', ']'))
+        .or_else(|| first_json_value(trimmed, '[', ']'));
+    match embedded {
+        Some(value) => Ok(value),
+        None if trimmed.contains(['{', '[']) => Err(format!("Response is not valid JSON: {}", error)),
+        None => Err("Response does not contain JSON".to_string()),
+    }
+}
+
+/**
+ * Removes a markdown code fence around a response: the text inside the
+ * first fenced block, or the response unchanged if it has none.
+ *
+ * @param response The response text
+ * @return The fenced content, without the fence lines
+ */
+fn strip_code_fences(response: &str) -> &str {
+    let Some(open) = response.find("```") else {
+        return response.trim();
+    };
+    // Skip the language tag on the opening fence line
+    let after_open = &response[open + 3..];
+    let body = match after_open.find('\n') {
+        Some(newline) => &after_open[newline + 1..],
+        None => after_open,
+    };
+    match body.find("```") {
+        Some(close) => body[..close].trim(),
+        None => body.trim(),
+    }
+}
+
+/**
+ * Finds the first balanced object or array in a response that parses,
+ * skipping brackets that appear in prose before it. Brackets inside strings
+ * are ignored.
+ *
+ * @param response The response text
+ * @param open The opening bracket, '{' or '['
+ * @param close The matching closing bracket
+ * @return The parsed value, or None if the response contains none
+ */
+fn first_json_value(response: &str, open: char, close: char) -> Option<Value> {
+    response
+        .match_indices(open)
+        .find_map(|(start, _)| serde_json::from_str(balanced(&response[start..], open, close)?).ok())
+}
+
+/**
+ * Returns the prefix of `text` (which starts with `open`) up to its matching `close`.
+ */
+fn balanced(text: &str, open: char, close: char) -> Option<&str> {
+    let mut depth = 0usize;
+    let mut in_string = false;
+    let mut escaped = false;
+    for (index, c) in text.char_indices() {
+        if in_string {
+            match c {
+                _ if escaped => escaped = false,
+                '\\' => escaped = true,
+                '"' => in_string = false,
+                _ => {}
+            }
+            continue;
+        }
+        match c {
+            '"' => in_string = true,
+            _ if c == open => depth += 1,
+            _ if c == close => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(&text[..=index]);
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+/**
+ * Deserializes the JSON in a chatty model response, found by `parse_json`.
+ *
+ * @param response The response text
+ * @return The value, or a description of why the response couldn't be parsed
+ */
+pub fn parse_object<T: DeserializeOwned>(response: &str) -> Result<T, String> {
+    serde_json::from_value(parse_json(response)?).map_err(|e| e.to_string())
+}
+
+/**
+ * Unwraps JSON that a gateway double-encoded: a response that is a JSON
+ * string whose contents are themselves a JSON object or array.
+ *
+ * @param response The response text
+ * @return The inner JSON text, or None if the response isn't double-encoded
+ */
+pub fn unwrap_double_encoded(response: &str) -> Option<String> {
+    let inner: String = serde_json::from_str(response.trim()).ok()?;
+    let inner = inner.trim();
+    let looks_like_json = (inner.starts_with('{') && inner.ends_with('}'))
+        || (inner.starts_with('[') && inner.ends_with(']'));
+    (looks_like_json && serde_json::from_str::<Value>(inner).is_ok()).then(|| inner.to_string())
+}
+
+/**
+ * Parses the JSON in a model response and checks it against a schema, for
+ * endpoints whose output has a fixed shape.
+ *
+ * @return The value, or every problem found with it
+ */
+pub fn parse_validated(schema: &JSONSchema, response: &str) -> Result<Value, Vec<String>> {
+    let value = parse_json(response).map_err(|e| vec![e])?;
+    let errors = validate(schema, &value);
+    if errors.is_empty() {
+        Ok(value)
+    } else {
+        Err(errors)
+    }
+}
+
+/**
+ * Whether a value matches a built-in schema, for testing the schemas
+ * endpoints send to the model.
+ */
+#[cfg(test)]
+pub fn conforms(schema: &Value, instance: &Value) -> bool {
+    validate(&compile_schema(schema).expect("built-in schemas compile"), instance).is_empty()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{compile_schema, first_json_value, parse_json, parse_object, strip_code_fences, unwrap_double_encoded, validate};
+    use serde_json::{json, Value};
+
+    #[test]
+    fn parses_fenced_json() {
+        let value = parse_json("Here you go:\n```json\n{\"name\": \"Ada\"}\n```").unwrap();
+        assert_eq!(value, json!({ "name": "Ada" }));
+        assert_eq!(parse_json("[1, 2]").unwrap(), json!([1, 2]));
+        assert!(parse_json("no json here").is_err());
+        
+        // Prose with brackets either side of the JSON
+        let value = parse_json(r#"Use {braces} like this: {"a": [1]} and that's it [done]"#).unwrap();
+        assert_eq!(value, json!({ "a": [1] }));
+        assert!(parse_json("{ broken").unwrap_err().starts_with("Response is not valid JSON"));
+    }
+
+    #[test]
+    fn strips_code_fences() {
+        assert_eq!(strip_code_fences("```json\n{\"a\": 1}\n```"), "{\"a\": 1}");
+        assert_eq!(strip_code_fences("Sure!\n```\n[1]\n```\nAnything else?"), "[1]");
+        assert_eq!(strip_code_fences(" {\"a\": 1} "), "{\"a\": 1}");
+    }
+
+    #[test]
+    fn finds_the_first_balanced_value() {
+        let response = r#"Use {braces} like this: {"questions": ["Why {not}?", "How \"so\"?"]} and {"extra": true}"#;
+        assert_eq!(first_json_value(response, '{', '}'), Some(json!({ "questions": ["Why {not}?", "How \"so\"?"] })));
+        assert_eq!(first_json_value("{ unclosed", '{', '}'), None);
+        assert_eq!(first_json_value("See [note] for [1, 2].", '[', ']'), Some(json!([1, 2])));
+    }
+
+    #[test]
+    fn parses_objects_in_chatty_responses() {
+        let value: Value = parse_object("Here are your questions:\n```json\n{\"questions\": [\"Why?\"]}\n```").unwrap();
+        assert_eq!(value, json!({ "questions": ["Why?"] }));
+        let value: Value = parse_object("Questions: {\"questions\": []} Hope this helps!").unwrap();
+        assert_eq!(value, json!({ "questions": [] }));
+        assert!(parse_object::<Value>("I can't help with that.").is_err());
+    }
+
+    #[test]
+    fn unwraps_double_encoded_json() {
+        let encoded = serde_json::to_string(r#"{"questions": ["Why?"]}"#).unwrap();
+        assert_eq!(unwrap_double_encoded(&encoded).as_deref(), Some(r#"{"questions": ["Why?"]}"#));
+        assert_eq!(unwrap_double_encoded(r#"{"questions": ["Why?"]}"#), None);
+        assert_eq!(unwrap_double_encoded(r#""just a sentence""#), None);
+    }
+
+    #[test]
+    fn reports_violations_with_paths() {
+        let schema = compile_schema(&json!({
+            "type": "object",
+            "properties": { "age": { "type": "integer" } },
+            "required": ["name"]
+        }))
+        .unwrap();
+        
+        assert!(validate(&schema, &json!({ "name": "Ada", "age": 36 })).is_empty());
+        
+        let errors = validate(&schema, &json!({ "age": "old" }));
+        assert_eq!(errors.len(), 2);
+        assert!(errors.iter().any(|e| e.starts_with("/age:")));
+        assert!(errors.iter().any(|e| e.starts_with("/:") && e.contains("name")));
+    }
+
+    #[test]
+    fn rejects_invalid_schemas() {
+        assert!(compile_schema(&json!({ "type": "not-a-type" })).is_err());
+    }
+
+    #[test]
+    fn rejects_non_local_references() {
+        let file_ref = json!({ "properties": { "secret": { "$ref": "file:///etc/passwd" } } });
+        assert!(compile_schema(&file_ref).unwrap_err().contains("file:///etc/passwd"));
+        assert!(compile_schema(&json!({ "items": [{ "$ref": "http://169.254.169.254/schema" }] })).is_err());
+
+        let local_ref = json!({
+            "definitions": { "name": { "type": "string" } },
+            "properties": { "name": { "$ref": "#/definitions/name" }, "$ref": { "type": "string" } }
+        });
+        assert!(compile_schema(&local_ref).is_ok());
+    }
+}
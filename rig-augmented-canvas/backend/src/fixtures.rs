@@ -0,0 +1,156 @@
+/**
+ * Fixtures module lets the backend run against recorded provider replies
+ * instead of the network. When `RIG_FIXTURES_DIR` is set, every prompt is
+ * looked up by a hash of its text; with `RIG_FIXTURES_MODE=record` missing
+ * replies are fetched from the provider and written to the directory.
+ */
+
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::path::PathBuf;
+
+/// Directory holding the recorded fixtures. Fixture mode is off when unset.
+pub const FIXTURES_DIR_ENV: &str = "RIG_FIXTURES_DIR";
+
+/// `replay` (default) or `record`.
+pub const FIXTURES_MODE_ENV: &str = "RIG_FIXTURES_MODE";
+
+/**
+ * Whether missing fixtures are an error or get recorded from the provider.
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FixtureMode {
+    Replay,
+    Record,
+}
+
+/**
+ * A recorded prompt and the reply the provider gave for it.
+ * The prompt is kept alongside the reply so fixtures are reviewable by hand.
+ */
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Fixture {
+    pub prompt: String,
+    pub response: String,
+}
+
+/**
+ * A directory of recorded fixtures.
+ */
+#[derive(Debug, Clone)]
+pub struct FixtureStore {
+    dir: PathBuf,
+    mode: FixtureMode,
+}
+
+impl FixtureStore {
+    pub fn new(dir: impl Into<PathBuf>, mode: FixtureMode) -> Self {
+        Self { dir: dir.into(), mode }
+    }
+
+    /**
+     * Builds a store from `RIG_FIXTURES_DIR` and `RIG_FIXTURES_MODE`.
+     *
+     * @return The configured store, or None when fixture mode is off
+     */
+    pub fn from_env() -> Option<Self> {
+        let dir = std::env::var(FIXTURES_DIR_ENV).ok().filter(|dir| !dir.is_empty())?;
+        let mode = match std::env::var(FIXTURES_MODE_ENV).as_deref() {
+            Ok("record") => FixtureMode::Record,
+            _ => FixtureMode::Replay,
+        };
+        Some(Self::new(dir, mode))
+    }
+
+    pub fn mode(&self) -> FixtureMode {
+        self.mode
+    }
+
+    /**
+     * Returns the file a prompt's fixture is stored in.
+     */
+    pub fn path_for(&self, prompt: &str) -> PathBuf {
+        self.dir.join(format!("{}.json", prompt_hash(prompt)))
+    }
+
+    /**
+     * Loads the recorded reply for a prompt.
+     *
+     * @param prompt The full prompt text
+     * @return The recorded reply, or None if nothing was recorded
+     */
+    pub fn load(&self, prompt: &str) -> Result<Option<String>, Box<dyn Error>> {
+        let path = self.path_for(prompt);
+        if !path.exists() {
+            return Ok(None);
+        }
+        
+        let fixture: Fixture = serde_json::from_str(&std::fs::read_to_string(&path)?)?;
+        Ok(Some(fixture.response))
+    }
+
+    /**
+     * Records the reply for a prompt, replacing any earlier recording.
+     *
+     * @param prompt The full prompt text
+     * @param response The provider's reply
+     */
+    pub fn save(&self, prompt: &str, response: &str) -> Result<(), Box<dyn Error>> {
+        std::fs::create_dir_all(&self.dir)?;
+        let fixture = Fixture {
+            prompt: prompt.to_string(),
+            response: response.to_string(),
+        };
+        std::fs::write(self.path_for(prompt), serde_json::to_string_pretty(&fixture)?)?;
+        Ok(())
+    }
+}
+
+/**
+ * Hashes prompt text with 64-bit FNV-1a. Unlike std's DefaultHasher the
+ * result is stable across Rust releases, so recorded file names stay valid.
+ *
+ * @param prompt The full prompt text
+ * @return A 16-character hexadecimal hash
+ */
+pub fn prompt_hash(prompt: &str) -> String {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for byte in prompt.as_bytes() {
+        hash ^= u64::from(*byte);
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    format!("{:016x}", hash)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{prompt_hash, FixtureMode, FixtureStore};
+
+    fn temp_store(name: &str) -> FixtureStore {
+        let dir = std::env::temp_dir().join(format!("rig-fixtures-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        FixtureStore::new(dir, FixtureMode::Replay)
+    }
+
+    #[test]
+    fn hash_is_stable() {
+        assert_eq!(prompt_hash(""), "cbf29ce484222325");
+        assert_eq!(prompt_hash("a"), "af63dc4c8601ec8c");
+        assert_ne!(prompt_hash("ab"), prompt_hash("ba"));
+    }
+
+    #[test]
+    fn missing_fixture_loads_as_none() {
+        let store = temp_store("missing");
+        assert_eq!(store.load("never recorded").unwrap(), None);
+    }
+
+    #[test]
+    fn saved_fixture_round_trips() {
+        let store = temp_store("round-trip");
+        store.save("What is Rust?", "A systems language.").unwrap();
+        assert_eq!(store.load("What is Rust?").unwrap().as_deref(), Some("A systems language."));
+        assert_eq!(store.load("What is Go?").unwrap(), None);
+        let _ = std::fs::remove_dir_all(&store.dir);
+    }
+}
@@ -0,0 +1,107 @@
+/**
+ * Frontmatter module separates the YAML block Obsidian notes often start
+ * with from the note body, so it doesn't cost tokens on every request.
+ */
+
+/**
+ * Splits a leading YAML frontmatter block from a note.
+ * The block must open with a `---` line at the very start of the note and
+ * close with a `---` or `...` line. Notes without a complete block are
+ * returned unchanged, so malformed frontmatter is never half-stripped.
+ *
+ * @param content The note text
+ * @return The frontmatter block including its delimiters and trailing newline, if any, and the remaining body
+ */
+pub fn split_frontmatter(content: &str) -> (Option<&str>, &str) {
+    let Some(after_open) = content
+        .strip_prefix("---\n")
+        .or_else(|| content.strip_prefix("---\r\n"))
+    else {
+        return (None, content);
+    };
+    
+    let mut offset = content.len() - after_open.len();
+    for line in after_open.split_inclusive('\n') {
+        offset += line.len();
+        let delimiter = line.trim_end_matches(['\r', '\n']);
+        if delimiter == "---" || delimiter == "..." {
+            return (Some(&content[..offset]), &content[offset..]);
+        }
+    }
+    
+    (None, content)
+}
+
+/**
+ * Puts a stripped frontmatter block back in front of a response. A response
+ * that already starts with a frontmatter block (the model kept or rewrote
+ * it) is returned unchanged rather than getting a second one; one that only
+ * opens with a `---` rule still gets the block.
+ *
+ * @param frontmatter The block returned by `split_frontmatter`
+ * @param response The model's response
+ * @return The response with the frontmatter in front
+ */
+pub fn reattach(frontmatter: &str, response: String) -> String {
+    if split_frontmatter(&response).0.is_some() {
+        return response;
+    }
+    
+    // A frontmatter-only note has no newline after its closing delimiter
+    let separator = if frontmatter.ends_with('\n') { "" } else { "\n" };
+    format!("{}{}{}", frontmatter, separator, response)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{reattach, split_frontmatter};
+
+    #[test]
+    fn strips_leading_frontmatter() {
+        let note = "---\ntags: [rust]\naliases: []\n---\n# Title\n\nBody";
+        assert_eq!(
+            split_frontmatter(note),
+            (Some("---\ntags: [rust]\naliases: []\n---\n"), "# Title\n\nBody")
+        );
+    }
+
+    #[test]
+    fn handles_crlf_and_dot_terminator() {
+        let note = "---\r\ntitle: x\r\n...\r\nBody";
+        assert_eq!(split_frontmatter(note), (Some("---\r\ntitle: x\r\n...\r\n"), "Body"));
+    }
+
+    #[test]
+    fn handles_frontmatter_only_note() {
+        assert_eq!(split_frontmatter("---\ntitle: x\n---"), (Some("---\ntitle: x\n---"), ""));
+    }
+
+    #[test]
+    fn leaves_notes_without_frontmatter_alone() {
+        assert_eq!(split_frontmatter("# Title\n\nBody"), (None, "# Title\n\nBody"));
+        assert_eq!(split_frontmatter("Intro\n---\ntitle: x\n---\n"), (None, "Intro\n---\ntitle: x\n---\n"));
+    }
+
+    #[test]
+    fn leaves_malformed_frontmatter_alone() {
+        // Never closed
+        assert_eq!(split_frontmatter("---\ntitle: x\n# Title"), (None, "---\ntitle: x\n# Title"));
+        // Opening delimiter not on its own line
+        assert_eq!(split_frontmatter("--- title\n---\nBody"), (None, "--- title\n---\nBody"));
+    }
+
+    #[test]
+    fn reattaches_frontmatter_once() {
+        let frontmatter = "---\ntitle: x\n---\n";
+        assert_eq!(reattach(frontmatter, "Body".to_string()), "---\ntitle: x\n---\nBody");
+        
+        // The model already returned frontmatter, so none is added
+        let rewritten = "---\ntitle: y\n---\nBody".to_string();
+        assert_eq!(reattach(frontmatter, rewritten.clone()), rewritten);
+        
+        // A leading rule isn't frontmatter
+        assert_eq!(reattach(frontmatter, "---\nBody".to_string()), "---\ntitle: x\n---\n---\nBody");
+        
+        assert_eq!(reattach("---\ntitle: x\n---", "Body".to_string()), "---\ntitle: x\n---\nBody");
+    }
+}
@@ -0,0 +1,52 @@
+/**
+ * Gaps module holds the knowledge gaps found in a note: questions it raises
+ * without answering and concepts it relies on without explaining, plus the
+ * JSON schema the output is checked against.
+ */
+
+use serde_json::{json, Value};
+use std::collections::HashSet;
+
+/**
+ * JSON schema gap output must match.
+ */
+pub fn gaps_schema() -> Value {
+    json!({
+        "type": "object",
+        "properties": {
+            "gaps": {
+                "type": "array",
+                "items": { "type": "string", "minLength": 1 }
+            }
+        },
+        "required": ["gaps"]
+    })
+}
+
+/**
+ * Trims gaps and drops blank and repeated ones (ignoring case).
+ *
+ * @param gaps The gaps returned by the model
+ * @return The remaining gaps, in the model's order
+ */
+pub fn cleaned(gaps: Vec<String>) -> Vec<String> {
+    let mut seen = HashSet::new();
+    gaps.into_iter()
+        .map(|gap| gap.trim().to_string())
+        .filter(|gap| !gap.is_empty() && seen.insert(gap.to_lowercase()))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::cleaned;
+
+    #[test]
+    fn drops_blank_and_repeated_gaps() {
+        let gaps = ["Why does the cache expire? ", "", "why does the cache expire?", "What is a write-ahead log?"];
+        assert_eq!(
+            cleaned(gaps.map(str::to_string).to_vec()),
+            vec!["Why does the cache expire?", "What is a write-ahead log?"]
+        );
+    }
+}
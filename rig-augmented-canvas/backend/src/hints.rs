@@ -0,0 +1,77 @@
+/**
+ * Hints module holds progressive hints for active recall: each hint reveals
+ * a little more than the one before, and the last stops just short of the
+ * answer. It also holds the JSON schema the output is checked against.
+ */
+
+use serde_json::{json, Value};
+
+/// Fewest hints in a sequence.
+pub const MIN_HINTS: usize = 3;
+
+/// Most hints in a sequence.
+pub const MAX_HINTS: usize = 5;
+
+/// Shortest answer checked for in hints; shorter ones match too much by accident.
+const MIN_ANSWER_CHARS: usize = 3;
+
+/**
+ * JSON schema hint output must match.
+ */
+pub fn hints_schema() -> Value {
+    json!({
+        "type": "object",
+        "properties": {
+            "hints": {
+                "type": "array",
+                "minItems": MIN_HINTS,
+                "maxItems": MAX_HINTS,
+                "items": { "type": "string", "minLength": 1 }
+            }
+        },
+        "required": ["hints"]
+    })
+}
+
+/**
+ * Trims hints and drops any that give the answer away verbatim, which
+ * would defeat the point of recalling it.
+ *
+ * @param hints The hints returned by the model
+ * @param answer The answer being hinted at
+ * @return The remaining hints, in order
+ */
+pub fn cleaned(hints: Vec<String>, answer: &str) -> Vec<String> {
+    let answer = answer.trim().to_lowercase();
+    let reveals = |hint: &str| answer.chars().count() >= MIN_ANSWER_CHARS && hint.to_lowercase().contains(&answer);
+    hints
+        .into_iter()
+        .map(|hint| hint.trim().to_string())
+        .filter(|hint| !hint.is_empty() && !reveals(hint))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{cleaned, hints_schema};
+    use crate::extract::conforms;
+    use serde_json::json;
+
+    #[test]
+    fn schema_requires_three_to_five_hints() {
+        let schema = hints_schema();
+        assert!(conforms(&schema, &json!({ "hints": ["a", "b", "c"] })));
+        assert!(!conforms(&schema, &json!({ "hints": ["a", "b"] })));
+        assert!(!conforms(&schema, &json!({ "hints": ["a", "b", "c", "d", "e", "f"] })));
+    }
+
+    #[test]
+    fn drops_hints_revealing_the_answer() {
+        let hints = ["It's an organelle. ", "Known as the powerhouse of the cell", "It's the Mitochondria", " "];
+        assert_eq!(
+            cleaned(hints.map(str::to_string).to_vec(), " mitochondria"),
+            vec!["It's an organelle.", "Known as the powerhouse of the cell"]
+        );
+        assert_eq!(cleaned(vec!["Think of a letter".to_string()], "A"), vec!["Think of a letter"]);
+    }
+}
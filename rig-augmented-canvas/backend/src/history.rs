@@ -0,0 +1,110 @@
+/**
+ * History module carries the earlier turns of a conversation, so follow-up
+ * prompts on a node keep their context. History is sent oldest first and
+ * only the most recent `max_history_turns` turns are applied.
+ */
+
+use serde::{Deserialize, Serialize};
+
+/// Default number of earlier turns applied to a prompt.
+pub const DEFAULT_MAX_HISTORY_TURNS: usize = 20;
+
+/**
+ * Who wrote a turn of the conversation.
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ChatRole {
+    User,
+    Assistant,
+}
+
+/**
+ * One earlier turn of the conversation.
+ */
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ChatTurn {
+    pub role: ChatRole,
+    pub content: String,
+}
+
+/**
+ * The turns applied to a prompt: the most recent `max_turns`, oldest first.
+ *
+ * @param history The full history, oldest first
+ * @param max_turns The number of turns to keep
+ * @return The turns to send
+ */
+pub fn recent_turns(history: &[ChatTurn], max_turns: usize) -> &[ChatTurn] {
+    &history[history.len().saturating_sub(max_turns)..]
+}
+
+/**
+ * Writes the history and the new prompt as one labelled transcript, for
+ * the legacy completion API (which has no messages) and for keying cached
+ * and recorded responses. Without history the prompt is returned unchanged.
+ *
+ * @param history The turns to include, oldest first
+ * @param content The new prompt
+ * @return The transcript
+ */
+pub fn transcript(history: &[ChatTurn], content: &str) -> String {
+    if history.is_empty() {
+        return content.to_string();
+    }
+
+    let mut transcript = String::new();
+    for turn in history {
+        let label = match turn.role {
+            ChatRole::User => "User",
+            ChatRole::Assistant => "Assistant",
+        };
+        transcript.push_str(&format!("{}: {}\n\n", label, turn.content));
+    }
+    transcript.push_str(&format!("User: {}\n\nAssistant:", content));
+    transcript
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{recent_turns, transcript, ChatRole, ChatTurn};
+
+    fn turn(role: ChatRole, content: &str) -> ChatTurn {
+        ChatTurn {
+            role,
+            content: content.to_string(),
+        }
+    }
+
+    #[test]
+    fn keeps_the_most_recent_turns() {
+        let history = vec![
+            turn(ChatRole::User, "What is ownership?"),
+            turn(ChatRole::Assistant, "Each value has one owner."),
+            turn(ChatRole::User, "And borrowing?"),
+        ];
+        assert_eq!(recent_turns(&history, 2), &history[1..]);
+        assert_eq!(recent_turns(&history, 10), &history[..]);
+        assert!(recent_turns(&history, 0).is_empty());
+    }
+
+    #[test]
+    fn writes_transcripts_oldest_first() {
+        assert_eq!(transcript(&[], "Hello"), "Hello");
+
+        let history = vec![
+            turn(ChatRole::User, "What is ownership?"),
+            turn(ChatRole::Assistant, "Each value has one owner."),
+        ];
+        assert_eq!(
+            transcript(&history, "And borrowing?"),
+            "User: What is ownership?\n\nAssistant: Each value has one owner.\n\nUser: And borrowing?\n\nAssistant:"
+        );
+    }
+
+    #[test]
+    fn parses_roles() {
+        let turn: ChatTurn = serde_json::from_str(r#"{"role":"assistant","content":"Hi"}"#).unwrap();
+        assert_eq!(turn.role, ChatRole::Assistant);
+    }
+}
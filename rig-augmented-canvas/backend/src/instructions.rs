@@ -0,0 +1,184 @@
+/**
+ * Instructions module holds the built-in prompt templates for questions,
+ * flashcards (basic and cloze) and changelog summaries in each supported
+ * instruction language. Instructions written in the language of the
+ * content steer the model's output better than English ones with a request
+ * to answer in another language. JSON field names stay in English so
+ * responses parse the same.
+ *
+ * Templates use `{name}` placeholders, filled in by `render`.
+ */
+
+/**
+ * The built-in templates for one instruction language.
+ */
+pub struct InstructionSet {
+    /// Questions prompt; placeholders `{count}` and `{content}`.
+    pub questions: &'static str,
+    /// Flashcards prompt; placeholders `{title}`, `{fields}` and `{content}`.
+    pub flashcards: &'static str,
+    /// Streaming flashcards prompt; placeholders `{title}` and `{content}`.
+    pub flashcards_stream: &'static str,
+    /// Cloze deletion flashcards prompt; placeholders `{title}` and `{content}`.
+    pub flashcards_cloze: &'static str,
+    /// What to study when no flashcard title is given.
+    pub default_title: &'static str,
+    /// The card fields, for the `{fields}` placeholder.
+    pub card_fields: &'static str,
+    /// The card fields with example sentences, for the `{fields}` placeholder.
+    pub card_fields_with_examples: &'static str,
+    /// Changelog summary prompt; placeholder `{diff}`.
+    pub changelog: &'static str,
+}
+
+const ENGLISH: InstructionSet = InstructionSet {
+    questions: "Based on the following content, generate {count} thoughtful questions that would help someone understand the material better. Return the response as a JSON object with a 'questions' field containing an array of strings.\n\nContent: {content}\n\nQuestions:",
+    flashcards: "Create flashcards for studying {title}. Each flashcard should have a question on the front and the answer on the back. Return the response as a JSON object with a 'filename' field containing a suggested filename (without extension) and a 'flashcards' field containing an array of objects, each with {fields}.\n\nContent: {content}\n\nFlashcards:",
+    flashcards_stream: "Create flashcards for studying {title}. Each flashcard should have a question on the front and the answer on the back. Output one flashcard per line as a compact JSON object with 'front' and 'back' fields, then a final line with a JSON object containing a 'filename' field with a suggested filename (without extension). Output nothing else: no array, no code fences, no commentary.\n\nContent: {content}\n\nFlashcards:",
+    flashcards_cloze: "Create cloze deletion flashcards for studying {title}. Each flashcard is one self-contained sentence from the material in which a key term or fact is hidden with Anki's cloze syntax, e.g. 'The {{c1::mitochondria}} produce most of the cell's energy.' Use {{c1::...}} for the first deletion in a sentence and {{c2::...}}, {{c3::...}} for any further ones. Return the response as a JSON object with a 'filename' field containing a suggested filename (without extension) and a 'flashcards' field containing an array of objects, each with a 'front' field holding the sentence with its deletions and a 'back' field holding the full sentence without markers.\n\nContent: {content}\n\nFlashcards:",
+    default_title: "this content",
+    card_fields: "'front' and 'back' fields",
+    card_fields_with_examples: "'front', 'back' and 'example' fields, where 'example' is a natural sentence using the term from the front in context",
+    changelog: "Summarize the following edits to a note as a short changelog entry (one to three sentences). Describe what was added, removed or reworded, not the diff syntax. Return only the summary.\n\nDiff:\n{diff}\n\nSummary:",
+};
+
+const SPANISH: InstructionSet = InstructionSet {
+    questions: "A partir del siguiente contenido, genera {count} preguntas reflexivas que ayuden a alguien a comprender mejor el material. Devuelve la respuesta como un objeto JSON con un campo 'questions' que contenga un array de cadenas.\n\nContenido: {content}\n\nPreguntas:",
+    flashcards: "Crea tarjetas de estudio para estudiar {title}. Cada tarjeta debe tener una pregunta en el anverso y la respuesta en el reverso. Devuelve la respuesta como un objeto JSON con un campo 'filename' que contenga un nombre de archivo sugerido (sin extensión) y un campo 'flashcards' que contenga un array de objetos, cada uno con {fields}.\n\nContenido: {content}\n\nTarjetas:",
+    flashcards_stream: "Crea tarjetas de estudio para estudiar {title}. Cada tarjeta debe tener una pregunta en el anverso y la respuesta en el reverso. Escribe una tarjeta por línea como un objeto JSON compacto con los campos 'front' y 'back', y después una última línea con un objeto JSON que contenga un campo 'filename' con un nombre de archivo sugerido (sin extensión). No escribas nada más: ni arrays, ni bloques de código, ni comentarios.\n\nContenido: {content}\n\nTarjetas:",
+    flashcards_cloze: "Crea tarjetas de estudio con huecos (cloze) para estudiar {title}. Cada tarjeta es una frase autónoma del material en la que un término o dato clave se oculta con la sintaxis cloze de Anki, p. ej. 'Las {{c1::mitocondrias}} producen la mayor parte de la energía de la célula.' Usa {{c1::...}} para el primer hueco de una frase y {{c2::...}}, {{c3::...}} para los siguientes. Devuelve la respuesta como un objeto JSON con un campo 'filename' que contenga un nombre de archivo sugerido (sin extensión) y un campo 'flashcards' que contenga un array de objetos, cada uno con un campo 'front' con la frase y sus huecos y un campo 'back' con la frase completa sin marcadores.\n\nContenido: {content}\n\nTarjetas:",
+    default_title: "este contenido",
+    card_fields: "los campos 'front' y 'back'",
+    card_fields_with_examples: "los campos 'front', 'back' y 'example', donde 'example' es una frase natural que usa en contexto el término del anverso",
+    changelog: "Resume las siguientes ediciones de una nota como una entrada breve de registro de cambios (de una a tres frases). Describe lo que se añadió, eliminó o reformuló, no la sintaxis del diff. Devuelve solo el resumen.\n\nDiff:\n{diff}\n\nResumen:",
+};
+
+const FRENCH: InstructionSet = InstructionSet {
+    questions: "À partir du contenu suivant, génère {count} questions réfléchies qui aideraient quelqu'un à mieux comprendre le sujet. Renvoie la réponse sous forme d'objet JSON avec un champ 'questions' contenant un tableau de chaînes.\n\nContenu : {content}\n\nQuestions :",
+    flashcards: "Crée des fiches de révision pour étudier {title}. Chaque fiche doit comporter une question au recto et la réponse au verso. Renvoie la réponse sous forme d'objet JSON avec un champ 'filename' contenant un nom de fichier suggéré (sans extension) et un champ 'flashcards' contenant un tableau d'objets, chacun avec {fields}.\n\nContenu : {content}\n\nFiches :",
+    flashcards_stream: "Crée des fiches de révision pour étudier {title}. Chaque fiche doit comporter une question au recto et la réponse au verso. Écris une fiche par ligne sous forme d'objet JSON compact avec les champs 'front' et 'back', puis une dernière ligne avec un objet JSON contenant un champ 'filename' avec un nom de fichier suggéré (sans extension). N'écris rien d'autre : pas de tableau, pas de blocs de code, pas de commentaire.\n\nContenu : {content}\n\nFiches :",
+    flashcards_cloze: "Crée des fiches de révision à trous (cloze) pour étudier {title}. Chaque fiche est une phrase autonome tirée du contenu dans laquelle un terme ou un fait clé est masqué avec la syntaxe cloze d'Anki, par exemple 'Les {{c1::mitochondries}} produisent l'essentiel de l'énergie de la cellule.' Utilise {{c1::...}} pour le premier trou d'une phrase et {{c2::...}}, {{c3::...}} pour les suivants. Renvoie la réponse sous forme d'objet JSON avec un champ 'filename' contenant un nom de fichier suggéré (sans extension) et un champ 'flashcards' contenant un tableau d'objets, chacun avec un champ 'front' contenant la phrase avec ses trous et un champ 'back' contenant la phrase complète sans marqueurs.\n\nContenu : {content}\n\nFiches :",
+    default_title: "ce contenu",
+    card_fields: "les champs 'front' et 'back'",
+    card_fields_with_examples: "les champs 'front', 'back' et 'example', où 'example' est une phrase naturelle utilisant en contexte le terme du recto",
+    changelog: "Résume les modifications suivantes d'une note sous forme d'une courte entrée de journal des modifications (une à trois phrases). Décris ce qui a été ajouté, supprimé ou reformulé, pas la syntaxe du diff. Renvoie uniquement le résumé.\n\nDiff :\n{diff}\n\nRésumé :",
+};
+
+const GERMAN: InstructionSet = InstructionSet {
+    questions: "Erstelle auf Grundlage des folgenden Inhalts {count} durchdachte Fragen, die jemandem helfen, den Stoff besser zu verstehen. Gib die Antwort als JSON-Objekt mit einem Feld 'questions' zurück, das ein Array von Strings enthält.\n\nInhalt: {content}\n\nFragen:",
+    flashcards: "Erstelle Lernkarten für {title}. Jede Karte soll auf der Vorderseite eine Frage und auf der Rückseite die Antwort haben. Gib die Antwort als JSON-Objekt mit einem Feld 'filename' mit einem vorgeschlagenen Dateinamen (ohne Endung) und einem Feld 'flashcards' mit einem Array von Objekten zurück, jeweils mit {fields}.\n\nInhalt: {content}\n\nLernkarten:",
+    flashcards_stream: "Erstelle Lernkarten für {title}. Jede Karte soll auf der Vorderseite eine Frage und auf der Rückseite die Antwort haben. Gib eine Karte pro Zeile als kompaktes JSON-Objekt mit den Feldern 'front' und 'back' aus, danach eine letzte Zeile mit einem JSON-Objekt mit einem Feld 'filename', das einen vorgeschlagenen Dateinamen (ohne Endung) enthält. Gib nichts anderes aus: kein Array, keine Codeblöcke, keine Kommentare.\n\nInhalt: {content}\n\nLernkarten:",
+    flashcards_cloze: "Erstelle Lückentext-Lernkarten (Cloze) für {title}. Jede Karte ist ein in sich verständlicher Satz aus dem Stoff, in dem ein zentraler Begriff oder Fakt mit der Cloze-Syntax von Anki verborgen ist, z. B. 'Die {{c1::Mitochondrien}} erzeugen den Großteil der Energie der Zelle.' Verwende {{c1::...}} für die erste Lücke eines Satzes und {{c2::...}}, {{c3::...}} für weitere. Gib die Antwort als JSON-Objekt mit einem Feld 'filename' mit einem vorgeschlagenen Dateinamen (ohne Endung) und einem Feld 'flashcards' mit einem Array von Objekten zurück, jeweils mit einem Feld 'front' mit dem Satz samt Lücken und einem Feld 'back' mit dem vollständigen Satz ohne Markierungen.\n\nInhalt: {content}\n\nLernkarten:",
+    default_title: "diesen Inhalt",
+    card_fields: "den Feldern 'front' und 'back'",
+    card_fields_with_examples: "den Feldern 'front', 'back' und 'example', wobei 'example' ein natürlicher Satz ist, der den Begriff von der Vorderseite im Kontext verwendet",
+    changelog: "Fasse die folgenden Änderungen an einer Notiz als kurzen Changelog-Eintrag (ein bis drei Sätze) zusammen. Beschreibe, was hinzugefügt, entfernt oder umformuliert wurde, nicht die Diff-Syntax. Gib nur die Zusammenfassung zurück.\n\nDiff:\n{diff}\n\nZusammenfassung:",
+};
+
+/// Supported instruction languages by ISO 639-1 code.
+const INSTRUCTION_SETS: [(&str, &InstructionSet); 4] = [
+    ("en", &ENGLISH),
+    ("es", &SPANISH),
+    ("fr", &FRENCH),
+    ("de", &GERMAN),
+];
+
+/**
+ * Looks up the templates for an instruction language.
+ *
+ * @param language An ISO 639-1 code (e.g., "de"), case-insensitive
+ * @return The templates, or None if the language isn't supported
+ */
+pub fn lookup(language: &str) -> Option<&'static InstructionSet> {
+    let language = language.trim();
+    INSTRUCTION_SETS
+        .iter()
+        .find(|(code, _)| code.eq_ignore_ascii_case(language))
+        .map(|(_, set)| *set)
+}
+
+/**
+ * The templates for the configured instruction language, English when none
+ * is set or the language isn't supported.
+ */
+pub fn for_language(language: Option<&str>) -> &'static InstructionSet {
+    language.and_then(lookup).unwrap_or(&ENGLISH)
+}
+
+/**
+ * The supported instruction language codes.
+ */
+pub fn supported_languages() -> Vec<&'static str> {
+    INSTRUCTION_SETS.iter().map(|(code, _)| *code).collect()
+}
+
+/**
+ * Fills in a template's `{name}` placeholders in a single pass, so values
+ * that themselves contain braces are inserted as-is. Unknown placeholders
+ * are left untouched.
+ *
+ * @param template The template to fill in
+ * @param values The placeholder names and their values
+ * @return The filled-in text
+ */
+pub fn render(template: &str, values: &[(&str, &str)]) -> String {
+    let mut rendered = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(open) = rest.find('{') {
+        rendered.push_str(&rest[..open]);
+        let placeholder = &rest[open + 1..];
+        let value = values
+            .iter()
+            .find(|(name, _)| placeholder.starts_with(name) && placeholder[name.len()..].starts_with('}'));
+        match value {
+            Some((name, value)) => {
+                rendered.push_str(value);
+                rest = &placeholder[name.len() + 1..];
+            }
+            None => {
+                rendered.push('{');
+                rest = placeholder;
+            }
+        }
+    }
+    rendered.push_str(rest);
+    rendered
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{for_language, lookup, render, INSTRUCTION_SETS};
+
+    #[test]
+    fn renders_placeholders_once() {
+        assert_eq!(
+            render("Study {title}: {content} {unknown}", &[("title", "{content}"), ("content", "notes")]),
+            "Study {content}: notes {unknown}"
+        );
+    }
+
+    #[test]
+    fn falls_back_to_english() {
+        assert!(lookup(" DE ").is_some());
+        assert!(lookup("xx").is_none());
+        assert!(for_language(Some("xx")).questions.starts_with("Based on"));
+        assert!(for_language(None).questions.starts_with("Based on"));
+    }
+
+    #[test]
+    fn every_language_has_the_same_placeholders() {
+        for (code, set) in INSTRUCTION_SETS {
+            for (template, placeholders) in [
+                (set.questions, &["{count}", "{content}"][..]),
+                (set.flashcards, &["{title}", "{fields}", "{content}"][..]),
+                (set.flashcards_stream, &["{title}", "{content}"][..]),
+                (set.flashcards_cloze, &["{title}", "{content}"][..]),
+                (set.changelog, &["{diff}"][..]),
+            ] {
+                for placeholder in placeholders {
+                    assert_eq!(template.matches(placeholder).count(), 1, "{} is missing {}", code, placeholder);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,112 @@
+/**
+ * Interview module holds the practice questions returned by the interview
+ * endpoint and the JSON schema their output is checked against. Each
+ * question carries a rubric of grading criteria for self-assessment.
+ */
+
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+/**
+ * A practice question with the criteria a good answer should meet.
+ */
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct InterviewQuestion {
+    pub question: String,
+    pub rubric: Vec<String>,
+}
+
+/**
+ * Builds the JSON schema interview output must match: an object with up to
+ * `count` questions, each with a non-empty question and rubric.
+ *
+ * @param count The maximum number of questions
+ * @return The schema
+ */
+pub fn interview_schema(count: usize) -> Value {
+    json!({
+        "type": "object",
+        "properties": {
+            "questions": {
+                "type": "array",
+                "minItems": 1,
+                "maxItems": count,
+                "items": {
+                    "type": "object",
+                    "properties": {
+                        "question": { "type": "string", "minLength": 1 },
+                        "rubric": {
+                            "type": "array",
+                            "minItems": 1,
+                            "items": { "type": "string", "minLength": 1 }
+                        }
+                    },
+                    "required": ["question", "rubric"]
+                }
+            }
+        },
+        "required": ["questions"]
+    })
+}
+
+/**
+ * Trims questions and rubric criteria, dropping blank criteria and
+ * questions left without a rubric.
+ */
+pub fn cleaned(questions: Vec<InterviewQuestion>) -> Vec<InterviewQuestion> {
+    questions
+        .into_iter()
+        .filter_map(|question| {
+            let rubric: Vec<String> = question
+                .rubric
+                .iter()
+                .map(|criterion| criterion.trim())
+                .filter(|criterion| !criterion.is_empty())
+                .map(str::to_string)
+                .collect();
+            let text = question.question.trim();
+            (!text.is_empty() && !rubric.is_empty()).then(|| InterviewQuestion {
+                question: text.to_string(),
+                rubric,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{cleaned, interview_schema, InterviewQuestion};
+    use crate::extract::conforms;
+    use serde_json::json;
+
+    #[test]
+    fn schema_limits_question_count() {
+        let schema = interview_schema(1);
+        let question = json!({ "question": "What is a closure?", "rubric": ["Mentions captured environment"] });
+
+        assert!(conforms(&schema, &json!({ "questions": [question.clone()] })));
+        assert!(!conforms(&schema, &json!({ "questions": [question.clone(), question] })));
+        assert!(!conforms(&schema, &json!({ "questions": [{ "question": "Why?", "rubric": [] }] })));
+    }
+
+    #[test]
+    fn drops_questions_without_criteria() {
+        let questions = vec![
+            InterviewQuestion {
+                question: " Explain ownership. ".to_string(),
+                rubric: vec![" Defines moves ".to_string(), "".to_string()],
+            },
+            InterviewQuestion {
+                question: "What is a trait?".to_string(),
+                rubric: vec!["  ".to_string()],
+            },
+        ];
+        assert_eq!(
+            cleaned(questions),
+            vec![InterviewQuestion {
+                question: "Explain ownership.".to_string(),
+                rubric: vec!["Defines moves".to_string()],
+            }]
+        );
+    }
+}
@@ -0,0 +1,93 @@
+/**
+ * Jargon module holds the terms a note uses without defining, with the
+ * definitions proposed for them, and the JSON schema their output is
+ * checked against. Terms the reader already knows are left out.
+ */
+
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::HashSet;
+
+/**
+ * A term used but not defined in a note, with a proposed definition.
+ */
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct UndefinedTerm {
+    pub term: String,
+    pub suggested_definition: String,
+}
+
+/**
+ * JSON schema jargon output must match.
+ */
+pub fn jargon_schema() -> Value {
+    json!({
+        "type": "object",
+        "properties": {
+            "undefined": {
+                "type": "array",
+                "items": {
+                    "type": "object",
+                    "properties": {
+                        "term": { "type": "string", "minLength": 1 },
+                        "suggested_definition": { "type": "string", "minLength": 1 }
+                    },
+                    "required": ["term", "suggested_definition"]
+                }
+            }
+        },
+        "required": ["undefined"]
+    })
+}
+
+/**
+ * Trims terms and definitions, dropping blank entries, repeated terms and
+ * terms the reader already knows. Terms are compared case-insensitively.
+ *
+ * @param terms The terms returned by the model
+ * @param known_terms Terms to leave out
+ * @return The remaining terms, in the model's order
+ */
+pub fn excluding_known(terms: Vec<UndefinedTerm>, known_terms: &[String]) -> Vec<UndefinedTerm> {
+    let mut seen: HashSet<String> = known_terms.iter().map(|term| term.trim().to_lowercase()).collect();
+    terms
+        .into_iter()
+        .filter_map(|term| {
+            let name = term.term.trim();
+            let definition = term.suggested_definition.trim();
+            if name.is_empty() || definition.is_empty() || !seen.insert(name.to_lowercase()) {
+                return None;
+            }
+            Some(UndefinedTerm {
+                term: name.to_string(),
+                suggested_definition: definition.to_string(),
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{excluding_known, UndefinedTerm};
+
+    fn term(term: &str, definition: &str) -> UndefinedTerm {
+        UndefinedTerm {
+            term: term.to_string(),
+            suggested_definition: definition.to_string(),
+        }
+    }
+
+    #[test]
+    fn drops_known_and_repeated_terms() {
+        let terms = excluding_known(
+            vec![
+                term(" Monad ", " A composable computation. "),
+                term("functor", "A mappable container."),
+                term("monad", "Repeated."),
+                term("Lens", "  "),
+            ],
+            &[" Functor".to_string()],
+        );
+        assert_eq!(terms, vec![term("Monad", "A composable computation.")]);
+    }
+}
@@ -0,0 +1,171 @@
+/**
+ * Kanban module extracts the tasks from a note and assembles the model's
+ * column assignment into a board, checking that every task lands in exactly
+ * one column.
+ */
+
+use serde::{Deserialize, Serialize};
+
+/**
+ * A board column with its cards (task texts) in order.
+ */
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct KanbanColumn {
+    pub title: String,
+    pub cards: Vec<String>,
+}
+
+/**
+ * A column as returned by the model: a title and the indices of its tasks.
+ */
+#[derive(Deserialize, Debug, Clone)]
+pub struct ColumnAssignment {
+    pub title: String,
+    #[serde(default)]
+    pub tasks: Vec<usize>,
+}
+
+/**
+ * Extracts the tasks from a note. Checklist items (`- [ ] task`, `- [x] task`)
+ * are used when present; otherwise every bullet or numbered list item is a task.
+ *
+ * @param note The note text
+ * @return The task texts, with list markers removed, in order
+ */
+pub fn extract_tasks(note: &str) -> Vec<String> {
+    let items: Vec<&str> = note.lines().filter_map(list_item).collect();
+    let checklist: Vec<String> = items
+        .iter()
+        .filter_map(|item| checkbox_text(item))
+        .filter(|task| !task.is_empty())
+        .map(str::to_string)
+        .collect();
+    
+    if !checklist.is_empty() {
+        return checklist;
+    }
+    items
+        .into_iter()
+        .map(str::trim)
+        .filter(|item| !item.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+/**
+ * Returns the text after a list marker (`-`, `*`, `+` or `1.`), if the line is a list item.
+ */
+fn list_item(line: &str) -> Option<&str> {
+    let line = line.trim_start();
+    if let Some(rest) = line
+        .strip_prefix("- ")
+        .or_else(|| line.strip_prefix("* "))
+        .or_else(|| line.strip_prefix("+ "))
+    {
+        return Some(rest);
+    }
+    
+    let digits = line.chars().take_while(|c| c.is_ascii_digit()).count();
+    if digits > 0 {
+        return line[digits..].strip_prefix(". ").or_else(|| line[digits..].strip_prefix(") "));
+    }
+    None
+}
+
+/**
+ * Returns the task text of a checklist item (`[ ] task`, `[x] task`, ...).
+ */
+fn checkbox_text(item: &str) -> Option<&str> {
+    let rest = item.strip_prefix('[')?;
+    let mut chars = rest.chars();
+    chars.next()?;
+    let rest = chars.as_str().strip_prefix(']')?;
+    Some(rest.trim())
+}
+
+/**
+ * Builds the board from the model's assignment of task indices to columns.
+ * Columns without tasks are dropped.
+ *
+ * @param tasks The tasks sent to the model
+ * @param assignments The columns returned by the model
+ * @return The board, or an error if a task is missing, repeated or unknown
+ */
+pub fn assemble_columns(tasks: &[String], assignments: Vec<ColumnAssignment>) -> Result<Vec<KanbanColumn>, String> {
+    let mut placed = vec![false; tasks.len()];
+    let mut columns = Vec::new();
+    
+    for assignment in assignments {
+        let mut cards = Vec::with_capacity(assignment.tasks.len());
+        for index in assignment.tasks {
+            let task = tasks
+                .get(index)
+                .ok_or_else(|| format!("Column '{}' references unknown task {}", assignment.title, index))?;
+            if std::mem::replace(&mut placed[index], true) {
+                return Err(format!("Task {} ('{}') was placed in more than one column", index, task));
+            }
+            cards.push(task.clone());
+        }
+        
+        if !cards.is_empty() {
+            columns.push(KanbanColumn {
+                title: assignment.title.trim().to_string(),
+                cards,
+            });
+        }
+    }
+    
+    if let Some(index) = placed.iter().position(|placed| !placed) {
+        return Err(format!("Task {} ('{}') was not placed in any column", index, tasks[index]));
+    }
+    Ok(columns)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{assemble_columns, extract_tasks, ColumnAssignment, KanbanColumn};
+
+    fn assignment(title: &str, tasks: &[usize]) -> ColumnAssignment {
+        ColumnAssignment {
+            title: title.to_string(),
+            tasks: tasks.to_vec(),
+        }
+    }
+
+    #[test]
+    fn prefers_checklist_items() {
+        let note = "# Sprint\n- [ ] Write docs\n- [x] Fix bug\n  - [/] Review PR\n- a plain bullet\n";
+        assert_eq!(extract_tasks(note), vec!["Write docs", "Fix bug", "Review PR"]);
+    }
+
+    #[test]
+    fn falls_back_to_list_items() {
+        let note = "Tasks:\n1. Plan\n2) Build\n* Ship\nNot a task";
+        assert_eq!(extract_tasks(note), vec!["Plan", "Build", "Ship"]);
+    }
+
+    #[test]
+    fn assembles_columns_in_order() {
+        let tasks = vec!["a".to_string(), "b".to_string(), "c".to_string()];
+        let columns = assemble_columns(
+            &tasks,
+            vec![assignment("To Do", &[2, 0]), assignment("Doing", &[]), assignment("Done", &[1])],
+        )
+        .unwrap();
+        assert_eq!(
+            columns,
+            vec![
+                KanbanColumn { title: "To Do".to_string(), cards: vec!["c".to_string(), "a".to_string()] },
+                KanbanColumn { title: "Done".to_string(), cards: vec!["b".to_string()] },
+            ]
+        );
+    }
+
+    #[test]
+    fn rejects_missing_repeated_and_unknown_tasks() {
+        let tasks = vec!["a".to_string(), "b".to_string()];
+        assert!(assemble_columns(&tasks, vec![assignment("To Do", &[0])]).is_err());
+        assert!(assemble_columns(&tasks, vec![assignment("To Do", &[0, 1]), assignment("Done", &[1])]).is_err());
+        assert!(assemble_columns(&tasks, vec![assignment("To Do", &[0, 1, 2])]).is_err());
+    }
+}
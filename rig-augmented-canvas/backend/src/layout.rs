@@ -0,0 +1,150 @@
+/**
+ * Layout module computes deterministic positions for generated canvas nodes.
+ * Nodes are arranged in layers: roots on the first row, their children on the
+ * next, and so on, with each row centered horizontally around x = 0.
+ */
+
+use std::collections::VecDeque;
+
+pub const NODE_WIDTH: i64 = 400;
+pub const NODE_HEIGHT: i64 = 200;
+pub const HORIZONTAL_GAP: i64 = 80;
+pub const VERTICAL_GAP: i64 = 120;
+
+/**
+ * Top-left corner of a laid-out node.
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Position {
+    pub x: i64,
+    pub y: i64,
+}
+
+/**
+ * Assigns each node to a layer using a breadth-first walk from the roots.
+ * Nodes only reachable through cycles are treated as additional roots.
+ *
+ * @param node_count Number of nodes in the graph
+ * @param edges Directed edges as (from, to) node indices
+ * @return The layer index of each node
+ */
+pub fn assign_layers(node_count: usize, edges: &[(usize, usize)]) -> Vec<usize> {
+    let mut children = vec![Vec::new(); node_count];
+    let mut has_parent = vec![false; node_count];
+    for &(from, to) in edges {
+        if from < node_count && to < node_count && from != to {
+            children[from].push(to);
+            has_parent[to] = true;
+        }
+    }
+
+    let mut layers: Vec<Option<usize>> = vec![None; node_count];
+    let mut queue = VecDeque::new();
+    for (index, parented) in has_parent.iter().enumerate() {
+        if !parented {
+            layers[index] = Some(0);
+            queue.push_back(index);
+        }
+    }
+
+    let mut next_root = 0;
+    loop {
+        while let Some(node) = queue.pop_front() {
+            let layer = layers[node].unwrap_or(0);
+            for &child in &children[node] {
+                if layers[child].is_none() {
+                    layers[child] = Some(layer + 1);
+                    queue.push_back(child);
+                }
+            }
+        }
+
+        // Anything left is part of a cycle with no entry point; start a new walk from it
+        while next_root < node_count && layers[next_root].is_some() {
+            next_root += 1;
+        }
+        if next_root == node_count {
+            break;
+        }
+        layers[next_root] = Some(0);
+        queue.push_back(next_root);
+    }
+
+    layers.into_iter().map(|layer| layer.unwrap_or(0)).collect()
+}
+
+/**
+ * Computes a position for every node, keeping the input order within each layer.
+ *
+ * @param node_count Number of nodes in the graph
+ * @param edges Directed edges as (from, to) node indices
+ * @return The position of each node, indexed like the input
+ */
+pub fn layered_layout(node_count: usize, edges: &[(usize, usize)]) -> Vec<Position> {
+    let layers = assign_layers(node_count, edges);
+    let layer_count = layers.iter().copied().max().map_or(0, |max| max + 1);
+
+    let mut rows: Vec<Vec<usize>> = vec![Vec::new(); layer_count];
+    for (index, &layer) in layers.iter().enumerate() {
+        rows[layer].push(index);
+    }
+
+    let mut positions = vec![Position { x: 0, y: 0 }; node_count];
+    for (layer, row) in rows.iter().enumerate() {
+        let count = row.len() as i64;
+        let row_width = count * NODE_WIDTH + (count - 1).max(0) * HORIZONTAL_GAP;
+        let start_x = -row_width / 2;
+        let y = layer as i64 * (NODE_HEIGHT + VERTICAL_GAP);
+
+        for (column, &index) in row.iter().enumerate() {
+            positions[index] = Position {
+                x: start_x + column as i64 * (NODE_WIDTH + HORIZONTAL_GAP),
+                y,
+            };
+        }
+    }
+
+    positions
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{assign_layers, layered_layout, Position, NODE_HEIGHT, VERTICAL_GAP};
+
+    const ROW: i64 = NODE_HEIGHT + VERTICAL_GAP;
+
+    #[test]
+    fn layers_a_chain() {
+        assert_eq!(assign_layers(3, &[(0, 1), (1, 2)]), vec![0, 1, 2]);
+        assert_eq!(
+            layered_layout(3, &[(0, 1), (1, 2)]),
+            vec![Position { x: -200, y: 0 }, Position { x: -200, y: ROW }, Position { x: -200, y: 2 * ROW }]
+        );
+    }
+
+    #[test]
+    fn centers_a_fan_out() {
+        assert_eq!(assign_layers(3, &[(0, 1), (0, 2)]), vec![0, 1, 1]);
+        assert_eq!(
+            layered_layout(3, &[(0, 1), (0, 2)]),
+            vec![Position { x: -200, y: 0 }, Position { x: -440, y: ROW }, Position { x: 40, y: ROW }]
+        );
+    }
+
+    #[test]
+    fn starts_a_pure_cycle_at_its_first_node() {
+        assert_eq!(assign_layers(3, &[(0, 1), (1, 2), (2, 0)]), vec![0, 1, 2]);
+    }
+
+    #[test]
+    fn layers_a_cycle_below_its_root() {
+        assert_eq!(assign_layers(3, &[(0, 1), (1, 2), (2, 1)]), vec![0, 1, 2]);
+    }
+
+    #[test]
+    fn ignores_self_edges_and_out_of_range_edges() {
+        assert_eq!(assign_layers(2, &[(0, 0), (1, 1), (0, 5), (7, 1)]), vec![0, 0]);
+        assert_eq!(assign_layers(2, &[(1, 0), (0, 0)]), vec![1, 0]);
+        assert_eq!(layered_layout(0, &[(0, 1)]), Vec::new());
+    }
+}
@@ -6,22 +6,126 @@
 
 mod rig_service;
 mod models;
+mod analogy;
+mod answer;
+mod api_error;
+mod auth;
+mod batch;
+mod body_limit;
+mod budget;
+mod cache;
+mod canvas;
+mod chunk;
+mod cite;
+mod cloze;
+mod compare;
+mod concepts;
+mod config_file;
+mod context;
+mod dates;
+mod deadline;
+mod dedupe;
+mod diff;
+mod embeddings;
+mod error;
+mod export;
+mod extract;
+mod fixtures;
+mod frontmatter;
+mod gaps;
+mod hints;
+mod history;
+mod instructions;
+mod interview;
+mod jargon;
+mod kanban;
+mod layout;
+mod ndjson;
+mod note_abstract;
+mod occlusion;
+mod outline;
+mod plugin_errors;
+mod pricing;
+mod profiles;
+mod rate_limit;
+mod quiz;
+mod reading_levels;
+mod refactor;
+mod request_limit;
+mod request_trace;
+mod resume;
+mod sanitize;
+mod semantic_diff;
+mod split;
+mod srs;
+mod stream_limit;
+mod telemetry;
+mod template;
+mod throttle;
+mod timeline;
+mod tokens;
+mod transform;
+mod truncate;
+mod usage;
+mod vision;
+mod weighting;
 
 use axum::{
-    extract::State,
-    http::StatusCode,
-    response::IntoResponse,
+    extract::{Path, Query, State},
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    middleware,
+    response::{
+        sse::{KeepAlive, Sse},
+        IntoResponse,
+    },
     routing::{get, post},
     Json, Router,
 };
-use models::ModelConfig;
+use batch::{BatchItem, BatchItemResult, BatchSummary};
+use futures::StreamExt;
+use metrics_exporter_prometheus::PrometheusHandle;
+use models::{ModelConfig, MultiNodeMode, SamplingParams, UsageReporting};
 use tokio::net::TcpListener;
 use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+use std::error::Error;
 use std::sync::Arc;
 use tower_http::cors::{Any, CorsLayer};
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
 
-use crate::rig_service::RigService;
+use crate::analogy::AnalogyMapping;
+use crate::api_error::ApiError;
+use crate::cache::CachePolicy;
+use crate::kanban::KanbanColumn;
+use crate::note_abstract::{NoteAbstract, DEFAULT_MAX_KEYWORDS, MAX_KEYWORDS_LIMIT};
+use crate::occlusion::OcclusionCard;
+use crate::srs::{ReviewCard, ScheduledCard};
+use crate::cite::CitationStyle;
+use crate::compare::{ModelComparison, MAX_COMPARE_MODELS};
+use crate::concepts::ConceptMap;
+use crate::context::{ContextReport, NodeContent};
+use crate::export::ExportFormat;
+use crate::pricing::UsageReport;
+use crate::history::ChatTurn;
+use crate::interview::InterviewQuestion;
+use crate::quiz::QuizQuestion;
+use crate::jargon::UndefinedTerm;
+use crate::outline::OutlineNode;
+use crate::reading_levels::ReadingLevel;
+use crate::refactor::{RefactorNote, RefactorSuggestion};
+use crate::resume::StreamRegistry;
+use crate::semantic_diff::{SemanticDiff, DEFAULT_SEMANTIC_DIFF_THRESHOLD};
+use crate::split::SplitResponse;
+use crate::stream_limit::{StreamLimiter, StreamPermit, TooManyStreams};
+use crate::timeline::TimelineEvent;
+use crate::dates::MentionedDate;
+use crate::usage::UsageMeter;
+use crate::vision::{AltText, ImageInput};
+use crate::ndjson::LineBuffer;
+use crate::rig_service::{
+    ChangelogEntry, CommandSuggestion, FlashcardStreamLine, NoteVersion, PromptOptions, ProsCons, Reference, RigService,
+    StudyDeck, ToneAnalysis,
+};
 
 /**
  * AppState holds shared state accessible by all request handlers.
@@ -30,6 +134,9 @@ use crate::rig_service::RigService;
 #[derive(Clone)]
 struct AppState {
     rig_service: Arc<RigService>,
+    streams: Arc<StreamRegistry>, // Recent SSE streams, resumable via Last-Event-ID
+    stream_limiter: Arc<StreamLimiter>, // Open streaming connections, capped by max_streams
+    metrics: PrometheusHandle,     // Renders the recorded metrics for /metrics
 }
 
 /**
@@ -67,23 +174,115 @@ async fn main() {
         }
     };
 
+    // Record metrics for /metrics
+    let metrics = match telemetry::install() {
+        Ok(handle) => handle,
+        Err(e) => {
+            eprintln!("Failed to install the metrics recorder: {}", e);
+            std::process::exit(1);
+        }
+    };
+
+    // Require a bearer token if one is configured
+    let auth_token = auth::AuthToken::from_env();
+    if auth_token.is_enabled() {
+        tracing::info!("Bearer token auth enabled ({} is set)", auth::AUTH_TOKEN_ENV);
+    }
+
+    // Limit requests per client if a limit is configured
+    let rate_limiter = Arc::new(rate_limit::RateLimiter::from_env());
+    if let Some(limit) = rate_limiter.requests_per_minute() {
+        tracing::info!("Rate limiting clients to {} requests per minute", limit);
+    }
+
+    // Cap request bodies, measured after decompression
+    let max_body_bytes = body_limit::max_body_bytes_from_env();
+    tracing::info!("Accepting request bodies up to {} bytes", max_body_bytes);
+
     // Create shared application state
-    let state = AppState { rig_service };
+    let state = AppState {
+        rig_service,
+        streams: Arc::new(StreamRegistry::new()),
+        stream_limiter: Arc::new(StreamLimiter::new()),
+        metrics,
+    };
 
     // CORS configuration to allow cross-origin requests from the Obsidian plugin
     let cors = CorsLayer::new()
         .allow_origin(Any)
         .allow_methods(Any)
-        .allow_headers(Any);
+        .allow_headers(Any)
+        // Let the plugin read the id to report alongside failures
+        .expose_headers([header::HeaderName::from_static(request_trace::REQUEST_ID_HEADER)]);
 
     // Create router with all API endpoints
     let app = Router::new()
         .route("/health", get(health_check))
+        .route("/metrics", get(render_metrics))
         .route("/api/prompt", post(handle_prompt))
+        .route("/api/prompt/stream", post(handle_prompt_stream))
+        .route("/api/prompt/batch", post(handle_prompt_batch))
+        .route("/api/prompt/batch/stream", post(handle_prompt_batch_stream))
+        .route("/api/tokens", post(handle_tokens))
+        .route("/api/summarize", post(handle_summarize))
         .route("/api/questions", post(handle_questions))
+        .route("/api/answer", post(handle_answer))
+        .route("/api/quiz", post(handle_quiz))
         .route("/api/flashcards", post(handle_flashcards))
+        .route("/api/flashcards/dedupe", post(handle_flashcards_dedupe))
+        .route("/api/flashcards/export", post(handle_flashcards_export))
+        .route("/api/flashcards/stream", post(handle_flashcards_stream))
+        .route("/api/flashcards/translate", post(handle_flashcards_translate))
+        .route("/api/schedule-cards", post(handle_schedule_cards))
+        .route("/api/study-deck", post(handle_study_deck))
+        .route("/api/canvas", post(handle_canvas))
+        .route("/api/elaborate-outline", post(handle_elaborate_outline))
+        .route("/api/tags", post(handle_tags))
+        .route("/api/tags/consistent", post(handle_consistent_tags))
+        .route("/api/changelog", post(handle_changelog))
+        .route("/api/lengths", post(handle_lengths))
+        .route("/api/references", post(handle_references))
+        .route("/api/cite", post(handle_cite))
+        .route("/api/tone", post(handle_tone))
+        .route("/api/pros-cons", post(handle_pros_cons))
+        .route("/api/kanban", post(handle_kanban))
+        .route("/api/concept-map", post(handle_concept_map))
+        .route("/api/extract", post(handle_extract))
+        .route("/api/interview", post(handle_interview))
+        .route("/api/refactor", post(handle_refactor))
+        .route("/api/alt-text", post(handle_alt_text))
+        .route("/api/timeline", post(handle_timeline))
+        .route("/api/dates", post(handle_dates))
+        .route("/api/abstract", post(handle_abstract))
+        .route("/api/simplify-levels", post(handle_simplify_levels))
+        .route("/api/jargon", post(handle_jargon))
+        .route("/api/gaps", post(handle_gaps))
+        .route("/api/analogy", post(handle_analogy))
+        .route("/api/hints", post(handle_hints))
+        .route("/api/compare-models", post(handle_compare_models))
+        .route("/api/draft", post(handle_draft))
+        .route("/api/commands", post(handle_commands))
+        .route("/api/template-from-examples", post(handle_template_from_examples))
+        .route("/api/occlusion", post(handle_occlusion))
+        .route("/api/embeddings", post(handle_embeddings))
+        .route("/api/semantic-diff", post(handle_semantic_diff))
         .route("/api/model-config", get(get_model_config))
         .route("/api/model-config", post(update_model_config))
+        .route("/api/cache/clear", post(clear_cache))
+        .route("/api/profiles", get(list_profiles))
+        .route("/api/profiles/:name", post(upsert_profile))
+        // Innermost, so everything the handler does (including its deadline) runs with the profile
+        .layer(middleware::from_fn_with_state(state.rig_service.clone(), profiles::select_profile))
+        .layer(middleware::from_fn(deadline::enforce_deadline))
+        // Checked before the profile so unauthenticated requests learn nothing about profiles
+        .layer(middleware::from_fn_with_state(auth_token, auth::require_token))
+        .layer(middleware::from_fn_with_state(rate_limiter, rate_limit::limit_requests))
+        // Outside the deadline layer so its 504 is rewritten too
+        .layer(middleware::from_fn_with_state(state.rig_service.clone(), plugin_errors::errors_as_200));
+    // Accept `Content-Encoding: gzip` bodies; the limit is enforced as they're decoded
+    let app = body_limit::limit_bodies(app, max_body_bytes)
+        // Outside everything but CORS so every response, including rejections, gets an id
+        .layer(middleware::from_fn_with_state(state.rig_service.clone(), request_trace::trace_request))
         .layer(cors)
         .with_state(state);
 
@@ -91,15 +290,41 @@ async fn main() {
     let addr = std::net::SocketAddr::from(([127, 0, 0, 1], 3000));
     tracing::info!("Listening on {}", addr);
     let listener = TcpListener::bind(addr).await.unwrap();
-    axum::serve(listener, app).await.unwrap();
+    // Connection info gives the rate limiter each client's address
+    axum::serve(listener, app.into_make_service_with_connect_info::<std::net::SocketAddr>())
+        .await
+        .unwrap();
+}
+
+#[derive(Serialize)]
+struct HealthResponse {
+    status: &'static str,
+    active_streams: usize,
 }
 
 /**
  * Simple health check endpoint to verify the server is running.
- * Returns a 200 OK status with no body.
+ * Returns a 200 OK status with the number of streaming connections open.
+ */
+async fn health_check(State(state): State<AppState>) -> impl IntoResponse {
+    (
+        StatusCode::OK,
+        Json(HealthResponse {
+            status: "ok",
+            active_streams: state.stream_limiter.active(),
+        }),
+    )
+}
+
+/**
+ * Endpoint exposing the recorded metrics in the Prometheus text format.
  */
-async fn health_check() -> impl IntoResponse {
-    StatusCode::OK
+async fn render_metrics(State(state): State<AppState>) -> impl IntoResponse {
+    (
+        StatusCode::OK,
+        [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+        state.metrics.render(),
+    )
 }
 
 /**
@@ -113,48 +338,162 @@ async fn get_model_config(
         Ok(config) => (
             StatusCode::OK,
             Json(config),
-        ),
+        )
+            .into_response(),
         Err(e) => {
             tracing::error!("Error getting model config: {}", e);
-            (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(ModelConfig::default()),
-            )
+            service_error(&state, e.as_ref()).into_response()
         }
     }
 }
 
+/**
+ * Query parameters of `POST /api/model-config`.
+ */
+#[derive(Deserialize)]
+struct ConfigQuery {
+    persist: Option<bool>,
+}
+
 /**
  * Endpoint to update the model configuration.
- * Accepts a JSON payload with the new configuration.
+ * Accepts a JSON payload with the new configuration; with `?persist=true`
+ * it is also written to the configuration file.
  */
 async fn update_model_config(
     State(state): State<AppState>,
+    Query(query): Query<ConfigQuery>,
     Json(config): Json<ModelConfig>,
 ) -> impl IntoResponse {
-    match state.rig_service.update_config(config) {
-        Ok(_) => StatusCode::OK,
+    match state.rig_service.update_config(config, query.persist.unwrap_or(false)) {
+        Ok(_) => StatusCode::OK.into_response(),
         Err(e) => {
             tracing::error!("Error updating model config: {}", e);
-            StatusCode::INTERNAL_SERVER_ERROR
+            service_error(&state, e.as_ref()).into_response()
+        }
+    }
+}
+
+/**
+ * Response for `POST /api/cache/clear`.
+ */
+#[derive(Serialize)]
+struct ClearCacheResponse {
+    cleared: usize,
+}
+
+/**
+ * Endpoint emptying the response cache, so every prompt is answered fresh.
+ * Returns how many cached responses were removed.
+ */
+async fn clear_cache(State(state): State<AppState>) -> impl IntoResponse {
+    let cleared = state.rig_service.clear_response_cache();
+    tracing::info!("Cleared {} cached responses", cleared);
+    (StatusCode::OK, Json(ClearCacheResponse { cleared }))
+}
+
+/**
+ * Response for `GET /api/profiles`: every named profile's configuration.
+ */
+#[derive(Serialize)]
+struct ProfilesResponse {
+    profiles: BTreeMap<String, ModelConfig>,
+}
+
+/**
+ * Endpoint listing the named configuration profiles.
+ */
+async fn list_profiles(State(state): State<AppState>) -> impl IntoResponse {
+    match state.rig_service.list_profiles() {
+        Ok(profiles) => (StatusCode::OK, Json(ProfilesResponse { profiles })).into_response(),
+        Err(e) => {
+            tracing::error!("Error listing profiles: {}", e);
+            service_error(&state, e.as_ref()).into_response()
+        }
+    }
+}
+
+/**
+ * Endpoint adding a named configuration profile, or replacing the one with
+ * that name. Accepts the same JSON payload as `POST /api/model-config`.
+ */
+async fn upsert_profile(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+    Json(config): Json<ModelConfig>,
+) -> impl IntoResponse {
+    match state.rig_service.upsert_profile(&name, config) {
+        Ok(_) => StatusCode::OK.into_response(),
+        Err(e) => {
+            tracing::error!("Error saving profile '{}': {}", name, e);
+            ApiError::bad_request(e.to_string()).into_response()
+        }
+    }
+}
+
+/**
+ * Combines the nodes of a multi-node request with its prompt, as sent in
+ * `combine` mode. Nodes are ordered by weight, and the lightest are dropped
+ * when they don't fit `max_multinode_chars`.
+ *
+ * @param nodes The request's nodes
+ * @param prompt The user's prompt
+ * @param system_prompt The request's system prompt, counted against the budget when a ratio is set
+ * @param config The current configuration
+ * @return The combined content
+ */
+fn combine_nodes(nodes: &[NodeContent], prompt: &str, system_prompt: Option<&str>, config: &ModelConfig) -> String {
+    // With a ratio set, the budget covers the whole prompt and the instruction's share is never cut
+    let content_budget = match (config.max_multinode_chars, config.instruction_budget_ratio) {
+        (Some(max_chars), Some(ratio)) => {
+            let instruction_chars = system_prompt.map_or(0, |system_prompt| system_prompt.chars().count())
+                + prompt.chars().count();
+            Some(budget::content_budget(max_chars, instruction_chars, ratio))
+        }
+        (max_chars, _) => max_chars,
+    };
+    
+    // Order nodes by weight, dropping the lightest if they don't fit the budget
+    let weights: Vec<Option<f32>> = nodes.iter().map(|node| node.weight).collect();
+    let lengths: Vec<usize> = nodes.iter().map(|node| node.content.chars().count()).collect();
+    let arranged = weighting::arrange(&weights, &lengths, content_budget);
+    if arranged.len() < nodes.len() {
+        tracing::warn!(
+            "Dropped {} low-weight nodes to fit max_multinode_chars",
+            nodes.len() - arranged.len()
+        );
+    }
+    
+    // Combine all node contents with the prompt
+    let mut combined_content = String::new();
+    
+    // Add each node's content, labelled with its emphasis and instruction when it has them
+    for (i, arranged_node) in arranged.iter().enumerate() {
+        let node = &nodes[arranged_node.index];
+        let labels: Vec<String> = arranged_node
+            .emphasis
+            .map(str::to_string)
+            .into_iter()
+            .chain(node.instruction().map(|instruction| format!("instruction: {}", instruction)))
+            .collect();
+        if labels.is_empty() {
+            combined_content.push_str(&format!("Node {}: {}\n\n", i + 1, node.content));
+        } else {
+            combined_content.push_str(&format!("Node {} ({}): {}\n\n", i + 1, labels.join("; "), node.content));
         }
     }
+    
+    // Add the user's prompt
+    combined_content.push_str(&format!("Prompt: {}", prompt));
+    combined_content
 }
 
 /**
  * Data structures for handling prompt requests and responses.
  * 
- * NodeContent: Represents a single canvas node with its content
  * PromptRequest: Can be either a single node request or a multi-node request
  * PromptResponse: Contains the AI-generated response
  */
-#[derive(Deserialize)]
-struct NodeContent {
-    #[allow(dead_code)]
-    id: String,
-    content: String,
-}
-
 #[derive(Deserialize)]
 #[serde(untagged)]
 enum PromptRequest {
@@ -162,99 +501,550 @@ enum PromptRequest {
     SingleNode {
         content: String,
         system_prompt: Option<String>,
+        include_datetime: Option<bool>,
+        params: Option<SamplingParams>,
+        // Shorthand for the same fields in params, which win if both are set
+        temperature: Option<f64>,
+        max_tokens: Option<u64>,
+        reattach_frontmatter: Option<bool>,
+        // Earlier turns of the conversation, oldest first
+        #[serde(default)]
+        history: Vec<ChatTurn>,
     },
     // For processing multiple nodes with a custom prompt
     MultiNode {
         nodes: Vec<NodeContent>,
         prompt: String,
         system_prompt: Option<String>,
+        include_datetime: Option<bool>,
+        params: Option<SamplingParams>,
+        temperature: Option<f64>,
+        max_tokens: Option<u64>,
     },
 }
 
+impl PromptRequest {
+    /**
+     * Moves top-level `temperature` and `max_tokens` into `params`, so the
+     * rest of the request handling only has to look there.
+     */
+    fn with_shorthand_params(mut self) -> Self {
+        let (PromptRequest::SingleNode { params, temperature, max_tokens, .. }
+        | PromptRequest::MultiNode { params, temperature, max_tokens, .. }) = &mut self;
+        if temperature.is_some() || max_tokens.is_some() {
+            let shorthand = SamplingParams {
+                temperature: temperature.take(),
+                max_tokens: max_tokens.take(),
+                ..Default::default()
+            };
+            *params = Some(params.take().unwrap_or_default().merged_over(&shorthand));
+        }
+        self
+    }
+}
+
 #[derive(Serialize)]
 struct PromptResponse {
     response: String,
+    truncated: bool,
+    #[serde(flatten, skip_serializing_if = "Option::is_none")]
+    split: Option<SplitResponse>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    effective_config: Option<ModelConfig>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    context: Option<ContextReport>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    usage: Option<UsageReport>,
+}
+
+/**
+ * Query parameters accepted by the prompt endpoint.
+ * explain_config (or the X-Rig-Echo-Config header) echoes the effective,
+ * secret-redacted configuration used for the request. cache=false skips
+ * the response cache and asks the model for a fresh answer.
+ */
+#[derive(Deserialize, Default)]
+struct PromptQuery {
+    explain_config: Option<bool>,
+    cache: Option<bool>,
 }
 
 /**
  * Main endpoint for handling AI prompts.
  * Supports both single-node and multi-node requests.
  * Extracts the API key from headers if provided.
+ *
+ * Multi-node requests follow the configured `multinode_mode`:
+ * - `combine` (default): all nodes are merged into one prompt and the
+ *   response is `{ "response": String }`.
+ * - `per_node`: the prompt runs against each node independently and the
+ *   response is `{ "results": [{ id, response } | { id, error }], "summary": {...} }`,
+ *   the same shape as `/api/prompt/batch`.
+ *
+ * Cacheable responses carry an `ETag`; sending it back in `If-None-Match`
+ * returns `304 Not Modified` while the result is still cached.
  */
 async fn handle_prompt(
     State(state): State<AppState>,
+    Query(query): Query<PromptQuery>,
     headers: axum::http::HeaderMap,
     Json(request): Json<PromptRequest>,
 ) -> impl IntoResponse {
+    let request = request.with_shorthand_params();
+    
     // Check for API key in header (allows per-request API keys)
     let api_key = headers.get("x-openai-key").and_then(|v| v.to_str().ok()).map(|s| s.to_string());
-    match request {
-        PromptRequest::SingleNode { content, system_prompt } => {
-            match state
-                .rig_service
-                .generate_response(&content, system_prompt.as_deref(), api_key.as_deref())
-                .await
-            {
-                Ok(response) => (
-                    StatusCode::OK,
-                    Json(PromptResponse { response }),
-                ),
-                Err(e) => {
-                    tracing::error!("Error generating response: {}", e);
-                    let error_message = if e.to_string().contains("API key not found") {
-                        "Error: OpenAI API key not found. Please enter your API key in the plugin settings or set the OPENAI_API_KEY environment variable before starting the backend."
-                    } else {
-                        &format!("Error: {}", e)
-                    };
-                    
-                    (
-                        StatusCode::INTERNAL_SERVER_ERROR,
-                        Json(PromptResponse {
-                            response: error_message.to_string(),
-                        }),
-                    )
-                }
-            }
+    let explain_config = query.explain_config.unwrap_or(false) || headers.contains_key("x-rig-echo-config");
+    let effective_config = |options: &PromptOptions| {
+        if explain_config {
+            state.rig_service.explain_config(options).ok()
+        } else {
+            None
+        }
+    };
+    
+    // Reject out-of-range sampling parameters before doing any work
+    let params = match &request {
+        PromptRequest::SingleNode { params, .. } | PromptRequest::MultiNode { params, .. } => params,
+    };
+    if let Some(Err(e)) = params.as_ref().map(SamplingParams::validate) {
+        return ApiError::bad_request(format!("Invalid params: {}", e)).into_response();
+    }
+    
+    // Node weights must be usable for ordering
+    if let PromptRequest::MultiNode { nodes, .. } = &request {
+        if let Some(node) = nodes.iter().find(|node| node.weight.is_some_and(|weight| !weighting::is_valid_weight(weight))) {
+            return ApiError::bad_request(format!(
+                "Invalid weight for node '{}': must be a non-negative number",
+                node.id
+            ))
+            .into_response();
+        }
+    }
+    
+    // Assemble the content to send; per-node mode returns its own response shape
+    let (content, system_prompt, options, context) = match request {
+        PromptRequest::SingleNode { content, system_prompt, include_datetime, params, reattach_frontmatter, history, .. } => {
+            let options = PromptOptions {
+                include_datetime,
+                params,
+                reattach_frontmatter: reattach_frontmatter.unwrap_or(false),
+                history,
+                cache: CachePolicy::from_flag(query.cache),
+                ..Default::default()
+            };
+            (content, system_prompt, options, None)
         },
-        PromptRequest::MultiNode { nodes, prompt, system_prompt } => {
-            // Combine all node contents with the prompt
-            let mut combined_content = String::new();
+        PromptRequest::MultiNode { nodes, prompt, system_prompt, include_datetime, params, .. } => {
+            let options = PromptOptions {
+                include_datetime,
+                params,
+                cache: CachePolicy::from_flag(query.cache),
+                ..Default::default()
+            };
+            let config = state.rig_service.get_config().unwrap_or_default();
             
-            // Add each node's content
-            for (i, node) in nodes.iter().enumerate() {
-                combined_content.push_str(&format!("Node {}: {}\n\n", i + 1, node.content));
+            // In per-node mode, run the prompt against each node independently
+            if config.multinode_mode == MultiNodeMode::PerNode {
+                let items = nodes
+                    .into_iter()
+                    .map(|node| {
+                        let content = node.prompt_content(&prompt);
+                        BatchItem {
+                            id: node.id,
+                            content,
+                            system_prompt: system_prompt.clone(),
+                        }
+                    })
+                    .collect();
+                let mut results: Vec<BatchItemResult> = batch::run_batch(
+                    &state.rig_service,
+                    items,
+                    api_key.as_deref(),
+                    &options,
+                    batch::effective_concurrency(None),
+                )
+                .collect()
+                .await;
+                results.sort_by_key(|result| result.index);
+                
+                let mut summary = BatchSummary::default();
+                for result in &results {
+                    summary.record(result);
+                }
+                
+                let effective_config = effective_config(&options);
+                return (StatusCode::OK, Json(BatchPromptResponse { results, summary, effective_config })).into_response();
             }
             
-            // Add the user's prompt
-            combined_content.push_str(&format!("Prompt: {}", prompt));
-            
-            // Generate response
-            match state
+            // Drop or summarize nodes that don't fit the model's context window
+            let fitted = state
                 .rig_service
-                .generate_response(&combined_content, system_prompt.as_deref(), api_key.as_deref())
-                .await
-            {
-                Ok(response) => (
-                    StatusCode::OK,
-                    Json(PromptResponse { response }),
-                ),
+                .fit_to_context(nodes, &prompt, system_prompt.as_deref(), api_key.as_deref(), &options)
+                .await;
+            let (nodes, context) = match fitted {
+                Ok(fitted) => fitted,
                 Err(e) => {
-                    tracing::error!("Error generating multi-node response: {}", e);
-                    let error_message = if e.to_string().contains("API key not found") {
-                        "Error: OpenAI API key not found. Please enter your API key in the plugin settings or set the OPENAI_API_KEY environment variable before starting the backend."
-                    } else {
-                        &format!("Error: {}", e)
-                    };
-                    
-                    (
-                        StatusCode::INTERNAL_SERVER_ERROR,
-                        Json(PromptResponse {
-                            response: error_message.to_string(),
-                        }),
-                    )
+                    tracing::error!("Error fitting nodes to the context window: {}", e);
+                    return service_error(&state, e.as_ref()).into_response();
                 }
+            };
+            
+            let combined_content = combine_nodes(&nodes, &prompt, system_prompt.as_deref(), &config);
+            (combined_content, system_prompt, options, Some(context).filter(|context| !context.is_empty()))
+        }
+    };
+    
+    // Answer with 304 when the client already holds the cached result for this exact request
+    let cache_key = state
+        .rig_service
+        .response_key(&content, system_prompt.as_deref(), &options)
+        .ok()
+        .flatten();
+    if let (CachePolicy::Use, Some(key)) = (options.cache, &cache_key) {
+        if let Some(etag) = state.rig_service.cached_response(key).map(|cached| cache::response_etag(&cached.text)) {
+            if etag_matches(&headers, &etag) {
+                return (StatusCode::NOT_MODIFIED, [(header::ETAG, quoted_etag(&etag))]).into_response();
+            }
+        }
+    }
+    
+    // Generate response
+    match state
+        .rig_service
+        .generate_response_with_usage(&content, system_prompt.as_deref(), api_key.as_deref(), &options)
+        .await
+    {
+        Ok((response, usage)) => {
+            // Tag the response as the service stored it
+            let etag = cache_key.map(|_| cache::response_etag(&response.text));
+            let mut http_response = (
+                StatusCode::OK,
+                Json(PromptResponse {
+                    split: state.rig_service.split_response(&response.text),
+                    response: response.text,
+                    truncated: response.truncated,
+                    effective_config: effective_config(&options),
+                    context,
+                    usage,
+                }),
+            )
+                .into_response();
+            if let Some(etag) = etag {
+                http_response.headers_mut().insert(header::ETAG, quoted_etag(&etag));
+            }
+            http_response
+        },
+        Err(e) => {
+            tracing::error!("Error generating response: {}", e);
+            service_error(&state, e.as_ref()).into_response()
+        }
+    }
+}
+
+/**
+ * Maps a service error to its response, showing the configured
+ * `fallback_message` instead of the raw error when the provider can't be
+ * reached. The raw error stays in `details` for logs.
+ */
+fn service_error(state: &AppState, error: &(dyn Error + 'static)) -> ApiError {
+    ApiError::from_error(error).with_fallback(state.rig_service.fallback_message(error))
+}
+
+/**
+ * Formats a response's ETag as a quoted header value.
+ */
+fn quoted_etag(etag: &str) -> HeaderValue {
+    HeaderValue::from_str(&format!("\"{}\"", etag)).unwrap_or_else(|_| HeaderValue::from_static("\"\""))
+}
+
+/**
+ * Checks whether the request's If-None-Match header matches the given ETag.
+ * Handles lists of tags, weak validators and the `*` wildcard.
+ */
+fn etag_matches(headers: &HeaderMap, etag: &str) -> bool {
+    headers
+        .get_all(header::IF_NONE_MATCH)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(|tag| tag.trim().trim_start_matches("W/").trim_matches('"'))
+        .any(|tag| tag == "*" || tag == etag)
+}
+
+/**
+ * Data structures for handling token estimate responses.
+ * Requests have the same shapes as `/api/prompt`.
+ */
+#[derive(Serialize)]
+struct TokensResponse {
+    estimated_tokens: usize,
+}
+
+/**
+ * Endpoint for counting the input tokens a prompt request would send,
+ * without calling the model. Content is assembled as `/api/prompt`
+ * assembles it; in per-node mode the counts of all nodes are added up.
+ */
+async fn handle_tokens(
+    State(state): State<AppState>,
+    Json(request): Json<PromptRequest>,
+) -> impl IntoResponse {
+    let request = request.with_shorthand_params();
+    let params = match &request {
+        PromptRequest::SingleNode { params, .. } | PromptRequest::MultiNode { params, .. } => params,
+    };
+    if let Some(Err(e)) = params.as_ref().map(SamplingParams::validate) {
+        return ApiError::bad_request(format!("Invalid params: {}", e)).into_response();
+    }
+    
+    let config = state.rig_service.get_config().unwrap_or_default();
+    let counted = match request {
+        PromptRequest::SingleNode { content, system_prompt, include_datetime, params, history, .. } => {
+            let options = PromptOptions { include_datetime, params, history, ..Default::default() };
+            state.rig_service.count_input_tokens(&content, system_prompt.as_deref(), &options)
+        },
+        PromptRequest::MultiNode { nodes, prompt, system_prompt, include_datetime, params, .. } => {
+            let options = PromptOptions { include_datetime, params, ..Default::default() };
+            if config.multinode_mode == MultiNodeMode::PerNode {
+                nodes
+                    .iter()
+                    .map(|node| {
+                        state
+                            .rig_service
+                            .count_input_tokens(&node.prompt_content(&prompt), system_prompt.as_deref(), &options)
+                    })
+                    .sum()
+            } else {
+                let content = combine_nodes(&nodes, &prompt, system_prompt.as_deref(), &config);
+                state.rig_service.count_input_tokens(&content, system_prompt.as_deref(), &options)
             }
         }
+    };
+    
+    match counted {
+        Ok(estimated_tokens) => (StatusCode::OK, Json(TokensResponse { estimated_tokens })).into_response(),
+        Err(e) => {
+            tracing::error!("Error counting tokens: {}", e);
+            service_error(&state, e.as_ref()).into_response()
+        }
+    }
+}
+
+/**
+ * Data structures for handling batch prompt requests and responses.
+ * Each item is processed independently with bounded concurrency.
+ */
+#[derive(Deserialize)]
+struct BatchPromptRequest {
+    items: Vec<BatchItem>,
+    concurrency: Option<usize>,
+}
+
+#[derive(Serialize)]
+struct BatchPromptResponse {
+    results: Vec<BatchItemResult>,
+    summary: BatchSummary,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    effective_config: Option<ModelConfig>,
+}
+
+/**
+ * Endpoint for running many single-node prompts in one request.
+ * Results are returned in the same order as the submitted items,
+ * with per-item errors instead of failing the whole batch.
+ */
+async fn handle_prompt_batch(
+    State(state): State<AppState>,
+    headers: axum::http::HeaderMap,
+    Json(request): Json<BatchPromptRequest>,
+) -> impl IntoResponse {
+    // Check for API key in header (allows per-request API keys)
+    let api_key = headers.get("x-openai-key").and_then(|v| v.to_str().ok()).map(|s| s.to_string());
+    let concurrency = batch::effective_concurrency(request.concurrency);
+
+    let mut results: Vec<BatchItemResult> =
+        batch::run_batch(&state.rig_service, request.items, api_key.as_deref(), &PromptOptions::default(), concurrency)
+            .collect()
+            .await;
+    results.sort_by_key(|result| result.index);
+
+    let mut summary = BatchSummary::default();
+    for result in &results {
+        summary.record(result);
+    }
+
+    (
+        StatusCode::OK,
+        Json(BatchPromptResponse {
+            results,
+            summary,
+            effective_config: None,
+        }),
+    )
+}
+
+/**
+ * Streaming variant of the batch endpoint.
+ * Emits a `result` SSE event for each item as soon as it finishes
+ * (`{ id, response }` or `{ id, error }`), followed by a final `summary` event.
+ * Estimated token counts are sent as `usage` events per `usage_reporting`.
+ */
+async fn handle_prompt_batch_stream(
+    State(state): State<AppState>,
+    headers: axum::http::HeaderMap,
+    Json(request): Json<BatchPromptRequest>,
+) -> axum::response::Response {
+    let permit = match acquire_stream(&state) {
+        Ok(permit) => permit,
+        Err(rejection) => return rejection.into_response(),
+    };
+    
+    // A reconnecting client resumes the buffered stream instead of rerunning the batch
+    if let Some((buffer, cursor)) = resumed_stream(&state, &headers) {
+        let events = stream_limit::hold(resume::subscribe(buffer, Some(cursor)), permit);
+        return Sse::new(events).keep_alive(KeepAlive::default()).into_response();
+    }
+    
+    // Check for API key in header (allows per-request API keys)
+    let api_key = headers.get("x-openai-key").and_then(|v| v.to_str().ok()).map(|s| s.to_string());
+    let concurrency = batch::effective_concurrency(request.concurrency);
+    let usage_reporting = usage_reporting(&state);
+    let buffer = state.streams.create();
+    let producer = buffer.clone();
+    // The generation keeps its slot until it ends, even if the client drops
+    let producer_permit = permit.clone();
+
+    tokio::spawn(profiles::carry(async move {
+        let _permit = producer_permit;
+        resume::produce(
+            &producer,
+            stream_batch_into(&state, request, api_key.as_deref(), concurrency, usage_reporting, &producer),
+        )
+        .await;
+    }));
+
+    let events = stream_limit::hold(resume::subscribe(buffer, None), permit);
+    Sse::new(events).keep_alive(KeepAlive::default()).into_response()
+}
+
+/**
+ * Runs a batch and pushes a `result` event per item, then a `summary` event, into the stream
+ * buffer, with `usage` events as configured. Items keep running while the client is away so
+ * it can resume, until the stream is abandoned.
+ */
+async fn stream_batch_into(
+    state: &AppState,
+    request: BatchPromptRequest,
+    api_key: Option<&str>,
+    concurrency: usize,
+    usage_reporting: UsageReporting,
+    buffer: &resume::StreamBuffer,
+) {
+    // Every item's prompt is sent, so prompt tokens are counted up front
+    let mut usage = UsageMeter::default();
+    for item in &request.items {
+        usage.add_prompt(item.system_prompt.as_deref().unwrap_or_default());
+        usage.add_prompt(&item.content);
+    }
+    
+    let options = PromptOptions::default();
+    let results = batch::run_batch(&state.rig_service, request.items, api_key, &options, concurrency);
+    futures::pin_mut!(results);
+
+    let mut summary = BatchSummary::default();
+    while let Some(result) = results.next().await {
+        summary.record(&result);
+        buffer.push("result", &result);
+        usage.add_response(result.response.as_deref().unwrap_or_default());
+        if usage_reporting == UsageReporting::Incremental {
+            buffer.push("usage", &usage.stats());
+        }
+    }
+
+    if usage_reporting == UsageReporting::Final {
+        buffer.push("usage", &usage.stats());
+    }
+    buffer.push("summary", &summary);
+}
+
+/**
+ * The configured `usage_reporting` mode for streaming endpoints.
+ */
+fn usage_reporting(state: &AppState) -> UsageReporting {
+    state
+        .rig_service
+        .get_config()
+        .map(|config| config.usage_reporting)
+        .unwrap_or_default()
+}
+
+/**
+ * Takes a streaming slot, or rejects the stream when `max_streams` streams
+ * are already open.
+ */
+fn acquire_stream(state: &AppState) -> Result<StreamPermit, TooManyStreams> {
+    let max_streams = state.rig_service.get_config().ok().and_then(|config| config.max_streams);
+    state.stream_limiter.try_acquire(max_streams).ok_or_else(|| {
+        tracing::warn!("Rejecting stream: {} streams already open", state.stream_limiter.active());
+        TooManyStreams
+    })
+}
+
+/**
+ * Looks up the buffered stream a reconnecting client's `Last-Event-ID` refers to.
+ */
+fn resumed_stream(state: &AppState, headers: &HeaderMap) -> Option<(Arc<resume::StreamBuffer>, u64)> {
+    let last_event_id = headers.get(resume::LAST_EVENT_ID_HEADER)?.to_str().ok()?;
+    let resumed = state.streams.resume(last_event_id);
+    if resumed.is_none() {
+        tracing::info!("Cannot resume stream from event '{}', starting over", last_event_id);
+    }
+    resumed
+}
+
+/**
+ * Data structures for handling summarization requests and responses.
+ */
+#[derive(Deserialize)]
+struct SummarizeRequest {
+    content: String,
+    max_words: Option<usize>,
+}
+
+#[derive(Serialize)]
+struct SummarizeResponse {
+    summary: String,
+}
+
+/**
+ * Endpoint for summarizing content, e.g. a canvas node.
+ * Takes content and an optional `max_words` limit (0 means no limit).
+ * Returns the summary.
+ */
+async fn handle_summarize(
+    State(state): State<AppState>,
+    headers: axum::http::HeaderMap,
+    Json(request): Json<SummarizeRequest>,
+) -> impl IntoResponse {
+    // Check for API key in header (allows per-request API keys)
+    let api_key = headers.get("x-openai-key").and_then(|v| v.to_str().ok()).map(|s| s.to_string());
+    let max_words = request.max_words.filter(|max_words| *max_words > 0);
+    
+    match state
+        .rig_service
+        .summarize(&request.content, max_words, api_key.as_deref())
+        .await
+    {
+        Ok(summary) => (
+            StatusCode::OK,
+            Json(SummarizeResponse { summary }),
+        )
+            .into_response(),
+        Err(e) => {
+            tracing::error!("Error summarizing content: {}", e);
+            service_error(&state, e.as_ref()).into_response()
+        }
     }
 }
 
@@ -293,37 +1083,121 @@ async fn handle_questions(
         Ok(questions) => (
             StatusCode::OK,
             Json(QuestionsResponse { questions }),
-        ),
+        )
+            .into_response(),
         Err(e) => {
             tracing::error!("Error generating questions: {}", e);
-            let error_message = if e.to_string().contains("API key not found") {
-                "OpenAI API key not found. Please enter your API key in the plugin settings or set the OPENAI_API_KEY environment variable before starting the backend."
-            } else {
-                &format!("{}", e)
-            };
-            
-            (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(QuestionsResponse {
-                    questions: vec![format!("Error: {}", error_message)],
-                }),
-            )
+            service_error(&state, e.as_ref()).into_response()
         }
     }
 }
 
 /**
- * Data structures for handling flashcard generation requests and responses.
+ * Data structures for handling question answering requests and responses.
  */
 #[derive(Deserialize)]
-struct FlashcardsRequest {
+struct AnswerRequest {
     content: String,
-    title: Option<String>,
+    questions: Vec<String>,
 }
 
-// Use the Flashcard type from rig_service to avoid type mismatch
-use crate::rig_service::Flashcard;
-
+#[derive(Serialize)]
+struct AnswerResponse {
+    answers: Vec<String>,
+}
+
+/**
+ * Endpoint for answering questions (e.g., from `/api/questions`) strictly
+ * from the given content, for self-quizzing. Returns one answer per
+ * question, aligned index-for-index; questions the content doesn't answer
+ * get "Not stated in the content".
+ * Responds with 422 if the model's output doesn't match the expected schema.
+ */
+async fn handle_answer(
+    State(state): State<AppState>,
+    headers: axum::http::HeaderMap,
+    Json(request): Json<AnswerRequest>,
+) -> impl IntoResponse {
+    // Check for API key in header (allows per-request API keys)
+    let api_key = headers.get("x-openai-key").and_then(|v| v.to_str().ok()).map(|s| s.to_string());
+    
+    match state
+        .rig_service
+        .answer_questions(&request.content, &request.questions, api_key.as_deref())
+        .await
+    {
+        Ok(answers) => (
+            StatusCode::OK,
+            Json(AnswerResponse { answers }),
+        )
+            .into_response(),
+        Err(e) => {
+            tracing::error!("Error answering questions: {}", e);
+            service_error(&state, e.as_ref()).into_response()
+        }
+    }
+}
+
+/**
+ * Data structures for handling quiz requests and responses.
+ */
+#[derive(Deserialize)]
+struct QuizRequest {
+    content: String,
+    count: Option<usize>,
+}
+
+#[derive(Serialize)]
+struct QuizResponse {
+    questions: Vec<QuizQuestion>,
+}
+
+/**
+ * Endpoint for generating multiple-choice quiz questions, each with its
+ * choices and the index of the correct one.
+ * Responds with 422 if the model's output is still malformed after a retry.
+ */
+async fn handle_quiz(
+    State(state): State<AppState>,
+    headers: axum::http::HeaderMap,
+    Json(request): Json<QuizRequest>,
+) -> impl IntoResponse {
+    // Check for API key in header (allows per-request API keys)
+    let api_key = headers.get("x-openai-key").and_then(|v| v.to_str().ok()).map(|s| s.to_string());
+    let count = request.count.unwrap_or(5).clamp(1, 20);
+    
+    match state
+        .rig_service
+        .generate_quiz(&request.content, count, api_key.as_deref())
+        .await
+    {
+        Ok(questions) => (
+            StatusCode::OK,
+            Json(QuizResponse { questions }),
+        )
+            .into_response(),
+        Err(e) => {
+            tracing::error!("Error generating quiz: {}", e);
+            service_error(&state, e.as_ref()).into_response()
+        }
+    }
+}
+
+/**
+ * Data structures for handling flashcard generation requests and responses.
+ */
+#[derive(Deserialize)]
+struct FlashcardsRequest {
+    content: String,
+    title: Option<String>,
+    with_examples: Option<bool>,
+    #[serde(default)]
+    style: FlashcardStyle,
+}
+
+// Use the Flashcard type from rig_service to avoid type mismatch
+use crate::rig_service::{Flashcard, FlashcardStyle};
+
 #[derive(Serialize)]
 struct FlashcardsResponse {
     filename: String,
@@ -332,7 +1206,9 @@ struct FlashcardsResponse {
 
 /**
  * Endpoint for generating flashcards based on content.
- * Takes content and an optional title parameter.
+ * Takes content, an optional title parameter and an optional with_examples
+ * flag that adds an example sentence to each card (useful for vocabulary).
+ * `style: "cloze"` asks for Anki cloze deletions instead of questions.
  * Returns a suggested filename and a list of flashcards with front/back content.
  */
 async fn handle_flashcards(
@@ -345,7 +1221,13 @@ async fn handle_flashcards(
     
     match state
         .rig_service
-        .generate_flashcards(&request.content, request.title.as_deref(), api_key.as_deref())
+        .generate_flashcards(
+            &request.content,
+            request.title.as_deref(),
+            request.with_examples.unwrap_or(false),
+            request.style,
+            api_key.as_deref(),
+        )
         .await
     {
         Ok((filename, flashcards)) => (
@@ -354,22 +1236,1961 @@ async fn handle_flashcards(
                 filename,
                 flashcards: flashcards.clone(),
             }),
-        ),
+        )
+            .into_response(),
         Err(e) => {
             tracing::error!("Error generating flashcards: {}", e);
-            let error_message = if e.to_string().contains("API key not found") {
-                "OpenAI API key not found. Please enter your API key in the plugin settings or set the OPENAI_API_KEY environment variable before starting the backend."
-            } else {
-                &format!("{}", e)
-            };
-            
+            service_error(&state, e.as_ref()).into_response()
+        }
+    }
+}
+
+/**
+ * Request for `/api/flashcards/export`: a flashcards request and the format
+ * to export the cards in.
+ */
+#[derive(Deserialize)]
+struct FlashcardsExportRequest {
+    #[serde(flatten)]
+    flashcards: FlashcardsRequest,
+    #[serde(default)]
+    format: ExportFormat,
+}
+
+/**
+ * Endpoint for generating flashcards as a file Anki can import.
+ * Takes the same fields as `/api/flashcards` plus `format` (`tsv`, the
+ * default, or `csv`) and returns the cards as delimited text, with the
+ * suggested filename in `Content-Disposition`.
+ */
+async fn handle_flashcards_export(
+    State(state): State<AppState>,
+    headers: axum::http::HeaderMap,
+    Json(request): Json<FlashcardsExportRequest>,
+) -> impl IntoResponse {
+    // Check for API key in header (allows per-request API keys)
+    let api_key = headers.get("x-openai-key").and_then(|v| v.to_str().ok()).map(|s| s.to_string());
+    let FlashcardsExportRequest { flashcards: request, format } = request;
+    
+    match state
+        .rig_service
+        .generate_flashcards(
+            &request.content,
+            request.title.as_deref(),
+            request.with_examples.unwrap_or(false),
+            request.style,
+            api_key.as_deref(),
+        )
+        .await
+    {
+        Ok((filename, flashcards)) => {
+            let disposition = format!("attachment; filename=\"{}.{}\"", filename.replace('"', ""), format.extension());
+            let disposition = HeaderValue::from_str(&disposition)
+                .unwrap_or_else(|_| HeaderValue::from_static("attachment"));
             (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(FlashcardsResponse {
-                    filename: format!("error: {}", error_message),
-                    flashcards: vec![],
-                }),
+                StatusCode::OK,
+                [
+                    (header::CONTENT_TYPE, HeaderValue::from_static(format.content_type())),
+                    (header::CONTENT_DISPOSITION, disposition),
+                ],
+                export::export_flashcards(&flashcards, format),
             )
+                .into_response()
+        },
+        Err(e) => {
+            tracing::error!("Error exporting flashcards: {}", e);
+            service_error(&state, e.as_ref()).into_response()
+        }
+    }
+}
+
+/**
+ * Data structures for handling streamed prompt requests and their events.
+ */
+#[derive(Deserialize)]
+struct PromptStreamRequest {
+    content: String,
+    system_prompt: Option<String>,
+}
+
+#[derive(Serialize)]
+struct PromptStreamToken {
+    text: String,
+}
+
+#[derive(Serialize)]
+struct PromptStreamDone {
+    chars: usize,
+}
+
+/**
+ * Streaming variant of the single-node prompt endpoint.
+ * Emits a `token` SSE event with `{ text }` for each chunk as the model
+ * generates it, then a final `done` event with the response length in
+ * characters so the client knows to stop listening. Failures are reported
+ * as an `error` event.
+ */
+async fn handle_prompt_stream(
+    State(state): State<AppState>,
+    headers: axum::http::HeaderMap,
+    Json(request): Json<PromptStreamRequest>,
+) -> axum::response::Response {
+    let permit = match acquire_stream(&state) {
+        Ok(permit) => permit,
+        Err(rejection) => return rejection.into_response(),
+    };
+    
+    // A reconnecting client resumes the buffered stream instead of regenerating
+    if let Some((buffer, cursor)) = resumed_stream(&state, &headers) {
+        let events = stream_limit::hold(resume::subscribe(buffer, Some(cursor)), permit);
+        return Sse::new(events).keep_alive(KeepAlive::default()).into_response();
+    }
+    
+    // Check for API key in header (allows per-request API keys)
+    let api_key = headers.get("x-openai-key").and_then(|v| v.to_str().ok()).map(|s| s.to_string());
+    let usage_reporting = usage_reporting(&state);
+    let buffer = state.streams.create();
+    let producer = buffer.clone();
+    // The generation keeps its slot until it ends, even if the client drops
+    let producer_permit = permit.clone();
+    
+    tokio::spawn(profiles::carry(async move {
+        let _permit = producer_permit;
+        resume::produce(&producer, stream_prompt_into(&state, &request, api_key.as_deref(), usage_reporting, &producer)).await;
+    }));
+    
+    let events = stream_limit::hold(resume::subscribe(buffer, None), permit);
+    Sse::new(events).keep_alive(KeepAlive::default()).into_response()
+}
+
+/**
+ * Streams a response and pushes `token`, then `done` (or `error`) events into the stream buffer,
+ * with `usage` events as configured. Generation continues while the client is away so it can resume.
+ */
+async fn stream_prompt_into(
+    state: &AppState,
+    request: &PromptStreamRequest,
+    api_key: Option<&str>,
+    usage_reporting: UsageReporting,
+    buffer: &resume::StreamBuffer,
+) {
+    // Convert the error up front so nothing non-Send is held across an await
+    let chunks = state
+        .rig_service
+        .stream_response(&request.content, request.system_prompt.as_deref(), api_key)
+        .await
+        .map_err(|e| stream_error(state, e.as_ref()));
+    let mut chunks = match chunks {
+        Ok(chunks) => chunks,
+        Err(e) => {
+            tracing::error!("Error streaming response: {}", e.error);
+            buffer.push("error", &e);
+            return;
+        }
+    };
+    
+    let mut usage = UsageMeter::default();
+    usage.add_prompt(&request.content);
+    if let Some(system_prompt) = &request.system_prompt {
+        usage.add_prompt(system_prompt);
+    }
+    let mut chars = 0;
+    while let Some(chunk) = chunks.next().await {
+        match chunk {
+            Ok(text) => {
+                chars += text.chars().count();
+                usage.add_chunk(&text);
+                buffer.push("token", &PromptStreamToken { text });
+                if usage_reporting == UsageReporting::Incremental {
+                    buffer.push("usage", &usage.stats());
+                }
+            }
+            Err(e) => {
+                tracing::error!("Error streaming response: {}", e);
+                buffer.push("error", &stream_error(state, e.as_ref()));
+                return;
+            }
+        }
+    }
+    
+    if usage_reporting == UsageReporting::Final {
+        buffer.push("usage", &usage.stats());
+    }
+    buffer.push("done", &PromptStreamDone { chars });
+}
+
+#[derive(Serialize)]
+struct FlashcardsStreamDone {
+    filename: String,
+    count: usize,
+}
+
+#[derive(Serialize)]
+struct StreamError {
+    error: String,
+}
+
+/**
+ * The `error` event for a failed stream, showing the configured
+ * `fallback_message` instead of the raw error when the provider can't be reached.
+ */
+fn stream_error(state: &AppState, error: &(dyn Error + 'static)) -> StreamError {
+    StreamError {
+        error: state.rig_service.fallback_message(error).unwrap_or_else(|| error.to_string()),
+    }
+}
+
+/**
+ * Streaming variant of the flashcards endpoint.
+ * Emits a `card` SSE event with `{ front, back }` for each flashcard as soon
+ * as it is generated, then a final `done` event with the suggested filename.
+ * Failures are reported as an `error` event. Only basic cards stream; a
+ * `cloze` request is rejected with 400 rather than answered with basic cards.
+ */
+async fn handle_flashcards_stream(
+    State(state): State<AppState>,
+    headers: axum::http::HeaderMap,
+    Json(request): Json<FlashcardsRequest>,
+) -> axum::response::Response {
+    if request.style == FlashcardStyle::Cloze {
+        return ApiError::bad_request("Cloze flashcards can't be streamed; use /api/flashcards instead").into_response();
+    }
+    
+    let permit = match acquire_stream(&state) {
+        Ok(permit) => permit,
+        Err(rejection) => return rejection.into_response(),
+    };
+    
+    // A reconnecting client resumes the buffered stream instead of regenerating
+    if let Some((buffer, cursor)) = resumed_stream(&state, &headers) {
+        let events = stream_limit::hold(resume::subscribe(buffer, Some(cursor)), permit);
+        return Sse::new(events).keep_alive(KeepAlive::default()).into_response();
+    }
+    
+    // Check for API key in header (allows per-request API keys)
+    let api_key = headers.get("x-openai-key").and_then(|v| v.to_str().ok()).map(|s| s.to_string());
+    let usage_reporting = usage_reporting(&state);
+    let buffer = state.streams.create();
+    let producer = buffer.clone();
+    // The generation keeps its slot until it ends, even if the client drops
+    let producer_permit = permit.clone();
+    
+    tokio::spawn(profiles::carry(async move {
+        let _permit = producer_permit;
+        resume::produce(&producer, stream_flashcards_into(&state, &request, api_key.as_deref(), usage_reporting, &producer)).await;
+    }));
+    
+    let events = stream_limit::hold(resume::subscribe(buffer, None), permit);
+    Sse::new(events).keep_alive(KeepAlive::default()).into_response()
+}
+
+/**
+ * Generates flashcards and pushes `card`, then `done` (or `error`) events into the stream buffer,
+ * with `usage` events as configured. Token counts are estimated from the note and the output.
+ * Generation continues while the client is away so it can resume from the buffer.
+ */
+async fn stream_flashcards_into(
+    state: &AppState,
+    request: &FlashcardsRequest,
+    api_key: Option<&str>,
+    usage_reporting: UsageReporting,
+    buffer: &resume::StreamBuffer,
+) {
+    // Convert the error up front so nothing non-Send is held across an await
+    let chunks = state
+        .rig_service
+        .stream_flashcards(&request.content, request.title.as_deref(), api_key)
+        .await
+        .map_err(|e| stream_error(state, e.as_ref()));
+    let mut chunks = match chunks {
+        Ok(chunks) => chunks,
+        Err(e) => {
+            tracing::error!("Error streaming flashcards: {}", e.error);
+            buffer.push("error", &e);
+            return;
+        }
+    };
+    
+    let mut usage = UsageMeter::default();
+    usage.add_prompt(&request.content);
+    let mut lines = LineBuffer::new();
+    let mut filename = None;
+    let mut count = 0;
+    let mut finished = false;
+    while !finished {
+        let completed = match chunks.next().await {
+            Some(Ok(chunk)) => {
+                usage.add_chunk(&chunk);
+                if usage_reporting == UsageReporting::Incremental {
+                    buffer.push("usage", &usage.stats());
+                }
+                lines.push(&chunk)
+            }
+            Some(Err(e)) => {
+                tracing::error!("Error streaming flashcards: {}", e);
+                buffer.push("error", &stream_error(state, e.as_ref()));
+                return;
+            }
+            None => {
+                finished = true;
+                lines.finish().into_iter().collect()
+            }
+        };
+        
+        for line in &completed {
+            match rig_service::parse_flashcard_line(line) {
+                Some(FlashcardStreamLine::Card(card)) => {
+                    count += 1;
+                    buffer.push("card", &card);
+                }
+                Some(FlashcardStreamLine::Filename { filename: name }) => filename = Some(name),
+                None => {}
+            }
+        }
+    }
+    
+    let filename_style = state
+        .rig_service
+        .get_config()
+        .map(|config| config.filename_style)
+        .unwrap_or_default();
+    let done = FlashcardsStreamDone {
+        filename: sanitize::sanitize_filename(filename.as_deref().unwrap_or("flashcards"), filename_style),
+        count,
+    };
+    if usage_reporting == UsageReporting::Final {
+        buffer.push("usage", &usage.stats());
+    }
+    buffer.push("done", &done);
+}
+
+/**
+ * Data structures for handling flashcard deduplication requests and responses.
+ */
+#[derive(Deserialize)]
+struct FlashcardsDedupeRequest {
+    flashcards: Vec<Flashcard>,
+    threshold: Option<f64>,
+}
+
+#[derive(Serialize)]
+struct FlashcardsDedupeResponse {
+    flashcards: Vec<Flashcard>,
+    removed: usize,
+}
+
+/**
+ * Endpoint for merging near-duplicate flashcards, e.g. from overlapping notes.
+ * Uses the configured dedupe threshold unless the request provides one.
+ * Runs entirely locally without a model call.
+ */
+async fn handle_flashcards_dedupe(
+    State(state): State<AppState>,
+    Json(request): Json<FlashcardsDedupeRequest>,
+) -> impl IntoResponse {
+    let threshold = match request.threshold {
+        Some(threshold) => threshold,
+        None => state
+            .rig_service
+            .get_config()
+            .map(|config| config.dedupe_threshold)
+            .unwrap_or(dedupe::DEFAULT_DEDUPE_THRESHOLD),
+    }
+    .clamp(0.0, 1.0);
+    
+    let original_count = request.flashcards.len();
+    let flashcards = dedupe::dedupe_flashcards(request.flashcards, threshold);
+    let removed = original_count - flashcards.len();
+    
+    (StatusCode::OK, Json(FlashcardsDedupeResponse { flashcards, removed }))
+}
+
+/**
+ * Data structures for handling card scheduling requests and responses.
+ */
+#[derive(Deserialize)]
+struct ScheduleCardsRequest {
+    cards: Vec<ReviewCard>,
+}
+
+#[derive(Serialize)]
+struct ScheduleCardsResponse {
+    cards: Vec<ScheduledCard>,
+}
+
+/**
+ * Endpoint for scheduling each card's next review with SM-2 spaced
+ * repetition, counting days from today in the configured timezone.
+ * Runs entirely locally without a model call.
+ */
+async fn handle_schedule_cards(
+    State(state): State<AppState>,
+    Json(request): Json<ScheduleCardsRequest>,
+) -> impl IntoResponse {
+    let today = state.rig_service.today();
+    let scheduled: Result<Vec<ScheduledCard>, String> = request
+        .cards
+        .into_iter()
+        .enumerate()
+        .map(|(index, card)| srs::schedule(card, today).map_err(|e| format!("Card {}: {}", index, e)))
+        .collect();
+    
+    match scheduled {
+        Ok(cards) => (StatusCode::OK, Json(ScheduleCardsResponse { cards })).into_response(),
+        Err(error) => ApiError::bad_request(error).into_response(),
+    }
+}
+
+/**
+ * Data structures for handling flashcard translation requests and responses.
+ */
+#[derive(Deserialize)]
+struct FlashcardsTranslateRequest {
+    flashcards: Vec<Flashcard>,
+    target_language: String,
+    concurrency: Option<usize>,
+}
+
+#[derive(Serialize)]
+struct FlashcardsTranslateResponse {
+    flashcards: Vec<Flashcard>,
+}
+
+/**
+ * Endpoint for translating a whole flashcard deck into another language.
+ * Both sides and any example sentences are translated; card order is preserved.
+ */
+async fn handle_flashcards_translate(
+    State(state): State<AppState>,
+    headers: axum::http::HeaderMap,
+    Json(request): Json<FlashcardsTranslateRequest>,
+) -> impl IntoResponse {
+    // Check for API key in header (allows per-request API keys)
+    let api_key = headers.get("x-openai-key").and_then(|v| v.to_str().ok()).map(|s| s.to_string());
+    
+    let target_language = request.target_language.trim();
+    if target_language.is_empty() {
+        return ApiError::bad_request("target_language must not be empty").into_response();
+    }
+    
+    match state
+        .rig_service
+        .translate_flashcards(
+            &request.flashcards,
+            target_language,
+            batch::effective_concurrency(request.concurrency),
+            api_key.as_deref(),
+        )
+        .await
+    {
+        Ok(flashcards) => (
+            StatusCode::OK,
+            Json(FlashcardsTranslateResponse { flashcards }),
+        )
+            .into_response(),
+        Err(e) => {
+            tracing::error!("Error translating flashcards: {}", e);
+            service_error(&state, e.as_ref()).into_response()
+        }
+    }
+}
+
+/**
+ * Data structures for handling canvas generation requests.
+ * The response body is the canvas document itself.
+ */
+#[derive(Deserialize)]
+struct CanvasRequest {
+    content: String,
+    max_nodes: Option<usize>,
+}
+
+/**
+ * Endpoint for generating an Obsidian canvas from a topic or note.
+ * Takes content and an optional max_nodes parameter (default 8, at most 30).
+ * Returns valid `.canvas` JSON with positioned nodes and edges.
+ */
+async fn handle_canvas(
+    State(state): State<AppState>,
+    headers: axum::http::HeaderMap,
+    Json(request): Json<CanvasRequest>,
+) -> impl IntoResponse {
+    // Check for API key in header (allows per-request API keys)
+    let api_key = headers.get("x-openai-key").and_then(|v| v.to_str().ok()).map(|s| s.to_string());
+    let max_nodes = request.max_nodes.unwrap_or(8).clamp(1, 30);
+    
+    match state
+        .rig_service
+        .generate_canvas(&request.content, max_nodes, api_key.as_deref())
+        .await
+    {
+        Ok(canvas) => (StatusCode::OK, Json(canvas)).into_response(),
+        Err(e) => {
+            tracing::error!("Error generating canvas: {}", e);
+            service_error(&state, e.as_ref()).into_response()
+        }
+    }
+}
+
+/**
+ * Data structures for handling outline elaboration requests and responses.
+ */
+#[derive(Deserialize)]
+struct ElaborateOutlineRequest {
+    outline: Vec<OutlineNode>,
+    context: Option<String>,
+    concurrency: Option<usize>,
+}
+
+#[derive(Serialize)]
+struct ElaborateOutlineResponse {
+    outline: Vec<OutlineNode>,
+}
+
+/**
+ * Endpoint for turning an outline into a draft document.
+ * Each leaf is elaborated with its own model call; failures are reported
+ * per leaf in an `error` field instead of failing the whole request.
+ */
+async fn handle_elaborate_outline(
+    State(state): State<AppState>,
+    headers: axum::http::HeaderMap,
+    Json(request): Json<ElaborateOutlineRequest>,
+) -> impl IntoResponse {
+    // Check for API key in header (allows per-request API keys)
+    let api_key = headers.get("x-openai-key").and_then(|v| v.to_str().ok()).map(|s| s.to_string());
+    let concurrency = batch::effective_concurrency(request.concurrency);
+    
+    let outline = state
+        .rig_service
+        .elaborate_outline(request.outline, request.context.as_deref(), concurrency, api_key.as_deref())
+        .await;
+    
+    (StatusCode::OK, Json(ElaborateOutlineResponse { outline }))
+}
+
+/**
+ * Data structures for handling tag extraction requests and responses.
+ */
+#[derive(Deserialize)]
+struct TagsRequest {
+    content: String,
+    count: Option<usize>,
+}
+
+#[derive(Serialize)]
+struct TagsResponse {
+    tags: Vec<String>,
+}
+
+/**
+ * Endpoint for suggesting tags for a note.
+ * Takes content and an optional count parameter (default 5).
+ * Returns lowercase tags without a leading '#', deduplicated.
+ */
+async fn handle_tags(
+    State(state): State<AppState>,
+    headers: axum::http::HeaderMap,
+    Json(request): Json<TagsRequest>,
+) -> impl IntoResponse {
+    // Check for API key in header (allows per-request API keys)
+    let api_key = headers.get("x-openai-key").and_then(|v| v.to_str().ok()).map(|s| s.to_string());
+    
+    match state
+        .rig_service
+        .extract_tags(&request.content, request.count.unwrap_or(5), api_key.as_deref())
+        .await
+    {
+        Ok(tags) => (
+            StatusCode::OK,
+            Json(TagsResponse { tags }),
+        )
+            .into_response(),
+        Err(e) => {
+            tracing::error!("Error extracting tags: {}", e);
+            service_error(&state, e.as_ref()).into_response()
+        }
+    }
+}
+
+/**
+ * Data structures for handling vocabulary-constrained tag requests and responses.
+ */
+#[derive(Deserialize)]
+struct ConsistentTagsRequest {
+    content: String,
+    vocabulary: Vec<String>,
+    max_tags: Option<usize>,
+    allow_new_tag: Option<bool>,
+}
+
+#[derive(Serialize)]
+struct ConsistentTagsResponse {
+    tags: Vec<String>,
+    new_tag: Option<String>,
+}
+
+/**
+ * Endpoint for tagging a note using the vault's existing tag vocabulary.
+ * Hallucinated tags outside the vocabulary are dropped; at most one new tag
+ * is suggested when allow_new_tag is set (default true).
+ */
+async fn handle_consistent_tags(
+    State(state): State<AppState>,
+    headers: axum::http::HeaderMap,
+    Json(request): Json<ConsistentTagsRequest>,
+) -> impl IntoResponse {
+    // Check for API key in header (allows per-request API keys)
+    let api_key = headers.get("x-openai-key").and_then(|v| v.to_str().ok()).map(|s| s.to_string());
+    let max_tags = request.max_tags.unwrap_or(5).clamp(1, 20);
+    
+    match state
+        .rig_service
+        .suggest_consistent_tags(
+            &request.content,
+            &request.vocabulary,
+            max_tags,
+            request.allow_new_tag.unwrap_or(true),
+            api_key.as_deref(),
+        )
+        .await
+    {
+        Ok((tags, new_tag)) => (
+            StatusCode::OK,
+            Json(ConsistentTagsResponse { tags, new_tag }),
+        )
+            .into_response(),
+        Err(e) => {
+            tracing::error!("Error suggesting consistent tags: {}", e);
+            service_error(&state, e.as_ref()).into_response()
+        }
+    }
+}
+
+/**
+ * Data structures for handling changelog requests and responses.
+ */
+#[derive(Deserialize)]
+struct ChangelogRequest {
+    versions: Vec<NoteVersion>,
+    concurrency: Option<usize>,
+}
+
+#[derive(Serialize)]
+struct ChangelogResponse {
+    entries: Vec<ChangelogEntry>,
+}
+
+/**
+ * Endpoint for summarizing how a note evolved across versions.
+ * Takes snapshots oldest first and returns one entry per subsequent version.
+ */
+async fn handle_changelog(
+    State(state): State<AppState>,
+    headers: axum::http::HeaderMap,
+    Json(request): Json<ChangelogRequest>,
+) -> impl IntoResponse {
+    // Check for API key in header (allows per-request API keys)
+    let api_key = headers.get("x-openai-key").and_then(|v| v.to_str().ok()).map(|s| s.to_string());
+    
+    if request.versions.len() < 2 {
+        return ApiError::bad_request("At least two versions are required to build a changelog").into_response();
+    }
+    
+    let concurrency = batch::effective_concurrency(request.concurrency);
+    match state
+        .rig_service
+        .generate_changelog(&request.versions, concurrency, api_key.as_deref())
+        .await
+    {
+        Ok(entries) => (
+            StatusCode::OK,
+            Json(ChangelogResponse { entries }),
+        )
+            .into_response(),
+        Err(e) => {
+            tracing::error!("Error generating changelog: {}", e);
+            service_error(&state, e.as_ref()).into_response()
+        }
+    }
+}
+
+/**
+ * Data structures for handling multi-length paraphrase requests and responses.
+ */
+#[derive(Deserialize)]
+struct LengthsRequest {
+    content: String,
+    lengths: Vec<String>,
+}
+
+#[derive(Serialize)]
+struct LengthsResponse {
+    variants: BTreeMap<String, String>,
+}
+
+/**
+ * Endpoint for paraphrasing content at several lengths in one model call.
+ * Accepted lengths are "one_line", "paragraph" and "full"; unknown or
+ * missing lengths are rejected with 400.
+ */
+async fn handle_lengths(
+    State(state): State<AppState>,
+    headers: axum::http::HeaderMap,
+    Json(request): Json<LengthsRequest>,
+) -> impl IntoResponse {
+    // Check for API key in header (allows per-request API keys)
+    let api_key = headers.get("x-openai-key").and_then(|v| v.to_str().ok()).map(|s| s.to_string());
+    
+    // Validate the requested lengths before calling the model
+    let mut lengths: Vec<String> = Vec::new();
+    for length in request.lengths {
+        if rig_service::length_instruction(&length).is_none() {
+            return ApiError::bad_request(format!(
+                "Unknown length '{}'. Expected one of: one_line, paragraph, full",
+                length
+            ))
+            .into_response();
+        }
+        if !lengths.contains(&length) {
+            lengths.push(length);
+        }
+    }
+    if lengths.is_empty() {
+        return ApiError::bad_request("At least one length is required").into_response();
+    }
+    
+    match state
+        .rig_service
+        .paraphrase_lengths(&request.content, &lengths, api_key.as_deref())
+        .await
+    {
+        Ok(variants) => (
+            StatusCode::OK,
+            Json(LengthsResponse { variants }),
+        )
+            .into_response(),
+        Err(e) => {
+            tracing::error!("Error paraphrasing lengths: {}", e);
+            service_error(&state, e.as_ref()).into_response()
+        }
+    }
+}
+
+/**
+ * Data structures for handling reference extraction requests and responses.
+ */
+#[derive(Deserialize)]
+struct ReferencesRequest {
+    content: String,
+}
+
+#[derive(Serialize)]
+struct ReferencesResponse {
+    references: Vec<Reference>,
+}
+
+/**
+ * Endpoint for extracting a bibliography from a note.
+ * Returns each reference with its title and, when present, authors, year and url.
+ */
+async fn handle_references(
+    State(state): State<AppState>,
+    headers: axum::http::HeaderMap,
+    Json(request): Json<ReferencesRequest>,
+) -> impl IntoResponse {
+    // Check for API key in header (allows per-request API keys)
+    let api_key = headers.get("x-openai-key").and_then(|v| v.to_str().ok()).map(|s| s.to_string());
+    
+    match state
+        .rig_service
+        .extract_references(&request.content, api_key.as_deref())
+        .await
+    {
+        Ok(references) => (
+            StatusCode::OK,
+            Json(ReferencesResponse { references }),
+        )
+            .into_response(),
+        Err(e) => {
+            tracing::error!("Error extracting references: {}", e);
+            service_error(&state, e.as_ref()).into_response()
+        }
+    }
+}
+
+/**
+ * Data structures for handling citation requests and responses.
+ */
+#[derive(Deserialize)]
+struct CiteRequest {
+    references: Vec<Reference>,
+    style: String,
+}
+
+#[derive(Serialize)]
+struct CiteResponse {
+    citations: Vec<String>,
+}
+
+/**
+ * Endpoint for formatting references (e.g., from `/api/references`) as
+ * citations in APA, MLA or Chicago style, one citation per reference.
+ * Responds with 400 for an unknown style and 422 if the model's output
+ * doesn't match the expected schema.
+ */
+async fn handle_cite(
+    State(state): State<AppState>,
+    headers: axum::http::HeaderMap,
+    Json(request): Json<CiteRequest>,
+) -> impl IntoResponse {
+    // Check for API key in header (allows per-request API keys)
+    let api_key = headers.get("x-openai-key").and_then(|v| v.to_str().ok()).map(|s| s.to_string());
+    
+    let style = match CitationStyle::parse(&request.style) {
+        Some(style) => style,
+        None => {
+            return ApiError::bad_request(format!("Unknown citation style '{}'; expected apa, mla or chicago", request.style)).into_response();
+        }
+    };
+    if request.references.is_empty() {
+        return ApiError::bad_request("At least one reference is required").into_response();
+    }
+    
+    match state
+        .rig_service
+        .format_citations(&request.references, style, api_key.as_deref())
+        .await
+    {
+        Ok(citations) => (
+            StatusCode::OK,
+            Json(CiteResponse { citations }),
+        )
+            .into_response(),
+        Err(e) => {
+            tracing::error!("Error formatting citations: {}", e);
+            service_error(&state, e.as_ref()).into_response()
+        }
+    }
+}
+
+/**
+ * Data structures for handling tone analysis requests and responses.
+ */
+#[derive(Deserialize)]
+struct ToneRequest {
+    content: String,
+}
+
+#[derive(Serialize)]
+struct ToneResponse {
+    #[serde(flatten)]
+    analysis: ToneAnalysis,
+}
+
+/**
+ * Endpoint for analyzing the sentiment and emotional tone of a note.
+ * Returns a sentiment label, descriptive tones and a score from -1.0 to 1.0.
+ */
+async fn handle_tone(
+    State(state): State<AppState>,
+    headers: axum::http::HeaderMap,
+    Json(request): Json<ToneRequest>,
+) -> impl IntoResponse {
+    // Check for API key in header (allows per-request API keys)
+    let api_key = headers.get("x-openai-key").and_then(|v| v.to_str().ok()).map(|s| s.to_string());
+    
+    match state
+        .rig_service
+        .analyze_tone(&request.content, api_key.as_deref())
+        .await
+    {
+        Ok(analysis) => (
+            StatusCode::OK,
+            Json(ToneResponse { analysis }),
+        )
+            .into_response(),
+        Err(e) => {
+            tracing::error!("Error analyzing tone: {}", e);
+            service_error(&state, e.as_ref()).into_response()
+        }
+    }
+}
+
+/**
+ * Data structures for handling pros/cons requests and responses.
+ */
+#[derive(Deserialize)]
+struct ProsConsRequest {
+    content: String,
+}
+
+#[derive(Serialize)]
+struct ProsConsResponse {
+    #[serde(flatten)]
+    analysis: ProsCons,
+}
+
+/**
+ * Endpoint for listing the pros and cons of a decision or argument in a note.
+ * The plugin can render each side as its own canvas node.
+ */
+async fn handle_pros_cons(
+    State(state): State<AppState>,
+    headers: axum::http::HeaderMap,
+    Json(request): Json<ProsConsRequest>,
+) -> impl IntoResponse {
+    // Check for API key in header (allows per-request API keys)
+    let api_key = headers.get("x-openai-key").and_then(|v| v.to_str().ok()).map(|s| s.to_string());
+    
+    match state
+        .rig_service
+        .analyze_pros_cons(&request.content, api_key.as_deref())
+        .await
+    {
+        Ok(analysis) => (
+            StatusCode::OK,
+            Json(ProsConsResponse { analysis }),
+        )
+            .into_response(),
+        Err(e) => {
+            tracing::error!("Error analyzing pros and cons: {}", e);
+            service_error(&state, e.as_ref()).into_response()
+        }
+    }
+}
+
+/**
+ * Data structures for handling Kanban requests and responses.
+ */
+#[derive(Deserialize)]
+struct KanbanRequest {
+    content: String,
+}
+
+#[derive(Serialize)]
+struct KanbanResponse {
+    columns: Vec<KanbanColumn>,
+}
+
+/**
+ * Endpoint for turning the tasks in a note into a Kanban board.
+ * Every checklist item (or list item, if there are no checkboxes) appears
+ * in exactly one column.
+ */
+async fn handle_kanban(
+    State(state): State<AppState>,
+    headers: axum::http::HeaderMap,
+    Json(request): Json<KanbanRequest>,
+) -> impl IntoResponse {
+    // Check for API key in header (allows per-request API keys)
+    let api_key = headers.get("x-openai-key").and_then(|v| v.to_str().ok()).map(|s| s.to_string());
+    
+    if kanban::extract_tasks(&request.content).is_empty() {
+        return ApiError::bad_request("No tasks found in the note").into_response();
+    }
+    
+    match state
+        .rig_service
+        .generate_kanban(&request.content, api_key.as_deref())
+        .await
+    {
+        Ok(columns) => (
+            StatusCode::OK,
+            Json(KanbanResponse { columns }),
+        )
+            .into_response(),
+        Err(e) => {
+            tracing::error!("Error generating kanban board: {}", e);
+            service_error(&state, e.as_ref()).into_response()
+        }
+    }
+}
+
+/**
+ * Data structures for handling concept map requests and responses.
+ */
+#[derive(Deserialize)]
+struct ConceptMapRequest {
+    content: String,
+}
+
+#[derive(Serialize)]
+struct ConceptMapResponse {
+    #[serde(flatten)]
+    map: ConceptMap,
+}
+
+/**
+ * Endpoint for generating a concept map with typed relationships.
+ * The plugin renders concepts as nodes and relations as labeled edges.
+ */
+async fn handle_concept_map(
+    State(state): State<AppState>,
+    headers: axum::http::HeaderMap,
+    Json(request): Json<ConceptMapRequest>,
+) -> impl IntoResponse {
+    // Check for API key in header (allows per-request API keys)
+    let api_key = headers.get("x-openai-key").and_then(|v| v.to_str().ok()).map(|s| s.to_string());
+    
+    match state
+        .rig_service
+        .generate_concept_map(&request.content, api_key.as_deref())
+        .await
+    {
+        Ok(map) => (
+            StatusCode::OK,
+            Json(ConceptMapResponse { map }),
+        )
+            .into_response(),
+        Err(e) => {
+            tracing::error!("Error generating concept map: {}", e);
+            service_error(&state, e.as_ref()).into_response()
+        }
+    }
+}
+
+/**
+ * Data structures for handling extraction requests and responses.
+ */
+#[derive(Deserialize)]
+struct ExtractRequest {
+    content: String,
+    json_schema: serde_json::Value,
+}
+
+#[derive(Serialize)]
+struct ExtractResponse {
+    data: serde_json::Value,
+}
+
+/**
+ * Endpoint for extracting structured data matching a user-supplied JSON schema.
+ * Responds with 400 if the schema itself is invalid, and with 422 and the
+ * individual violations in `details` if the model's output doesn't match it.
+ */
+async fn handle_extract(
+    State(state): State<AppState>,
+    headers: axum::http::HeaderMap,
+    Json(request): Json<ExtractRequest>,
+) -> impl IntoResponse {
+    // Check for API key in header (allows per-request API keys)
+    let api_key = headers.get("x-openai-key").and_then(|v| v.to_str().ok()).map(|s| s.to_string());
+    
+    match state
+        .rig_service
+        .extract(&request.content, &request.json_schema, api_key.as_deref())
+        .await
+    {
+        Ok(data) => (
+            StatusCode::OK,
+            Json(ExtractResponse { data }),
+        )
+            .into_response(),
+        Err(e) => {
+            tracing::error!("Error extracting data: {}", e);
+            service_error(&state, e.as_ref()).into_response()
+        }
+    }
+}
+
+/**
+ * Data structures for handling interview question requests and responses.
+ */
+#[derive(Deserialize)]
+struct InterviewRequest {
+    content: String,
+    count: Option<usize>,
+}
+
+#[derive(Serialize)]
+struct InterviewResponse {
+    questions: Vec<InterviewQuestion>,
+}
+
+/**
+ * Endpoint for generating job or exam practice questions with grading rubrics.
+ * Responds with 422 if the model's output doesn't match the expected schema.
+ */
+async fn handle_interview(
+    State(state): State<AppState>,
+    headers: axum::http::HeaderMap,
+    Json(request): Json<InterviewRequest>,
+) -> impl IntoResponse {
+    // Check for API key in header (allows per-request API keys)
+    let api_key = headers.get("x-openai-key").and_then(|v| v.to_str().ok()).map(|s| s.to_string());
+    let count = request.count.unwrap_or(5).clamp(1, 15);
+    
+    match state
+        .rig_service
+        .generate_interview(&request.content, count, api_key.as_deref())
+        .await
+    {
+        Ok(questions) => (
+            StatusCode::OK,
+            Json(InterviewResponse { questions }),
+        )
+            .into_response(),
+        Err(e) => {
+            tracing::error!("Error generating interview questions: {}", e);
+            service_error(&state, e.as_ref()).into_response()
+        }
+    }
+}
+
+/**
+ * Data structures for handling refactoring requests and responses.
+ */
+#[derive(Deserialize)]
+struct RefactorRequest {
+    notes: Vec<RefactorNote>,
+}
+
+#[derive(Serialize)]
+struct RefactorResponse {
+    suggestions: Vec<RefactorSuggestion>,
+}
+
+/**
+ * Endpoint for suggesting how to merge, split or rename a set of notes.
+ * Note ids must be unique; every suggestion's `targets` refer to them.
+ * Responds with 422 if the model's output doesn't match the expected schema.
+ */
+async fn handle_refactor(
+    State(state): State<AppState>,
+    headers: axum::http::HeaderMap,
+    Json(request): Json<RefactorRequest>,
+) -> impl IntoResponse {
+    // Check for API key in header (allows per-request API keys)
+    let api_key = headers.get("x-openai-key").and_then(|v| v.to_str().ok()).map(|s| s.to_string());
+    
+    // Targets are matched by id, so ids must identify a single note
+    let mut ids = HashSet::new();
+    let invalid = if request.notes.is_empty() {
+        Some("At least one note is required".to_string())
+    } else {
+        request
+            .notes
+            .iter()
+            .find(|note| note.id.trim().is_empty() || !ids.insert(note.id.as_str()))
+            .map(|note| format!("Note ids must be unique and non-empty (got '{}')", note.id))
+    };
+    if let Some(error) = invalid {
+        return ApiError::bad_request(error).into_response();
+    }
+    
+    match state
+        .rig_service
+        .suggest_refactoring(&request.notes, api_key.as_deref())
+        .await
+    {
+        Ok(suggestions) => (
+            StatusCode::OK,
+            Json(RefactorResponse { suggestions }),
+        )
+            .into_response(),
+        Err(e) => {
+            tracing::error!("Error suggesting refactoring: {}", e);
+            service_error(&state, e.as_ref()).into_response()
+        }
+    }
+}
+
+/**
+ * Data structures for handling alt-text requests and responses.
+ */
+#[derive(Deserialize)]
+struct AltTextRequest {
+    images: Vec<ImageInput>,
+    context: Option<String>,
+}
+
+#[derive(Serialize)]
+struct AltTextResponse {
+    descriptions: Vec<AltText>,
+}
+
+/**
+ * Endpoint for writing alt text for images referenced in a note.
+ * Images that fail to load or can't be described are flagged with a
+ * per-image `error` instead of failing the whole request.
+ */
+async fn handle_alt_text(
+    State(state): State<AppState>,
+    headers: axum::http::HeaderMap,
+    Json(request): Json<AltTextRequest>,
+) -> impl IntoResponse {
+    // Check for API key in header (allows per-request API keys)
+    let api_key = headers.get("x-openai-key").and_then(|v| v.to_str().ok()).map(|s| s.to_string());
+    
+    if request.images.is_empty() {
+        return ApiError::bad_request("At least one image is required").into_response();
+    }
+    
+    match state
+        .rig_service
+        .generate_alt_text(&request.images, request.context.as_deref(), api_key.as_deref())
+        .await
+    {
+        Ok(descriptions) => (
+            StatusCode::OK,
+            Json(AltTextResponse { descriptions }),
+        )
+            .into_response(),
+        Err(e) => {
+            tracing::error!("Error generating alt text: {}", e);
+            service_error(&state, e.as_ref()).into_response()
+        }
+    }
+}
+
+/**
+ * Data structures for handling timeline requests and responses.
+ */
+#[derive(Deserialize)]
+struct TimelineRequest {
+    content: String,
+}
+
+#[derive(Serialize)]
+struct TimelineResponse {
+    events: Vec<TimelineEvent>,
+}
+
+/**
+ * Endpoint for extracting a chronological timeline of events from a note.
+ * The plugin can lay the events out left to right as a canvas timeline.
+ * Responds with 422 if the model's output doesn't match the expected schema.
+ */
+async fn handle_timeline(
+    State(state): State<AppState>,
+    headers: axum::http::HeaderMap,
+    Json(request): Json<TimelineRequest>,
+) -> impl IntoResponse {
+    // Check for API key in header (allows per-request API keys)
+    let api_key = headers.get("x-openai-key").and_then(|v| v.to_str().ok()).map(|s| s.to_string());
+    
+    match state
+        .rig_service
+        .extract_timeline(&request.content, api_key.as_deref())
+        .await
+    {
+        Ok(events) => (
+            StatusCode::OK,
+            Json(TimelineResponse { events }),
+        )
+            .into_response(),
+        Err(e) => {
+            tracing::error!("Error extracting timeline: {}", e);
+            service_error(&state, e.as_ref()).into_response()
+        }
+    }
+}
+
+/**
+ * Data structures for handling date extraction requests and responses.
+ */
+#[derive(Deserialize)]
+struct DatesRequest {
+    content: String,
+    reference_date: Option<String>,
+}
+
+#[derive(Serialize)]
+struct DatesResponse {
+    dates: Vec<MentionedDate>,
+}
+
+/**
+ * Endpoint for extracting the dates and times mentioned in a note,
+ * normalized to ISO-8601 for task and calendar workflows. Relative dates
+ * are resolved against `reference_date` (`YYYY-MM-DD`, default today).
+ * Responds with 422 if the model's output doesn't match the expected schema.
+ */
+async fn handle_dates(
+    State(state): State<AppState>,
+    headers: axum::http::HeaderMap,
+    Json(request): Json<DatesRequest>,
+) -> impl IntoResponse {
+    // Check for API key in header (allows per-request API keys)
+    let api_key = headers.get("x-openai-key").and_then(|v| v.to_str().ok()).map(|s| s.to_string());
+    
+    let reference_date = match request.reference_date.as_deref().map(dates::parse_reference_date).transpose() {
+        Ok(reference_date) => reference_date,
+        Err(error) => {
+            return ApiError::bad_request(error).into_response();
+        }
+    };
+    
+    match state
+        .rig_service
+        .extract_dates(&request.content, reference_date, api_key.as_deref())
+        .await
+    {
+        Ok(dates) => (
+            StatusCode::OK,
+            Json(DatesResponse { dates }),
+        )
+            .into_response(),
+        Err(e) => {
+            tracing::error!("Error extracting dates: {}", e);
+            service_error(&state, e.as_ref()).into_response()
+        }
+    }
+}
+
+/**
+ * Data structures for handling abstract requests and responses.
+ */
+#[derive(Deserialize)]
+struct AbstractRequest {
+    content: String,
+    max_keywords: Option<usize>,
+}
+
+#[derive(Serialize)]
+struct AbstractResponse {
+    #[serde(flatten)]
+    note_abstract: NoteAbstract,
+}
+
+/**
+ * Endpoint for writing a note's one-sentence pitch, detailed abstract and
+ * keywords in one call, e.g. to build a note index on a canvas dashboard.
+ * Responds with 422 if the model's output doesn't match the expected schema.
+ */
+async fn handle_abstract(
+    State(state): State<AppState>,
+    headers: axum::http::HeaderMap,
+    Json(request): Json<AbstractRequest>,
+) -> impl IntoResponse {
+    // Check for API key in header (allows per-request API keys)
+    let api_key = headers.get("x-openai-key").and_then(|v| v.to_str().ok()).map(|s| s.to_string());
+    let max_keywords = request.max_keywords.unwrap_or(DEFAULT_MAX_KEYWORDS).clamp(1, MAX_KEYWORDS_LIMIT);
+    
+    match state
+        .rig_service
+        .generate_abstract(&request.content, max_keywords, api_key.as_deref())
+        .await
+    {
+        Ok(note_abstract) => (
+            StatusCode::OK,
+            Json(AbstractResponse { note_abstract }),
+        )
+            .into_response(),
+        Err(e) => {
+            tracing::error!("Error generating abstract: {}", e);
+            service_error(&state, e.as_ref()).into_response()
+        }
+    }
+}
+
+/**
+ * Data structures for handling reading ladder requests and responses.
+ */
+#[derive(Deserialize)]
+struct SimplifyLevelsRequest {
+    content: String,
+}
+
+#[derive(Serialize)]
+struct SimplifyLevelsResponse {
+    levels: Vec<ReadingLevel>,
+}
+
+/**
+ * Endpoint for rewriting content at several reading levels, so educators can
+ * hand out the same material to readers of different abilities.
+ * Responds with 422 if the model's output doesn't match the expected schema
+ * or leaves out a level.
+ */
+async fn handle_simplify_levels(
+    State(state): State<AppState>,
+    headers: axum::http::HeaderMap,
+    Json(request): Json<SimplifyLevelsRequest>,
+) -> impl IntoResponse {
+    // Check for API key in header (allows per-request API keys)
+    let api_key = headers.get("x-openai-key").and_then(|v| v.to_str().ok()).map(|s| s.to_string());
+    
+    match state
+        .rig_service
+        .simplify_levels(&request.content, api_key.as_deref())
+        .await
+    {
+        Ok(levels) => (
+            StatusCode::OK,
+            Json(SimplifyLevelsResponse { levels }),
+        )
+            .into_response(),
+        Err(e) => {
+            tracing::error!("Error generating reading levels: {}", e);
+            service_error(&state, e.as_ref()).into_response()
+        }
+    }
+}
+
+/**
+ * Data structures for handling jargon requests and responses.
+ */
+#[derive(Deserialize)]
+struct JargonRequest {
+    content: String,
+    #[serde(default)]
+    known_terms: Vec<String>,
+}
+
+#[derive(Serialize)]
+struct JargonResponse {
+    undefined: Vec<UndefinedTerm>,
+}
+
+/**
+ * Endpoint for finding terms a note uses without defining, with suggested
+ * definitions, so authors can make notes self-contained.
+ * Responds with 422 if the model's output doesn't match the expected schema.
+ */
+async fn handle_jargon(
+    State(state): State<AppState>,
+    headers: axum::http::HeaderMap,
+    Json(request): Json<JargonRequest>,
+) -> impl IntoResponse {
+    // Check for API key in header (allows per-request API keys)
+    let api_key = headers.get("x-openai-key").and_then(|v| v.to_str().ok()).map(|s| s.to_string());
+    
+    match state
+        .rig_service
+        .detect_jargon(&request.content, &request.known_terms, api_key.as_deref())
+        .await
+    {
+        Ok(undefined) => (
+            StatusCode::OK,
+            Json(JargonResponse { undefined }),
+        )
+            .into_response(),
+        Err(e) => {
+            tracing::error!("Error detecting jargon: {}", e);
+            service_error(&state, e.as_ref()).into_response()
+        }
+    }
+}
+
+/**
+ * Data structures for handling knowledge gap requests and responses.
+ */
+#[derive(Deserialize)]
+struct GapsRequest {
+    content: String,
+}
+
+#[derive(Serialize)]
+struct GapsResponse {
+    gaps: Vec<String>,
+}
+
+/**
+ * Endpoint for listing what a note leaves unclear or unexplained, so a
+ * learner knows which gaps to fill next.
+ * Responds with 422 if the model's output doesn't match the expected schema.
+ */
+async fn handle_gaps(
+    State(state): State<AppState>,
+    headers: axum::http::HeaderMap,
+    Json(request): Json<GapsRequest>,
+) -> impl IntoResponse {
+    // Check for API key in header (allows per-request API keys)
+    let api_key = headers.get("x-openai-key").and_then(|v| v.to_str().ok()).map(|s| s.to_string());
+    
+    match state
+        .rig_service
+        .find_gaps(&request.content, api_key.as_deref())
+        .await
+    {
+        Ok(gaps) => (
+            StatusCode::OK,
+            Json(GapsResponse { gaps }),
+        )
+            .into_response(),
+        Err(e) => {
+            tracing::error!("Error finding gaps: {}", e);
+            service_error(&state, e.as_ref()).into_response()
+        }
+    }
+}
+
+/**
+ * Data structures for handling analogy requests and responses.
+ */
+#[derive(Deserialize)]
+struct AnalogyRequest {
+    source_domain: String,
+    target_domain: String,
+}
+
+#[derive(Serialize)]
+struct AnalogyResponse {
+    mappings: Vec<AnalogyMapping>,
+}
+
+/**
+ * Endpoint for explaining one domain through another, as a list of concept
+ * mappings with the reasoning behind each.
+ * Responds with 422 if the model's output doesn't match the expected schema.
+ */
+async fn handle_analogy(
+    State(state): State<AppState>,
+    headers: axum::http::HeaderMap,
+    Json(request): Json<AnalogyRequest>,
+) -> impl IntoResponse {
+    // Check for API key in header (allows per-request API keys)
+    let api_key = headers.get("x-openai-key").and_then(|v| v.to_str().ok()).map(|s| s.to_string());
+    
+    let (source_domain, target_domain) = (request.source_domain.trim(), request.target_domain.trim());
+    if source_domain.is_empty() || target_domain.is_empty() {
+        return ApiError::bad_request("source_domain and target_domain must not be empty").into_response();
+    }
+    
+    match state
+        .rig_service
+        .generate_analogy(source_domain, target_domain, api_key.as_deref())
+        .await
+    {
+        Ok(mappings) => (
+            StatusCode::OK,
+            Json(AnalogyResponse { mappings }),
+        )
+            .into_response(),
+        Err(e) => {
+            tracing::error!("Error generating analogy: {}", e);
+            service_error(&state, e.as_ref()).into_response()
+        }
+    }
+}
+
+/**
+ * Data structures for handling hint requests and responses.
+ */
+#[derive(Deserialize)]
+struct HintsRequest {
+    question: String,
+    answer: String,
+}
+
+#[derive(Serialize)]
+struct HintsResponse {
+    hints: Vec<String>,
+}
+
+/**
+ * Endpoint for progressively revealing hints toward an answer, so a learner
+ * stuck on a flashcard or quiz question can get a nudge instead of the answer.
+ * Responds with 422 if the model's output doesn't match the expected schema.
+ */
+async fn handle_hints(
+    State(state): State<AppState>,
+    headers: axum::http::HeaderMap,
+    Json(request): Json<HintsRequest>,
+) -> impl IntoResponse {
+    // Check for API key in header (allows per-request API keys)
+    let api_key = headers.get("x-openai-key").and_then(|v| v.to_str().ok()).map(|s| s.to_string());
+    
+    let (question, answer) = (request.question.trim(), request.answer.trim());
+    if question.is_empty() || answer.is_empty() {
+        return ApiError::bad_request("question and answer must not be empty").into_response();
+    }
+    
+    match state
+        .rig_service
+        .generate_hints(question, answer, api_key.as_deref())
+        .await
+    {
+        Ok(hints) => (
+            StatusCode::OK,
+            Json(HintsResponse { hints }),
+        )
+            .into_response(),
+        Err(e) => {
+            tracing::error!("Error generating hints: {}", e);
+            service_error(&state, e.as_ref()).into_response()
+        }
+    }
+}
+
+/**
+ * Data structures for handling model comparison requests and responses.
+ */
+#[derive(Deserialize)]
+struct CompareModelsRequest {
+    content: String,
+    models: Vec<String>,
+}
+
+#[derive(Serialize)]
+struct CompareModelsResponse {
+    results: Vec<ModelComparison>,
+}
+
+/**
+ * Endpoint for running one prompt against several models side by side.
+ * Each result carries the model's response (or `error`) and `latency_ms`,
+ * so a failing model doesn't hide the others' answers.
+ */
+async fn handle_compare_models(
+    State(state): State<AppState>,
+    headers: axum::http::HeaderMap,
+    Json(request): Json<CompareModelsRequest>,
+) -> impl IntoResponse {
+    // Check for API key in header (allows per-request API keys)
+    let api_key = headers.get("x-openai-key").and_then(|v| v.to_str().ok()).map(|s| s.to_string());
+    
+    if request.models.is_empty() || request.models.len() > MAX_COMPARE_MODELS {
+        return ApiError::bad_request(format!("Between 1 and {} models are required", MAX_COMPARE_MODELS)).into_response();
+    }
+    
+    let results = state
+        .rig_service
+        .compare_models(&request.content, &request.models, api_key.as_deref())
+        .await;
+    (StatusCode::OK, Json(CompareModelsResponse { results })).into_response()
+}
+
+/**
+ * Data structures for handling keyword draft requests and responses.
+ */
+#[derive(Deserialize)]
+struct DraftRequest {
+    keywords: Vec<String>,
+    style: Option<String>,
+}
+
+#[derive(Serialize)]
+struct DraftResponse {
+    draft: String,
+}
+
+/**
+ * Endpoint for drafting a note from a list of disconnected keywords.
+ * Responds with 400 if no non-empty keyword is given.
+ */
+async fn handle_draft(
+    State(state): State<AppState>,
+    headers: axum::http::HeaderMap,
+    Json(request): Json<DraftRequest>,
+) -> impl IntoResponse {
+    // Check for API key in header (allows per-request API keys)
+    let api_key = headers.get("x-openai-key").and_then(|v| v.to_str().ok()).map(|s| s.to_string());
+    
+    if request.keywords.iter().all(|keyword| keyword.trim().is_empty()) {
+        return ApiError::bad_request("At least one keyword is required").into_response();
+    }
+    
+    match state
+        .rig_service
+        .generate_from_keywords(request.keywords, request.style, api_key.as_deref())
+        .await
+    {
+        Ok(draft) => (
+            StatusCode::OK,
+            Json(DraftResponse { draft }),
+        )
+            .into_response(),
+        Err(e) => {
+            tracing::error!("Error drafting from keywords: {}", e);
+            service_error(&state, e.as_ref()).into_response()
+        }
+    }
+}
+
+/**
+ * Data structures for handling embedding requests and responses.
+ */
+#[derive(Deserialize)]
+struct EmbeddingsRequest {
+    texts: Vec<String>,
+}
+
+#[derive(Serialize)]
+struct EmbeddingsResponse {
+    embeddings: Vec<Vec<f64>>,
+}
+
+/**
+ * Endpoint for computing embedding vectors, one per text.
+ * Requests for a single text are micro-batched with other concurrent
+ * single-text requests (see `embedding_batch_window_ms`).
+ */
+async fn handle_embeddings(
+    State(state): State<AppState>,
+    headers: axum::http::HeaderMap,
+    Json(request): Json<EmbeddingsRequest>,
+) -> impl IntoResponse {
+    // Check for API key in header (allows per-request API keys)
+    let api_key = headers.get("x-openai-key").and_then(|v| v.to_str().ok()).map(|s| s.to_string());
+    
+    if request.texts.is_empty() {
+        return ApiError::bad_request("At least one text is required").into_response();
+    }
+    
+    match state.rig_service.embed(request.texts, api_key.as_deref()).await {
+        Ok(embeddings) => (
+            StatusCode::OK,
+            Json(EmbeddingsResponse { embeddings }),
+        )
+            .into_response(),
+        Err(e) => {
+            tracing::error!("Error computing embeddings: {}", e);
+            service_error(&state, e.as_ref()).into_response()
+        }
+    }
+}
+
+/**
+ * Data structures for handling semantic diff requests and responses.
+ */
+#[derive(Deserialize)]
+struct SemanticDiffRequest {
+    a: String,
+    b: String,
+    threshold: Option<f64>,
+}
+
+#[derive(Serialize)]
+struct SemanticDiffResponse {
+    #[serde(flatten)]
+    diff: SemanticDiff,
+}
+
+/**
+ * Endpoint for comparing two notes by meaning using embeddings.
+ * Reports the passages present in only one note and those in both, so
+ * reworded content still counts as shared.
+ */
+async fn handle_semantic_diff(
+    State(state): State<AppState>,
+    headers: axum::http::HeaderMap,
+    Json(request): Json<SemanticDiffRequest>,
+) -> impl IntoResponse {
+    // Check for API key in header (allows per-request API keys)
+    let api_key = headers.get("x-openai-key").and_then(|v| v.to_str().ok()).map(|s| s.to_string());
+    let threshold = request.threshold.unwrap_or(DEFAULT_SEMANTIC_DIFF_THRESHOLD);
+    
+    let invalid = if request.a.trim().is_empty() || request.b.trim().is_empty() {
+        Some("Both notes must be non-empty".to_string())
+    } else if !(threshold > 0.0 && threshold <= 1.0) {
+        Some("threshold must be greater than 0 and at most 1".to_string())
+    } else {
+        None
+    };
+    if let Some(error) = invalid {
+        return ApiError::bad_request(error).into_response();
+    }
+    
+    match state
+        .rig_service
+        .semantic_diff(&request.a, &request.b, threshold, api_key.as_deref())
+        .await
+    {
+        Ok(diff) => (
+            StatusCode::OK,
+            Json(SemanticDiffResponse { diff }),
+        )
+            .into_response(),
+        Err(e) => {
+            tracing::error!("Error computing semantic diff: {}", e);
+            service_error(&state, e.as_ref()).into_response()
+        }
+    }
+}
+
+/**
+ * Data structures for handling command suggestion requests and responses.
+ */
+#[derive(Deserialize)]
+struct CommandsRequest {
+    content: String,
+    max_commands: Option<usize>,
+}
+
+#[derive(Serialize)]
+struct CommandsResponse {
+    commands: Vec<CommandSuggestion>,
+}
+
+/**
+ * Endpoint for suggesting plugin commands from a note describing a workflow.
+ * Returns names and descriptions the plugin can register as Obsidian commands.
+ */
+async fn handle_commands(
+    State(state): State<AppState>,
+    headers: axum::http::HeaderMap,
+    Json(request): Json<CommandsRequest>,
+) -> impl IntoResponse {
+    // Check for API key in header (allows per-request API keys)
+    let api_key = headers.get("x-openai-key").and_then(|v| v.to_str().ok()).map(|s| s.to_string());
+    let max_commands = request.max_commands.unwrap_or(5).clamp(1, 10);
+    
+    match state
+        .rig_service
+        .suggest_commands(&request.content, max_commands, api_key.as_deref())
+        .await
+    {
+        Ok(commands) => (
+            StatusCode::OK,
+            Json(CommandsResponse { commands }),
+        )
+            .into_response(),
+        Err(e) => {
+            tracing::error!("Error suggesting commands: {}", e);
+            service_error(&state, e.as_ref()).into_response()
+        }
+    }
+}
+
+/**
+ * Data structures for handling template generation requests and responses.
+ */
+#[derive(Deserialize)]
+struct TemplateFromExamplesRequest {
+    examples: Vec<String>,
+}
+
+#[derive(Serialize)]
+struct TemplateFromExamplesResponse {
+    template: String,
+}
+
+/**
+ * Endpoint for generating a note template from several example notes.
+ * The template keeps the structure the examples share and marks the
+ * varying parts with `{{placeholders}}`. At least two examples are required.
+ */
+async fn handle_template_from_examples(
+    State(state): State<AppState>,
+    headers: axum::http::HeaderMap,
+    Json(request): Json<TemplateFromExamplesRequest>,
+) -> impl IntoResponse {
+    // Check for API key in header (allows per-request API keys)
+    let api_key = headers.get("x-openai-key").and_then(|v| v.to_str().ok()).map(|s| s.to_string());
+    
+    // A common structure needs something to compare against
+    let examples: Vec<String> = request
+        .examples
+        .into_iter()
+        .filter(|example| !example.trim().is_empty())
+        .collect();
+    if examples.len() < 2 {
+        return ApiError::bad_request("At least two non-empty examples are required").into_response();
+    }
+    
+    match state
+        .rig_service
+        .template_from_examples(&examples, api_key.as_deref())
+        .await
+    {
+        Ok(template) => (
+            StatusCode::OK,
+            Json(TemplateFromExamplesResponse { template }),
+        )
+            .into_response(),
+        Err(e) => {
+            tracing::error!("Error generating template: {}", e);
+            service_error(&state, e.as_ref()).into_response()
+        }
+    }
+}
+
+/**
+ * Data structures for handling image-occlusion card requests and responses.
+ */
+#[derive(Deserialize)]
+struct OcclusionRequest {
+    labels: Vec<String>,
+    with_hints: Option<bool>,
+}
+
+#[derive(Serialize)]
+struct OcclusionResponse {
+    cards: Vec<OcclusionCard>,
+}
+
+/**
+ * Endpoint for building image-occlusion cards from diagram labels.
+ * Each card hides one label and shows the others. Cards are computed without
+ * a model call; `with_hints` adds a model-generated hint to each card.
+ * If hint generation fails, the cards are still returned without hints.
+ */
+async fn handle_occlusion(
+    State(state): State<AppState>,
+    headers: axum::http::HeaderMap,
+    Json(request): Json<OcclusionRequest>,
+) -> impl IntoResponse {
+    let mut cards = occlusion::occlusion_cards(&request.labels);
+    if cards.is_empty() {
+        return ApiError::bad_request("At least one non-empty label is required").into_response();
+    }
+    
+    if !request.with_hints.unwrap_or(false) {
+        return (StatusCode::OK, Json(OcclusionResponse { cards })).into_response();
+    }
+    
+    // Check for API key in header (allows per-request API keys)
+    let api_key = headers.get("x-openai-key").and_then(|v| v.to_str().ok()).map(|s| s.to_string());
+    let labels: Vec<String> = cards.iter().map(|card| card.hidden.clone()).collect();
+    
+    match state
+        .rig_service
+        .occlusion_hints(&labels, api_key.as_deref())
+        .await
+    {
+        Ok(hints) => occlusion::apply_hints(&mut cards, &hints),
+        // The cards don't depend on the model, so they're still useful without hints
+        Err(e) => tracing::warn!("Returning occlusion cards without hints: {}", e),
+    }
+    (StatusCode::OK, Json(OcclusionResponse { cards })).into_response()
+}
+
+/**
+ * Data structures for handling study deck requests and responses.
+ */
+#[derive(Deserialize)]
+struct StudyDeckRequest {
+    content: String,
+    title: Option<String>,
+    question_count: Option<usize>,
+}
+
+#[derive(Serialize)]
+struct StudyDeckResponse {
+    #[serde(flatten)]
+    deck: StudyDeck,
+}
+
+/**
+ * Endpoint for generating a complete study deck in one model call.
+ * Returns a title, flashcards and open-ended questions, replacing separate
+ * calls to the questions and flashcards endpoints.
+ */
+async fn handle_study_deck(
+    State(state): State<AppState>,
+    headers: axum::http::HeaderMap,
+    Json(request): Json<StudyDeckRequest>,
+) -> impl IntoResponse {
+    // Check for API key in header (allows per-request API keys)
+    let api_key = headers.get("x-openai-key").and_then(|v| v.to_str().ok()).map(|s| s.to_string());
+    let question_count = request.question_count.unwrap_or(5).clamp(1, 20);
+    
+    match state
+        .rig_service
+        .generate_study_deck(&request.content, request.title.as_deref(), question_count, api_key.as_deref())
+        .await
+    {
+        Ok(deck) => (
+            StatusCode::OK,
+            Json(StudyDeckResponse { deck }),
+        )
+            .into_response(),
+        Err(e) => {
+            tracing::error!("Error generating study deck: {}", e);
+            service_error(&state, e.as_ref()).into_response()
         }
     }
 }
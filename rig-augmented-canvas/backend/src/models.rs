@@ -3,9 +3,24 @@
  * It provides a wrapper around the Rig library's Agent type.
  */
 
-use rig::{agent::Agent, providers::openai};
+use crate::context::{self, ContextOverflow};
+use crate::error::RigError;
+use crate::fixtures::{FixtureMode, FixtureStore};
+use crate::history::{self, ChatRole, ChatTurn};
+use crate::pricing::{self, ModelPrice, TokenUsage};
+use crate::tokens;
+use crate::transform::ContentTransformKind;
+use futures::stream::{self, Stream, StreamExt};
+use rig::{
+    agent::Agent,
+    completion::{AssistantContent, Completion, Message},
+    providers::openai,
+    streaming::{StreamingChoice, StreamingPrompt},
+};
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::error::Error;
+use std::pin::Pin;
 
 /**
  * Supported AI model providers.
@@ -26,6 +41,296 @@ pub struct ModelConfig {
     pub model_name: String,          // The specific model to use (e.g., "o3-mini")
     pub api_key_env: Option<String>, // Environment variable name for the API key
     pub base_url: Option<String>,    // Optional custom API endpoint
+    #[serde(default)]
+    pub model_aliases: HashMap<String, String>, // Friendly names (e.g., "fast") mapped to concrete models
+    #[serde(default)]
+    pub include_datetime: bool,      // Prepend the current date/time to the system context
+    #[serde(default)]
+    pub timezone: Option<String>,    // IANA timezone used for the date/time context (defaults to UTC)
+    #[serde(default = "default_dedupe_threshold")]
+    pub dedupe_threshold: f64,       // Similarity (0.0-1.0) at which flashcards are merged as duplicates
+    #[serde(default = "default_true")]
+    pub dedupe_questions: bool,      // Drop near-identical generated questions, topping up to the requested count
+    #[serde(default = "default_dedupe_threshold")]
+    pub question_dedupe_threshold: f64, // Similarity (0.0-1.0) at which generated questions count as duplicates
+    #[serde(default)]
+    pub api_style: Option<String>,   // "chat" (default) or "completion" for legacy-only servers
+    #[serde(default = "default_true")]
+    pub sanitize_responses: bool,    // Strip stray control characters from model responses
+    #[serde(default)]
+    pub multinode_mode: MultiNodeMode, // Combine nodes into one prompt, or process each node separately
+    #[serde(default)]
+    pub tpm_limit: Option<u32>,      // Tokens-per-minute budget per model; requests wait when exceeded
+    #[serde(default)]
+    pub sampling: SamplingParams,    // Default sampling parameters, overridable per request
+    #[serde(default)]
+    pub filename_style: FilenameStyle, // Casing applied to suggested filenames
+    #[serde(default)]
+    pub strip_frontmatter: bool,     // Remove leading YAML frontmatter from content before prompting
+    #[serde(default)]
+    pub content_transforms: Vec<ContentTransformKind>, // Preprocessing steps applied to prompt content, in order
+    #[serde(default)]
+    pub split_large_responses: bool, // Return oversized responses pre-split at headings as linked parts
+    #[serde(default = "default_split_threshold")]
+    pub split_threshold: usize,      // Response size (in characters) above which responses are split
+    #[serde(default)]
+    pub blocked_patterns: Vec<String>, // Regexes; matching content is rejected before reaching the provider
+    #[serde(default = "default_embedding_model")]
+    pub embedding_model: String,     // Model used by the embeddings endpoint
+    #[serde(default = "default_embedding_batch_window_ms")]
+    pub embedding_batch_window_ms: u64, // Window for batching single-text embedding requests (0 disables)
+    #[serde(default = "default_true")]
+    pub embeddings_enabled: bool,    // Set to false for providers without embeddings; dependent endpoints return 501
+    #[serde(default)]
+    pub endpoint_system_prompts: HashMap<String, String>, // System prompts keyed by endpoint (e.g., "flashcards")
+    #[serde(default = "default_true")]
+    pub suppress_reasoning_in_output: bool, // Ask structured endpoints for the final answer only and strip leaked reasoning
+    #[serde(default = "default_vision_model")]
+    pub vision_model: String,        // Vision-capable model used for image descriptions (alt text)
+    #[serde(default)]
+    pub max_response_chars: Option<usize>, // Prompt responses longer than this are cut at a word boundary
+    #[serde(default)]
+    pub unwrap_double_encoded_json: bool, // Decode structured responses a gateway sent as a JSON-encoded string
+    #[serde(default)]
+    pub max_streams: Option<usize>,  // Streaming connections allowed at once; new streams get 503 when full
+    #[serde(default)]
+    pub instruction_language: Option<String>, // Language of the built-in instruction templates (e.g., "de"); English when unset
+    #[serde(default)]
+    pub max_multinode_chars: Option<usize>, // Combined multi-node content beyond this drops the lowest-weight nodes first
+    #[serde(default)]
+    pub instruction_budget_ratio: Option<f64>, // Share of max_multinode_chars reserved for the system prompt and prompt
+    #[serde(default)]
+    pub fallback_message: Option<String>, // Shown instead of the raw error when the provider can't be reached
+    #[serde(default = "default_request_timeout_secs")]
+    pub request_timeout_secs: u64,   // Prompts waiting longer than this for the model fail with a timeout
+    #[serde(default = "default_chunk_size_tokens")]
+    pub chunk_size_tokens: usize,    // Window size used when long notes are processed in chunks
+    #[serde(default = "default_chunk_overlap_tokens")]
+    pub chunk_overlap_tokens: usize, // Tokens shared by consecutive chunks so boundary context isn't lost
+    #[serde(default)]
+    pub usage_reporting: UsageReporting, // When streaming endpoints emit `usage` events with token counts
+    #[serde(default)]
+    pub auto_model_rules: Vec<AutoModelRule>, // Models picked by content length; the first matching rule wins
+    #[serde(default)]
+    pub errors_as_200: bool, // Return errors as 200 with `{ ok: false, error }` for clients that hide non-2xx bodies
+    #[serde(default)]
+    pub max_input_tokens: Option<usize>, // Prompts counted at more tokens than this are refused before calling the model
+    #[serde(default = "default_max_history_turns")]
+    pub max_history_turns: usize, // Earlier conversation turns applied to a prompt; older turns are dropped
+    #[serde(default)]
+    pub context_limits: HashMap<String, usize>, // Context window in tokens by model name, overriding the defaults
+    #[serde(default)]
+    pub context_overflow: ContextOverflow, // Truncate or summarize combined nodes that don't fit the context window
+    #[serde(default)]
+    pub model_prices: HashMap<String, ModelPrice>, // USD per million tokens by model name, overriding the defaults
+    #[serde(default = "default_max_concurrent_requests")]
+    pub max_concurrent_requests: usize, // Provider calls in flight at once; further prompts queue for a slot
+    #[serde(default)]
+    pub questions_model: Option<String>, // Model (or alias) for question generation instead of model_name
+    #[serde(default)]
+    pub flashcards_model: Option<String>, // Model (or alias) for flashcard generation instead of model_name
+    #[serde(default)]
+    pub summary_model: Option<String>, // Model (or alias) for summaries instead of model_name
+}
+
+/**
+ * Casing convention applied to generated filenames so they match the vault.
+ * Raw keeps the model's suggestion, only removing characters that aren't
+ * allowed in file names.
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FilenameStyle {
+    Kebab,
+    Snake,
+    Title,
+    #[default]
+    Raw,
+}
+
+/**
+ * Sampling parameters sent to the provider.
+ * Every field is optional; unset fields use the provider's defaults.
+ */
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SamplingParams {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_tokens: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub top_p: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub frequency_penalty: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub presence_penalty: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub seed: Option<i64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stop: Option<Vec<String>>,
+}
+
+impl SamplingParams {
+    /**
+     * Checks that every set field is within the range accepted by OpenAI.
+     *
+     * @return An error describing the first invalid field, if any
+     */
+    pub fn validate(&self) -> Result<(), String> {
+        fn check_range(name: &str, value: Option<f64>, min: f64, max: f64) -> Result<(), String> {
+            match value {
+                Some(value) if !(min..=max).contains(&value) => Err(format!(
+                    "{} must be between {} and {}, got {}",
+                    name, min, max, value
+                )),
+                _ => Ok(()),
+            }
+        }
+        
+        check_range("temperature", self.temperature, 0.0, 2.0)?;
+        check_range("top_p", self.top_p, 0.0, 1.0)?;
+        check_range("frequency_penalty", self.frequency_penalty, -2.0, 2.0)?;
+        check_range("presence_penalty", self.presence_penalty, -2.0, 2.0)?;
+        if self.max_tokens == Some(0) {
+            return Err("max_tokens must be greater than 0".to_string());
+        }
+        if let Some(stop) = &self.stop {
+            if stop.len() > 4 {
+                return Err(format!("stop accepts at most 4 sequences, got {}", stop.len()));
+            }
+        }
+        Ok(())
+    }
+    
+    /**
+     * Layers these parameters over a set of defaults; fields set here win.
+     *
+     * @param defaults The parameters to fall back to
+     * @return The merged parameters
+     */
+    pub fn merged_over(&self, defaults: &SamplingParams) -> SamplingParams {
+        SamplingParams {
+            temperature: self.temperature.or(defaults.temperature),
+            max_tokens: self.max_tokens.or(defaults.max_tokens),
+            top_p: self.top_p.or(defaults.top_p),
+            frequency_penalty: self.frequency_penalty.or(defaults.frequency_penalty),
+            presence_penalty: self.presence_penalty.or(defaults.presence_penalty),
+            seed: self.seed.or(defaults.seed),
+            stop: self.stop.clone().or_else(|| defaults.stop.clone()),
+        }
+    }
+    
+    /**
+     * Collects the parameters Rig's agent builder has no dedicated setter for.
+     *
+     * @return A JSON object of extra request parameters, or None if there are none
+     */
+    fn additional_params(&self) -> Option<serde_json::Value> {
+        let mut params = serde_json::Map::new();
+        if let Some(top_p) = self.top_p {
+            params.insert("top_p".to_string(), top_p.into());
+        }
+        if let Some(frequency_penalty) = self.frequency_penalty {
+            params.insert("frequency_penalty".to_string(), frequency_penalty.into());
+        }
+        if let Some(presence_penalty) = self.presence_penalty {
+            params.insert("presence_penalty".to_string(), presence_penalty.into());
+        }
+        if let Some(seed) = self.seed {
+            params.insert("seed".to_string(), seed.into());
+        }
+        if let Some(stop) = &self.stop {
+            params.insert("stop".to_string(), stop.clone().into());
+        }
+        
+        if params.is_empty() {
+            None
+        } else {
+            Some(serde_json::Value::Object(params))
+        }
+    }
+}
+
+/**
+ * Picks `model` for prompts of at most `max_chars` characters.
+ * Rules are checked in order and must have increasing `max_chars`.
+ */
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AutoModelRule {
+    pub max_chars: usize,
+    pub model: String,
+}
+
+/**
+ * When streaming endpoints report token usage.
+ * Final sends one `usage` event with the totals before the stream ends;
+ * Incremental sends running totals after every chunk or batch item; Off
+ * sends none.
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum UsageReporting {
+    #[default]
+    Final,
+    Incremental,
+    Off,
+}
+
+/**
+ * How multi-node prompts are processed.
+ * Combine synthesizes all nodes in a single call; PerNode runs the prompt
+ * against each node independently so one problematic node can't sink the rest.
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MultiNodeMode {
+    #[default]
+    Combine,
+    PerNode,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_request_timeout_secs() -> u64 {
+    60
+}
+
+fn default_dedupe_threshold() -> f64 {
+    crate::dedupe::DEFAULT_DEDUPE_THRESHOLD
+}
+
+fn default_split_threshold() -> usize {
+    crate::split::DEFAULT_SPLIT_THRESHOLD
+}
+
+fn default_embedding_model() -> String {
+    crate::embeddings::DEFAULT_EMBEDDING_MODEL.to_string()
+}
+
+fn default_vision_model() -> String {
+    crate::vision::DEFAULT_VISION_MODEL.to_string()
+}
+
+fn default_chunk_size_tokens() -> usize {
+    crate::chunk::DEFAULT_CHUNK_SIZE_TOKENS
+}
+
+fn default_chunk_overlap_tokens() -> usize {
+    crate::chunk::DEFAULT_CHUNK_OVERLAP_TOKENS
+}
+
+fn default_max_history_turns() -> usize {
+    crate::history::DEFAULT_MAX_HISTORY_TURNS
+}
+
+fn default_embedding_batch_window_ms() -> u64 {
+    crate::embeddings::DEFAULT_EMBEDDING_BATCH_WINDOW_MS
+}
+
+fn default_max_concurrent_requests() -> usize {
+    crate::request_limit::DEFAULT_MAX_CONCURRENT_REQUESTS
 }
 
 /**
@@ -39,8 +344,233 @@ impl Default for ModelConfig {
             model_name: "o3-mini".to_string(),
             api_key_env: Some("OPENAI_API_KEY".to_string()),
             base_url: None,
+            model_aliases: HashMap::new(),
+            include_datetime: false,
+            timezone: None,
+            dedupe_threshold: default_dedupe_threshold(),
+            dedupe_questions: true,
+            question_dedupe_threshold: default_dedupe_threshold(),
+            api_style: None,
+            sanitize_responses: true,
+            multinode_mode: MultiNodeMode::Combine,
+            tpm_limit: None,
+            sampling: SamplingParams::default(),
+            filename_style: FilenameStyle::Raw,
+            strip_frontmatter: false,
+            content_transforms: Vec::new(),
+            split_large_responses: false,
+            split_threshold: default_split_threshold(),
+            blocked_patterns: Vec::new(),
+            embedding_model: default_embedding_model(),
+            embedding_batch_window_ms: default_embedding_batch_window_ms(),
+            embeddings_enabled: true,
+            endpoint_system_prompts: HashMap::new(),
+            suppress_reasoning_in_output: true,
+            vision_model: default_vision_model(),
+            max_response_chars: None,
+            unwrap_double_encoded_json: false,
+            max_streams: None,
+            instruction_language: None,
+            max_multinode_chars: None,
+            instruction_budget_ratio: None,
+            fallback_message: None,
+            request_timeout_secs: default_request_timeout_secs(),
+            chunk_size_tokens: default_chunk_size_tokens(),
+            chunk_overlap_tokens: default_chunk_overlap_tokens(),
+            usage_reporting: UsageReporting::Final,
+            auto_model_rules: Vec::new(),
+            errors_as_200: false,
+            max_input_tokens: None,
+            max_history_turns: default_max_history_turns(),
+            context_limits: HashMap::new(),
+            context_overflow: ContextOverflow::Truncate,
+            model_prices: HashMap::new(),
+            max_concurrent_requests: default_max_concurrent_requests(),
+            questions_model: None,
+            flashcards_model: None,
+            summary_model: None,
+        }
+    }
+}
+
+impl ModelConfig {
+    /**
+     * Resolves a model name through the configured aliases.
+     * Names without an alias are returned unchanged.
+     *
+     * @param name The model name or alias to resolve
+     * @return The concrete model name to send to the provider
+     */
+    pub fn resolve_model(&self, name: &str) -> String {
+        match self.model_aliases.get(name) {
+            Some(model) => {
+                tracing::info!("Resolved model alias '{}' to '{}'", name, model);
+                model.clone()
+            }
+            None => name.to_string(),
         }
     }
+
+    /**
+     * The context window of a model: the configured `context_limits` entry,
+     * or the known default for its name.
+     *
+     * @param model The concrete model name (aliases already resolved)
+     * @return The limit in tokens
+     */
+    pub fn context_limit(&self, model: &str) -> usize {
+        self.context_limits
+            .get(model)
+            .copied()
+            .unwrap_or_else(|| context::default_context_limit(model))
+    }
+
+    /**
+     * The model configured for one of the built-in features, overriding
+     * `model_name` for that feature only.
+     *
+     * @param endpoint The endpoint's key (e.g., "flashcards")
+     * @return The model (or alias), or None to use `model_name`
+     */
+    pub fn feature_model(&self, endpoint: &str) -> Option<&str> {
+        let model = match endpoint {
+            "questions" => &self.questions_model,
+            "flashcards" => &self.flashcards_model,
+            "summarize" => &self.summary_model,
+            _ => return None,
+        };
+        model.as_deref().map(str::trim).filter(|model| !model.is_empty())
+    }
+
+    /**
+     * The price of a model: the configured `model_prices` entry, or the
+     * known default for its name.
+     *
+     * @param model The concrete model name (aliases already resolved)
+     * @return The price, or None if it isn't known
+     */
+    pub fn model_price(&self, model: &str) -> Option<ModelPrice> {
+        self.model_prices.get(model).copied().or_else(|| pricing::default_price(model))
+    }
+
+    /**
+     * Picks a model for content of the given length from `auto_model_rules`.
+     *
+     * @param content_chars The length of the prompt in characters
+     * @return The model (or alias) of the first rule that fits, if any
+     */
+    pub fn auto_model(&self, content_chars: usize) -> Option<&str> {
+        self.auto_model_rules
+            .iter()
+            .find(|rule| content_chars <= rule.max_chars)
+            .map(|rule| rule.model.as_str())
+    }
+
+    /**
+     * Whether a model name is one this configuration recognizes: an alias,
+     * a model with a configured context limit or price, or an OpenAI model
+     * family with a known tokenizer. A custom `base_url` (e.g., a local
+     * Ollama server) can serve models under any name, so every name is
+     * accepted there.
+     *
+     * @param name The model name or alias
+     * @return true if the name is known
+     */
+    pub fn is_known_model(&self, name: &str) -> bool {
+        uses_custom_endpoint(self)
+            || self.model_aliases.contains_key(name)
+            || self.context_limits.contains_key(name)
+            || self.model_prices.contains_key(name)
+            || tokens::encoding_for(name).is_some()
+    }
+
+    /**
+     * Checks that `auto_model_rules` are ordered by strictly increasing
+     * `max_chars` (so every rule can match) and name a known model (see
+     * `is_known_model`), so a typo is caught when the configuration is
+     * applied rather than failing requests at the provider.
+     */
+    pub fn validate_auto_model_rules(&self) -> Result<(), String> {
+        let mut previous: Option<usize> = None;
+        for rule in &self.auto_model_rules {
+            let model = rule.model.trim();
+            if model.is_empty() || model.contains(char::is_whitespace) {
+                return Err(format!("auto_model_rules: invalid model name '{}'", rule.model));
+            }
+            if !self.is_known_model(model) {
+                return Err(format!(
+                    "auto_model_rules: unknown model '{}'; use an OpenAI model, an alias from model_aliases, or a model listed in context_limits or model_prices",
+                    rule.model
+                ));
+            }
+            if previous.is_some_and(|previous| rule.max_chars <= previous) {
+                return Err("auto_model_rules must be ordered by increasing max_chars".to_string());
+            }
+            previous = Some(rule.max_chars);
+        }
+        Ok(())
+    }
+
+    /**
+     * Returns a copy safe to show to users: credentials embedded in the
+     * base URL (user info or query string) are masked.
+     *
+     * @return The redacted configuration
+     */
+    pub fn redacted(&self) -> ModelConfig {
+        let mut config = self.clone();
+        config.base_url = config.base_url.as_deref().map(redact_url);
+        config
+    }
+
+    /**
+     * Parses the configured API style, defaulting to chat.
+     *
+     * @return The API style used to talk to the provider
+     */
+    pub fn api_style(&self) -> Result<ApiStyle, Box<dyn Error>> {
+        match self.api_style.as_deref().map(|style| style.trim().to_lowercase()) {
+            None => Ok(ApiStyle::Chat),
+            Some(style) if style.is_empty() || style == "chat" => Ok(ApiStyle::Chat),
+            Some(style) if style == "completion" => Ok(ApiStyle::Completion),
+            Some(style) => Err(format!("Unknown api_style '{}'. Expected \"chat\" or \"completion\"", style).into()),
+        }
+    }
+}
+
+/**
+ * Masks the user info and query string of a URL, which may carry credentials.
+ */
+fn redact_url(url: &str) -> String {
+    let (without_query, had_query) = match url.split_once('?') {
+        Some((base, _)) => (base, true),
+        None => (url, false),
+    };
+    let mut redacted = match without_query.split_once("://") {
+        Some((scheme, rest)) => {
+            let authority_end = rest.find('/').unwrap_or(rest.len());
+            match rest[..authority_end].rfind('@') {
+                Some(at) => format!("{}://***@{}", scheme, &rest[at + 1..]),
+                None => without_query.to_string(),
+            }
+        }
+        None => without_query.to_string(),
+    };
+    if had_query {
+        redacted.push_str("?***");
+    }
+    redacted
+}
+
+/**
+ * How requests are sent to an OpenAI-compatible provider.
+ * Chat uses `/chat/completions`; Completion uses the legacy `/completions`
+ * endpoint that some self-hosted servers still only implement.
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ApiStyle {
+    Chat,
+    Completion,
 }
 
 /**
@@ -68,29 +598,388 @@ pub fn has_api_key(config: &ModelConfig, direct_api_key: Option<&str>) -> bool {
         }
     }
     
-    false
+    // Custom endpoints (e.g., a local Ollama server) can be used without a key
+    uses_custom_endpoint(config)
 }
 
 /**
  * Wrapper around the Rig library's Agent type.
- * Provides a simplified interface for prompting the AI model.
+ * Provides a simplified interface for prompting the AI model, regardless of
+ * whether the provider is reached through the chat or legacy completion API.
  */
-pub struct AgentWrapper(pub Agent<openai::CompletionModel>);
+pub enum AgentWrapper {
+    Chat(Agent<openai::CompletionModel>),
+    Completion(Box<CompletionAgent>),
+}
+
+/**
+ * A response from the AI model, with the token usage the provider reported
+ * (None when it didn't report any, e.g. for recorded fixtures).
+ */
+#[derive(Debug, Clone, PartialEq)]
+pub struct AgentResponse {
+    pub text: String,
+    pub usage: Option<TokenUsage>,
+}
 
 impl AgentWrapper {
     /**
-     * Sends a prompt to the AI model and returns the response as a String.
+     * Sends a prompt to the AI model and returns the response with its usage.
+     * 
+     * @param content The text to send to the AI model
+     * @param fixtures Recorded replies to serve instead of calling the provider, if any
+     * @return The AI-generated response
+     */
+    pub async fn prompt(&self, content: &str, fixtures: Option<&FixtureStore>) -> Result<AgentResponse, Box<dyn Error>> {
+        self.chat(content, &[], fixtures).await
+    }
+
+    /**
+     * Sends a prompt following earlier turns of a conversation. The chat API
+     * receives the turns as messages; the legacy completion API receives
+     * them as a transcript.
      * 
      * @param content The text to send to the AI model
+     * @param history Earlier turns, oldest first
+     * @param fixtures Recorded replies to serve instead of calling the provider, if any
      * @return The AI-generated response
      */
-    pub async fn prompt(&self, content: &str) -> Result<String, Box<dyn Error>> {
-        // Call the parent prompt method and convert the result to String
-        let result = rig::completion::Prompt::prompt(&self.0, content).await?;
-        Ok(result.to_string())
+    pub async fn chat(
+        &self,
+        content: &str,
+        history: &[ChatTurn],
+        fixtures: Option<&FixtureStore>,
+    ) -> Result<AgentResponse, Box<dyn Error>> {
+        // Serve recorded replies instead of calling the provider when fixtures are configured
+        let Some(fixtures) = fixtures else {
+            return self.prompt_provider(content, history).await;
+        };
+        
+        // Recordings are keyed by the whole conversation, so a prompt without history keeps its key
+        let key = history::transcript(history, content);
+        if let Some(text) = fixtures.load(&key)? {
+            return Ok(AgentResponse { text, usage: None });
+        }
+        
+        match fixtures.mode() {
+            FixtureMode::Record => {
+                let response = self.prompt_provider(content, history).await?;
+                fixtures.save(&key, &response.text)?;
+                tracing::info!("Recorded fixture {}", fixtures.path_for(&key).display());
+                Ok(response)
+            }
+            FixtureMode::Replay => Err(format!(
+                "No recorded fixture for prompt (expected {})",
+                fixtures.path_for(&key).display()
+            )
+            .into()),
+        }
+    }
+
+    /**
+     * Sends a prompt straight to the provider, bypassing fixtures.
+     */
+    async fn prompt_provider(&self, content: &str, history: &[ChatTurn]) -> Result<AgentResponse, Box<dyn Error>> {
+        let result = match self {
+            AgentWrapper::Chat(agent) => Self::complete(agent, content, history).await,
+            AgentWrapper::Completion(agent) => agent.prompt(&history::transcript(history, content)).await,
+        };
+        result.map_err(classify_provider_error)
+    }
+
+    /**
+     * Runs one chat completion through Rig, keeping the raw response for its
+     * usage. Agents are built without tools, so only text is collected.
+     */
+    async fn complete(
+        agent: &Agent<openai::CompletionModel>,
+        content: &str,
+        history: &[ChatTurn],
+    ) -> Result<AgentResponse, Box<dyn Error>> {
+        let messages = history
+            .iter()
+            .map(|turn| match turn.role {
+                ChatRole::User => Message::user(turn.content.as_str()),
+                ChatRole::Assistant => Message::assistant(turn.content.as_str()),
+            })
+            .collect();
+        let response = Completion::completion(agent, content, messages).await?.send().await?;
+        
+        let text = response
+            .choice
+            .iter()
+            .filter_map(|content| match content {
+                AssistantContent::Text(text) => Some(text.text.as_str()),
+                _ => None,
+            })
+            .collect();
+        // OpenAI reports the prompt and total counts; the completion is the difference
+        let usage = response.raw_response.usage.map(|usage| TokenUsage {
+            prompt_tokens: usage.prompt_tokens as u32,
+            completion_tokens: usage.total_tokens.saturating_sub(usage.prompt_tokens) as u32,
+        });
+        Ok(AgentResponse { text, usage })
+    }
+
+    /**
+     * Sends a prompt to the AI model and streams the response text as it arrives.
+     * 
+     * @param content The text to send to the AI model
+     * @param fixtures Recorded replies to serve instead of calling the provider, if any
+     * @return A stream of response text chunks
+     */
+    pub async fn stream_prompt(&self, content: &str, fixtures: Option<&FixtureStore>) -> Result<ResponseStream, Box<dyn Error>> {
+        // Recorded replies are played back as a single chunk
+        if fixtures.is_some() {
+            let text = self.prompt(content, fixtures).await?.text;
+            return Ok(Box::pin(stream::once(async move { Ok(text) })));
+        }
+        
+        match self {
+            AgentWrapper::Chat(agent) => {
+                let chunks = StreamingPrompt::stream_prompt(agent, content)
+                    .await
+                    .map_err(|e| classify_provider_error(e.into()))?;
+                Ok(Box::pin(chunks.filter_map(|chunk| async move {
+                    match chunk {
+                        Ok(StreamingChoice::Message(text)) => Some(Ok(text)),
+                        // Agents are built without tools, so tool calls are ignored
+                        Ok(_) => None,
+                        Err(e) => Some(Err(e.into())),
+                    }
+                })))
+            }
+            AgentWrapper::Completion(agent) => {
+                // The legacy endpoint is used without streaming; emit the whole completion at once
+                let text = agent.prompt(content).await?.text;
+                Ok(Box::pin(stream::once(async move { Ok(text) })))
+            }
+        }
+    }
+}
+
+/**
+ * Classifies a provider failure: errors from failing to reach the provider
+ * at all (refused connections, DNS failures, timeouts) become
+ * `RigError::ProviderUnreachable`, rate limit rejections become
+ * `RigError::RateLimited` and anything else the provider returned becomes
+ * `RigError::ProviderError`, so handlers can tell them apart.
+ */
+fn classify_provider_error(e: Box<dyn Error>) -> Box<dyn Error> {
+    if e.downcast_ref::<RigError>().is_some() {
+        return e;
+    }
+    
+    let message = e.to_string();
+    let mut source: Option<&(dyn Error + 'static)> = Some(e.as_ref());
+    while let Some(error) = source {
+        if let Some(http) = error.downcast_ref::<reqwest::Error>() {
+            if http.is_connect() || http.is_timeout() {
+                return Box::new(RigError::ProviderUnreachable { message });
+            }
+            if http.status() == Some(reqwest::StatusCode::TOO_MANY_REQUESTS) {
+                return Box::new(RigError::RateLimited { message });
+            }
+        }
+        source = error.source();
+    }
+    
+    if is_rate_limit_message(&message) {
+        Box::new(RigError::RateLimited { message })
+    } else {
+        Box::new(RigError::ProviderError { message })
+    }
+}
+
+/**
+ * Checks whether a provider error message reports a rate limit. Rig passes
+ * most provider errors through as text, so the status isn't always available.
+ */
+fn is_rate_limit_message(message: &str) -> bool {
+    let message = message.to_lowercase();
+    message.contains("rate limit") || message.contains("too many requests")
+}
+
+/**
+ * A stream of response text chunks from the AI model.
+ */
+pub type ResponseStream = Pin<Box<dyn Stream<Item = Result<String, Box<dyn Error + Send + Sync>>> + Send>>;
+
+/**
+ * Minimal client for the legacy OpenAI `/completions` endpoint, which Rig
+ * doesn't support. The preamble is folded into the prompt text since the
+ * endpoint has no notion of a system message.
+ */
+pub struct CompletionAgent {
+    http: reqwest::Client,
+    base_url: String,
+    api_key: String,
+    model: String,
+    preamble: Option<String>,
+    sampling: SamplingParams,
+    max_tokens: u64, // Always sent; the endpoint's own default is only 16 tokens
+}
+
+#[derive(Deserialize)]
+struct LegacyCompletionResponse {
+    choices: Vec<LegacyCompletionChoice>,
+    #[serde(default)]
+    usage: Option<LegacyCompletionUsage>,
+}
+
+#[derive(Deserialize)]
+struct LegacyCompletionUsage {
+    prompt_tokens: u32,
+    completion_tokens: u32,
+}
+
+#[derive(Deserialize)]
+struct LegacyCompletionChoice {
+    text: String,
+}
+
+impl CompletionAgent {
+    /**
+     * Sends a prompt to the legacy completions endpoint.
+     * 
+     * @param content The text to send to the AI model
+     * @return The text of the first completion choice, with the reported usage
+     */
+    pub async fn prompt(&self, content: &str) -> Result<AgentResponse, Box<dyn Error>> {
+        let prompt = match &self.preamble {
+            Some(preamble) => format!("{}\n\n{}", preamble, content),
+            None => content.to_string(),
+        };
+        
+        let mut body = serde_json::json!({
+            "model": self.model,
+            "prompt": prompt,
+        });
+        if let Some(temperature) = self.sampling.temperature {
+            body["temperature"] = temperature.into();
+        }
+        body["max_tokens"] = self.max_tokens.into();
+        if let (Some(body), Some(serde_json::Value::Object(extra))) = (body.as_object_mut(), self.sampling.additional_params()) {
+            body.extend(extra);
+        }
+        
+        let response = self
+            .http
+            .post(format!("{}/completions", self.base_url.trim_end_matches('/')))
+            .bearer_auth(&self.api_key)
+            .json(&body)
+            .send()
+            .await?
+            .error_for_status()?
+            .json::<LegacyCompletionResponse>()
+            .await?;
+        
+        let usage = response.usage.map(|usage| TokenUsage {
+            prompt_tokens: usage.prompt_tokens,
+            completion_tokens: usage.completion_tokens,
+        });
+        response
+            .choices
+            .into_iter()
+            .next()
+            .map(|choice| AgentResponse {
+                text: choice.text.trim().to_string(),
+                usage,
+            })
+            .ok_or_else(|| "Completion response contained no choices".into())
     }
 }
 
+/**
+ * Builds an agent for the given key and model using the configured API style.
+ * 
+ * @param config The model configuration
+ * @param api_key The resolved API key
+ * @param preamble Optional system prompt for the agent
+ * @return A wrapped agent ready for prompting
+ */
+fn build_agent(config: &ModelConfig, api_key: &str, preamble: Option<&str>) -> Result<AgentWrapper, Box<dyn Error>> {
+    let model_name = config.resolve_model(&config.model_name);
+    tracing::debug!("Creating agent for model '{}'", model_name);
+    
+    match config.api_style()? {
+        ApiStyle::Chat => {
+            let client = openai_client(config.base_url.as_deref(), api_key);
+            let mut builder = client.agent(&model_name);
+            if let Some(preamble) = preamble {
+                builder = builder.preamble(preamble);
+            }
+            if let Some(temperature) = config.sampling.temperature {
+                builder = builder.temperature(temperature);
+            }
+            if let Some(max_tokens) = config.sampling.max_tokens {
+                builder = builder.max_tokens(max_tokens);
+            }
+            if let Some(params) = config.sampling.additional_params() {
+                builder = builder.additional_params(params);
+            }
+            Ok(AgentWrapper::Chat(builder.build()))
+        }
+        ApiStyle::Completion => Ok(AgentWrapper::Completion(Box::new(CompletionAgent {
+            http: reqwest::Client::new(),
+            base_url: config
+                .base_url
+                .clone()
+                .unwrap_or_else(|| DEFAULT_OPENAI_BASE_URL.to_string()),
+            api_key: api_key.to_string(),
+            preamble: preamble.map(|p| p.to_string()),
+            sampling: config.sampling.clone(),
+            max_tokens: config
+                .sampling
+                .max_tokens
+                .unwrap_or_else(|| default_completion_max_tokens(config.context_limit(&model_name))),
+            model: model_name,
+        }))),
+    }
+}
+
+/**
+ * Tokens a legacy completion may generate when `max_tokens` isn't set. The
+ * endpoint would otherwise stop after 16 tokens, so a quarter of the model's
+ * context window is allowed instead, up to the usual response reserve.
+ *
+ * @param context_limit The model's context window in tokens
+ * @return The completion token limit to send
+ */
+fn default_completion_max_tokens(context_limit: usize) -> u64 {
+    (context_limit / 4).min(context::DEFAULT_RESPONSE_RESERVE_TOKENS) as u64
+}
+
+pub const DEFAULT_OPENAI_BASE_URL: &str = "https://api.openai.com/v1";
+
+/**
+ * Builds an OpenAI client, pointed at `base_url` when one is configured
+ * (e.g., a local Ollama server at `http://localhost:11434/v1`).
+ *
+ * @param base_url The configured `base_url`, if any
+ * @param api_key The resolved API key, possibly empty for local servers
+ * @return The client
+ */
+pub fn openai_client(base_url: Option<&str>, api_key: &str) -> openai::Client {
+    match base_url.filter(|url| !url.trim().is_empty()) {
+        Some(base_url) => openai::Client::from_url(api_key, base_url),
+        None => openai::Client::new(api_key),
+    }
+}
+
+/**
+ * Tells whether requests go to a custom OpenAI-compatible endpoint rather
+ * than OpenAI itself. Local servers often need no API key, so one isn't
+ * required for them.
+ */
+fn uses_custom_endpoint(config: &ModelConfig) -> bool {
+    config
+        .base_url
+        .as_deref()
+        .map(|url| url.trim().trim_end_matches('/'))
+        .is_some_and(|url| !url.is_empty() && url != DEFAULT_OPENAI_BASE_URL)
+}
+
 /**
  * Creates an agent with the specified configuration.
  * 
@@ -101,25 +990,10 @@ impl AgentWrapper {
 pub fn create_agent(config: &ModelConfig, direct_api_key: Option<&str>) -> Result<AgentWrapper, Box<dyn Error>> {
     match config.provider {
         ModelProvider::OpenAI => {
-            // First try to use the direct API key if provided
-            let api_key = if let Some(key) = direct_api_key {
-                if !key.is_empty() {
-                    key.to_string()
-                } else {
-                    // If direct key is empty, fall back to environment variable
-                    get_api_key_from_env(config)?
-                }
-            } else {
-                // No direct key, use environment variable
-                get_api_key_from_env(config)?
-            };
-            
-            let client = openai::Client::new(&api_key);
+            let api_key = resolve_api_key(config, direct_api_key)?;
             
             // Create the agent
-            let agent = client.agent(&config.model_name).build();
-            
-            Ok(AgentWrapper(agent))
+            build_agent(config, &api_key, None)
         }
     }
 }
@@ -136,29 +1010,35 @@ pub fn create_agent(config: &ModelConfig, direct_api_key: Option<&str>) -> Resul
 pub fn create_agent_with_system_prompt(config: &ModelConfig, system_prompt: &str, direct_api_key: Option<&str>) -> Result<AgentWrapper, Box<dyn Error>> {
     match config.provider {
         ModelProvider::OpenAI => {
-            // First try to use the direct API key if provided
-            let api_key = if let Some(key) = direct_api_key {
-                if !key.is_empty() {
-                    key.to_string()
-                } else {
-                    // If direct key is empty, fall back to environment variable
-                    get_api_key_from_env(config)?
-                }
-            } else {
-                // No direct key, use environment variable
-                get_api_key_from_env(config)?
-            };
-            
-            let client = openai::Client::new(&api_key);
+            let api_key = resolve_api_key(config, direct_api_key)?;
             
             // Create the agent with system prompt
-            let agent = client.agent(&config.model_name).preamble(system_prompt).build();
-            
-            Ok(AgentWrapper(agent))
+            build_agent(config, &api_key, Some(system_prompt))
         }
     }
 }
 
+/**
+ * Resolves the API key a request will use.
+ * A non-empty direct key wins; otherwise the configured environment variable is read.
+ * With a custom `base_url` and no key anywhere, an empty key is used.
+ *
+ * @param config The model configuration
+ * @param direct_api_key Optional API key provided directly (e.g., from plugin settings)
+ * @return The API key
+ */
+pub fn resolve_api_key(config: &ModelConfig, direct_api_key: Option<&str>) -> Result<String, Box<dyn Error>> {
+    match direct_api_key {
+        Some(key) if !key.is_empty() => Ok(key.to_string()),
+        // No direct key (or an empty one), fall back to the environment variable
+        _ => match get_api_key_from_env(config) {
+            // Local OpenAI-compatible servers usually accept requests without a key
+            Err(_) if uses_custom_endpoint(config) => Ok(String::new()),
+            result => result,
+        },
+    }
+}
+
 /**
  * Helper function to get API key from environment variable.
  * 
@@ -166,14 +1046,73 @@ pub fn create_agent_with_system_prompt(config: &ModelConfig, system_prompt: &str
  * @return The API key as a string
  */
 fn get_api_key_from_env(config: &ModelConfig) -> Result<String, Box<dyn Error>> {
-    if let Some(key_env) = &config.api_key_env {
-        std::env::var(key_env).map_err(|_| {
-            format!(
-                "OpenAI API key not found. Please either:\n1. Set the {} environment variable, or\n2. Enter your API key in the plugin settings",
-                key_env
-            ).into()
-        })
-    } else {
-        Err("API key environment variable not specified for OpenAI".into())
+    let env_var = config.api_key_env.clone();
+    env_var
+        .as_deref()
+        .and_then(|key_env| std::env::var(key_env).ok())
+        .ok_or_else(|| RigError::MissingApiKey { env_var }.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SamplingParams;
+
+    #[test]
+    fn validates_sampling_ranges() {
+        type Setter = fn(f64) -> SamplingParams;
+        let fields: [(&str, Setter, f64, f64); 4] = [
+            ("temperature", |value| SamplingParams { temperature: Some(value), ..SamplingParams::default() }, 0.0, 2.0),
+            ("top_p", |value| SamplingParams { top_p: Some(value), ..SamplingParams::default() }, 0.0, 1.0),
+            ("frequency_penalty", |value| SamplingParams { frequency_penalty: Some(value), ..SamplingParams::default() }, -2.0, 2.0),
+            ("presence_penalty", |value| SamplingParams { presence_penalty: Some(value), ..SamplingParams::default() }, -2.0, 2.0),
+        ];
+        for (name, params, min, max) in fields {
+            for value in [min, (min + max) / 2.0, max] {
+                assert!(params(value).validate().is_ok(), "{} = {} should be accepted", name, value);
+            }
+            for value in [min - 0.01, max + 0.01, f64::NAN, f64::INFINITY] {
+                let error = params(value).validate().unwrap_err();
+                assert!(error.starts_with(name), "{} = {} should be rejected, got {}", name, value, error);
+            }
+        }
+        assert!(SamplingParams::default().validate().is_ok());
+    }
+
+    #[test]
+    fn validates_max_tokens_and_stop() {
+        assert!(SamplingParams { max_tokens: Some(0), ..SamplingParams::default() }.validate().is_err());
+        assert!(SamplingParams { max_tokens: Some(1), ..SamplingParams::default() }.validate().is_ok());
+        
+        let stop = |count: usize| SamplingParams { stop: Some(vec!["\n".to_string(); count]), ..SamplingParams::default() };
+        assert!(stop(4).validate().is_ok());
+        assert!(stop(5).validate().is_err());
+    }
+
+    #[test]
+    fn merged_params_prefer_the_override() {
+        let defaults = SamplingParams {
+            temperature: Some(0.7),
+            max_tokens: Some(500),
+            top_p: Some(0.9),
+            stop: Some(vec!["END".to_string()]),
+            ..SamplingParams::default()
+        };
+        let overrides = SamplingParams {
+            temperature: Some(0.0),
+            seed: Some(42),
+            stop: Some(Vec::new()),
+            ..SamplingParams::default()
+        };
+        let merged = overrides.merged_over(&defaults);
+        assert_eq!(merged.temperature, Some(0.0));
+        assert_eq!(merged.max_tokens, Some(500));
+        assert_eq!(merged.top_p, Some(0.9));
+        assert_eq!(merged.frequency_penalty, None);
+        assert_eq!(merged.seed, Some(42));
+        assert_eq!(merged.stop, Some(Vec::new()));
+        
+        let merged = SamplingParams::default().merged_over(&defaults);
+        assert_eq!(merged.temperature, Some(0.7));
+        assert_eq!(merged.stop, Some(vec!["END".to_string()]));
     }
 }
@@ -0,0 +1,100 @@
+/**
+ * NDJSON module reassembles newline-delimited records from a stream of text
+ * chunks. Model output arrives in arbitrary fragments, so a record is only
+ * complete once its terminating newline has been seen.
+ */
+
+/**
+ * Accumulates streamed text and hands back complete lines.
+ */
+#[derive(Default)]
+pub struct LineBuffer {
+    pending: String,
+}
+
+impl LineBuffer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /**
+     * Appends a chunk and returns every line completed by it, trimmed and
+     * without empty lines.
+     *
+     * @param chunk The next piece of streamed text
+     * @return The completed lines, in order
+     */
+    pub fn push(&mut self, chunk: &str) -> Vec<String> {
+        self.pending.push_str(chunk);
+
+        let mut lines = Vec::new();
+        while let Some(newline) = self.pending.find('\n') {
+            let line: String = self.pending.drain(..=newline).collect();
+            let line = line.trim();
+            if !line.is_empty() {
+                lines.push(line.to_string());
+            }
+        }
+        lines
+    }
+
+    /**
+     * Returns whatever is left once the stream has ended.
+     *
+     * @return The final unterminated line, if it isn't empty
+     */
+    pub fn finish(&mut self) -> Option<String> {
+        let rest = std::mem::take(&mut self.pending);
+        let rest = rest.trim();
+        if rest.is_empty() {
+            None
+        } else {
+            Some(rest.to_string())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::LineBuffer;
+
+    #[test]
+    fn joins_records_split_across_chunks() {
+        let mut buffer = LineBuffer::new();
+        assert!(buffer.push("{\"front\": \"Wh").is_empty());
+        assert!(buffer.push("at is a move?\"}").is_empty());
+        assert_eq!(buffer.push("\n{\"fr"), vec!["{\"front\": \"What is a move?\"}"]);
+        assert_eq!(buffer.push("ont\": \"Why?\"}\n"), vec!["{\"front\": \"Why?\"}"]);
+        assert_eq!(buffer.finish(), None);
+    }
+
+    #[test]
+    fn returns_every_record_in_a_chunk() {
+        let mut buffer = LineBuffer::new();
+        assert_eq!(buffer.push("{\"a\": 1}\n{\"b\": 2}\n{\"c\""), vec!["{\"a\": 1}", "{\"b\": 2}"]);
+        assert_eq!(buffer.finish(), Some("{\"c\"".to_string()));
+    }
+
+    #[test]
+    fn skips_blank_lines_and_handles_crlf() {
+        let mut buffer = LineBuffer::new();
+        assert_eq!(buffer.push("\n  \n{\"a\": 1}\r\n\r\n{\"b\": 2}\r"), vec!["{\"a\": 1}"]);
+        assert_eq!(buffer.push("\n"), vec!["{\"b\": 2}"]);
+    }
+
+    #[test]
+    fn finishes_with_the_unterminated_line() {
+        let mut buffer = LineBuffer::new();
+        assert!(buffer.push("{\"a\": 1}").is_empty());
+        assert_eq!(buffer.finish(), Some("{\"a\": 1}".to_string()));
+        assert_eq!(buffer.finish(), None);
+
+        let mut buffer = LineBuffer::new();
+        assert_eq!(buffer.push("{\"a\": 1}\n"), vec!["{\"a\": 1}"]);
+        assert_eq!(buffer.finish(), None);
+
+        let mut buffer = LineBuffer::new();
+        buffer.push("{\"a\": 1}\n  \r");
+        assert_eq!(buffer.finish(), None);
+    }
+}
@@ -0,0 +1,95 @@
+/**
+ * Note abstract module holds what's needed to index a note, produced in one
+ * call: an elevator pitch, a detailed abstract and keywords, plus the JSON
+ * schema the output is checked against.
+ */
+
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+/// Keywords returned when the request doesn't say how many.
+pub const DEFAULT_MAX_KEYWORDS: usize = 8;
+
+/// Most keywords a request may ask for.
+pub const MAX_KEYWORDS_LIMIT: usize = 20;
+
+/**
+ * A note's one-sentence pitch, abstract and keywords.
+ */
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct NoteAbstract {
+    pub one_sentence: String,
+    #[serde(rename = "abstract")]
+    pub abstract_text: String,
+    pub keywords: Vec<String>,
+}
+
+/**
+ * Builds the JSON schema abstract output must match.
+ *
+ * @param max_keywords The most keywords allowed
+ * @return The schema
+ */
+pub fn abstract_schema(max_keywords: usize) -> Value {
+    json!({
+        "type": "object",
+        "properties": {
+            "one_sentence": { "type": "string", "minLength": 1 },
+            "abstract": { "type": "string", "minLength": 1 },
+            "keywords": {
+                "type": "array",
+                "maxItems": max_keywords,
+                "items": { "type": "string", "minLength": 1 }
+            }
+        },
+        "required": ["one_sentence", "abstract", "keywords"]
+    })
+}
+
+/**
+ * Trims the text fields and cleans up keywords: blank and repeated ones
+ * (ignoring case) are dropped and at most `max_keywords` are kept.
+ *
+ * @param note_abstract The abstract returned by the model
+ * @param max_keywords The most keywords to keep
+ * @return The cleaned abstract
+ */
+pub fn cleaned(note_abstract: NoteAbstract, max_keywords: usize) -> NoteAbstract {
+    let mut keywords: Vec<String> = Vec::new();
+    for keyword in &note_abstract.keywords {
+        let keyword = keyword.trim();
+        if !keyword.is_empty() && !keywords.iter().any(|existing| existing.to_lowercase() == keyword.to_lowercase()) {
+            keywords.push(keyword.to_string());
+        }
+    }
+    keywords.truncate(max_keywords);
+
+    NoteAbstract {
+        one_sentence: note_abstract.one_sentence.trim().to_string(),
+        abstract_text: note_abstract.abstract_text.trim().to_string(),
+        keywords,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{cleaned, NoteAbstract};
+
+    #[test]
+    fn cleans_and_clamps_keywords() {
+        let note_abstract = NoteAbstract {
+            one_sentence: " Rust guarantees memory safety. ".to_string(),
+            abstract_text: "An overview of ownership.".to_string(),
+            keywords: ["rust", "Ownership", " ", "RUST", "borrowing", "lifetimes"].map(str::to_string).to_vec(),
+        };
+        let cleaned = cleaned(note_abstract, 3);
+        assert_eq!(cleaned.one_sentence, "Rust guarantees memory safety.");
+        assert_eq!(cleaned.keywords, vec!["rust", "Ownership", "borrowing"]);
+    }
+
+    #[test]
+    fn serializes_abstract_field_name() {
+        let value = serde_json::to_value(NoteAbstract::default()).unwrap();
+        assert!(value.get("abstract").is_some());
+    }
+}
@@ -0,0 +1,123 @@
+/**
+ * Occlusion module builds image-occlusion style cards from the labels of a
+ * diagram: each card hides one label and shows the rest as context.
+ * This is a pure transformation; hints are optional and filled in separately.
+ */
+
+use serde::Serialize;
+use std::collections::HashMap;
+
+/**
+ * A card hiding one label of a diagram. `visible` lists the other labels in
+ * their original order.
+ */
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct OcclusionCard {
+    pub visible: Vec<String>,
+    pub hidden: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hint: Option<String>,
+}
+
+/**
+ * Trims labels and drops empty and duplicate ones (case-insensitively),
+ * keeping the first spelling of each.
+ *
+ * @param labels The labels as sent by the client
+ * @return The distinct, non-empty labels in their original order
+ */
+pub fn clean_labels(labels: &[String]) -> Vec<String> {
+    let mut cleaned: Vec<String> = Vec::new();
+    for label in labels {
+        let label = label.trim();
+        if !label.is_empty() && !cleaned.iter().any(|existing| existing.to_lowercase() == label.to_lowercase()) {
+            cleaned.push(label.to_string());
+        }
+    }
+    cleaned
+}
+
+/**
+ * Builds one card per label, hiding that label.
+ *
+ * @param labels The diagram labels; cleaned with `clean_labels` first
+ * @return The cards, in label order
+ */
+pub fn occlusion_cards(labels: &[String]) -> Vec<OcclusionCard> {
+    let labels = clean_labels(labels);
+    (0..labels.len())
+        .map(|hidden| OcclusionCard {
+            visible: labels
+                .iter()
+                .enumerate()
+                .filter(|(i, _)| *i != hidden)
+                .map(|(_, label)| label.clone())
+                .collect(),
+            hidden: labels[hidden].clone(),
+            hint: None,
+        })
+        .collect()
+}
+
+/**
+ * Attaches hints to the cards whose hidden label has one.
+ *
+ * @param cards The cards to update
+ * @param hints Hints keyed by label
+ */
+pub fn apply_hints(cards: &mut [OcclusionCard], hints: &HashMap<String, String>) {
+    for card in cards {
+        card.hint = hints
+            .get(&card.hidden)
+            .map(|hint| hint.trim().to_string())
+            .filter(|hint| !hint.is_empty());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{apply_hints, clean_labels, occlusion_cards};
+    use std::collections::HashMap;
+
+    fn labels(values: &[&str]) -> Vec<String> {
+        values.iter().map(|value| value.to_string()).collect()
+    }
+
+    #[test]
+    fn hides_each_label_once() {
+        let cards = occlusion_cards(&labels(&["Nucleus", "Membrane", "Cytoplasm"]));
+        assert_eq!(cards.len(), 3);
+        assert_eq!(cards[0].hidden, "Nucleus");
+        assert_eq!(cards[0].visible, labels(&["Membrane", "Cytoplasm"]));
+        assert_eq!(cards[2].hidden, "Cytoplasm");
+        assert_eq!(cards[2].visible, labels(&["Nucleus", "Membrane"]));
+    }
+
+    #[test]
+    fn cleans_blank_and_duplicate_labels() {
+        assert_eq!(
+            clean_labels(&labels(&[" Nucleus ", "", "nucleus", "Membrane", "  "])),
+            labels(&["Nucleus", "Membrane"])
+        );
+        assert!(occlusion_cards(&labels(&["", " "])).is_empty());
+    }
+
+    #[test]
+    fn single_label_has_no_visible_context() {
+        let cards = occlusion_cards(&labels(&["Nucleus"]));
+        assert_eq!(cards.len(), 1);
+        assert!(cards[0].visible.is_empty());
+    }
+
+    #[test]
+    fn applies_only_matching_non_empty_hints() {
+        let mut cards = occlusion_cards(&labels(&["Nucleus", "Membrane"]));
+        let hints = HashMap::from([
+            ("Nucleus".to_string(), " Holds the DNA ".to_string()),
+            ("Membrane".to_string(), "".to_string()),
+        ]);
+        apply_hints(&mut cards, &hints);
+        assert_eq!(cards[0].hint.as_deref(), Some("Holds the DNA"));
+        assert_eq!(cards[1].hint, None);
+    }
+}
@@ -0,0 +1,126 @@
+/**
+ * Outline module defines the hierarchical outline structure shared by the
+ * outline endpoints, plus helpers to address its leaves by path so they can
+ * be processed independently and reassembled.
+ */
+
+use serde::{Deserialize, Serialize};
+
+/**
+ * A single point in an outline. Leaves are points without children.
+ * `elaboration` and `error` are filled in by the elaboration endpoint.
+ */
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OutlineNode {
+    pub title: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub children: Vec<OutlineNode>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub elaboration: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/**
+ * A leaf located within an outline, with the titles of all its ancestors
+ * so it can be elaborated in context.
+ */
+#[derive(Debug, Clone)]
+pub struct OutlineLeaf {
+    pub path: Vec<usize>,
+    pub breadcrumb: Vec<String>,
+}
+
+impl OutlineLeaf {
+    pub fn title(&self) -> &str {
+        self.breadcrumb.last().map(String::as_str).unwrap_or_default()
+    }
+}
+
+/**
+ * Collects every leaf in the outline in document order.
+ *
+ * @param outline The top-level outline points
+ * @return The leaves with their paths and breadcrumbs
+ */
+pub fn collect_leaves(outline: &[OutlineNode]) -> Vec<OutlineLeaf> {
+    fn walk(nodes: &[OutlineNode], path: &mut Vec<usize>, breadcrumb: &mut Vec<String>, leaves: &mut Vec<OutlineLeaf>) {
+        for (index, node) in nodes.iter().enumerate() {
+            path.push(index);
+            breadcrumb.push(node.title.clone());
+            if node.children.is_empty() {
+                leaves.push(OutlineLeaf {
+                    path: path.clone(),
+                    breadcrumb: breadcrumb.clone(),
+                });
+            } else {
+                walk(&node.children, path, breadcrumb, leaves);
+            }
+            path.pop();
+            breadcrumb.pop();
+        }
+    }
+
+    let mut leaves = Vec::new();
+    walk(outline, &mut Vec::new(), &mut Vec::new(), &mut leaves);
+    leaves
+}
+
+/**
+ * Looks up a node by its path of child indices.
+ *
+ * @param outline The top-level outline points
+ * @param path Child indices from the top level down
+ * @return The node at that path, if it exists
+ */
+pub fn node_at_mut<'a>(outline: &'a mut [OutlineNode], path: &[usize]) -> Option<&'a mut OutlineNode> {
+    let (first, rest) = path.split_first()?;
+    let node = outline.get_mut(*first)?;
+    if rest.is_empty() {
+        Some(node)
+    } else {
+        node_at_mut(&mut node.children, rest)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{collect_leaves, node_at_mut, OutlineNode};
+
+    fn node(title: &str, children: Vec<OutlineNode>) -> OutlineNode {
+        OutlineNode { title: title.to_string(), children, elaboration: None, error: None }
+    }
+
+    #[test]
+    fn collects_and_fills_nested_leaves() {
+        let mut outline = vec![
+            node("Ownership", vec![node("Moves", vec![]), node("Borrowing", vec![node("Shared", vec![]), node("Mutable", vec![])])]),
+            node("Lifetimes", vec![]),
+        ];
+        
+        let leaves = collect_leaves(&outline);
+        let paths: Vec<&[usize]> = leaves.iter().map(|leaf| leaf.path.as_slice()).collect();
+        assert_eq!(paths, vec![&[0, 0][..], &[0, 1, 0], &[0, 1, 1], &[1]]);
+        assert_eq!(leaves[2].breadcrumb, vec!["Ownership", "Borrowing", "Mutable"]);
+        assert_eq!(leaves[2].title(), "Mutable");
+        
+        // Every leaf's path leads back to that leaf, so results land in place
+        for leaf in &leaves {
+            let node = node_at_mut(&mut outline, &leaf.path).unwrap();
+            assert_eq!(node.title, leaf.title());
+            node.elaboration = Some(format!("About {}", leaf.title()));
+        }
+        assert_eq!(outline[0].children[1].children[1].elaboration.as_deref(), Some("About Mutable"));
+        assert_eq!(outline[1].elaboration.as_deref(), Some("About Lifetimes"));
+        assert_eq!(outline[0].elaboration, None);
+    }
+
+    #[test]
+    fn missing_paths_find_nothing() {
+        let mut outline = vec![node("Ownership", vec![node("Moves", vec![])])];
+        assert!(node_at_mut(&mut outline, &[]).is_none());
+        assert!(node_at_mut(&mut outline, &[1]).is_none());
+        assert!(node_at_mut(&mut outline, &[0, 0, 0]).is_none());
+        assert!(collect_leaves(&[]).is_empty());
+    }
+}
@@ -0,0 +1,132 @@
+/**
+ * Plugin errors module implements the `errors_as_200` mode for HTTP clients
+ * that treat any non-2xx response as a hard failure and hide its message.
+ * When enabled, every error response is rewritten to `200` with
+ * `{ "ok": false, "error": ... }` and JSON success bodies gain `"ok": true`.
+ * Streaming (SSE) responses pass through unchanged.
+ */
+
+use axum::{
+    body::{to_bytes, Body},
+    extract::{Request, State},
+    http::{header, HeaderValue, StatusCode},
+    middleware::Next,
+    response::Response,
+};
+use serde_json::{json, Value};
+use std::sync::Arc;
+
+use crate::rig_service::RigService;
+
+/**
+ * Middleware applying `errors_as_200` when it is enabled in the config.
+ * The original status of a rewritten error is logged, not returned.
+ *
+ * @param rig_service The service holding the current configuration
+ * @param request The incoming request
+ * @param next The rest of the middleware stack and the handler
+ * @return The handler's response, rewritten if the mode is on
+ */
+pub async fn errors_as_200(
+    State(rig_service): State<Arc<RigService>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let response = next.run(request).await;
+    let enabled = rig_service.get_config().map(|config| config.errors_as_200).unwrap_or(false);
+    if !enabled {
+        return response;
+    }
+
+    let status = response.status();
+    let is_json = response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.starts_with("application/json"));
+    // Only error responses and JSON success bodies are rewritten; streams stay as they are
+    if status.is_success() && !is_json {
+        return response;
+    }
+
+    let (mut parts, body) = response.into_parts();
+    let bytes = match to_bytes(body, usize::MAX).await {
+        Ok(bytes) => bytes,
+        Err(e) => {
+            tracing::error!("Failed to read response body for errors_as_200: {}", e);
+            return Response::from_parts(parts, Body::empty());
+        }
+    };
+    if !status.is_success() {
+        tracing::warn!("Returning {} as 200 (errors_as_200)", status);
+    }
+
+    let body = with_ok_field(&bytes, status);
+    parts.status = StatusCode::OK;
+    parts.headers.remove(header::CONTENT_LENGTH);
+    parts.headers.insert(header::CONTENT_TYPE, HeaderValue::from_static("application/json"));
+    Response::from_parts(parts, Body::from(body.to_string()))
+}
+
+/**
+ * Adds the `ok` field to a response body. JSON objects keep their fields;
+ * an error without an `error` field gets one from its `message` (the
+ * standard error body), the body text (e.g., an axum rejection message) or
+ * the status reason.
+ *
+ * @param body The original response body
+ * @param status The original status
+ * @return The body to send with status 200
+ */
+fn with_ok_field(body: &[u8], status: StatusCode) -> Value {
+    let ok = status.is_success();
+    let mut value = match serde_json::from_slice::<Value>(body) {
+        Ok(Value::Object(object)) => Value::Object(object),
+        Ok(other) if ok => json!({ "data": other }),
+        _ => json!({}),
+    };
+
+    let object = value.as_object_mut().expect("body is an object");
+    if !ok && !matches!(object.get("error"), Some(Value::String(_))) {
+        let text = String::from_utf8_lossy(body).trim().to_string();
+        let error = if let Some(Value::String(message)) = object.get("message") {
+            message.clone()
+        } else if text.is_empty() || serde_json::from_slice::<Value>(body).is_ok() {
+            status.canonical_reason().unwrap_or("Request failed").to_string()
+        } else {
+            text
+        };
+        object.insert("error".to_string(), Value::String(error));
+    }
+    object.insert("ok".to_string(), Value::Bool(ok));
+    value
+}
+
+#[cfg(test)]
+mod tests {
+    use super::with_ok_field;
+    use axum::http::StatusCode;
+    use serde_json::json;
+
+    #[test]
+    fn marks_success_bodies() {
+        let body = with_ok_field(br#"{"tags":["rust"]}"#, StatusCode::OK);
+        assert_eq!(body, json!({ "ok": true, "tags": ["rust"] }));
+    }
+
+    #[test]
+    fn keeps_error_messages() {
+        let body = with_ok_field(br#"{"error":"Invalid style"}"#, StatusCode::BAD_REQUEST);
+        assert_eq!(body, json!({ "ok": false, "error": "Invalid style" }));
+
+        // Extractor rejections are plain text
+        let body = with_ok_field(b"Failed to parse the request body as JSON", StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(body, json!({ "ok": false, "error": "Failed to parse the request body as JSON" }));
+
+        let body = with_ok_field(br#"{"code":"rate_limited","message":"Slow down"}"#, StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(body, json!({ "ok": false, "code": "rate_limited", "message": "Slow down", "error": "Slow down" }));
+
+        let body = with_ok_field(b"", StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(body, json!({ "ok": false, "error": "Service Unavailable" }));
+    }
+}
@@ -0,0 +1,129 @@
+/**
+ * Pricing module estimates what a model call cost from the token usage the
+ * provider reported. Prices are per million tokens in USD; known OpenAI
+ * models have defaults, and `model_prices` in the configuration overrides
+ * them or adds prices for other models. The estimate ignores discounts such
+ * as cached input.
+ */
+
+use serde::{Deserialize, Serialize};
+
+/**
+ * Token counts the provider reported for one model call.
+ */
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+pub struct TokenUsage {
+    pub prompt_tokens: u32,
+    pub completion_tokens: u32,
+}
+
+/**
+ * Price of a model in USD per million tokens.
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct ModelPrice {
+    pub input_per_million: f64,
+    pub output_per_million: f64,
+}
+
+/**
+ * Usage reported with a response, with its estimated cost when the model's
+ * price is known.
+ */
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+pub struct UsageReport {
+    pub prompt_tokens: u32,
+    pub completion_tokens: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub estimated_cost_usd: Option<f64>,
+}
+
+/**
+ * The list price of a model, by the prefix of its name.
+ *
+ * @param model The concrete model name (aliases already resolved)
+ * @return The price, or None if the model isn't known
+ */
+pub fn default_price(model: &str) -> Option<ModelPrice> {
+    let model = model.trim().to_lowercase();
+    let model = model.strip_prefix("openai/").unwrap_or(&model);
+    // More specific prefixes come first
+    let prices: [(&str, f64, f64); 14] = [
+        ("gpt-4o-mini", 0.15, 0.60),
+        ("gpt-4o", 2.50, 10.00),
+        ("gpt-4.1-nano", 0.10, 0.40),
+        ("gpt-4.1-mini", 0.40, 1.60),
+        ("gpt-4.1", 2.00, 8.00),
+        ("gpt-4-turbo", 10.00, 30.00),
+        ("gpt-4", 30.00, 60.00),
+        ("gpt-3.5-turbo", 0.50, 1.50),
+        ("o1-mini", 1.10, 4.40),
+        ("o1", 15.00, 60.00),
+        ("o3-mini", 1.10, 4.40),
+        ("o3", 2.00, 8.00),
+        ("o4-mini", 1.10, 4.40),
+        ("text-embedding-3-small", 0.02, 0.0),
+    ];
+    prices
+        .iter()
+        .find(|(prefix, _, _)| model.starts_with(prefix))
+        .map(|(_, input, output)| ModelPrice {
+            input_per_million: *input,
+            output_per_million: *output,
+        })
+}
+
+/**
+ * Estimates the cost of a model call.
+ *
+ * @param price The model's price
+ * @param usage The tokens the call used
+ * @return The cost in USD
+ */
+pub fn estimate_cost(price: ModelPrice, usage: TokenUsage) -> f64 {
+    (f64::from(usage.prompt_tokens) * price.input_per_million
+        + f64::from(usage.completion_tokens) * price.output_per_million)
+        / 1_000_000.0
+}
+
+/**
+ * Reports a call's usage with its estimated cost.
+ *
+ * @param usage The tokens the call used
+ * @param price The model's price, if known
+ * @return The report
+ */
+pub fn usage_report(usage: TokenUsage, price: Option<ModelPrice>) -> UsageReport {
+    UsageReport {
+        prompt_tokens: usage.prompt_tokens,
+        completion_tokens: usage.completion_tokens,
+        estimated_cost_usd: price.map(|price| estimate_cost(price, usage)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{default_price, usage_report, TokenUsage};
+
+    #[test]
+    fn looks_up_prices_by_model() {
+        assert_eq!(default_price("gpt-4o-mini-2024-07-18").unwrap().input_per_million, 0.15);
+        assert_eq!(default_price("openai/gpt-4o").unwrap().output_per_million, 10.0);
+        assert_eq!(default_price("o3-mini").unwrap().input_per_million, 1.10);
+        assert!(default_price("llama3").is_none());
+    }
+
+    #[test]
+    fn estimates_costs() {
+        let usage = TokenUsage {
+            prompt_tokens: 1_000,
+            completion_tokens: 500,
+        };
+        let report = usage_report(usage, default_price("gpt-4o"));
+        assert!((report.estimated_cost_usd.unwrap() - 0.0075).abs() < 1e-12);
+
+        // Usage is still reported when the price isn't known
+        let report = usage_report(usage, None);
+        assert_eq!((report.prompt_tokens, report.estimated_cost_usd), (1_000, None));
+    }
+}
@@ -0,0 +1,147 @@
+/**
+ * Profiles module lets a request pick a named configuration instead of the
+ * current default, e.g. a fast cheap model for tags and a stronger one for
+ * flashcards, without posting the whole configuration before each call.
+ * The profile is named by the `X-Rig-Profile` header or the `profile` query
+ * parameter and applies to everything the request does.
+ */
+
+use axum::{
+    extract::{Query, Request, State},
+    http::StatusCode,
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use serde::Deserialize;
+use std::future::Future;
+use std::sync::Arc;
+use tracing::Instrument;
+
+use crate::api_error::ApiError;
+use crate::rig_service::RigService;
+
+/// Header naming the profile a request runs with.
+pub const PROFILE_HEADER: &str = "x-rig-profile";
+
+tokio::task_local! {
+    static ACTIVE_PROFILE: String;
+}
+
+#[derive(Deserialize)]
+struct ProfileQuery {
+    profile: Option<String>,
+}
+
+/**
+ * The profile the current request runs with, if it named one.
+ */
+pub fn active_profile() -> Option<String> {
+    ACTIVE_PROFILE.try_with(Clone::clone).ok()
+}
+
+/**
+ * Keeps the current request's profile for work it hands off to a spawned
+ * task (e.g., a stream producer), which would otherwise run with the default.
+ * The work also stays in the request's tracing span, so its logs carry the
+ * request id.
+ *
+ * @param future The work to run
+ * @return The work, running with the current profile
+ */
+pub fn carry<F: Future>(future: F) -> impl Future<Output = F::Output> {
+    // Read now, while still inside the request's scope
+    let profile = active_profile();
+    async move {
+        match profile {
+            Some(profile) => ACTIVE_PROFILE.scope(profile, future).await,
+            None => future.await,
+        }
+    }
+    .in_current_span()
+}
+
+/**
+ * The profile a request names: the header, or else the query parameter.
+ * Blank names count as none.
+ */
+fn requested_profile(request: &Request) -> Option<String> {
+    let from_header = request
+        .headers()
+        .get(PROFILE_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_string);
+    let from_query = || {
+        Query::<ProfileQuery>::try_from_uri(request.uri())
+            .ok()
+            .and_then(|Query(query)| query.profile)
+    };
+    from_header
+        .or_else(from_query)
+        .map(|profile| profile.trim().to_string())
+        .filter(|profile| !profile.is_empty())
+}
+
+/**
+ * Middleware running the handler with the requested profile. Requests
+ * naming an unknown profile are rejected with `404 Not Found`.
+ *
+ * @param rig_service The service holding the profiles
+ * @param request The incoming request
+ * @param next The rest of the middleware stack and the handler
+ * @return The handler's response, or the error for an unknown profile
+ */
+pub async fn select_profile(
+    State(rig_service): State<Arc<RigService>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let Some(profile) = requested_profile(&request) else {
+        return next.run(request).await;
+    };
+    let model = match rig_service.profile(&profile) {
+        Ok(config) => config.map(|config| config.model_name),
+        Err(e) => return ApiError::from_error(e.as_ref()).into_response(),
+    };
+    let Some(model) = model else {
+        return ApiError::new(
+            StatusCode::NOT_FOUND,
+            "unknown_profile",
+            format!("No configuration profile named '{}'", profile),
+        )
+        .into_response();
+    };
+    // The request's span (see `request_trace`) was opened with the default model
+    tracing::Span::current().record("model", model.as_str());
+    ACTIVE_PROFILE.scope(profile, next.run(request)).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{active_profile, carry, requested_profile, ACTIVE_PROFILE};
+    use axum::{body::Body, extract::Request};
+
+    #[test]
+    fn reads_the_header_before_the_query() {
+        let request = Request::builder()
+            .uri("/api/tags?profile=cheap")
+            .header("x-rig-profile", "strong")
+            .body(Body::empty())
+            .unwrap();
+        assert_eq!(requested_profile(&request).as_deref(), Some("strong"));
+
+        let request = Request::builder().uri("/api/tags?profile=cheap").body(Body::empty()).unwrap();
+        assert_eq!(requested_profile(&request).as_deref(), Some("cheap"));
+
+        let request = Request::builder().uri("/api/tags?profile=%20").body(Body::empty()).unwrap();
+        assert_eq!(requested_profile(&request), None);
+    }
+
+    #[tokio::test]
+    async fn carries_the_profile_into_spawned_tasks() {
+        let profile = ACTIVE_PROFILE
+            .scope("cheap".to_string(), async { tokio::spawn(carry(async { active_profile() })).await.unwrap() })
+            .await;
+        assert_eq!(profile.as_deref(), Some("cheap"));
+        assert_eq!(active_profile(), None);
+    }
+}
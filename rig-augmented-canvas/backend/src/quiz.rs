@@ -0,0 +1,158 @@
+/**
+ * Quiz module holds the multiple-choice questions returned by the quiz
+ * endpoint, the JSON schema their output is checked against, and the checks
+ * the schema can't express (e.g., that `correct_index` points at a choice).
+ */
+
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+/// Fewest choices a question may offer.
+pub const MIN_CHOICES: usize = 3;
+
+/**
+ * A multiple-choice question; `correct_index` is the position of the right
+ * answer in `choices`, counting from 0.
+ */
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct QuizQuestion {
+    pub prompt: String,
+    pub choices: Vec<String>,
+    pub correct_index: usize,
+}
+
+/**
+ * Builds the JSON schema quiz output must match: an object with up to
+ * `count` questions, each with a prompt, at least `MIN_CHOICES` choices and
+ * the index of the correct one.
+ *
+ * @param count The maximum number of questions
+ * @return The schema
+ */
+pub fn quiz_schema(count: usize) -> Value {
+    json!({
+        "type": "object",
+        "properties": {
+            "questions": {
+                "type": "array",
+                "minItems": 1,
+                "maxItems": count,
+                "items": {
+                    "type": "object",
+                    "properties": {
+                        "prompt": { "type": "string", "minLength": 1 },
+                        "choices": {
+                            "type": "array",
+                            "minItems": MIN_CHOICES,
+                            "items": { "type": "string", "minLength": 1 }
+                        },
+                        "correct_index": { "type": "integer", "minimum": 0 }
+                    },
+                    "required": ["prompt", "choices", "correct_index"]
+                }
+            }
+        },
+        "required": ["questions"]
+    })
+}
+
+/**
+ * Trims prompts and choices, then lists what is still wrong with the
+ * questions: blank prompts or choices, too few choices, or a
+ * `correct_index` past the last choice.
+ *
+ * @param questions The questions returned by the model
+ * @return The trimmed questions, or one message per problem
+ */
+pub fn checked(questions: Vec<QuizQuestion>) -> Result<Vec<QuizQuestion>, Vec<String>> {
+    let mut problems = Vec::new();
+    let questions: Vec<QuizQuestion> = questions
+        .into_iter()
+        .map(|question| QuizQuestion {
+            prompt: question.prompt.trim().to_string(),
+            choices: question.choices.iter().map(|choice| choice.trim().to_string()).collect(),
+            correct_index: question.correct_index,
+        })
+        .collect();
+
+    for (index, question) in questions.iter().enumerate() {
+        let number = index + 1;
+        if question.prompt.is_empty() {
+            problems.push(format!("question {} has a blank prompt", number));
+        }
+        if question.choices.iter().any(String::is_empty) {
+            problems.push(format!("question {} has a blank choice", number));
+        }
+        if question.choices.len() < MIN_CHOICES {
+            problems.push(format!(
+                "question {} has {} choices, at least {} are required",
+                number,
+                question.choices.len(),
+                MIN_CHOICES
+            ));
+        }
+        if question.correct_index >= question.choices.len() {
+            problems.push(format!(
+                "question {} has correct_index {} but only {} choices",
+                number,
+                question.correct_index,
+                question.choices.len()
+            ));
+        }
+    }
+
+    if problems.is_empty() {
+        Ok(questions)
+    } else {
+        Err(problems)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{checked, quiz_schema, QuizQuestion};
+    use crate::extract::conforms;
+    use serde_json::json;
+
+    fn question(prompt: &str, choices: &[&str], correct_index: usize) -> QuizQuestion {
+        QuizQuestion {
+            prompt: prompt.to_string(),
+            choices: choices.iter().map(|choice| choice.to_string()).collect(),
+            correct_index,
+        }
+    }
+
+    #[test]
+    fn schema_requires_three_choices() {
+        let schema = quiz_schema(2);
+        let valid = json!({ "prompt": "Who owns a value?", "choices": ["One owner", "Two", "None"], "correct_index": 0 });
+
+        assert!(conforms(&schema, &json!({ "questions": [valid] })));
+        assert!(!conforms(&schema, &json!({ "questions": [{ "prompt": "Why?", "choices": ["A", "B"], "correct_index": 0 }] })));
+        assert!(!conforms(&schema, &json!({ "questions": [{ "prompt": "Why?", "choices": ["A", "B", "C"], "correct_index": -1 }] })));
+    }
+
+    #[test]
+    fn trims_valid_questions() {
+        let questions = vec![question(" What moves a value? ", &[" Assignment ", "Borrowing", "Copying"], 0)];
+        assert_eq!(
+            checked(questions),
+            Ok(vec![question("What moves a value?", &["Assignment", "Borrowing", "Copying"], 0)])
+        );
+    }
+
+    #[test]
+    fn reports_out_of_bounds_answers_and_blank_choices() {
+        let questions = vec![
+            question("What is a slice?", &["A view", "A copy", "A box"], 3),
+            question("What is a trait?", &["Shared behavior", " ", "A type"], 0),
+        ];
+        assert_eq!(
+            checked(questions),
+            Err(vec![
+                "question 1 has correct_index 3 but only 3 choices".to_string(),
+                "question 2 has a blank choice".to_string(),
+            ])
+        );
+    }
+}
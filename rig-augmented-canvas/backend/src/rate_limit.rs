@@ -0,0 +1,193 @@
+/**
+ * Rate limit module caps how many requests each client may make per minute,
+ * so rapid canvas edits can't burn through the provider quota. Each client
+ * gets a token bucket holding up to a minute's worth of requests that
+ * refills continuously; a request that finds it empty is rejected with
+ * `429 Too Many Requests` and a `Retry-After` header. Clients are told apart
+ * by a `ClientKey` function, the peer IP address by default.
+ */
+
+use axum::{
+    extract::{ConnectInfo, Request, State},
+    http::{header, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::api_error::ApiError;
+
+/// Environment variable holding the requests allowed per client per minute.
+pub const RATE_LIMIT_ENV: &str = "RIG_RATE_LIMIT_RPM";
+
+/// Paths that are never rate limited, so monitoring keeps working.
+const EXEMPT_PATHS: [&str; 2] = ["/health", "/metrics"];
+
+/// Buckets kept before full (idle) ones are dropped.
+const MAX_IDLE_BUCKETS: usize = 1024;
+
+/**
+ * Identifies the client a request counts against.
+ */
+pub type ClientKey = fn(&Request) -> String;
+
+/**
+ * Keys requests by the peer's IP address. Requests served without
+ * connection info share one bucket.
+ */
+pub fn peer_ip(request: &Request) -> String {
+    request
+        .extensions()
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|ConnectInfo(addr)| addr.ip().to_string())
+        .unwrap_or_else(|| "unknown".to_string())
+}
+
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+/**
+ * Per-client token buckets. With no limit configured every request passes.
+ */
+pub struct RateLimiter {
+    requests_per_minute: Option<u32>,
+    key: ClientKey,
+    buckets: Mutex<HashMap<String, Bucket>>,
+}
+
+impl RateLimiter {
+    pub fn new(requests_per_minute: Option<u32>, key: ClientKey) -> Self {
+        Self {
+            requests_per_minute: requests_per_minute.filter(|limit| *limit > 0),
+            key,
+            buckets: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /**
+     * Reads the limit from `RIG_RATE_LIMIT_RPM`, keying clients by IP.
+     * Unset, zero or unparsable values turn the limit off.
+     */
+    pub fn from_env() -> Self {
+        let limit = std::env::var(RATE_LIMIT_ENV).ok().and_then(|value| value.trim().parse().ok());
+        Self::new(limit, peer_ip)
+    }
+
+    pub fn requests_per_minute(&self) -> Option<u32> {
+        self.requests_per_minute
+    }
+
+    /**
+     * Takes a token from the client's bucket, refilling it for the time
+     * since it was last used.
+     *
+     * @param client The client's key
+     * @param now The current time
+     * @return Ok, or how long until the next token is available
+     */
+    fn try_acquire(&self, client: &str, now: Instant) -> Result<(), Duration> {
+        let Some(limit) = self.requests_per_minute else {
+            return Ok(());
+        };
+        let capacity = f64::from(limit);
+        let per_second = capacity / 60.0;
+
+        let mut buckets = match self.buckets.lock() {
+            Ok(buckets) => buckets,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        if buckets.len() >= MAX_IDLE_BUCKETS {
+            // A full bucket behaves like a new one, so it can go
+            buckets.retain(|_, bucket| {
+                bucket.tokens + now.duration_since(bucket.updated).as_secs_f64() * per_second < capacity
+            });
+        }
+
+        let bucket = buckets.entry(client.to_string()).or_insert(Bucket { tokens: capacity, updated: now });
+        let refilled = now.duration_since(bucket.updated).as_secs_f64() * per_second;
+        bucket.tokens = (bucket.tokens + refilled).min(capacity);
+        bucket.updated = now;
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(Duration::from_secs_f64((1.0 - bucket.tokens) / per_second))
+        }
+    }
+}
+
+/**
+ * Middleware rejecting requests from clients over the per-minute limit with
+ * `429 Too Many Requests`. Health and metrics requests are never limited.
+ *
+ * @param limiter The per-client buckets
+ * @param request The incoming request
+ * @param next The rest of the middleware stack and the handler
+ * @return The handler's response, or 429 with `Retry-After` if over the limit
+ */
+pub async fn limit_requests(State(limiter): State<Arc<RateLimiter>>, request: Request, next: Next) -> Response {
+    if EXEMPT_PATHS.contains(&request.uri().path()) {
+        return next.run(request).await;
+    }
+    let client = (limiter.key)(&request);
+    let wait = match limiter.try_acquire(&client, Instant::now()) {
+        Ok(()) => return next.run(request).await,
+        Err(wait) => wait,
+    };
+
+    // Round up so a client retrying on time finds a token
+    let retry_after = wait.as_secs() + u64::from(wait.subsec_nanos() > 0);
+    tracing::warn!("Rate limiting client {}; retry in {}s", client, retry_after);
+    (
+        [(header::RETRY_AFTER, retry_after.to_string())],
+        ApiError::new(
+            StatusCode::TOO_MANY_REQUESTS,
+            "too_many_requests",
+            format!(
+                "More than {} requests per minute. Please retry in {} seconds.",
+                limiter.requests_per_minute.unwrap_or_default(),
+                retry_after
+            ),
+        ),
+    )
+        .into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{peer_ip, RateLimiter};
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn allows_a_minutes_worth_then_refills() {
+        let limiter = RateLimiter::new(Some(60), peer_ip);
+        let start = Instant::now();
+        for _ in 0..60 {
+            assert!(limiter.try_acquire("10.0.0.1", start).is_ok());
+        }
+        assert_eq!(limiter.try_acquire("10.0.0.1", start), Err(Duration::from_secs(1)));
+
+        // Other clients have their own bucket
+        assert!(limiter.try_acquire("10.0.0.2", start).is_ok());
+
+        // One request's worth refills each second at 60 per minute
+        let later = start + Duration::from_secs(1);
+        assert!(limiter.try_acquire("10.0.0.1", later).is_ok());
+        assert!(limiter.try_acquire("10.0.0.1", later).is_err());
+    }
+
+    #[test]
+    fn no_limit_allows_everything() {
+        for limiter in [RateLimiter::new(None, peer_ip), RateLimiter::new(Some(0), peer_ip)] {
+            assert_eq!(limiter.requests_per_minute(), None);
+            for _ in 0..100 {
+                assert!(limiter.try_acquire("10.0.0.1", Instant::now()).is_ok());
+            }
+        }
+    }
+}
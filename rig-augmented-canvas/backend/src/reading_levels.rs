@@ -0,0 +1,121 @@
+/**
+ * Reading levels module holds the reading ladder returned by the
+ * simplify-levels endpoint: the same content rewritten for several reading
+ * levels, from early primary school to college.
+ */
+
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+/// Reading levels every ladder covers, from easiest to hardest.
+pub const READING_LEVELS: [&str; 4] = ["grade 3", "grade 6", "grade 9", "college"];
+
+/**
+ * The content rewritten for one reading level.
+ */
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ReadingLevel {
+    pub grade: String,
+    pub text: String,
+}
+
+/**
+ * JSON schema reading ladder output must match: one non-empty rewrite for
+ * each of the reading levels.
+ */
+pub fn reading_ladder_schema() -> Value {
+    json!({
+        "type": "object",
+        "properties": {
+            "levels": {
+                "type": "array",
+                "minItems": READING_LEVELS.len(),
+                "maxItems": READING_LEVELS.len(),
+                "items": {
+                    "type": "object",
+                    "properties": {
+                        "grade": { "type": "string", "enum": READING_LEVELS },
+                        "text": { "type": "string", "minLength": 1 }
+                    },
+                    "required": ["grade", "text"]
+                }
+            }
+        },
+        "required": ["levels"]
+    })
+}
+
+/**
+ * Puts the rewrites in ladder order, easiest first, and checks that every
+ * reading level has a non-blank rewrite.
+ *
+ * @param levels The rewrites returned by the model
+ * @return The ordered rewrites, or the levels that are missing
+ */
+pub fn ordered(levels: Vec<ReadingLevel>) -> Result<Vec<ReadingLevel>, Vec<String>> {
+    let mut ladder = Vec::with_capacity(READING_LEVELS.len());
+    let mut missing = Vec::new();
+    for grade in READING_LEVELS {
+        let text = levels
+            .iter()
+            .find(|level| level.grade.trim().eq_ignore_ascii_case(grade) && !level.text.trim().is_empty())
+            .map(|level| level.text.trim().to_string());
+        match text {
+            Some(text) => ladder.push(ReadingLevel {
+                grade: grade.to_string(),
+                text,
+            }),
+            None => missing.push(format!("Missing rewrite for reading level '{}'", grade)),
+        }
+    }
+
+    if missing.is_empty() {
+        Ok(ladder)
+    } else {
+        Err(missing)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ordered, ReadingLevel, READING_LEVELS};
+
+    fn level(grade: &str, text: &str) -> ReadingLevel {
+        ReadingLevel {
+            grade: grade.to_string(),
+            text: text.to_string(),
+        }
+    }
+
+    #[test]
+    fn orders_levels_easiest_first() {
+        let ladder = ordered(vec![
+            level("college", " Photosynthesis converts light energy. "),
+            level("grade 3", "Plants eat sunlight."),
+            level("Grade 9", "Plants turn light into sugar."),
+            level("grade 6", "Plants use sunlight to make food."),
+        ])
+        .unwrap();
+        let grades: Vec<&str> = ladder.iter().map(|level| level.grade.as_str()).collect();
+        assert_eq!(grades, READING_LEVELS);
+        assert_eq!(ladder[3].text, "Photosynthesis converts light energy.");
+    }
+
+    #[test]
+    fn reports_missing_levels() {
+        let errors = ordered(vec![
+            level("grade 3", "Plants eat sunlight."),
+            level("grade 3", "Plants eat sunlight too."),
+            level("grade 6", "  "),
+            level("college", "Photosynthesis converts light energy."),
+        ])
+        .unwrap_err();
+        assert_eq!(
+            errors,
+            vec![
+                "Missing rewrite for reading level 'grade 6'".to_string(),
+                "Missing rewrite for reading level 'grade 9'".to_string(),
+            ]
+        );
+    }
+}
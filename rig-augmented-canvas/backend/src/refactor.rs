@@ -0,0 +1,152 @@
+/**
+ * Refactor module holds the reorganization advice returned for a set of
+ * notes and checks that each suggestion only targets notes that were sent.
+ */
+
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+/**
+ * A note offered for refactoring advice.
+ */
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RefactorNote {
+    pub id: String,
+    pub title: String,
+    pub content: String,
+}
+
+/**
+ * How a group of notes should be reorganized.
+ */
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum RefactorAction {
+    Merge,
+    Split,
+    Rename,
+}
+
+/**
+ * A suggested merge, split or rename, with the ids of the notes it applies to.
+ */
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct RefactorSuggestion {
+    pub action: RefactorAction,
+    pub targets: Vec<String>,
+    pub rationale: String,
+}
+
+/**
+ * JSON schema refactoring output must match. Target ids are checked
+ * separately by `validated` so one bad suggestion doesn't reject the rest.
+ */
+pub fn refactor_schema() -> Value {
+    json!({
+        "type": "object",
+        "properties": {
+            "suggestions": {
+                "type": "array",
+                "items": {
+                    "type": "object",
+                    "properties": {
+                        "action": { "enum": ["merge", "split", "rename"] },
+                        "targets": {
+                            "type": "array",
+                            "minItems": 1,
+                            "items": { "type": "string" }
+                        },
+                        "rationale": { "type": "string" }
+                    },
+                    "required": ["action", "targets", "rationale"]
+                }
+            }
+        },
+        "required": ["suggestions"]
+    })
+}
+
+/**
+ * Keeps the suggestions that make sense for the given notes: targets are
+ * trimmed, deduped and must be known note ids; merges need at least two
+ * targets while splits and renames apply to exactly one.
+ *
+ * @param suggestions The suggestions returned by the model
+ * @param note_ids The ids of the notes in the request
+ * @return The valid suggestions and the number dropped
+ */
+pub fn validated(suggestions: Vec<RefactorSuggestion>, note_ids: &[String]) -> (Vec<RefactorSuggestion>, usize) {
+    let total = suggestions.len();
+    let mut valid: Vec<RefactorSuggestion> = Vec::new();
+    for suggestion in suggestions {
+        let mut targets: Vec<String> = Vec::new();
+        let mut unknown = false;
+        for target in &suggestion.targets {
+            let target = target.trim();
+            if !note_ids.iter().any(|id| id == target) {
+                unknown = true;
+                break;
+            }
+            if !targets.iter().any(|existing| existing == target) {
+                targets.push(target.to_string());
+            }
+        }
+
+        let arity_ok = match suggestion.action {
+            RefactorAction::Merge => targets.len() >= 2,
+            RefactorAction::Split | RefactorAction::Rename => targets.len() == 1,
+        };
+        if unknown || !arity_ok {
+            continue;
+        }
+
+        valid.push(RefactorSuggestion {
+            action: suggestion.action,
+            targets,
+            rationale: suggestion.rationale.trim().to_string(),
+        });
+    }
+
+    let dropped = total - valid.len();
+    (valid, dropped)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{validated, RefactorAction, RefactorSuggestion};
+
+    fn suggestion(action: RefactorAction, targets: &[&str]) -> RefactorSuggestion {
+        RefactorSuggestion {
+            action,
+            targets: targets.iter().map(|target| target.to_string()).collect(),
+            rationale: " Overlapping content ".to_string(),
+        }
+    }
+
+    #[test]
+    fn keeps_suggestions_for_known_notes() {
+        let ids = vec!["a".to_string(), "b".to_string()];
+        let (suggestions, dropped) = validated(vec![suggestion(RefactorAction::Merge, &["a", " b ", "a"])], &ids);
+        assert_eq!(dropped, 0);
+        assert_eq!(suggestions[0].targets, vec!["a", "b"]);
+        assert_eq!(suggestions[0].rationale, "Overlapping content");
+    }
+
+    #[test]
+    fn drops_unknown_targets_and_wrong_arity() {
+        let ids = vec!["a".to_string(), "b".to_string()];
+        let (suggestions, dropped) = validated(
+            vec![
+                suggestion(RefactorAction::Merge, &["a", "missing"]),
+                suggestion(RefactorAction::Merge, &["a", "a"]),
+                suggestion(RefactorAction::Split, &["a", "b"]),
+                suggestion(RefactorAction::Rename, &["b"]),
+            ],
+            &ids,
+        );
+        assert_eq!(suggestions.len(), 1);
+        assert_eq!(suggestions[0].action, RefactorAction::Rename);
+        assert_eq!(suggestions[0].targets, vec!["b"]);
+        assert_eq!(dropped, 3);
+    }
+}
@@ -0,0 +1,165 @@
+/**
+ * Request limit module bounds the number of provider calls in flight at
+ * once. Canvas-wide operations (e.g., processing every node) can fire many
+ * prompts together; beyond `max_concurrent_requests` they queue for a slot
+ * instead of all hitting the provider's rate limit. The limit is resized
+ * when the configuration changes, keeping the queue and the calls in
+ * flight, so the cap holds across the change.
+ */
+
+use std::sync::Mutex;
+use tokio::sync::{Semaphore, SemaphorePermit};
+
+/// Provider calls allowed in flight at once unless configured otherwise.
+pub const DEFAULT_MAX_CONCURRENT_REQUESTS: usize = 4;
+
+struct Capacity {
+    limit: usize,  // Configured number of calls allowed in flight
+    excess: usize, // Slots held by calls in flight, retired when those calls finish
+}
+
+/**
+ * Hands out slots for provider calls, queueing callers when all are taken.
+ */
+pub struct RequestLimiter {
+    semaphore: Semaphore,
+    capacity: Mutex<Capacity>,
+}
+
+/**
+ * A slot for one provider call, released when dropped.
+ */
+pub struct RequestSlot<'a> {
+    permit: Option<SemaphorePermit<'a>>,
+    limiter: &'a RequestLimiter,
+}
+
+impl Drop for RequestSlot<'_> {
+    fn drop(&mut self) {
+        // Slots beyond a lowered limit are retired rather than handed to the next call
+        let mut capacity = self.limiter.capacity();
+        if capacity.excess > 0 {
+            capacity.excess -= 1;
+            if let Some(permit) = self.permit.take() {
+                permit.forget();
+            }
+        }
+    }
+}
+
+impl Default for RequestLimiter {
+    fn default() -> Self {
+        Self::new(DEFAULT_MAX_CONCURRENT_REQUESTS)
+    }
+}
+
+impl RequestLimiter {
+    pub fn new(limit: usize) -> Self {
+        Self {
+            semaphore: Semaphore::new(limit),
+            capacity: Mutex::new(Capacity { limit, excess: 0 }),
+        }
+    }
+
+    fn capacity(&self) -> std::sync::MutexGuard<'_, Capacity> {
+        match self.capacity.lock() {
+            Ok(capacity) => capacity,
+            Err(poisoned) => poisoned.into_inner(),
+        }
+    }
+
+    /**
+     * Changes the number of calls allowed in flight. Raising it frees slots
+     * for queued calls right away; lowering it retires free slots, and
+     * slots held by calls in flight as those calls finish.
+     *
+     * @param limit The new number of calls allowed in flight
+     */
+    pub fn set_limit(&self, limit: usize) {
+        let mut capacity = self.capacity();
+        if limit > capacity.limit {
+            // Slots still due to be retired are kept before new ones are added
+            let added = limit - capacity.limit;
+            let kept = added.min(capacity.excess);
+            capacity.excess -= kept;
+            self.semaphore.add_permits(added - kept);
+        } else if limit < capacity.limit {
+            let removed = capacity.limit - limit;
+            let forgotten = self.semaphore.forget_permits(removed);
+            capacity.excess += removed - forgotten;
+        }
+        capacity.limit = limit;
+    }
+
+    /**
+     * Waits for a slot, queueing in arrival order.
+     *
+     * @return The slot, released when dropped
+     */
+    pub async fn acquire(&self) -> RequestSlot<'_> {
+        let permit = self
+            .semaphore
+            .acquire()
+            .await
+            .expect("request limiter semaphore is never closed");
+        RequestSlot {
+            permit: Some(permit),
+            limiter: self,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::RequestLimiter;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn queues_calls_beyond_the_limit() {
+        let limiter = RequestLimiter::new(1);
+        let held = limiter.acquire().await;
+
+        // The second call waits until the first slot is released
+        let waiting = tokio::time::timeout(Duration::from_millis(20), limiter.acquire()).await;
+        assert!(waiting.is_err());
+
+        drop(held);
+        let next = tokio::time::timeout(Duration::from_millis(20), limiter.acquire()).await;
+        assert!(next.is_ok());
+    }
+
+    #[tokio::test]
+    async fn raising_the_limit_frees_slots() {
+        let limiter = RequestLimiter::new(1);
+        let _held = limiter.acquire().await;
+
+        limiter.set_limit(2);
+        let second = tokio::time::timeout(Duration::from_millis(20), limiter.acquire()).await;
+        assert!(second.is_ok());
+    }
+
+    #[tokio::test]
+    async fn lowering_the_limit_counts_calls_in_flight() {
+        let limiter = RequestLimiter::new(3);
+        let first = limiter.acquire().await;
+        let second = limiter.acquire().await;
+
+        // Both calls are still in flight, so no new call fits under a limit of 1
+        limiter.set_limit(1);
+        assert!(tokio::time::timeout(Duration::from_millis(20), limiter.acquire()).await.is_err());
+
+        drop(first);
+        assert!(tokio::time::timeout(Duration::from_millis(20), limiter.acquire()).await.is_err());
+
+        drop(second);
+        let next = tokio::time::timeout(Duration::from_millis(20), limiter.acquire()).await;
+        assert!(next.is_ok());
+        drop(next);
+
+        // Raising the limit again adds slots back
+        limiter.set_limit(2);
+        let _a = limiter.acquire().await;
+        let _b = limiter.acquire().await;
+        assert!(tokio::time::timeout(Duration::from_millis(20), limiter.acquire()).await.is_err());
+    }
+}
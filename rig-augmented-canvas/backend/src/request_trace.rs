@@ -0,0 +1,130 @@
+/**
+ * Request trace module gives every request an id and runs it inside a
+ * tracing span carrying the id, the endpoint and the model, so all log lines
+ * for one request (including its errors) can be found by grepping the id.
+ * The id is echoed in the `X-Request-Id` response header so the plugin can
+ * report it alongside a failure.
+ */
+
+use axum::{
+    extract::{MatchedPath, Request, State},
+    http::HeaderValue,
+    middleware::Next,
+    response::Response,
+};
+use std::collections::hash_map::RandomState;
+use std::hash::BuildHasher;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Instant;
+use tracing::Instrument;
+
+use crate::rig_service::RigService;
+use crate::telemetry;
+
+/// Header carrying the request's id, on the response (and optionally the request).
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+/// Endpoint label counted for requests that matched no route.
+const UNMATCHED_ENDPOINT: &str = "unmatched";
+
+/// Longest client-supplied id that is reused instead of generating one.
+const MAX_CLIENT_ID_LEN: usize = 64;
+
+static REQUESTS: AtomicU64 = AtomicU64::new(0);
+
+/**
+ * Generates a new request id: 16 hex digits, unique within the process and
+ * unlikely to repeat across restarts.
+ */
+fn generate_id() -> String {
+    let count = REQUESTS.fetch_add(1, Ordering::Relaxed);
+    format!("{:016x}", RandomState::new().hash_one(count))
+}
+
+/**
+ * The id a request runs with: the client's `X-Request-Id` if it sent a
+ * short printable one, so its logs and ours line up, or else a new one.
+ */
+fn request_id(request: &Request) -> String {
+    request
+        .headers()
+        .get(REQUEST_ID_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .map(str::trim)
+        .filter(|id| !id.is_empty() && id.len() <= MAX_CLIENT_ID_LEN && id.chars().all(|c| c.is_ascii_graphic()))
+        .map(str::to_string)
+        .unwrap_or_else(generate_id)
+}
+
+/**
+ * Middleware counting the request for its endpoint (see `telemetry`) and
+ * running it in an `info` span with its id, endpoint and model, logging its
+ * status and latency when it completes, and adding the `X-Request-Id`
+ * header to the response. The model is the configured default;
+ * `profiles::select_profile` replaces it when a profile is picked.
+ *
+ * @param rig_service The service holding the configuration
+ * @param request The incoming request
+ * @param next The rest of the middleware stack and the handler
+ * @return The response, with its request id
+ */
+pub async fn trace_request(
+    State(rig_service): State<Arc<RigService>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let id = request_id(&request);
+    let matched = request.extensions().get::<MatchedPath>().map(|path| path.as_str().to_string());
+    // Unrouted paths share one label so arbitrary URLs can't grow the metric without bound
+    telemetry::record_request(matched.as_deref().unwrap_or(UNMATCHED_ENDPOINT));
+    let endpoint = matched.unwrap_or_else(|| request.uri().path().to_string());
+    let model = rig_service.get_config().map(|config| config.model_name).unwrap_or_default();
+    let span = tracing::info_span!(
+        "request",
+        request_id = %id,
+        method = %request.method(),
+        endpoint = %endpoint,
+        model = %model,
+    );
+
+    let started = Instant::now();
+    let mut response = next.run(request).instrument(span.clone()).await;
+    span.in_scope(|| {
+        tracing::info!(
+            status = response.status().as_u16(),
+            latency_ms = started.elapsed().as_millis() as u64,
+            "Request completed"
+        );
+    });
+
+    if let Ok(value) = HeaderValue::from_str(&id) {
+        response.headers_mut().insert(REQUEST_ID_HEADER, value);
+    }
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{generate_id, request_id};
+    use axum::{body::Body, extract::Request};
+
+    #[test]
+    fn generates_distinct_ids() {
+        let first = generate_id();
+        assert_eq!(first.len(), 16);
+        assert_ne!(first, generate_id());
+    }
+
+    #[test]
+    fn reuses_printable_client_ids() {
+        let request = Request::builder().header("x-request-id", " canvas-42 ").body(Body::empty()).unwrap();
+        assert_eq!(request_id(&request), "canvas-42");
+
+        let request = Request::builder().header("x-request-id", "has spaces").body(Body::empty()).unwrap();
+        assert_eq!(request_id(&request).len(), 16);
+
+        let request = Request::builder().header("x-request-id", "x".repeat(65)).body(Body::empty()).unwrap();
+        assert_eq!(request_id(&request).len(), 16);
+    }
+}
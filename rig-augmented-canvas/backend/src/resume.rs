@@ -0,0 +1,388 @@
+/**
+ * Resume module keeps a short-lived buffer of the events sent on each SSE
+ * stream, so a client that reconnects with `Last-Event-ID` picks up where
+ * it left off instead of starting the generation over.
+ *
+ * Memory is bounded: at most MAX_RESUMABLE_STREAMS streams are tracked
+ * (least recently used evicted first), each keeping its last
+ * MAX_BUFFERED_EVENTS events, and a finished stream stays resumable for
+ * RESUME_WINDOW. Generation stops once a stream has had no client for
+ * ABANDON_GRACE. Stream ids are random, so a client can't guess another
+ * client's id and replay its output.
+ */
+
+use axum::response::sse::Event;
+use futures::stream::{self, Stream};
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::future::Future;
+use std::ops::Deref;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::watch;
+
+use crate::cache::BoundedCache;
+
+/// How long a finished stream can still be resumed.
+pub const RESUME_WINDOW: Duration = Duration::from_secs(60);
+
+/// How long a stream keeps generating with no client connected.
+pub const ABANDON_GRACE: Duration = Duration::from_secs(30);
+
+/// Events kept per stream; older events can no longer be replayed.
+pub const MAX_BUFFERED_EVENTS: usize = 1024;
+
+/// Streams tracked at once.
+pub const MAX_RESUMABLE_STREAMS: usize = 32;
+
+/// Header sent by reconnecting SSE clients.
+pub const LAST_EVENT_ID_HEADER: &str = "last-event-id";
+
+struct BufferedEvent {
+    seq: u64,
+    name: String,
+    data: String,
+}
+
+#[derive(Default)]
+struct BufferState {
+    events: VecDeque<BufferedEvent>,
+    next_seq: u64,
+    finished_at: Option<Instant>,
+}
+
+/**
+ * The events of one stream. Producers push events; any number of
+ * subscribers (the original connection and reconnections) read them.
+ */
+pub struct StreamBuffer {
+    id: String,
+    state: Mutex<BufferState>,
+    updates: watch::Sender<()>,
+    subscribers: watch::Sender<usize>,
+}
+
+enum NextEvent {
+    Ready(u64, Event),
+    Pending,
+    Finished,
+}
+
+impl StreamBuffer {
+    fn new(id: String) -> Self {
+        Self {
+            id,
+            state: Mutex::new(BufferState::default()),
+            updates: watch::channel(()).0,
+            subscribers: watch::channel(0).0,
+        }
+    }
+
+    /**
+     * Appends an event with JSON data, dropping the oldest event when full.
+     *
+     * @param name The SSE event name
+     * @param data The event payload
+     */
+    pub fn push(&self, name: &str, data: &impl Serialize) {
+        let data = match serde_json::to_string(data) {
+            Ok(data) => data,
+            Err(e) => {
+                tracing::error!("Failed to serialize '{}' event: {}", name, e);
+                return;
+            }
+        };
+        
+        if let Ok(mut state) = self.state.lock() {
+            let seq = state.next_seq;
+            state.next_seq += 1;
+            state.events.push_back(BufferedEvent {
+                seq,
+                name: name.to_string(),
+                data,
+            });
+            if state.events.len() > MAX_BUFFERED_EVENTS {
+                state.events.pop_front();
+            }
+        }
+        self.updates.send_replace(());
+    }
+
+    /**
+     * Marks the stream as complete; subscribers end once they have read everything.
+     */
+    pub fn finish(&self) {
+        if let Ok(mut state) = self.state.lock() {
+            state.finished_at.get_or_insert_with(Instant::now);
+        }
+        self.updates.send_replace(());
+    }
+
+    fn next_after(&self, cursor: Option<u64>) -> NextEvent {
+        let Ok(state) = self.state.lock() else {
+            return NextEvent::Finished;
+        };
+        
+        let next = state
+            .events
+            .iter()
+            .find(|event| match cursor {
+                Some(cursor) => event.seq > cursor,
+                None => true,
+            });
+        match next {
+            Some(event) => NextEvent::Ready(
+                event.seq,
+                Event::default()
+                    .id(format!("{}:{}", self.id, event.seq))
+                    .event(&event.name)
+                    .data(&event.data),
+            ),
+            None if state.finished_at.is_some() => NextEvent::Finished,
+            None => NextEvent::Pending,
+        }
+    }
+
+    /**
+     * Resolves once the stream has had no subscribers for ABANDON_GRACE.
+     */
+    async fn abandoned(&self) {
+        let mut subscribers = self.subscribers.subscribe();
+        loop {
+            let connected = if *subscribers.borrow_and_update() == 0 {
+                // Give a dropped client time to reconnect before giving up on the stream
+                match tokio::time::timeout(ABANDON_GRACE, subscribers.wait_for(|count| *count > 0)).await {
+                    Ok(connected) => connected.is_ok(),
+                    Err(_) => return,
+                }
+            } else {
+                subscribers.wait_for(|count| *count == 0).await.is_ok()
+            };
+            if !connected {
+                return;
+            }
+        }
+    }
+
+    /**
+     * Whether events after `seq` can still be replayed without a gap.
+     */
+    fn can_resume_after(&self, seq: u64) -> bool {
+        let Ok(state) = self.state.lock() else {
+            return false;
+        };
+        
+        let expired = state
+            .finished_at
+            .is_some_and(|finished_at| finished_at.elapsed() > RESUME_WINDOW);
+        let first_seq = state.events.front().map_or(state.next_seq, |event| event.seq);
+        !expired && seq < state.next_seq && seq + 1 >= first_seq
+    }
+}
+
+/**
+ * Counts a subscriber for as long as it is alive.
+ */
+struct Subscriber(Arc<StreamBuffer>);
+
+impl Subscriber {
+    fn new(buffer: Arc<StreamBuffer>) -> Self {
+        buffer.subscribers.send_modify(|count| *count += 1);
+        Self(buffer)
+    }
+}
+
+impl Deref for Subscriber {
+    type Target = StreamBuffer;
+
+    fn deref(&self) -> &StreamBuffer {
+        &self.0
+    }
+}
+
+impl Drop for Subscriber {
+    fn drop(&mut self) {
+        self.0.subscribers.send_modify(|count| *count -= 1);
+    }
+}
+
+/**
+ * Streams a buffer's events after `cursor` (from the start when None),
+ * waiting for new events until the buffer is finished.
+ *
+ * @param buffer The stream's buffer
+ * @param cursor Sequence number of the last event the client has seen
+ * @return The SSE events, each with an id usable as `Last-Event-ID`
+ */
+pub fn subscribe(buffer: Arc<StreamBuffer>, cursor: Option<u64>) -> impl Stream<Item = Result<Event, axum::Error>> {
+    let updates = buffer.updates.subscribe();
+    let buffer = Subscriber::new(buffer);
+    stream::unfold((buffer, updates, cursor), |(buffer, mut updates, cursor)| async move {
+        loop {
+            // Mark the current state as seen before reading it so no update is missed
+            updates.borrow_and_update();
+            match buffer.next_after(cursor) {
+                NextEvent::Ready(seq, event) => return Some((Ok(event), (buffer, updates, Some(seq)))),
+                NextEvent::Finished => return None,
+                NextEvent::Pending => {
+                    if updates.changed().await.is_err() {
+                        return None;
+                    }
+                }
+            }
+        }
+    })
+}
+
+/**
+ * Runs the task generating a stream's events, then marks the stream
+ * finished. The task is cancelled once the stream has had no subscribers
+ * for ABANDON_GRACE, so a client that leaves for good doesn't keep the
+ * provider busy.
+ *
+ * @param buffer The stream's buffer
+ * @param producer The task pushing the stream's events
+ */
+pub async fn produce(buffer: &StreamBuffer, producer: impl Future<Output = ()>) {
+    tokio::select! {
+        () = producer => {}
+        () = buffer.abandoned() => {
+            tracing::info!("Stopped stream {}: no client for {} seconds", buffer.id, ABANDON_GRACE.as_secs());
+        }
+    }
+    buffer.finish();
+}
+
+/**
+ * The resumable streams currently tracked.
+ */
+pub struct StreamRegistry {
+    streams: BoundedCache<Arc<StreamBuffer>>,
+}
+
+impl StreamRegistry {
+    pub fn new() -> Self {
+        Self {
+            streams: BoundedCache::new(MAX_RESUMABLE_STREAMS),
+        }
+    }
+
+    /**
+     * Starts tracking a new stream under a random 128-bit id.
+     *
+     * @return The new stream's buffer
+     */
+    pub fn create(&self) -> Arc<StreamBuffer> {
+        let id = format!("{:032x}", rand::random::<u128>());
+        
+        let buffer = Arc::new(StreamBuffer::new(id.clone()));
+        self.streams.insert(id, buffer.clone());
+        buffer
+    }
+
+    /**
+     * Finds the stream a `Last-Event-ID` refers to.
+     *
+     * @param last_event_id The id of the last event the client received
+     * @return The buffer and the sequence number to resume after, or None if the
+     *         stream is unknown, expired or has dropped the events the client missed
+     */
+    pub fn resume(&self, last_event_id: &str) -> Option<(Arc<StreamBuffer>, u64)> {
+        let (id, seq) = last_event_id.trim().rsplit_once(':')?;
+        let seq: u64 = seq.parse().ok()?;
+        let buffer = self.streams.get(id)?;
+        buffer.can_resume_after(seq).then_some((buffer, seq))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{produce, subscribe, StreamRegistry, ABANDON_GRACE, MAX_BUFFERED_EVENTS};
+    use futures::StreamExt;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn replays_events_after_last_event_id() {
+        let registry = StreamRegistry::new();
+        let buffer = registry.create();
+        for i in 0..3 {
+            buffer.push("card", &i);
+        }
+        buffer.finish();
+        
+        let last_event_id = format!("{}:0", buffer.id);
+        let (resumed, cursor) = registry.resume(&last_event_id).unwrap();
+        assert_eq!(cursor, 0);
+        assert_eq!(subscribe(resumed, Some(cursor)).count().await, 2);
+    }
+
+    #[tokio::test]
+    async fn live_subscriber_sees_later_events() {
+        let registry = StreamRegistry::new();
+        let buffer = registry.create();
+        let events = subscribe(buffer.clone(), None);
+        
+        let producer = buffer.clone();
+        tokio::spawn(async move {
+            producer.push("result", &"a");
+            tokio::task::yield_now().await;
+            producer.push("result", &"b");
+            producer.finish();
+        });
+        
+        assert_eq!(events.count().await, 2);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn stops_producing_once_abandoned() {
+        let registry = StreamRegistry::new();
+        let buffer = registry.create();
+        let events = subscribe(buffer.clone(), None);
+
+        let producer = buffer.clone();
+        let task = tokio::spawn(async move {
+            produce(&producer, async {
+                loop {
+                    producer.push("token", &"a");
+                    tokio::time::sleep(Duration::from_secs(1)).await;
+                }
+            })
+            .await;
+        });
+
+        // The producer runs while a client is connected and for the grace period after it leaves
+        tokio::time::sleep(ABANDON_GRACE).await;
+        drop(events);
+        tokio::time::sleep(ABANDON_GRACE - Duration::from_secs(1)).await;
+        assert!(!task.is_finished());
+
+        tokio::time::sleep(Duration::from_secs(2)).await;
+        assert!(task.is_finished());
+
+        // The stream is marked finished, so new subscribers end after replaying it
+        assert!(subscribe(buffer, None).count().await > 0);
+    }
+
+    #[test]
+    fn generates_unguessable_ids() {
+        let registry = StreamRegistry::new();
+        let (first, second) = (registry.create(), registry.create());
+        assert_eq!(first.id.len(), 32);
+        assert_ne!(first.id, second.id);
+    }
+
+    #[test]
+    fn rejects_unknown_or_evicted_positions() {
+        let registry = StreamRegistry::new();
+        let buffer = registry.create();
+        for i in 0..MAX_BUFFERED_EVENTS + 10 {
+            buffer.push("card", &i);
+        }
+        
+        assert!(registry.resume("unknown:0").is_none());
+        assert!(registry.resume(&format!("{}:not-a-number", buffer.id)).is_none());
+        // Events right after 0 were dropped from the buffer
+        assert!(registry.resume(&format!("{}:0", buffer.id)).is_none());
+        assert!(registry.resume(&format!("{}:{}", buffer.id, MAX_BUFFERED_EVENTS + 5)).is_some());
+    }
+}
@@ -3,20 +3,88 @@
  * It provides methods for generating responses, questions, and flashcards.
  */
 
-use serde::{Deserialize, Serialize};
+use chrono::{NaiveDate, Utc};
+use chrono_tz::Tz;
+use futures::stream::{self, StreamExt};
+use jsonschema::JSONSchema;
+use regex::RegexSet;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
 use std::error::Error;
-use std::sync::RwLock;
+use std::path::PathBuf;
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
 
-use crate::models::{AgentWrapper, ModelConfig, create_agent, create_agent_with_system_prompt};
+use crate::analogy::{self, AnalogyMapping};
+use crate::answer::{self, NumberedAnswer};
+use crate::batch;
+use crate::budget;
+use crate::cache::{self, BoundedCache, CachePolicy, ResponseCache};
+use crate::compare::{self, ModelComparison};
+use crate::canvas::{CanvasDoc, CanvasEdge, CanvasNode};
+use crate::chunk;
+use crate::cite::{self, CitationStyle};
+use crate::cloze;
+use crate::dates::{self, MentionedDate};
+use crate::concepts::ConceptMap;
+use crate::config_file;
+use crate::context::{self, ContextOverflow, ContextReport, NodeContent};
+use crate::dedupe;
+use crate::diff;
+use crate::embeddings::{self, EmbeddingBatcher, EmbeddingTarget};
+use crate::error::RigError;
+use crate::extract;
+use crate::frontmatter::{self, split_frontmatter};
+use crate::fixtures::FixtureStore;
+use crate::gaps;
+use crate::hints;
+use crate::history::{self, ChatTurn};
+use crate::instructions::{self, InstructionSet};
+use crate::interview::{self, InterviewQuestion};
+use crate::jargon::{self, UndefinedTerm};
+use crate::kanban::{self, ColumnAssignment, KanbanColumn};
+use crate::note_abstract::{self, NoteAbstract};
+use crate::layout;
+use crate::outline::{self, OutlineNode};
+use crate::profiles;
+use crate::quiz::{self, QuizQuestion};
+use crate::pricing::{self, UsageReport};
+use crate::reading_levels::{self, ReadingLevel};
+use crate::request_limit::RequestLimiter;
+use crate::refactor::{self, RefactorNote, RefactorSuggestion};
+use crate::sanitize::{sanitize_filename, sanitize_response, strip_reasoning};
+use crate::semantic_diff::{self, SemanticDiff};
+use crate::split::{self, SplitResponse};
+use crate::telemetry;
+use crate::template::validate_placeholders;
+use crate::throttle::{self, TokenThrottle};
+use crate::tokens;
+use crate::timeline::{self, TimelineEvent};
+use crate::transform;
+use crate::truncate::{self, LimitedResponse};
+use crate::vision::{self, AltText, ImageInput};
+use crate::models::{
+    AgentWrapper, ModelConfig, ResponseStream, SamplingParams, create_agent, create_agent_with_system_prompt,
+    resolve_api_key, DEFAULT_OPENAI_BASE_URL,
+};
 
 /**
  * RigService is the main service for interacting with AI models.
- * It maintains a default agent instance and configuration.
+ * It maintains the configuration and the agents built from it.
  */
 pub struct RigService {
-    agent: Option<AgentWrapper>,  // Default agent for handling prompts (None if no API key available)
     config: RwLock<ModelConfig>,  // Thread-safe configuration
+    profiles: RwLock<BTreeMap<String, ModelConfig>>, // Named configurations requests can pick instead of the default
     has_api_key: bool,            // Flag indicating if an API key is available
+    throttle: TokenThrottle,      // Per-model tokens-per-minute budget
+    responses: ResponseCache,     // Recently generated responses, keyed by response_key
+    agents: BoundedCache<Arc<AgentWrapper>>, // Built agents reused across requests, keyed by agent_key
+    blocked: BoundedCache<Arc<RegexSet>>, // Compiled blocked_patterns, keyed by blocked_patterns_key
+    embeddings: EmbeddingBatcher, // Groups single-text embedding requests into shared provider calls
+    requests: RequestLimiter,     // Provider calls in flight, capped by max_concurrent_requests
+    http: reqwest::Client,        // Shared client for provider calls made outside Rig (vision)
+    fixtures: Option<FixtureStore>, // Recorded replies served instead of calling the provider
+    config_path: PathBuf,         // Configuration file read on startup and written by persisted updates
 }
 
 /**
@@ -33,49 +101,442 @@ struct FlashcardsOutput {
     flashcards: Vec<Flashcard>,
 }
 
+#[derive(Serialize, Deserialize)]
+struct TagsOutput {
+    tags: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct ConsistentTagsOutput {
+    tags: Vec<String>,
+    new_tag: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct LengthsOutput {
+    variants: BTreeMap<String, String>,
+}
+
+#[derive(Deserialize)]
+struct OcclusionHintsOutput {
+    #[serde(default)]
+    hints: HashMap<String, String>,
+}
+
+#[derive(Deserialize)]
+struct TranslatedFlashcardsOutput {
+    flashcards: Vec<Flashcard>,
+}
+
+#[derive(Deserialize)]
+struct CommandsOutput {
+    #[serde(default)]
+    commands: Vec<CommandSuggestion>,
+}
+
+#[derive(Deserialize)]
+struct KanbanOutput {
+    columns: Vec<ColumnAssignment>,
+}
+
+#[derive(Deserialize)]
+struct InterviewOutput {
+    questions: Vec<InterviewQuestion>,
+}
+
+#[derive(Deserialize)]
+struct RefactorOutput {
+    suggestions: Vec<RefactorSuggestion>,
+}
+
+#[derive(Deserialize)]
+struct TimelineOutput {
+    events: Vec<TimelineEvent>,
+}
+
+#[derive(Deserialize)]
+struct DatesOutput {
+    dates: Vec<MentionedDate>,
+}
+
+#[derive(Deserialize)]
+struct ReadingLadderOutput {
+    levels: Vec<ReadingLevel>,
+}
+
+#[derive(Deserialize)]
+struct CitationsOutput {
+    citations: Vec<String>,
+}
+
+#[derive(Deserialize)]
+struct JargonOutput {
+    undefined: Vec<UndefinedTerm>,
+}
+
+#[derive(Deserialize)]
+struct QuizOutput {
+    questions: Vec<QuizQuestion>,
+}
+
+#[derive(Deserialize)]
+struct AnswersOutput {
+    answers: Vec<NumberedAnswer>,
+}
+
+#[derive(Deserialize)]
+struct GapsOutput {
+    gaps: Vec<String>,
+}
+
+#[derive(Deserialize)]
+struct AnalogyOutput {
+    mappings: Vec<AnalogyMapping>,
+}
+
+#[derive(Deserialize)]
+struct HintsOutput {
+    hints: Vec<String>,
+}
+
+#[derive(Deserialize)]
+struct TemplateOutput {
+    template: String,
+}
+
+#[derive(Deserialize)]
+struct ReferencesOutput {
+    #[serde(default)]
+    references: Vec<serde_json::Value>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct CanvasOutput {
+    nodes: Vec<CanvasOutputNode>,
+    #[serde(default)]
+    edges: Vec<CanvasOutputEdge>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct CanvasOutputNode {
+    id: String,
+    text: String,
+}
+
+#[derive(Serialize, Deserialize)]
+struct CanvasOutputEdge {
+    from: String,
+    to: String,
+    label: Option<String>,
+}
+
+/**
+ * A single line of streamed flashcard output: either a card or the
+ * trailing filename suggestion.
+ */
+#[derive(Deserialize)]
+#[serde(untagged)]
+pub enum FlashcardStreamLine {
+    Card(Flashcard),
+    Filename { filename: String },
+}
+
+/**
+ * Parses one line of newline-delimited flashcard output.
+ * Lines that aren't a card or filename (e.g. stray code fences) yield None.
+ *
+ * @param line A complete line of model output
+ * @return The parsed line, if it is recognized
+ */
+pub fn parse_flashcard_line(line: &str) -> Option<FlashcardStreamLine> {
+    let line = line.trim().trim_end_matches(',');
+    match serde_json::from_str(line) {
+        Ok(parsed) => Some(parsed),
+        Err(_) => {
+            tracing::debug!("Skipping unrecognized flashcard stream line: {}", line);
+            None
+        }
+    }
+}
+
+/**
+ * Endpoints whose responses are parsed as structured output, and so are
+ * asked for the final answer only when `suppress_reasoning_in_output` is on.
+ */
+const CLEAN_OUTPUT_ENDPOINTS: [&str; 4] = ["flashcards", "questions", "study-deck", "interview"];
+
+/// Instruction added to the system prompt of clean-output endpoints.
+const FINAL_ANSWER_INSTRUCTION: &str =
+    "Respond with the final answer only. Do not include your reasoning, notes or any text outside the requested format.";
+
+/// Number of images described concurrently by the alt-text endpoint.
+pub const ALT_TEXT_CONCURRENCY: usize = 4;
+
+/// Number of flashcards translated per model call.
+pub const TRANSLATE_CHUNK_SIZE: usize = 20;
+
+/**
+ * Length variants supported by the multi-length paraphrase endpoint,
+ * with the instruction given to the model for each.
+ */
+pub const LENGTH_VARIANTS: [(&str, &str); 3] = [
+    ("one_line", "a single sentence capturing the core idea"),
+    ("paragraph", "one concise paragraph"),
+    ("full", "a complete rewrite that preserves all details"),
+];
+
+/**
+ * Looks up the instruction for a length variant.
+ *
+ * @param key The variant name (e.g., "one_line")
+ * @return The instruction, or None for unknown variants
+ */
+pub fn length_instruction(key: &str) -> Option<&'static str> {
+    LENGTH_VARIANTS
+        .iter()
+        .find(|(name, _)| *name == key)
+        .map(|(_, instruction)| *instruction)
+}
+
+/**
+ * A bibliographic reference extracted from a note.
+ */
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Reference {
+    pub title: String,
+    pub authors: Option<String>,
+    pub year: Option<String>,
+    pub url: Option<String>,
+}
+
+impl Reference {
+    /**
+     * Builds a reference from loosely-shaped model output. Authors may be a
+     * string or an array, and the year a string or a number; entries without
+     * a title are skipped.
+     */
+    fn from_value(value: &serde_json::Value) -> Option<Self> {
+        let text = |field: &str| -> Option<String> {
+            match value.get(field)? {
+                serde_json::Value::String(s) if !s.trim().is_empty() => Some(s.trim().to_string()),
+                serde_json::Value::Number(n) => Some(n.to_string()),
+                serde_json::Value::Array(items) => {
+                    let joined = items
+                        .iter()
+                        .filter_map(|item| item.as_str())
+                        .map(str::trim)
+                        .filter(|item| !item.is_empty())
+                        .collect::<Vec<_>>()
+                        .join(", ");
+                    (!joined.is_empty()).then_some(joined)
+                }
+                _ => None,
+            }
+        };
+        
+        Some(Reference {
+            title: text("title")?,
+            authors: text("authors"),
+            year: text("year"),
+            url: text("url"),
+        })
+    }
+}
+
+/**
+ * Arguments for and against a decision or position.
+ */
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Default)]
+pub struct ProsCons {
+    pub pros: Vec<String>,
+    pub cons: Vec<String>,
+}
+
+impl ProsCons {
+    /**
+     * Builds a pros/cons list from loosely-shaped model output. Each side may
+     * be an array of strings, an array of objects with a `point`/`text`/`title`
+     * field, or a single string; keys are matched case-insensitively.
+     */
+    fn from_value(value: &serde_json::Value) -> Option<Self> {
+        let object = value.as_object()?;
+        let side = |name: &str| -> Vec<String> {
+            let Some(items) = object
+                .iter()
+                .find(|(key, _)| key.eq_ignore_ascii_case(name))
+                .map(|(_, items)| items)
+            else {
+                return vec![];
+            };
+            
+            let point = |item: &serde_json::Value| -> Option<String> {
+                let text = match item {
+                    serde_json::Value::String(s) => s.as_str(),
+                    serde_json::Value::Object(fields) => ["point", "text", "title"]
+                        .iter()
+                        .find_map(|field| fields.get(*field).and_then(|v| v.as_str()))?,
+                    _ => return None,
+                };
+                let text = text.trim();
+                (!text.is_empty()).then(|| text.to_string())
+            };
+            
+            match items {
+                serde_json::Value::Array(items) => items.iter().filter_map(point).collect(),
+                item => point(item).into_iter().collect(),
+            }
+        };
+        
+        Some(ProsCons {
+            pros: side("pros"),
+            cons: side("cons"),
+        })
+    }
+}
+
+/**
+ * A labelled snapshot of a note, used to build changelogs.
+ */
+#[derive(Serialize, Deserialize, Clone)]
+pub struct NoteVersion {
+    pub version_label: String,
+    pub content: String,
+}
+
+/**
+ * Summary of what changed when a note moved to `version`.
+ */
+#[derive(Serialize, Deserialize, Clone)]
+pub struct ChangelogEntry {
+    pub version: String,
+    pub summary: String,
+}
+
+/**
+ * Per-request options layered over the service configuration.
+ * Fields left as None fall back to the configured defaults.
+ */
+#[derive(Debug, Clone, Default)]
+pub struct PromptOptions {
+    pub include_datetime: Option<bool>, // Prepend the current date/time to the system context
+    pub params: Option<SamplingParams>, // Sampling parameters merged over the configured defaults
+    pub reattach_frontmatter: bool,     // Put stripped frontmatter back in front of the response (rewrite-style calls)
+    pub model: Option<String>,          // Model (or alias) used instead of the configured model_name
+    pub history: Vec<ChatTurn>,         // Earlier conversation turns, oldest first
+    pub cache: CachePolicy,             // Whether the response is read from and stored in the response cache
+    pub structured: bool,               // The response is parsed, so max_response_chars never cuts it
+}
+
+/**
+ * The kind of flashcards to generate.
+ */
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum FlashcardStyle {
+    /// A question on the front and its answer on the back.
+    #[default]
+    Basic,
+    /// A sentence with `{{c1::...}}` deletions on the front and the full sentence on the back.
+    Cloze,
+}
+
 /**
  * Represents a single flashcard with front (question) and back (answer) sides.
+ * Vocabulary cards can carry an example sentence using the term in context.
  */
 #[derive(Serialize, Deserialize, Clone)]
 pub struct Flashcard {
     pub front: String,
     pub back: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub example: Option<String>,
+}
+
+/**
+ * A complete study set for a note: flashcards plus open-ended questions
+ * that don't reduce to a single answer.
+ */
+#[derive(Serialize, Deserialize, Clone)]
+pub struct StudyDeck {
+    pub title: String,
+    pub cards: Vec<Flashcard>,
+    #[serde(default)]
+    pub open_questions: Vec<String>,
+}
+
+/**
+ * Emotional tone of a note: an overall sentiment label, a few descriptive
+ * tones and a score from -1.0 (very negative) to 1.0 (very positive).
+ */
+#[derive(Serialize, Deserialize, Clone)]
+pub struct ToneAnalysis {
+    pub sentiment: String,
+    #[serde(default)]
+    pub tone: Vec<String>,
+    pub score: f32,
+}
+
+/**
+ * A command suggested for registration in the plugin's command palette.
+ */
+#[derive(Serialize, Deserialize, Clone)]
+pub struct CommandSuggestion {
+    pub name: String,
+    pub description: String,
 }
 
 impl RigService {
     /**
-     * Creates a new RigService with default configuration.
-     * Initializes the default agent for handling prompts if an API key is available.
+     * Creates a new RigService with the configuration file's settings, or the defaults.
      */
     pub fn new() -> Result<Self, Box<dyn Error>> {
-        let config = ModelConfig::default();
+        // Start from the configuration file when there is a usable one
+        let config_path = config_file::config_path();
+        let config = match config_file::load(&config_path) {
+            Some(config) => match validate_config(&config) {
+                Ok(_) => config,
+                Err(e) => {
+                    tracing::warn!("Invalid config file {}: {}; using the default configuration", config_path.display(), e);
+                    ModelConfig::default()
+                }
+            },
+            None => ModelConfig::default(),
+        };
         
         // Check if an API key is available in the environment
         let has_api_key = crate::models::has_api_key(&config, None);
         
-        // Try to create the agent if an API key is available
-        let agent = if has_api_key {
-            match create_agent(&config, None) {
-                Ok(agent) => Some(agent),
-                Err(e) => {
-                    // If there's an error other than missing API key, return it
-                    if !e.to_string().contains("API key not found") {
-                        return Err(e);
-                    }
-                    None
-                }
-            }
-        } else {
-            None
-        };
+        let requests = RequestLimiter::new(config.max_concurrent_requests);
         
         Ok(Self { 
-            agent,
             config: RwLock::new(config),
+            profiles: RwLock::new(BTreeMap::new()),
             has_api_key,
+            throttle: TokenThrottle::new(),
+            responses: ResponseCache::new(cache::DEFAULT_RESPONSE_CACHE_SIZE),
+            agents: BoundedCache::new(cache::DEFAULT_AGENT_CACHE_SIZE),
+            blocked: BoundedCache::new(cache::DEFAULT_PATTERN_CACHE_SIZE),
+            embeddings: EmbeddingBatcher::new(),
+            requests,
+            http: vision::http_client(),
+            fixtures: FixtureStore::from_env(),
+            config_path,
         })
     }
     
+    /**
+     * Serves prompts from the given fixture store instead of the one
+     * `RIG_FIXTURES_DIR` configures (or the provider, if none does).
+     * 
+     * @param fixtures The recorded replies to serve
+     * @return The service, using the store
+     */
+    #[cfg(test)]
+    pub fn with_fixtures(mut self, fixtures: FixtureStore) -> Self {
+        self.fixtures = Some(fixtures);
+        self
+    }
+    
     /**
      * Checks if the service has a valid API key.
      * 
@@ -86,21 +547,90 @@ impl RigService {
     }
     
     /**
-     * Retrieves the current model configuration.
+     * Retrieves the current model configuration: the profile the request
+     * runs with, if it named one, and otherwise the default configuration.
      */
     pub fn get_config(&self) -> Result<ModelConfig, Box<dyn Error>> {
+        if let Some(config) = profiles::active_profile().map(|name| self.profile(&name)).transpose()?.flatten() {
+            return Ok(config);
+        }
+        self.default_config()
+    }
+    
+    /**
+     * Retrieves the default configuration, ignoring any request profile.
+     */
+    fn default_config(&self) -> Result<ModelConfig, Box<dyn Error>> {
         match self.config.read() {
             Ok(config) => Ok(config.clone()),
             Err(e) => Err(format!("Failed to read config: {}", e).into()),
         }
     }
     
+    /**
+     * Looks up a named configuration profile.
+     *
+     * @param name The profile's name
+     * @return The profile's configuration, or None if there is no such profile
+     */
+    pub fn profile(&self, name: &str) -> Result<Option<ModelConfig>, Box<dyn Error>> {
+        match self.profiles.read() {
+            Ok(profiles) => Ok(profiles.get(name).cloned()),
+            Err(e) => Err(format!("Failed to read profiles: {}", e).into()),
+        }
+    }
+    
+    /**
+     * Lists the named configuration profiles.
+     */
+    pub fn list_profiles(&self) -> Result<BTreeMap<String, ModelConfig>, Box<dyn Error>> {
+        match self.profiles.read() {
+            Ok(profiles) => Ok(profiles.clone()),
+            Err(e) => Err(format!("Failed to read profiles: {}", e).into()),
+        }
+    }
+    
+    /**
+     * Adds a named configuration profile, or replaces the one with that name.
+     *
+     * @param name The profile's name
+     * @param config The profile's configuration, checked like `update_config`
+     */
+    pub fn upsert_profile(&self, name: &str, config: ModelConfig) -> Result<(), Box<dyn Error>> {
+        let name = name.trim();
+        if name.is_empty() {
+            return Err("Profile names must not be blank".into());
+        }
+        let blocked = validate_config(&config)?;
+        self.blocked.insert(blocked_patterns_key(&config), Arc::new(blocked));
+        
+        match self.profiles.write() {
+            Ok(mut profiles) => {
+                profiles.insert(name.to_string(), config);
+                Ok(())
+            },
+            Err(e) => Err(format!("Failed to write profiles: {}", e).into()),
+        }
+    }
+    
     /**
      * Updates the model configuration with new settings.
+     *
+     * @param new_config The configuration to apply
+     * @param persist Whether to also write it to the configuration file
      */
-    pub fn update_config(&self, new_config: ModelConfig) -> Result<(), Box<dyn Error>> {
+    pub fn update_config(&self, new_config: ModelConfig, persist: bool) -> Result<(), Box<dyn Error>> {
+        let blocked = validate_config(&new_config)?;
+        
+        // Write the file first so a failed save leaves the running configuration unchanged
+        if persist {
+            config_file::save(&self.config_path, &new_config)?;
+        }
+        
         match self.config.write() {
             Ok(mut config) => {
+                self.requests.set_limit(new_config.max_concurrent_requests);
+                self.blocked.insert(blocked_patterns_key(&new_config), Arc::new(blocked));
                 *config = new_config;
                 Ok(())
             },
@@ -109,144 +639,3004 @@ impl RigService {
     }
     
     /**
-     * Recreates the default agent with the current configuration.
-     * Useful after configuration changes.
+     * Resolves the configuration a request will actually run with, layering
+     * per-request options over the current configuration.
+     *
+     * @param options Per-request overrides
+     * @return The effective configuration for the request
      */
-    #[allow(dead_code)]
-    pub fn recreate_agent(&mut self) -> Result<(), Box<dyn Error>> {
-        let config = self.get_config()?;
+    pub fn effective_config(&self, options: &PromptOptions) -> Result<ModelConfig, Box<dyn Error>> {
+        let mut config = self.get_config()?;
         
-        // Check if an API key is available
-        self.has_api_key = crate::models::has_api_key(&config, None);
+        // Merge per-request sampling parameters over the configured defaults
+        if let Some(params) = &options.params {
+            params.validate()?;
+            config.sampling = params.merged_over(&config.sampling);
+        }
+        if let Some(include_datetime) = options.include_datetime {
+            config.include_datetime = include_datetime;
+        }
+        if let Some(model) = &options.model {
+            config.model_name = model.clone();
+        }
         
-        if self.has_api_key {
-            match create_agent(&config, None) {
-                Ok(agent) => {
-                    self.agent = Some(agent);
-                    Ok(())
-                },
-                Err(e) => Err(e)
+        Ok(config)
+    }
+
+    /**
+     * Applies `auto_model_rules`: when the request doesn't name a model, the
+     * first rule whose `max_chars` fits the content picks one, passed on as
+     * a per-request model override.
+     *
+     * @param content The text to send to the AI model
+     * @param options Per-request overrides
+     * @return The options to run the request with
+     */
+    fn with_auto_model(&self, content: &str, options: &PromptOptions) -> Result<PromptOptions, Box<dyn Error>> {
+        let mut options = options.clone();
+        if options.model.is_none() {
+            let config = self.get_config()?;
+            let content_chars = content.chars().count();
+            if let Some(model) = config.auto_model(content_chars) {
+                tracing::info!("Auto-selected model '{}' for {} characters of content", model, content_chars);
+                options.model = Some(model.to_string());
             }
-        } else {
-            self.agent = None;
-            Ok(())
         }
+        Ok(options)
     }
 
     /**
-     * Generates an AI response for the given content.
-     * 
+     * Describes the effective configuration of a request for debugging,
+     * with aliases resolved and secrets redacted.
+     *
+     * @param options Per-request overrides
+     * @return The redacted effective configuration
+     */
+    pub fn explain_config(&self, options: &PromptOptions) -> Result<ModelConfig, Box<dyn Error>> {
+        let mut config = self.effective_config(options)?.redacted();
+        config.model_name = config.resolve_model(&config.model_name);
+        Ok(config)
+    }
+
+    /**
+     * Computes a key identifying the output of a request: the same content,
+     * system prompt, model, endpoint (`base_url`) and sampling parameters
+     * produce the same key.
+     * Requests grounded in the current date/time are never cacheable.
+     *
      * @param content The text to send to the AI model
-     * @param system_prompt Optional system prompt to guide the AI's behavior
-     * @param api_key Optional API key to use for this specific request
-     * @return The AI-generated response
+     * @param system_prompt Optional system prompt
+     * @param options Per-request overrides
+     * @return The key, or None if the request shouldn't be cached
      */
-    pub async fn generate_response(
+    pub fn response_key(
         &self,
         content: &str,
         system_prompt: Option<&str>,
-        api_key: Option<&str>,
-    ) -> Result<String, Box<dyn Error>> {
-        // Get the response based on whether we have a system prompt and/or API key
-        let response = match (system_prompt, api_key) {
-            // Both system prompt and API key provided
-            (Some(system_prompt), Some(api_key)) => {
-                let config = self.get_config()?;
-                let temp_agent = create_agent_with_system_prompt(&config, system_prompt, Some(api_key))?;
-                temp_agent.prompt(content).await?
-            },
-            // Only system prompt provided
-            (Some(system_prompt), None) => {
-                let config = self.get_config()?;
-                let temp_agent = create_agent_with_system_prompt(&config, system_prompt, None)?;
-                temp_agent.prompt(content).await?
-            },
-            // Only API key provided
-            (None, Some(api_key)) => {
-                if !api_key.is_empty() {
-                    let config = self.get_config()?;
-                    let temp_agent = create_agent(&config, Some(api_key))?;
-                    temp_agent.prompt(content).await?
-                } else {
-                    // Empty API key, check if we have an existing agent
-                    if let Some(agent) = &self.agent {
-                        agent.prompt(content).await?
-                    } else {
-                        return Err("No API key provided and no default agent available. Please provide an OpenAI API key in the plugin settings.".into());
-                    }
-                }
-            },
-            // Neither system prompt nor API key provided
-            (None, None) => {
-                // Check if we have an existing agent
-                if let Some(agent) = &self.agent {
-                    agent.prompt(content).await?
-                } else {
-                    return Err("No API key provided and no default agent available. Please provide an OpenAI API key in the plugin settings.".into());
-                }
-            }
-        };
+        options: &PromptOptions,
+    ) -> Result<Option<String>, Box<dyn Error>> {
+        let config = self.effective_config(&self.with_auto_model(content, options)?)?;
+        if config.include_datetime {
+            return Ok(None);
+        }
         
-        Ok(response)
+        let model = config.resolve_model(&config.model_name);
+        let sampling = serde_json::to_string(&config.sampling)?;
+        let conversation = history::transcript(history::recent_turns(&options.history, config.max_history_turns), content);
+        let api_style = config.api_style.clone().unwrap_or_default();
+        let base_url = config.base_url.clone().unwrap_or_default();
+        let flags = format!(
+            "sanitize={} transforms={:?} reattach_frontmatter={} max_response_chars={:?}",
+            config.sanitize_responses,
+            transform::configured_steps(&config),
+            options.reattach_frontmatter,
+            config.max_response_chars
+        );
+        Ok(Some(cache::cache_key(&[
+            &model,
+            &base_url,
+            &api_style,
+            &sampling,
+            &flags,
+            system_prompt.unwrap_or_default(),
+            &conversation,
+        ])))
     }
 
     /**
-     * Generates a list of questions about the given content.
+     * Splits an oversized response at heading boundaries when
+     * `split_large_responses` is enabled.
+     *
+     * @param response The generated response
+     * @return The parts and suggested links, or None if splitting is off or not needed
+     */
+    pub fn split_response(&self, response: &str) -> Option<SplitResponse> {
+        let config = self.get_config().ok()?;
+        if !config.split_large_responses {
+            return None;
+        }
+        split::split_response(response, config.split_threshold)
+    }
+
+    /**
+     * The configured `fallback_message`, shown to users instead of the raw
+     * error when the provider can't be reached.
+     *
+     * @param error The error a request failed with
+     * @return The fallback, or None for other errors or when none is configured
+     */
+    pub fn fallback_message(&self, error: &(dyn Error + 'static)) -> Option<String> {
+        if !matches!(error.downcast_ref::<RigError>(), Some(RigError::ProviderUnreachable { .. })) {
+            return None;
+        }
+        self.get_config()
+            .ok()
+            .and_then(|config| config.fallback_message)
+            .filter(|message| !message.trim().is_empty())
+    }
+
+    /**
+     * Looks up a previously generated response by its response key.
+     */
+    pub fn cached_response(&self, key: &str) -> Option<LimitedResponse> {
+        self.responses.get(key)
+    }
+
+    /**
+     * Empties the response cache, e.g. after editing notes outside the canvas.
+     *
+     * @return The number of responses removed
+     */
+    pub fn clear_response_cache(&self) -> usize {
+        self.responses.clear()
+    }
+
+    /**
+     * Generates an AI response for one of the built-in endpoints, using the
+     * system prompt and model configured for that endpoint (if any).
      * 
-     * @param content The text to generate questions about
-     * @param count The number of questions to generate
+     * @param endpoint The endpoint's key in `endpoint_system_prompts` (e.g., "flashcards")
+     * @param content The text to send to the AI model
      * @param api_key Optional API key to use for this specific request
-     * @return A list of AI-generated questions
+     * @return The AI-generated response
      */
-    pub async fn generate_questions(
+    async fn generate_endpoint_response(
         &self,
+        endpoint: &str,
         content: &str,
-        count: usize,
         api_key: Option<&str>,
-    ) -> Result<Vec<String>, Box<dyn Error>> {
-        let prompt = format!(
-            "Based on the following content, generate {} thoughtful questions that would help someone understand the material better. Return the response as a JSON object with a 'questions' field containing an array of strings.\n\nContent: {}\n\nQuestions:",
-            count, content
-        );
-        
-        // Get the response as a String
-        let response_str = self.generate_response(&prompt, None, api_key).await?;
+    ) -> Result<String, Box<dyn Error>> {
+        let system_prompt = self.endpoint_system_prompt(endpoint)?;
+        let options = PromptOptions {
+            model: self.get_config()?.feature_model(endpoint).map(str::to_string),
+            structured: true,
+            ..PromptOptions::default()
+        };
+        let response = self
+            .generate_response_with_options(content, system_prompt.as_deref(), api_key, &options)
+            .await?;
         
-        // Parse the JSON response
-        let output: QuestionsOutput = serde_json::from_str(&response_str)
-            .map_err(|e| format!("Failed to parse questions response: {}", e))?;
+        // Some gateways send structured output as a JSON string containing the JSON
+        let response = if self.get_config()?.unwrap_double_encoded_json {
+            extract::unwrap_double_encoded(&response).unwrap_or(response)
+        } else {
+            response
+        };
         
-        Ok(output.questions)
+        // Drop any chain of thought that leaked in despite the instruction
+        if self.suppresses_reasoning(endpoint)? {
+            Ok(strip_reasoning(&response))
+        } else {
+            Ok(response)
+        }
     }
 
     /**
-     * Generates flashcards based on the given content.
+     * Generates a response for a built-in endpoint and deserializes the JSON
+     * object in it, tolerating code fences and prose around the JSON (see
+     * `extract::parse_object`). If the response still can't be parsed, the
+     * model is asked once more to return only valid JSON.
      * 
-     * @param content The text to create flashcards from
-     * @param title Optional title for the flashcards
+     * @param endpoint The endpoint's key in `endpoint_system_prompts` (e.g., "flashcards")
+     * @param prompt The prompt asking for JSON output
+     * @param what What is being generated, for error messages (e.g., "flashcards")
      * @param api_key Optional API key to use for this specific request
-     * @return A tuple with the suggested filename and list of flashcards
+     * @return The deserialized response
      */
-    pub async fn generate_flashcards(
+    async fn generate_json<T: DeserializeOwned>(
+        &self,
+        endpoint: &str,
+        prompt: &str,
+        what: &str,
+        api_key: Option<&str>,
+    ) -> Result<T, Box<dyn Error>> {
+        let response_str = self.generate_endpoint_response(endpoint, prompt, api_key).await?;
+        let error = match extract::parse_object(&response_str) {
+            Ok(output) => return Ok(output),
+            Err(e) => e,
+        };
+        
+        tracing::warn!("Could not parse {} response ({}), retrying once", what, error);
+        let retry_prompt = format!(
+            "{}\n\nYour previous answer could not be parsed ({}). Return only valid JSON, with no code fences or commentary.",
+            prompt, error
+        );
+        let response_str = self.generate_endpoint_response(endpoint, &retry_prompt, api_key).await?;
+        extract::parse_object(&response_str).map_err(|e| {
+            RigError::ParseError { message: format!("Failed to parse {} response: {}", what, e) }.into()
+        })
+    }
+
+    /**
+     * Generates a response for a built-in endpoint, checks the JSON in it
+     * against a schema before trusting its shape, and deserializes it.
+     *
+     * @param endpoint The endpoint's key in `endpoint_system_prompts` (e.g., "answer")
+     * @param prompt The prompt asking for JSON matching the schema
+     * @param schema The JSON schema the output must match
+     * @param what What is being generated, for error messages (e.g., "answers")
+     * @param api_key Optional API key to use for this specific request
+     * @return The deserialized response, or RigError::SchemaValidation listing every violation
+     */
+    async fn generate_validated<T: DeserializeOwned>(
+        &self,
+        endpoint: &str,
+        prompt: &str,
+        schema: &serde_json::Value,
+        what: &str,
+        api_key: Option<&str>,
+    ) -> Result<T, Box<dyn Error>> {
+        let schema = extract::compile_schema(schema)?;
+        let response_str = self.generate_endpoint_response(endpoint, prompt, api_key).await?;
+        let value = extract::parse_validated(&schema, &response_str)
+            .map_err(|errors| RigError::SchemaValidation { errors })?;
+        serde_json::from_value(value).map_err(|e| {
+            RigError::ParseError { message: format!("Failed to parse {} response: {}", what, e) }.into()
+        })
+    }
+
+    /**
+     * Looks up the configured system prompt for an endpoint.
+     * Blank prompts are treated as unset. Clean-output endpoints also get an
+     * instruction to return only the final answer.
+     */
+    fn endpoint_system_prompt(&self, endpoint: &str) -> Result<Option<String>, Box<dyn Error>> {
+        let config = self.get_config()?;
+        let configured = config
+            .endpoint_system_prompts
+            .get(endpoint)
+            .filter(|prompt| !prompt.trim().is_empty())
+            .cloned();
+        
+        if !self.suppresses_reasoning(endpoint)? {
+            return Ok(configured);
+        }
+        Ok(Some(match configured {
+            Some(prompt) => format!("{}\n\n{}", prompt, FINAL_ANSWER_INSTRUCTION),
+            None => FINAL_ANSWER_INSTRUCTION.to_string(),
+        }))
+    }
+
+    /**
+     * The built-in instruction templates in the configured language.
+     */
+    fn instructions(&self) -> Result<&'static InstructionSet, Box<dyn Error>> {
+        Ok(instructions::for_language(self.get_config()?.instruction_language.as_deref()))
+    }
+
+    /**
+     * Whether an endpoint's output should be kept free of model reasoning.
+     */
+    fn suppresses_reasoning(&self, endpoint: &str) -> Result<bool, Box<dyn Error>> {
+        Ok(self.get_config()?.suppress_reasoning_in_output && CLEAN_OUTPUT_ENDPOINTS.contains(&endpoint))
+    }
+
+    /**
+     * Fits the nodes of a combined multi-node prompt into the model's context
+     * window, keeping room for the response (`max_tokens`, or a default
+     * reserve). Nodes that fit are returned unchanged. Otherwise, with
+     * `context_overflow` set to `summarize`, every node is summarized first;
+     * then the least relevant nodes are dropped until the rest fit.
+     * 
+     * @param nodes The request's nodes
+     * @param prompt The user's prompt
+     * @param system_prompt The request's system prompt, if any
+     * @param api_key Optional API key to use for the summaries
+     * @param options Per-request overrides (e.g., the model)
+     * @return The nodes to combine, in request order, and what was changed
+     */
+    pub async fn fit_to_context(
+        &self,
+        nodes: Vec<NodeContent>,
+        prompt: &str,
+        system_prompt: Option<&str>,
+        api_key: Option<&str>,
+        options: &PromptOptions,
+    ) -> Result<(Vec<NodeContent>, ContextReport), Box<dyn Error>> {
+        let config = self.effective_config(options)?;
+        let model = config.resolve_model(&config.model_name);
+        let reserve = config
+            .sampling
+            .max_tokens
+            .map_or(context::DEFAULT_RESPONSE_RESERVE_TOKENS, |max_tokens| max_tokens as usize);
+        let overhead = tokens::count_prompt_tokens(&model, system_prompt, prompt);
+        let budget = config.context_limit(&model).saturating_sub(reserve).saturating_sub(overhead);
+        let node_tokens = |node: &NodeContent| {
+            tokens::count_tokens(&model, &node.content)
+                + node.instruction().map_or(0, |instruction| tokens::count_tokens(&model, instruction))
+                + context::NODE_LABEL_TOKENS
+        };
+        
+        let mut nodes = nodes;
+        let mut report = ContextReport::default();
+        let total: usize = nodes.iter().map(node_tokens).sum();
+        if total <= budget {
+            return Ok((nodes, report));
+        }
+        tracing::warn!("Combined nodes need {} tokens, {} fit the context window of {}", total, budget, model);
+        
+        // Map step: condense each node to its share of the budget before the final prompt
+        if config.context_overflow == ContextOverflow::Summarize {
+            let share_words = (budget / nodes.len().max(1) * 3 / 4).max(1);
+            let contents: Vec<String> = nodes.iter().map(|node| node.content.clone()).collect();
+            let summaries: Vec<Result<String, Box<dyn Error + Send + Sync>>> = stream::iter(contents)
+                .map(|content| async move { self.summarize(&content, Some(share_words), api_key).await.map_err(sendable) })
+                .buffered(batch::effective_concurrency(None))
+                .collect()
+                .await;
+            for (node, summary) in nodes.iter_mut().zip(summaries) {
+                node.content = summary.map_err(|e| e as Box<dyn Error>)?;
+                report.summarized.push(node.id.clone());
+            }
+        }
+        
+        // Drop the least relevant nodes until the rest fit
+        let weights: Vec<Option<f32>> = nodes.iter().map(|node| node.weight).collect();
+        let counts: Vec<usize> = nodes.iter().map(node_tokens).collect();
+        let dropped = context::nodes_to_drop(&weights, &counts, budget);
+        let mut kept = Vec::new();
+        for (index, node) in nodes.into_iter().enumerate() {
+            if dropped.contains(&index) {
+                report.summarized.retain(|id| *id != node.id);
+                report.dropped.push(node.id);
+            } else {
+                kept.push(node);
+            }
+        }
+        Ok((kept, report))
+    }
+
+    /**
+     * Counts the tokens a prompt would send, prepared exactly as
+     * `generate_response_with_options` prepares it: with the effective
+     * model's tokenizer, after the content transforms, and including the
+     * system prompt and applied history.
+     * 
+     * @param content The text to send to the AI model
+     * @param system_prompt Optional system prompt to guide the AI's behavior
+     * @param options Per-request overrides
+     * @return The number of input tokens
+     */
+    pub fn count_input_tokens(
+        &self,
+        content: &str,
+        system_prompt: Option<&str>,
+        options: &PromptOptions,
+    ) -> Result<usize, Box<dyn Error>> {
+        let options = &self.with_auto_model(content, options)?;
+        let config = self.effective_config(options)?;
+        let (content, system_prompt) = prepare_prompt(&config, content, system_prompt);
+        let history = history::recent_turns(&options.history, config.max_history_turns);
+        Ok(prompt_tokens(&config, system_prompt.as_deref(), history, &content))
+    }
+
+    /**
+     * Generates an AI response for the given content, applying per-request options
+     * on top of the current configuration. Free-text responses are cut at
+     * `max_response_chars`.
+     * 
+     * @param content The text to send to the AI model
+     * @param system_prompt Optional system prompt to guide the AI's behavior
+     * @param api_key Optional API key to use for this specific request
+     * @param options Per-request overrides (e.g., date/time grounding)
+     * @return The AI-generated response
+     */
+    pub async fn generate_response_with_options(
+        &self,
+        content: &str,
+        system_prompt: Option<&str>,
+        api_key: Option<&str>,
+        options: &PromptOptions,
+    ) -> Result<String, Box<dyn Error>> {
+        let (response, _) = self.generate_response_with_usage(content, system_prompt, api_key, options).await?;
+        Ok(response.text)
+    }
+
+    /**
+     * Generates an AI response like `generate_response_with_options`, also
+     * reporting the tokens the provider says the call used and their
+     * estimated cost. With `options.cache` set, repeated requests (same
+     * `response_key`) are answered from the response cache.
+     * 
+     * @param content The text to send to the AI model
+     * @param system_prompt Optional system prompt to guide the AI's behavior
+     * @param api_key Optional API key to use for this specific request
+     * @param options Per-request overrides
+     * @return The AI-generated response, and its usage if the provider reported it
+     */
+    pub async fn generate_response_with_usage(
+        &self,
+        content: &str,
+        system_prompt: Option<&str>,
+        api_key: Option<&str>,
+        options: &PromptOptions,
+    ) -> Result<(LimitedResponse, Option<UsageReport>), Box<dyn Error>> {
+        // Pick a model by content length unless the request names one
+        let options = &self.with_auto_model(content, options)?;
+        let config = self.effective_config(options)?;
+        
+        // Only the most recent turns of the conversation are applied
+        let history = history::recent_turns(&options.history, config.max_history_turns);
+        if history.len() < options.history.len() {
+            tracing::info!("Applying the last {} of {} history turns", history.len(), options.history.len());
+        }
+        
+        // Refuse a conversation matching a blocked pattern before it reaches the provider
+        self.check_blocked_patterns(&config, &history::transcript(history, content))?;
+        
+        // Answer a repeated request from the cache; no provider call means no usage to report
+        let cache_key = match options.cache {
+            CachePolicy::Skip => None,
+            CachePolicy::Use | CachePolicy::Refresh => self.response_key(content, system_prompt, options)?,
+        };
+        if let (CachePolicy::Use, Some(key)) = (options.cache, &cache_key) {
+            if let Some(response) = self.cached_response(key) {
+                tracing::info!("Returning cached response {}", key);
+                return Ok((response, None));
+            }
+        }
+        
+        // Run the configured preprocessing steps (e.g., stripping frontmatter) in order
+        let original = content;
+        let (content, system_prompt) = prepare_prompt(&config, content, system_prompt);
+        let content = &content;
+        let frontmatter = split_frontmatter(original).0.filter(|frontmatter| !content.contains(frontmatter));
+        
+        // Refuse prompts the configured input limit doesn't allow before paying for them
+        if let Some(limit) = config.max_input_tokens {
+            let tokens = prompt_tokens(&config, system_prompt.as_deref(), history, content);
+            if tokens > limit {
+                return Err(Box::new(RigError::InputTooLarge { tokens, limit }));
+            }
+        }
+        
+        // Wait for room in the tokens-per-minute budget before calling the provider
+        self.wait_for_token_budget(&config, &history::transcript(history, content), system_prompt.as_deref()).await;
+        
+        // Agents are cached per configuration, so configuration changes take effect right away
+        let api_key = api_key.filter(|key| !key.is_empty());
+        let agent = self.agent_for(&config, system_prompt.as_deref(), api_key)?;
+        
+        // Queue for a slot so canvas-wide operations don't flood the provider; the wait isn't part of the timeout
+        // The cap is shared by all requests, so it follows the default configuration rather than a profile
+        let _slot = self.requests.acquire().await;
+        
+        // Give up on a hung provider connection rather than waiting forever
+        let model = config.resolve_model(&config.model_name);
+        let started = Instant::now();
+        let response = tokio::time::timeout(Duration::from_secs(config.request_timeout_secs), agent.chat(content, history, self.fixtures.as_ref())).await;
+        telemetry::record_provider_call(&model, started.elapsed());
+        let response = match response {
+            Ok(response) => response?,
+            Err(_) => return Err(Box::new(RigError::Timeout { secs: config.request_timeout_secs })),
+        };
+        
+        // Usage is only reported when the provider returned it; cost needs a known price
+        let usage = response.usage.map(|usage| pricing::usage_report(usage, config.model_price(&model)));
+        if let Some(usage) = &usage {
+            telemetry::record_tokens(&model, usage);
+        }
+        
+        // The output counts toward the tokens-per-minute limit too, estimated when usage wasn't reported
+        let completion_tokens = usage
+            .as_ref()
+            .map_or_else(|| throttle::estimate_tokens(&response.text), |usage| usage.completion_tokens);
+        self.charge_token_budget(&config, completion_tokens);
+        
+        let response = if config.sanitize_responses {
+            sanitize_response(&response.text)
+        } else {
+            response.text
+        };
+        
+        let response = match frontmatter {
+            Some(block) if options.reattach_frontmatter => frontmatter::reattach(block, response),
+            _ => response,
+        };
+        
+        // Cutting structured output would leave it unparsable
+        let max_chars = config.max_response_chars.filter(|_| !options.structured);
+        let response = truncate::limit_response(response, max_chars);
+        if let Some(key) = cache_key {
+            self.responses.insert(key, response.clone());
+        }
+        Ok((response, usage))
+    }
+
+    /**
+     * Streams an AI response for the given content as it is generated.
+     * 
+     * @param content The text to send to the AI model
+     * @param system_prompt Optional system prompt to guide the AI's behavior
+     * @param api_key Optional API key to use for this specific request
+     * @return A stream of response text chunks
+     */
+    pub async fn stream_response(
+        &self,
+        content: &str,
+        system_prompt: Option<&str>,
+        api_key: Option<&str>,
+    ) -> Result<ResponseStream, Box<dyn Error>> {
+        let chunks = self.stream_response_with_model(content, system_prompt, api_key, None).await?;
+        Ok(truncate::limit_stream(chunks, self.get_config()?.max_response_chars))
+    }
+
+    /**
+     * Streams an AI response like `stream_response`, optionally with a model
+     * other than the configured `model_name`.
+     */
+    async fn stream_response_with_model(
+        &self,
+        content: &str,
+        system_prompt: Option<&str>,
+        api_key: Option<&str>,
+        model: Option<&str>,
+    ) -> Result<ResponseStream, Box<dyn Error>> {
+        let mut config = self.get_config()?;
+        if let Some(model) = model {
+            config.model_name = model.to_string();
+        }
+        let api_key = api_key.filter(|key| !key.is_empty());
+        
+        // Refuse content matching a blocked pattern before it reaches the provider
+        self.check_blocked_patterns(&config, content)?;
+        
+        let (content, system_prompt) = prepare_prompt(&config, content, system_prompt);
+        let (content, system_prompt) = (content.as_str(), system_prompt.as_deref());
+        let model = config.resolve_model(&config.model_name);
+        let prompt_tokens = tokens::count_prompt_tokens(&model, system_prompt, content);
+        
+        // Refuse prompts the configured input limit doesn't allow before paying for them
+        if let Some(limit) = config.max_input_tokens {
+            if prompt_tokens > limit {
+                return Err(Box::new(RigError::InputTooLarge { tokens: prompt_tokens, limit }));
+            }
+        }
+        
+        // Wait for room in the tokens-per-minute budget before calling the provider
+        self.wait_for_token_budget(&config, content, system_prompt).await;
+        
+        let agent = self.agent_for(&config, system_prompt, api_key)?;
+        let _slot = self.requests.acquire().await;
+        
+        // Give up on a provider that never starts the stream rather than waiting forever
+        let started = Instant::now();
+        let chunks = tokio::time::timeout(Duration::from_secs(config.request_timeout_secs), agent.stream_prompt(content, self.fixtures.as_ref())).await;
+        let chunks = match chunks {
+            Ok(Ok(chunks)) => chunks,
+            Ok(Err(e)) => {
+                telemetry::record_provider_call(&model, started.elapsed());
+                return Err(e);
+            }
+            Err(_) => {
+                telemetry::record_provider_call(&model, started.elapsed());
+                return Err(Box::new(RigError::Timeout { secs: config.request_timeout_secs }));
+            }
+        };
+        let chunks: ResponseStream = Box::pin(telemetry::MeteredStream::new(chunks, model, prompt_tokens, started));
+        
+        if config.sanitize_responses {
+            Ok(Box::pin(chunks.map(|chunk| chunk.map(|text| sanitize_response(&text)))))
+        } else {
+            Ok(chunks)
+        }
+    }
+
+    /**
+     * Returns an agent for the given configuration, system prompt and key,
+     * reusing a previously built one when all three match. The key is only
+     * ever stored hashed.
+     *
+     * @param config The effective configuration for the request
+     * @param system_prompt Optional system prompt (preamble) for the agent
+     * @param api_key Optional API key provided with the request
+     * @return The shared agent
+     */
+    fn agent_for(
+        &self,
+        config: &ModelConfig,
+        system_prompt: Option<&str>,
+        api_key: Option<&str>,
+    ) -> Result<Arc<AgentWrapper>, Box<dyn Error>> {
+        let resolved_key = resolve_api_key(config, api_key)?;
+        let sampling = serde_json::to_string(&config.sampling)?;
+        let key = cache::cache_key(&[
+            &config.resolve_model(&config.model_name),
+            config.base_url.as_deref().unwrap_or_default(),
+            config.api_style.as_deref().unwrap_or_default(),
+            &sampling,
+            if system_prompt.is_some() { "preamble" } else { "no-preamble" },
+            system_prompt.unwrap_or_default(),
+            &resolved_key,
+        ]);
+        
+        if let Some(agent) = self.agents.get(&key) {
+            return Ok(agent);
+        }
+        
+        let agent = Arc::new(match system_prompt {
+            Some(system_prompt) => create_agent_with_system_prompt(config, system_prompt, Some(&resolved_key))?,
+            None => create_agent(config, Some(&resolved_key))?,
+        });
+        self.agents.insert(key, agent.clone());
+        Ok(agent)
+    }
+
+    /**
+     * Waits until the request fits the configured tokens-per-minute budget.
+     * Does nothing when no budget is configured.
+     */
+    async fn wait_for_token_budget(&self, config: &ModelConfig, content: &str, system_prompt: Option<&str>) {
+        if let Some(tpm_limit) = config.tpm_limit {
+            let estimated_tokens = throttle::estimate_tokens(content)
+                + system_prompt.map(throttle::estimate_tokens).unwrap_or(0);
+            let model = config.resolve_model(&config.model_name);
+            self.throttle.acquire(&model, estimated_tokens, tpm_limit).await;
+        }
+    }
+
+    /**
+     * Charges tokens used by a finished call, such as its completion tokens,
+     * to the tokens-per-minute budget of the model it ran on. Does nothing
+     * when no budget is configured.
+     */
+    fn charge_token_budget(&self, config: &ModelConfig, tokens: u32) {
+        if config.tpm_limit.is_some() {
+            self.throttle.record(&config.resolve_model(&config.model_name), tokens);
+        }
+    }
+
+    /**
+     * Checks content against the configured blocked patterns, using the set
+     * compiled when the configuration was applied.
+     *
+     * @param config The effective configuration
+     * @param content The content about to be sent
+     * @return RigError::Blocked naming the first matching pattern, if any
+     */
+    fn check_blocked_patterns(&self, config: &ModelConfig, content: &str) -> Result<(), Box<dyn Error>> {
+        if config.blocked_patterns.is_empty() {
+            return Ok(());
+        }
+        
+        let key = blocked_patterns_key(config);
+        let patterns = match self.blocked.get(&key) {
+            Some(patterns) => patterns,
+            None => {
+                // Evicted sets, and the configuration file's read at startup, are compiled on first use
+                let patterns = Arc::new(compile_blocked_patterns(config)?);
+                self.blocked.insert(key, patterns.clone());
+                patterns
+            }
+        };
+        match patterns.matches(content).iter().next() {
+            Some(index) => Err(Box::new(RigError::Blocked {
+                pattern: config.blocked_patterns[index].clone(),
+            })),
+            None => Ok(()),
+        }
+    }
+
+    /**
+     * Summarizes the given content with a fixed summarization prompt.
+     * 
+     * @param content The text to summarize
+     * @param max_words Optional upper bound on the summary length, in words
+     * @param api_key Optional API key to use for this specific request
+     * @return The summary
+     */
+    pub async fn summarize(
+        &self,
+        content: &str,
+        max_words: Option<usize>,
+        api_key: Option<&str>,
+    ) -> Result<String, Box<dyn Error>> {
+        let prompt = summarize_prompt(content, max_words);
+        
+        let summary = self.generate_endpoint_response("summarize", &prompt, api_key).await?;
+        Ok(summary.trim().to_string())
+    }
+
+    /**
+     * Generates a list of questions about the given content.
+     * 
+     * @param content The text to generate questions about
+     * @param count The number of questions to generate
+     * @param api_key Optional API key to use for this specific request
+     * @return A list of AI-generated questions
+     */
+    pub async fn generate_questions(
+        &self,
+        content: &str,
+        count: usize,
+        api_key: Option<&str>,
+    ) -> Result<Vec<String>, Box<dyn Error>> {
+        let prompt = questions_prompt(self.instructions()?, content, count);
+        
+        let output: QuestionsOutput = self.generate_json("questions", &prompt, "questions", api_key).await?;
+        
+        // Drop near-identical questions, which are common at high counts
+        let config = self.get_config()?;
+        if config.dedupe_questions {
+            return Ok(dedupe::dedupe_questions(output.questions, config.question_dedupe_threshold, count));
+        }
+        Ok(output.questions)
+    }
+
+    /**
+     * Answers questions strictly from the given content, e.g. to check a
+     * self-quiz. Questions the content doesn't answer get `answer::NOT_STATED`.
+     * The output is validated against a JSON schema.
+     * 
+     * @param content The text the answers must come from
+     * @param questions The questions to answer
+     * @param api_key Optional API key to use for this specific request
+     * @return One answer per question, in question order
+     */
+    pub async fn answer_questions(
+        &self,
+        content: &str,
+        questions: &[String],
+        api_key: Option<&str>,
+    ) -> Result<Vec<String>, Box<dyn Error>> {
+        if questions.is_empty() {
+            return Ok(Vec::new());
+        }
+        
+        let schema_value = answer::answers_schema();
+        
+        let prompt = format!(
+            "Answer each numbered question using only the following content. Don't use outside knowledge or guess: if the content doesn't answer a question, answer exactly \"{}\". Keep each answer short. Return the response as JSON matching this JSON schema, with one entry per question and its number: {}\n\nContent: {}\n\nQuestions:\n{}\n\nAnswers:",
+            answer::NOT_STATED, schema_value, content, answer::numbered(questions)
+        );
+        
+        let output: AnswersOutput = self.generate_validated("answer", &prompt, &schema_value, "answers", api_key).await?;
+        Ok(answer::aligned(output.answers, questions.len()))
+    }
+
+    /**
+     * Generates flashcards based on the given content.
+     * 
+     * @param content The text to create flashcards from
+     * @param title Optional title for the flashcards
+     * @param with_examples Whether each card should include an example sentence (basic cards only)
+     * @param style Basic question/answer cards or cloze deletions
+     * @param api_key Optional API key to use for this specific request
+     * @return A tuple with the suggested filename and list of flashcards
+     */
+    pub async fn generate_flashcards(
+        &self,
+        content: &str,
+        title: Option<&str>,
+        with_examples: bool,
+        style: FlashcardStyle,
+        api_key: Option<&str>,
+    ) -> Result<(String, Vec<Flashcard>), Box<dyn Error>> {
+        let prompt = match style {
+            FlashcardStyle::Basic => flashcards_prompt(self.instructions()?, content, title, with_examples),
+            FlashcardStyle::Cloze => cloze_flashcards_prompt(self.instructions()?, content, title),
+        };
+        
+        let output: FlashcardsOutput = self.generate_json("flashcards", &prompt, "flashcards", api_key).await?;
+        
+        let filename = sanitize_filename(&output.filename, self.get_config()?.filename_style);
+        let flashcards = match style {
+            FlashcardStyle::Basic => output.flashcards,
+            FlashcardStyle::Cloze => cloze_cards(output.flashcards),
+        };
+        Ok((filename, flashcards))
+    }
+
+    /**
+     * Streams flashcards as they are generated.
+     * The model is asked for newline-delimited JSON so each card can be parsed
+     * as soon as its line is complete; see `parse_flashcard_line`.
+     * 
+     * @param content The text to create flashcards from
+     * @param title Optional title for the flashcards
+     * @param api_key Optional API key to use for this specific request
+     * @return A stream of raw response text chunks
+     */
+    pub async fn stream_flashcards(
+        &self,
+        content: &str,
+        title: Option<&str>,
+        api_key: Option<&str>,
+    ) -> Result<ResponseStream, Box<dyn Error>> {
+        let instructions = self.instructions()?;
+        let title_prompt = title.unwrap_or(instructions.default_title);
+        
+        let prompt = instructions::render(
+            instructions.flashcards_stream,
+            &[("title", title_prompt), ("content", content)],
+        );
+        
+        let system_prompt = self.endpoint_system_prompt("flashcards")?;
+        let model = self.get_config()?.feature_model("flashcards").map(str::to_string);
+        self.stream_response_with_model(&prompt, system_prompt.as_deref(), api_key, model.as_deref())
+            .await
+    }
+
+    /**
+     * Generates an Obsidian canvas document for a topic or note.
+     * The model decides the content and connections; positions are computed
+     * deterministically by the layout module.
+     *
+     * @param content The topic or note to turn into a canvas
+     * @param max_nodes The maximum number of nodes to generate
+     * @param api_key Optional API key to use for this specific request
+     * @return A canvas document ready to be saved as a `.canvas` file
+     */
+    pub async fn generate_canvas(
+        &self,
+        content: &str,
+        max_nodes: usize,
+        api_key: Option<&str>,
+    ) -> Result<CanvasDoc, Box<dyn Error>> {
+        let prompt = format!(
+            "Break the following content into at most {} key ideas for a visual canvas, with the central idea first. Return the response as a JSON object with a 'nodes' field containing an array of objects, each with 'id' (a short unique string) and 'text' (markdown) fields, and an 'edges' field containing an array of objects, each with 'from' and 'to' fields referencing node ids and an optional short 'label' describing the relationship.\n\nContent: {}\n\nCanvas:",
+            max_nodes, content
+        );
+        
+        // Get the response as a String
+        let response_str = self.generate_endpoint_response("canvas", &prompt, api_key).await?;
+        
+        // Parse the JSON response
+        let mut output: CanvasOutput = serde_json::from_str(&response_str)
+            .map_err(|e| RigError::ParseError { message: format!("Failed to parse canvas response: {}", e) })?;
+        output.nodes.truncate(max_nodes);
+        
+        // Map model ids to positions, dropping edges that reference unknown nodes
+        let index_of = |id: &str| output.nodes.iter().position(|node| node.id == id);
+        let edges: Vec<(usize, usize, Option<String>)> = output
+            .edges
+            .iter()
+            .filter_map(|edge| Some((index_of(&edge.from)?, index_of(&edge.to)?, edge.label.clone())))
+            .collect();
+        let pairs: Vec<(usize, usize)> = edges.iter().map(|(from, to, _)| (*from, *to)).collect();
+        let positions = layout::layered_layout(output.nodes.len(), &pairs);
+        
+        let node_id = |index: usize| format!("node-{}", index + 1);
+        let nodes = output
+            .nodes
+            .iter()
+            .zip(positions)
+            .enumerate()
+            .map(|(index, (node, position))| {
+                CanvasNode::text(
+                    node_id(index),
+                    node.text.clone(),
+                    position.x,
+                    position.y,
+                    layout::NODE_WIDTH,
+                    layout::NODE_HEIGHT,
+                )
+            })
+            .collect();
+        let edges = edges
+            .into_iter()
+            .enumerate()
+            .map(|(index, (from, to, label))| {
+                CanvasEdge::downward(format!("edge-{}", index + 1), node_id(from), node_id(to), label)
+            })
+            .collect();
+        
+        Ok(CanvasDoc { nodes, edges })
+    }
+
+    /**
+     * Enriches every leaf of an outline with a paragraph of elaboration.
+     * Leaves are elaborated independently with bounded concurrency; a failed
+     * leaf gets an `error` instead of failing the whole outline.
+     *
+     * @param outline The outline to elaborate
+     * @param context Optional source material the outline was built from
+     * @param concurrency Maximum number of leaves elaborated at once
+     * @param api_key Optional API key to use for this specific request
+     * @return The same outline with `elaboration` or `error` set on each leaf
+     */
+    pub async fn elaborate_outline(
+        &self,
+        mut outline: Vec<OutlineNode>,
+        context: Option<&str>,
+        concurrency: usize,
+        api_key: Option<&str>,
+    ) -> Vec<OutlineNode> {
+        let leaves = outline::collect_leaves(&outline);
+        
+        let results: Vec<_> = stream::iter(leaves)
+            .map(|leaf| async move {
+                let context_section = context
+                    .map(|context| format!("\n\nSource material: {}", context))
+                    .unwrap_or_default();
+                let prompt = format!(
+                    "You are expanding an outline into a draft document. Write one clear paragraph elaborating on the point \"{}\" (located at: {}). Return only the paragraph, without headings.{}\n\nParagraph:",
+                    leaf.title(),
+                    leaf.breadcrumb.join(" > "),
+                    context_section
+                );
+                let result = self
+                    .generate_endpoint_response("elaborate-outline", &prompt, api_key)
+                    .await
+                    .map_err(|e| e.to_string());
+                (leaf.path, result)
+            })
+            .buffer_unordered(concurrency.max(1))
+            .collect()
+            .await;
+        
+        // Reassemble the results into the outline
+        for (path, result) in results {
+            if let Some(node) = outline::node_at_mut(&mut outline, &path) {
+                match result {
+                    Ok(elaboration) => node.elaboration = Some(elaboration.trim().to_string()),
+                    Err(e) => {
+                        tracing::error!("Error elaborating outline point '{}': {}", node.title, e);
+                        node.error = Some(e);
+                    }
+                }
+            }
+        }
+        
+        outline
+    }
+
+    /**
+     * Suggests tags for a note. Tags are normalized to lowercase without a
+     * leading '#' and deduplicated, keeping the model's order.
+     * 
+     * @param content The note to tag
+     * @param count The number of tags to suggest
+     * @param api_key Optional API key to use for this specific request
+     * @return At most `count` tags
+     */
+    pub async fn extract_tags(
+        &self,
+        content: &str,
+        count: usize,
+        api_key: Option<&str>,
+    ) -> Result<Vec<String>, Box<dyn Error>> {
+        let prompt = tags_prompt(content, count);
+        
+        let output: TagsOutput = self.generate_json("tags", &prompt, "tags", api_key).await?;
+        
+        Ok(normalized_tags(output.tags, count))
+    }
+
+    /**
+     * Picks the most relevant tags for the content from an existing vocabulary.
+     * Tags the model invents outside the vocabulary are dropped; when allowed,
+     * at most one genuinely new tag is suggested separately.
+     *
+     * @param content The note to tag
+     * @param vocabulary The tags already used in the vault
+     * @param max_tags The maximum number of vocabulary tags to return
+     * @param allow_new_tag Whether the model may suggest one tag outside the vocabulary
+     * @param api_key Optional API key to use for this specific request
+     * @return The chosen vocabulary tags and an optional new tag
+     */
+    pub async fn suggest_consistent_tags(
+        &self,
+        content: &str,
+        vocabulary: &[String],
+        max_tags: usize,
+        allow_new_tag: bool,
+        api_key: Option<&str>,
+    ) -> Result<(Vec<String>, Option<String>), Box<dyn Error>> {
+        let new_tag_instruction = if allow_new_tag {
+            "If an important topic is not covered by the vocabulary, you may suggest at most one new tag in a 'new_tag' field; otherwise set 'new_tag' to null."
+        } else {
+            "Do not suggest new tags; set 'new_tag' to null."
+        };
+        let prompt = format!(
+            "Choose up to {} tags for the following content. Only use tags from this existing vocabulary, exactly as written: {}. {} Return the response as a JSON object with a 'tags' field containing an array of strings and a 'new_tag' field.\n\nContent: {}\n\nTags:",
+            max_tags,
+            vocabulary.join(", "),
+            new_tag_instruction,
+            content
+        );
+        
+        // Get the response as a String
+        let response_str = self.generate_endpoint_response("tags/consistent", &prompt, api_key).await?;
+        
+        // Parse the JSON response
+        let output: ConsistentTagsOutput = serde_json::from_str(&response_str)
+            .map_err(|e| RigError::ParseError { message: format!("Failed to parse tags response: {}", e) })?;
+        
+        // Keep only tags from the vocabulary, in the vocabulary's own spelling
+        let mut tags: Vec<String> = Vec::new();
+        for tag in &output.tags {
+            let key = tag_key(tag);
+            if let Some(known) = vocabulary.iter().find(|known| tag_key(known) == key) {
+                if !tags.contains(known) {
+                    tags.push(known.clone());
+                }
+            } else {
+                tracing::debug!("Dropping tag '{}' not present in vocabulary", tag);
+            }
+        }
+        tags.truncate(max_tags);
+        
+        let new_tag = if allow_new_tag {
+            output
+                .new_tag
+                .map(|tag| tag.trim().trim_start_matches('#').to_string())
+                .filter(|tag| !tag.is_empty() && !vocabulary.iter().any(|known| tag_key(known) == tag_key(tag)))
+        } else {
+            None
+        };
+        
+        Ok((tags, new_tag))
+    }
+
+    /**
+     * Summarizes the edits between consecutive versions of a note.
+     * Diffs are computed locally and each step is summarized with bounded
+     * concurrency; unchanged steps are reported without a model call.
+     *
+     * @param versions The note snapshots, oldest first
+     * @param concurrency Maximum number of steps summarized at once
+     * @param api_key Optional API key to use for this specific request
+     * @return One entry per version after the first, in order
+     */
+    pub async fn generate_changelog(
+        &self,
+        versions: &[NoteVersion],
+        concurrency: usize,
+        api_key: Option<&str>,
+    ) -> Result<Vec<ChangelogEntry>, Box<dyn Error>> {
+        let instructions = self.instructions()?;
+        let steps: Vec<(String, String)> = versions
+            .windows(2)
+            .map(|pair| {
+                let (previous, current) = (&pair[0], &pair[1]);
+                let changes = diff::unified_diff(
+                    &previous.version_label,
+                    &previous.content,
+                    &current.version_label,
+                    &current.content,
+                );
+                (current.version_label.clone(), changes)
+            })
+            .collect();
+        
+        let results: Vec<Result<ChangelogEntry, String>> = stream::iter(steps)
+            .map(|(version, changes)| async move {
+                if changes.is_empty() {
+                    return Ok(ChangelogEntry {
+                        version,
+                        summary: "No changes.".to_string(),
+                    });
+                }
+                
+                let prompt = instructions::render(instructions.changelog, &[("diff", &changes)]);
+                self.generate_endpoint_response("changelog", &prompt, api_key)
+                    .await
+                    .map(|summary| ChangelogEntry {
+                        version,
+                        summary: summary.trim().to_string(),
+                    })
+                    .map_err(|e| e.to_string())
+            })
+            .buffered(concurrency.max(1))
+            .collect()
+            .await;
+        
+        results
+            .into_iter()
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| e.into())
+    }
+
+    /**
+     * Paraphrases content at several lengths in a single model call.
+     *
+     * @param content The text to paraphrase
+     * @param lengths The length variants to produce (see LENGTH_VARIANTS)
+     * @param api_key Optional API key to use for this specific request
+     * @return A map from each requested variant to its text
+     */
+    pub async fn paraphrase_lengths(
+        &self,
+        content: &str,
+        lengths: &[String],
+        api_key: Option<&str>,
+    ) -> Result<BTreeMap<String, String>, Box<dyn Error>> {
+        let fields = lengths
+            .iter()
+            .map(|key| {
+                let instruction = length_instruction(key).ok_or_else(|| format!("Unknown length '{}'", key))?;
+                Ok(format!("'{}' ({})", key, instruction))
+            })
+            .collect::<Result<Vec<String>, String>>()?
+            .join(", ");
+        
+        let prompt = format!(
+            "Paraphrase the following content at several lengths. Return the response as a JSON object with a 'variants' field containing an object with these string fields: {}.\n\nContent: {}\n\nVariants:",
+            fields, content
+        );
+        
+        // Get the response as a String
+        let response_str = self.generate_endpoint_response("lengths", &prompt, api_key).await?;
+        
+        // Parse the JSON response
+        let mut output: LengthsOutput = serde_json::from_str(&response_str)
+            .map_err(|e| RigError::ParseError { message: format!("Failed to parse lengths response: {}", e) })?;
+        
+        // Keep exactly the requested variants, failing if any is missing
+        let mut variants = BTreeMap::new();
+        for key in lengths {
+            let text = output
+                .variants
+                .remove(key)
+                .ok_or_else(|| RigError::ParseError { message: format!("Model response is missing the '{}' variant", key) })?;
+            variants.insert(key.clone(), text.trim().to_string());
+        }
+        
+        Ok(variants)
+    }
+
+    /**
+     * Extracts bibliographic references (citations and links) from a note.
+     *
+     * @param content The note to extract references from
+     * @param api_key Optional API key to use for this specific request
+     * @return The references found, in order of appearance
+     */
+    pub async fn extract_references(
+        &self,
+        content: &str,
+        api_key: Option<&str>,
+    ) -> Result<Vec<Reference>, Box<dyn Error>> {
+        let prompt = format!(
+            "Extract every bibliographic reference from the following note: cited works, papers, books and linked sources. Do not invent references that are not in the text. Return the response as JSON matching this schema: {{\"references\": [{{\"title\": string, \"authors\": string | null, \"year\": string | null, \"url\": string | null}}]}}.\n\nContent: {}\n\nReferences:",
+            content
+        );
+        
+        // Get the response as a String
+        let response_str = self.generate_endpoint_response("references", &prompt, api_key).await?;
+        
+        // Parse the JSON response, skipping malformed entries rather than failing
+        let output: ReferencesOutput = serde_json::from_str(&response_str)
+            .map_err(|e| RigError::ParseError { message: format!("Failed to parse references response: {}", e) })?;
+        
+        Ok(output.references.iter().filter_map(Reference::from_value).collect())
+    }
+
+    /**
+     * Formats references as citations in the given style. The output is
+     * validated against a JSON schema requiring exactly one citation per
+     * reference, in the order the references were given.
+     *
+     * @param references The references to cite
+     * @param style The citation style to follow
+     * @param api_key Optional API key to use for this specific request
+     * @return One formatted citation per reference
+     */
+    pub async fn format_citations(
+        &self,
+        references: &[Reference],
+        style: CitationStyle,
+        api_key: Option<&str>,
+    ) -> Result<Vec<String>, Box<dyn Error>> {
+        let schema_value = cite::citation_schema(references.len());
+        
+        let references_json = serde_json::to_string(references)?;
+        let prompt = format!(
+            "Format each of the following {} references as a citation in {} style. Return exactly one citation per reference, in the same order, using only the details given and leaving out missing ones rather than inventing them. Use plain text with no numbering. Return the response as JSON matching this JSON schema: {}\n\nReferences: {}\n\nCitations:",
+            references.len(), style.guide(), schema_value, references_json
+        );
+        
+        let output: CitationsOutput = self.generate_validated("cite", &prompt, &schema_value, "citations", api_key).await?;
+        Ok(output.citations.iter().map(|citation| citation.trim().to_string()).collect())
+    }
+
+    /**
+     * Generates a markdown template capturing the structure shared by several example notes.
+     * Varying parts are replaced by `{{placeholder}}` fields; the result is rejected
+     * if any placeholder is malformed.
+     *
+     * @param examples The example notes to generalize
+     * @param api_key Optional API key to use for this specific request
+     * @return The markdown template
+     */
+    pub async fn template_from_examples(
+        &self,
+        examples: &[String],
+        api_key: Option<&str>,
+    ) -> Result<String, Box<dyn Error>> {
+        let mut combined_examples = String::new();
+        for (i, example) in examples.iter().enumerate() {
+            combined_examples.push_str(&format!("Example {}:\n{}\n\n", i + 1, example));
+        }
+        
+        let prompt = format!(
+            "The following example notes share a common structure. Write a single markdown template capturing that shared skeleton: keep the headings, labels and fixed text they have in common, and replace the parts that vary between notes with placeholders written as {{{{name}}}}, where name uses only letters, digits and underscores (for example {{{{title}}}} or {{{{meeting_date}}}}). Do not include content specific to a single example. Return the response as a JSON object with a 'template' field containing the template as a string.\n\n{}Template:",
+            combined_examples
+        );
+        
+        // Get the response as a String
+        let response_str = self.generate_endpoint_response("template-from-examples", &prompt, api_key).await?;
+        
+        // Parse the JSON response
+        let output: TemplateOutput = serde_json::from_str(&response_str)
+            .map_err(|e| RigError::ParseError { message: format!("Failed to parse template response: {}", e) })?;
+        
+        validate_placeholders(&output.template)
+            .map_err(|e| format!("Generated template has a malformed placeholder: {}", e))?;
+        
+        Ok(output.template)
+    }
+
+    /**
+     * Generates a short hint for each diagram label, to show on occlusion cards
+     * without giving the label away.
+     *
+     * @param labels The diagram labels
+     * @param api_key Optional API key to use for this specific request
+     * @return Hints keyed by label; labels the model skipped are missing
+     */
+    pub async fn occlusion_hints(
+        &self,
+        labels: &[String],
+        api_key: Option<&str>,
+    ) -> Result<HashMap<String, String>, Box<dyn Error>> {
+        let labels_json = serde_json::to_string(labels)?;
+        let prompt = format!(
+            "The following labels name the parts of a diagram. For each label, write a short hint (one sentence) that helps a student recall the label without containing it. Return the response as a JSON object with a 'hints' field containing an object that maps each label, spelled exactly as given, to its hint.\n\nLabels: {}\n\nHints:",
+            labels_json
+        );
+        
+        // Get the response as a String
+        let response_str = self.generate_endpoint_response("occlusion", &prompt, api_key).await?;
+        
+        // Parse the JSON response
+        let output: OcclusionHintsOutput = serde_json::from_str(&response_str)
+            .map_err(|e| RigError::ParseError { message: format!("Failed to parse occlusion hints response: {}", e) })?;
+        
+        Ok(output.hints)
+    }
+
+    /**
+     * Generates a complete study deck (title, flashcards and open questions) in one call.
+     *
+     * @param content The text to study
+     * @param title Optional title for the deck; the model suggests one otherwise
+     * @param question_count Number of open questions to generate
+     * @param api_key Optional API key to use for this specific request
+     * @return The study deck
+     */
+    pub async fn generate_study_deck(
+        &self,
+        content: &str,
+        title: Option<&str>,
+        question_count: usize,
+        api_key: Option<&str>,
+    ) -> Result<StudyDeck, Box<dyn Error>> {
+        let title_instruction = match title {
+            Some(title) => format!("Use \"{}\" as the title.", title),
+            None => "Suggest a short title for the deck.".to_string(),
+        };
+        
+        let prompt = format!(
+            "Create a study deck for the following content: flashcards with a question on the front and the answer on the back, plus {} open-ended questions that encourage deeper thinking and are not already covered by the flashcards. {} Return the response as JSON matching this schema: {{\"title\": string, \"cards\": [{{\"front\": string, \"back\": string}}], \"open_questions\": [string]}}.\n\nContent: {}\n\nStudy deck:",
+            question_count, title_instruction, content
+        );
+        
+        // Get the response as a String
+        let response_str = self.generate_endpoint_response("study-deck", &prompt, api_key).await?;
+        
+        // Parse the JSON response
+        let mut deck: StudyDeck = serde_json::from_str(&response_str)
+            .map_err(|e| RigError::ParseError { message: format!("Failed to parse study deck response: {}", e) })?;
+        
+        // The caller's title wins over whatever the model echoed back
+        if let Some(title) = title {
+            deck.title = title.to_string();
+        }
+        deck.open_questions.truncate(question_count);
+        
+        Ok(deck)
+    }
+
+    /**
+     * Translates both sides of every flashcard (and any example sentence) into
+     * another language. Cards are sent in chunks of TRANSLATE_CHUNK_SIZE with at
+     * most `concurrency` chunks in flight; card order is preserved.
+     *
+     * @param flashcards The deck to translate
+     * @param target_language The language to translate into
+     * @param concurrency Maximum number of chunks translated at once
+     * @param api_key Optional API key to use for this specific request
+     * @return The translated deck, in the original order
+     */
+    pub async fn translate_flashcards(
+        &self,
+        flashcards: &[Flashcard],
+        target_language: &str,
+        concurrency: usize,
+        api_key: Option<&str>,
+    ) -> Result<Vec<Flashcard>, Box<dyn Error>> {
+        // Chunks are owned so the stream's closure isn't tied to the slice's lifetime
+        let chunks: Vec<Vec<Flashcard>> = flashcards.chunks(TRANSLATE_CHUNK_SIZE).map(<[Flashcard]>::to_vec).collect();
+        let results: Vec<Result<Vec<Flashcard>, Box<dyn Error + Send + Sync>>> = stream::iter(chunks)
+            .map(|chunk| async move {
+                let cards_json = serde_json::to_string(&chunk)?;
+                let prompt = format!(
+                    "Translate the following flashcards into {}. Translate the 'front', 'back' and, where present, 'example' fields; keep the meaning, formatting and card order unchanged and return exactly {} cards. Return the response as JSON matching this schema: {{\"flashcards\": [{{\"front\": string, \"back\": string, \"example\": string | null}}]}}.\n\nFlashcards: {}\n\nTranslated flashcards:",
+                    target_language,
+                    chunk.len(),
+                    cards_json
+                );
+                
+                // Get the response as a String
+                let response_str = self
+                    .generate_endpoint_response("flashcards/translate", &prompt, api_key)
+                    .await
+                    .map_err(sendable)?;
+                
+                // Parse the JSON response
+                let output: TranslatedFlashcardsOutput = serde_json::from_str(&response_str).map_err(|e| RigError::ParseError {
+                    message: format!("Failed to parse translated flashcards response: {}", e),
+                })?;
+                if output.flashcards.len() != chunk.len() {
+                    return Err(RigError::ParseError {
+                        message: format!("Expected {} translated flashcards, got {}", chunk.len(), output.flashcards.len()),
+                    }
+                    .into());
+                }
+                
+                // Only cards that had an example keep one
+                Ok(chunk
+                    .iter()
+                    .zip(output.flashcards)
+                    .map(|(original, translated)| Flashcard {
+                        front: translated.front,
+                        back: translated.back,
+                        example: match (&original.example, translated.example) {
+                            (Some(_), Some(example)) => Some(example),
+                            (Some(example), None) => Some(example.clone()),
+                            (None, _) => None,
+                        },
+                    })
+                    .collect())
+            })
+            .buffered(concurrency.max(1))
+            .collect()
+            .await;
+        
+        let mut translated = Vec::with_capacity(flashcards.len());
+        for result in results {
+            translated.extend(result.map_err(|e| e as Box<dyn Error>)?);
+        }
+        Ok(translated)
+    }
+
+    /**
+     * Analyzes the sentiment and emotional tone of a note.
+     *
+     * @param content The note to analyze
+     * @param api_key Optional API key to use for this specific request
+     * @return The sentiment, tones and a score clamped to -1.0..=1.0
+     */
+    pub async fn analyze_tone(
+        &self,
+        content: &str,
+        api_key: Option<&str>,
+    ) -> Result<ToneAnalysis, Box<dyn Error>> {
+        let prompt = format!(
+            "Analyze the emotional content of the following note. Give the overall sentiment as one of \"positive\", \"negative\", \"neutral\" or \"mixed\", up to five single-word tones (for example \"hopeful\", \"anxious\", \"reflective\"), and a sentiment score from -1.0 (very negative) to 1.0 (very positive). Return the response as JSON matching this schema: {{\"sentiment\": string, \"tone\": [string], \"score\": number}}.\n\nContent: {}\n\nAnalysis:",
+            content
+        );
+        
+        // Get the response as a String
+        let response_str = self.generate_endpoint_response("tone", &prompt, api_key).await?;
+        
+        // Parse the JSON response
+        let mut analysis: ToneAnalysis = serde_json::from_str(&response_str)
+            .map_err(|e| RigError::ParseError { message: format!("Failed to parse tone response: {}", e) })?;
+        
+        analysis.sentiment = analysis.sentiment.trim().to_lowercase();
+        analysis.tone = analysis
+            .tone
+            .iter()
+            .map(|tone| tone.trim().to_lowercase())
+            .filter(|tone| !tone.is_empty())
+            .collect();
+        analysis.score = if analysis.score.is_finite() {
+            analysis.score.clamp(-1.0, 1.0)
+        } else {
+            0.0
+        };
+        
+        Ok(analysis)
+    }
+
+    /**
+     * Suggests plugin commands that would automate the workflow described in a note.
+     *
+     * @param content The note describing the workflow
+     * @param max_commands Maximum number of commands to suggest
+     * @param api_key Optional API key to use for this specific request
+     * @return The suggested commands, without blank or duplicate names
+     */
+    pub async fn suggest_commands(
+        &self,
+        content: &str,
+        max_commands: usize,
+        api_key: Option<&str>,
+    ) -> Result<Vec<CommandSuggestion>, Box<dyn Error>> {
+        let prompt = format!(
+            "The following note describes a workflow in Obsidian. Suggest up to {} commands that a plugin could add to the command palette to automate its repetitive steps. Each command needs a short name in title case (for example \"Create Weekly Review\") and a one-sentence description of what it does. Return the response as JSON matching this schema: {{\"commands\": [{{\"name\": string, \"description\": string}}]}}.\n\nContent: {}\n\nCommands:",
+            max_commands, content
+        );
+        
+        // Get the response as a String
+        let response_str = self.generate_endpoint_response("commands", &prompt, api_key).await?;
+        
+        // Parse the JSON response
+        let output: CommandsOutput = serde_json::from_str(&response_str)
+            .map_err(|e| RigError::ParseError { message: format!("Failed to parse commands response: {}", e) })?;
+        
+        // Command names must be unique to be registered
+        let mut commands: Vec<CommandSuggestion> = Vec::new();
+        for command in output.commands {
+            let name = command.name.trim();
+            if name.is_empty() || commands.iter().any(|existing| existing.name.eq_ignore_ascii_case(name)) {
+                continue;
+            }
+            commands.push(CommandSuggestion {
+                name: name.to_string(),
+                description: command.description.trim().to_string(),
+            });
+        }
+        commands.truncate(max_commands);
+        
+        Ok(commands)
+    }
+
+    /**
+     * Lists the pros and cons of a decision or argument in a note.
+     *
+     * @param content The note describing the decision or argument
+     * @param api_key Optional API key to use for this specific request
+     * @return The pros and cons
+     */
+    pub async fn analyze_pros_cons(
+        &self,
+        content: &str,
+        api_key: Option<&str>,
+    ) -> Result<ProsCons, Box<dyn Error>> {
+        let prompt = format!(
+            "Identify the decision or argument in the following note and list the strongest points in favor of it (pros) and against it (cons). Keep each point to one sentence and don't repeat points. Return the response as JSON matching this schema: {{\"pros\": [string], \"cons\": [string]}}.\n\nContent: {}\n\nPros and cons:",
+            content
+        );
+        
+        // Get the response as a String
+        let response_str = self.generate_endpoint_response("pros-cons", &prompt, api_key).await?;
+        
+        // Models sometimes wrap the JSON in code fences or prose; parse the outermost object
+        let json = match (response_str.find('{'), response_str.rfind('}')) {
+            (Some(start), Some(end)) if start < end => &response_str[start..=end],
+            _ => response_str.as_str(),
+        };
+        let value: serde_json::Value = serde_json::from_str(json)
+            .map_err(|e| RigError::ParseError { message: format!("Failed to parse pros/cons response: {}", e) })?;
+        
+        ProsCons::from_value(&value).ok_or_else(|| {
+            RigError::ParseError { message: "Pros/cons response is not a JSON object".to_string() }.into()
+        })
+    }
+
+    /**
+     * Tells whether embedding-dependent features can run: embeddings are
+     * enabled and an embedding model is configured.
+     */
+    pub fn embeddings_available(&self) -> bool {
+        self.get_config()
+            .map(|config| config.embeddings_enabled && !config.embedding_model.trim().is_empty())
+            .unwrap_or(false)
+    }
+
+    /**
+     * Computes embedding vectors for texts with the configured embedding model.
+     * A request for a single text waits up to `embedding_batch_window_ms` so it
+     * can share a provider call with other single-text requests; larger
+     * requests are already batched and are sent right away.
+     *
+     * @param texts The texts to embed
+     * @param api_key Optional API key to use for this specific request
+     * @return One vector per text, in order
+     */
+    pub async fn embed(
+        &self,
+        texts: Vec<String>,
+        api_key: Option<&str>,
+    ) -> Result<Vec<Vec<f64>>, Box<dyn Error>> {
+        if !self.embeddings_available() {
+            return Err(Box::new(RigError::EmbeddingsUnsupported {
+                message: "embeddings are disabled or unsupported by the configured provider".to_string(),
+            }));
+        }
+        
+        let config = self.get_config()?;
+        for text in &texts {
+            self.check_blocked_patterns(&config, text)?;
+        }
+        
+        let target = EmbeddingTarget {
+            model: config.resolve_model(&config.embedding_model),
+            api_key: resolve_api_key(&config, api_key)?,
+            base_url: config.base_url.clone(),
+        };
+        
+        // Batching only helps callers sending one text at a time
+        if texts.len() == 1 && config.embedding_batch_window_ms > 0 {
+            let window = Duration::from_millis(config.embedding_batch_window_ms);
+            let text = texts.into_iter().next().unwrap_or_default();
+            let vector = self
+                .embeddings
+                .embed(&target, text, window)
+                .await
+                .map_err(Self::embedding_error)?;
+            return Ok(vec![vector]);
+        }
+        
+        let mut vectors = Vec::with_capacity(texts.len());
+        for chunk in texts.chunks(embeddings::MAX_EMBEDDING_BATCH) {
+            let chunk_vectors = embeddings::embed_texts(&target, chunk.to_vec())
+                .await
+                .map_err(Self::embedding_error)?;
+            vectors.extend(chunk_vectors);
+        }
+        Ok(vectors)
+    }
+
+    /**
+     * Turns a provider error saying embeddings aren't offered into
+     * `RigError::EmbeddingsUnsupported`. Only the failing request is
+     * affected; set `embeddings_enabled` to false to turn the features off.
+     * Other errors pass through.
+     */
+    fn embedding_error(error: String) -> Box<dyn Error> {
+        if embeddings::is_unsupported_error(&error) {
+            tracing::warn!("Provider can't produce embeddings: {}", error);
+            return Box::new(RigError::EmbeddingsUnsupported { message: error });
+        }
+        error.into()
+    }
+
+    /**
+     * Compares two notes by meaning. Both notes are split into passages
+     * (paragraphs, chunked when longer than `chunk_size_tokens`), embedded in
+     * one call, and matched by cosine similarity.
+     *
+     * @param a The first note
+     * @param b The second note
+     * @param threshold The similarity at or above which passages match
+     * @param api_key Optional API key to use for this specific request
+     * @return The passages only in a, only in b, and in both
+     */
+    pub async fn semantic_diff(
+        &self,
+        a: &str,
+        b: &str,
+        threshold: f64,
+        api_key: Option<&str>,
+    ) -> Result<SemanticDiff, Box<dyn Error>> {
+        let config = self.get_config()?;
+        let passages_a = semantic_diff::passages(a, config.chunk_size_tokens, config.chunk_overlap_tokens);
+        let passages_b = semantic_diff::passages(b, config.chunk_size_tokens, config.chunk_overlap_tokens);
+        
+        let texts: Vec<String> = passages_a.iter().chain(&passages_b).cloned().collect();
+        let mut vectors = self.embed(texts, api_key).await?;
+        let vectors_b = vectors.split_off(passages_a.len());
+        
+        let a: Vec<(String, Vec<f64>)> = passages_a.into_iter().zip(vectors).collect();
+        let b: Vec<(String, Vec<f64>)> = passages_b.into_iter().zip(vectors_b).collect();
+        Ok(semantic_diff::compare(&a, &b, threshold))
+    }
+
+    /**
+     * Sorts the tasks in a note into Kanban columns (e.g. To Do / Doing / Done).
+     * Tasks are extracted in Rust and sent by index, so the board always contains
+     * exactly the note's tasks, each in one column.
+     *
+     * @param content The note containing the tasks
+     * @param api_key Optional API key to use for this specific request
+     * @return The board's columns
+     */
+    pub async fn generate_kanban(
+        &self,
+        content: &str,
+        api_key: Option<&str>,
+    ) -> Result<Vec<KanbanColumn>, Box<dyn Error>> {
+        let tasks = kanban::extract_tasks(content);
+        if tasks.is_empty() {
+            return Err("No tasks found in the note".into());
+        }
+        
+        let task_list = tasks
+            .iter()
+            .enumerate()
+            .map(|(i, task)| format!("{}: {}", i, task))
+            .collect::<Vec<_>>()
+            .join("\n");
+        let prompt = format!(
+            "Sort the following numbered tasks into Kanban columns, usually \"To Do\", \"Doing\" and \"Done\"; checked items are done. Every task number must appear in exactly one column. Return the response as JSON matching this schema: {{\"columns\": [{{\"title\": string, \"tasks\": [integer]}}]}}.\n\nNote: {}\n\nTasks:\n{}\n\nColumns:",
+            content, task_list
+        );
+        
+        // Get the response as a String
+        let response_str = self.generate_endpoint_response("kanban", &prompt, api_key).await?;
+        
+        // Parse the JSON response
+        let output: KanbanOutput = serde_json::from_str(&response_str)
+            .map_err(|e| RigError::ParseError { message: format!("Failed to parse kanban response: {}", e) })?;
+        
+        kanban::assemble_columns(&tasks, output.columns).map_err(|message| RigError::ParseError { message }.into())
+    }
+
+    /**
+     * Generates a concept map of a note: its key concepts and the typed
+     * relations between them. Relations that don't connect declared concepts
+     * are dropped.
+     *
+     * @param content The note to map
+     * @param api_key Optional API key to use for this specific request
+     * @return The validated concept map
+     */
+    pub async fn generate_concept_map(
+        &self,
+        content: &str,
+        api_key: Option<&str>,
+    ) -> Result<ConceptMap, Box<dyn Error>> {
+        let prompt = format!(
+            "Build a concept map of the following note. List its key concepts as short noun phrases, then the directed relations between them, where each relation's 'type' is a short verb phrase such as \"causes\", \"is part of\" or \"depends on\". Relations may only use concepts from the list, spelled exactly the same. Return the response as JSON matching this schema: {{\"concepts\": [string], \"relations\": [{{\"from\": string, \"to\": string, \"type\": string}}]}}.\n\nContent: {}\n\nConcept map:",
+            content
+        );
+        
+        // Get the response as a String
+        let response_str = self.generate_endpoint_response("concept-map", &prompt, api_key).await?;
+        
+        // Parse the JSON response
+        let output: ConceptMap = serde_json::from_str(&response_str)
+            .map_err(|e| RigError::ParseError { message: format!("Failed to parse concept map response: {}", e) })?;
+        
+        let (map, dropped) = output.validated();
+        if dropped > 0 {
+            tracing::warn!("Dropped {} invalid concept map relations", dropped);
+        }
+        Ok(map)
+    }
+
+    /**
+     * Generates interview or exam practice questions, each with a rubric of
+     * criteria a good answer should meet. The output is validated against
+     * a fixed JSON schema before it is returned.
+     *
+     * @param content The topic or note to practice
+     * @param count The maximum number of questions to generate
+     * @param api_key Optional API key to use for this specific request
+     * @return The questions with their rubrics
+     */
+    pub async fn generate_interview(
+        &self,
+        content: &str,
+        count: usize,
+        api_key: Option<&str>,
+    ) -> Result<Vec<InterviewQuestion>, Box<dyn Error>> {
+        let schema_value = interview::interview_schema(count);
+        
+        let prompt = format!(
+            "Write {} interview or exam practice questions about the following topic or note. For each question, give a rubric: 2 to 5 short, concrete criteria a strong answer must meet, usable for grading. Return the response as JSON matching this JSON schema: {}\n\nContent: {}\n\nQuestions:",
+            count, schema_value, content
+        );
+        
+        let output: InterviewOutput = self
+            .generate_validated("interview", &prompt, &schema_value, "interview", api_key)
+            .await?;
+        Ok(interview::cleaned(output.questions))
+    }
+
+    /**
+     * Generates multiple-choice quiz questions about the given content. The
+     * output is validated against a JSON schema and each question must have
+     * at least `quiz::MIN_CHOICES` choices and a `correct_index` pointing at
+     * one of them; if it doesn't, the model is asked once more with the
+     * problems listed.
+     *
+     * @param content The text to quiz on
+     * @param count The maximum number of questions to generate
+     * @param api_key Optional API key to use for this specific request
+     * @return The questions with their choices and correct answers
+     */
+    pub async fn generate_quiz(
+        &self,
+        content: &str,
+        count: usize,
+        api_key: Option<&str>,
+    ) -> Result<Vec<QuizQuestion>, Box<dyn Error>> {
+        let schema_value = quiz::quiz_schema(count);
+        let schema = extract::compile_schema(&schema_value)?;
+        
+        let prompt = format!(
+            "Write {} multiple-choice questions testing understanding of the following content. Give each question at least {} plausible choices with exactly one correct answer, and set correct_index to the position of the correct choice, counting from 0. Use only facts from the content. Return the response as JSON matching this JSON schema: {}\n\nContent: {}\n\nQuestions:",
+            count, quiz::MIN_CHOICES, schema_value, content
+        );
+        
+        let response_str = self.generate_endpoint_response("quiz", &prompt, api_key).await?;
+        let problems = match quiz_questions(&schema, &response_str) {
+            Ok(questions) => return Ok(questions),
+            Err(problems) => problems,
+        };
+        
+        tracing::warn!("Quiz response was malformed ({}), retrying once", problems.join("; "));
+        let retry_prompt = format!(
+            "{}\n\nYour previous answer was malformed: {}. Return only valid JSON matching the schema.",
+            prompt,
+            problems.join("; ")
+        );
+        let response_str = self.generate_endpoint_response("quiz", &retry_prompt, api_key).await?;
+        quiz_questions(&schema, &response_str).map_err(|errors| RigError::SchemaValidation { errors }.into())
+    }
+
+    /**
+     * Suggests how to reorganize a set of notes: which to merge, split or
+     * rename. The output is validated against a JSON schema, and suggestions
+     * targeting notes that weren't sent are dropped.
+     *
+     * @param notes The notes to consider, with their ids and titles
+     * @param api_key Optional API key to use for this specific request
+     * @return The valid suggestions
+     */
+    pub async fn suggest_refactoring(
+        &self,
+        notes: &[RefactorNote],
+        api_key: Option<&str>,
+    ) -> Result<Vec<RefactorSuggestion>, Box<dyn Error>> {
+        let schema_value = refactor::refactor_schema();
+        
+        let notes_text = notes
+            .iter()
+            .map(|note| format!("Note id: {}\nTitle: {}\n\n{}", note.id, note.title, note.content))
+            .collect::<Vec<String>>()
+            .join("\n\n---\n\n");
+        let prompt = format!(
+            "Review the following notes from one section of a vault and suggest how to reorganize them. Suggest \"merge\" for notes that cover the same ground (two or more targets), \"split\" for a note that covers several distinct topics (one target), and \"rename\" for a note whose title doesn't match its content (one target). Refer to notes only by their exact ids, explain each suggestion in one sentence, and suggest nothing if the notes are already well organized. Return the response as JSON matching this JSON schema: {}\n\nNotes:\n\n{}\n\nSuggestions:",
+            schema_value, notes_text
+        );
+        
+        let output: RefactorOutput = self.generate_validated("refactor", &prompt, &schema_value, "refactor", api_key).await?;
+        
+        let note_ids: Vec<String> = notes.iter().map(|note| note.id.clone()).collect();
+        let (suggestions, dropped) = refactor::validated(output.suggestions, &note_ids);
+        if dropped > 0 {
+            tracing::warn!("Dropped {} invalid refactor suggestions", dropped);
+        }
+        Ok(suggestions)
+    }
+
+    /**
+     * Writes alt text for images with the configured vision model. Each image
+     * is checked and described separately; images that fail to load or
+     * can't be described carry an `error` instead of failing the request.
+     * Vision calls go straight to the provider, so fixtures don't apply, but
+     * they share the concurrency cap, timeout and metrics of other calls.
+     *
+     * @param images The images to describe
+     * @param context Optional text of the note the images appear in
+     * @param api_key Optional API key to use for this specific request
+     * @return One result per image, in order
+     */
+    pub async fn generate_alt_text(
+        &self,
+        images: &[ImageInput],
+        context: Option<&str>,
+        api_key: Option<&str>,
+    ) -> Result<Vec<AltText>, Box<dyn Error>> {
+        let config = self.get_config()?;
+        if let Some(context) = context {
+            self.check_blocked_patterns(&config, context)?;
+        }
+        let resolved_key = resolve_api_key(&config, api_key)?;
+        let base_url = config.base_url.as_deref().unwrap_or(DEFAULT_OPENAI_BASE_URL);
+        let model = config.resolve_model(&config.vision_model);
+        let timeout = Duration::from_secs(config.request_timeout_secs);
+        
+        let context_section = context
+            .map(|context| format!(" The image appears in this note, which may help identify what it shows:\n\n{}", context))
+            .unwrap_or_default();
+        let prompt = format!(
+            "Write alt text for this image for a reader who can't see it. Describe what it shows and any text or data it contains, in one to three plain sentences. Return only the alt text.{}",
+            context_section
+        );
+        
+        // Images are owned so the stream's closure isn't tied to the slice's lifetime
+        let results: Vec<AltText> = stream::iter(images.to_vec())
+            .map(|image| {
+                let (config, prompt, model, resolved_key) = (&config, &prompt, &model, &resolved_key);
+                async move {
+                    let result = match vision::resolve_image(&image) {
+                        Ok(image_url) => {
+                            let _slot = self.requests.acquire().await;
+                            let started = Instant::now();
+                            let described = tokio::time::timeout(
+                                timeout,
+                                vision::describe_image(&self.http, base_url, resolved_key, model, prompt, &image_url),
+                            )
+                            .await;
+                            telemetry::record_provider_call(model, started.elapsed());
+                            match described {
+                                Ok(Ok((alt, usage))) => {
+                                    if let Some(usage) = usage {
+                                        telemetry::record_tokens(model, &pricing::usage_report(usage, config.model_price(model)));
+                                    }
+                                    Ok(alt)
+                                }
+                                Ok(Err(e)) => Err(e),
+                                Err(_) => Err(format!("Vision request timed out after {} seconds", timeout.as_secs())),
+                            }
+                        }
+                        Err(e) => Err(e),
+                    };
+                    match result {
+                        Ok(alt) => AltText { reference: image.reference, alt: Some(alt), error: None },
+                        Err(e) => {
+                            tracing::warn!("Skipping alt text for '{}': {}", image.reference, e);
+                            AltText { reference: image.reference, alt: None, error: Some(e) }
+                        }
+                    }
+                }
+            })
+            .buffered(ALT_TEXT_CONCURRENCY)
+            .collect()
+            .await;
+        
+        Ok(results)
+    }
+
+    /**
+     * Pulls the dated events out of narrative text as a timeline. The output
+     * is validated against a JSON schema; dates are then normalized to
+     * ISO-8601 where possible and the events sorted chronologically.
+     *
+     * @param content The note to extract events from
+     * @param api_key Optional API key to use for this specific request
+     * @return The events, earliest first
+     */
+    pub async fn extract_timeline(
+        &self,
+        content: &str,
+        api_key: Option<&str>,
+    ) -> Result<Vec<TimelineEvent>, Box<dyn Error>> {
+        let schema_value = timeline::timeline_schema();
+        
+        let prompt = format!(
+            "List the dated events described in the following note. Give each event's date as precisely as the note allows, in ISO-8601 (YYYY-MM-DD, YYYY-MM or YYYY) when possible, a short title and a one-sentence description. Leave out events the note gives no date or period for. Return the response as JSON matching this JSON schema: {}\n\nContent: {}\n\nEvents:",
+            schema_value, content
+        );
+        
+        let output: TimelineOutput = self.generate_validated("timeline", &prompt, &schema_value, "timeline", api_key).await?;
+        Ok(timeline::sorted(output.events))
+    }
+
+    /**
+     * Today's date in the configured timezone (UTC when unset or unknown).
+     */
+    pub fn today(&self) -> NaiveDate {
+        let timezone = self.get_config().ok().and_then(|config| config.timezone);
+        current_date(timezone.as_deref())
+    }
+
+    /**
+     * Finds the dates and times mentioned in a note, resolving relative ones
+     * ("next Friday") against a reference date. The output is validated
+     * against a JSON schema; absolute dates and simple relative expressions
+     * are then re-resolved locally rather than trusting the model.
+     *
+     * @param content The note to extract dates from
+     * @param reference_date The date relative expressions are resolved against; today in the configured timezone if None
+     * @param api_key Optional API key to use for this specific request
+     * @return The dates in the note's order
+     */
+    pub async fn extract_dates(
+        &self,
+        content: &str,
+        reference_date: Option<NaiveDate>,
+        api_key: Option<&str>,
+    ) -> Result<Vec<MentionedDate>, Box<dyn Error>> {
+        let reference = reference_date.unwrap_or_else(|| self.today());
+        let schema_value = dates::dates_schema();
+        
+        let prompt = format!(
+            "List the dates and times mentioned in the following note, in the order they appear. For each, give the text as written, the date in ISO-8601 (YYYY-MM-DD, or YYYY-MM-DDTHH:MM when a time is given), and whether it is absolute or relative. Resolve relative expressions against the reference date {} ({}). Return the response as JSON matching this JSON schema: {}\n\nContent: {}\n\nDates:",
+            reference.format("%Y-%m-%d"),
+            reference.format("%A"),
+            schema_value,
+            content
+        );
+        
+        let output: DatesOutput = self.generate_validated("dates", &prompt, &schema_value, "dates", api_key).await?;
+        Ok(dates::resolved(output.dates, reference))
+    }
+
+    /**
+     * Writes everything needed to index a note in one call: a one-sentence
+     * pitch, a detailed abstract and keywords. The output is validated
+     * against a JSON schema and keywords are clamped to `max_keywords`.
+     *
+     * @param content The note to summarize
+     * @param max_keywords The most keywords to return
+     * @param api_key Optional API key to use for this specific request
+     * @return The pitch, abstract and keywords
+     */
+    pub async fn generate_abstract(
+        &self,
+        content: &str,
+        max_keywords: usize,
+        api_key: Option<&str>,
+    ) -> Result<NoteAbstract, Box<dyn Error>> {
+        let schema_value = note_abstract::abstract_schema(max_keywords);
+        
+        let prompt = format!(
+            "Summarize the following note for an index. Write an elevator pitch in one sentence, a detailed abstract of one or two paragraphs covering the note's main points and conclusions, and up to {} keywords naming its key topics. Return the response as JSON matching this JSON schema: {}\n\nContent: {}\n\nIndex entry:",
+            max_keywords, schema_value, content
+        );
+        
+        let output: NoteAbstract = self.generate_validated("abstract", &prompt, &schema_value, "abstract", api_key).await?;
+        Ok(note_abstract::cleaned(output, max_keywords))
+    }
+
+    /**
+     * Rewrites content at several reading levels in one call, from grade 3
+     * to college. The output is validated against a JSON schema and must
+     * contain a rewrite for every level.
+     *
+     * @param content The content to rewrite
+     * @param api_key Optional API key to use for this specific request
+     * @return The rewrites, easiest first
+     */
+    pub async fn simplify_levels(
+        &self,
+        content: &str,
+        api_key: Option<&str>,
+    ) -> Result<Vec<ReadingLevel>, Box<dyn Error>> {
+        let schema_value = reading_levels::reading_ladder_schema();
+        
+        let prompt = format!(
+            "Rewrite the following content for each of these reading levels: {}. Each rewrite must keep the same facts and meaning, adjusting vocabulary, sentence length and the amount of explanation to suit readers at that level. Return the response as JSON matching this JSON schema: {}\n\nContent: {}\n\nReading levels:",
+            reading_levels::READING_LEVELS.join(", "), schema_value, content
+        );
+        
+        let output: ReadingLadderOutput = self
+            .generate_validated("simplify-levels", &prompt, &schema_value, "reading levels", api_key)
+            .await?;
+        let ladder = reading_levels::ordered(output.levels)
+            .map_err(|errors| RigError::SchemaValidation { errors })?;
+        Ok(ladder)
+    }
+
+    /**
+     * Finds what a note leaves unclear or unexplained: questions it raises
+     * without answering and concepts it relies on without explaining. The
+     * output is validated against a JSON schema.
+     *
+     * @param content The note to check
+     * @param api_key Optional API key to use for this specific request
+     * @return The gaps, each phrased as a question or concept to look into
+     */
+    pub async fn find_gaps(
+        &self,
+        content: &str,
+        api_key: Option<&str>,
+    ) -> Result<Vec<String>, Box<dyn Error>> {
+        let schema_value = gaps::gaps_schema();
+        
+        let prompt = format!(
+            "Read the following note as a learner and list what it leaves unclear or unexplained: questions it raises but doesn't answer, steps or claims it doesn't justify, and concepts it relies on without explaining. Phrase each gap as a short question or concept to look into. Don't quiz the reader on what the note does explain. Return the response as JSON matching this JSON schema: {}\n\nContent: {}\n\nGaps:",
+            schema_value, content
+        );
+        
+        let output: GapsOutput = self.generate_validated("gaps", &prompt, &schema_value, "gaps", api_key).await?;
+        Ok(gaps::cleaned(output.gaps))
+    }
+
+    /**
+     * Explains one domain through another by mapping concepts in the source
+     * domain to their counterparts in the target domain. The output is
+     * validated against a JSON schema.
+     *
+     * @param source_domain The domain to understand (e.g., "the immune system")
+     * @param target_domain The familiar domain to explain it through (e.g., "a medieval castle")
+     * @param api_key Optional API key to use for this specific request
+     * @return The concept mappings
+     */
+    pub async fn generate_analogy(
+        &self,
+        source_domain: &str,
+        target_domain: &str,
+        api_key: Option<&str>,
+    ) -> Result<Vec<AnalogyMapping>, Box<dyn Error>> {
+        let schema_value = analogy::analogy_schema();
+        
+        let prompt = format!(
+            "Explain the source domain through an analogy with the target domain. Map the key concepts of the source domain to their closest counterparts in the target domain, and for each pair explain in one or two sentences why they correspond and where the analogy breaks down, if it does. Cover the source domain's most important concepts, in an order that builds understanding. Return the response as JSON matching this JSON schema: {}\n\nSource domain: {}\n\nTarget domain: {}\n\nMappings:",
+            schema_value, source_domain, target_domain
+        );
+        
+        let output: AnalogyOutput = self.generate_validated("analogy", &prompt, &schema_value, "analogy", api_key).await?;
+        Ok(analogy::cleaned(output.mappings))
+    }
+
+    /**
+     * Writes a sequence of hints for a question, each revealing more than
+     * the last and the final one stopping just short of the answer, for
+     * learners who can't recall it yet. The output is validated against a
+     * JSON schema, and hints that state the answer are dropped; if fewer
+     * than `hints::MIN_HINTS` remain, the model is asked once more.
+     *
+     * @param question The question being practiced
+     * @param answer The answer the hints lead to
+     * @param api_key Optional API key to use for this specific request
+     * @return The hints, least revealing first
+     */
+    pub async fn generate_hints(
+        &self,
+        question: &str,
+        answer: &str,
+        api_key: Option<&str>,
+    ) -> Result<Vec<String>, Box<dyn Error>> {
+        let schema_value = hints::hints_schema();
+        let prompt = hints_prompt(question, answer);
+        
+        let output: HintsOutput = self.generate_validated("hints", &prompt, &schema_value, "hints", api_key).await?;
+        let cleaned = hints::cleaned(output.hints, answer);
+        if cleaned.len() >= hints::MIN_HINTS {
+            return Ok(cleaned);
+        }
+        
+        tracing::warn!("Only {} hints left after dropping those revealing the answer, retrying once", cleaned.len());
+        let retry_prompt = format!(
+            "{}\n\nSome of your previous hints stated the answer outright. Write hints that never contain the answer itself.",
+            prompt
+        );
+        let output: HintsOutput = self.generate_validated("hints", &retry_prompt, &schema_value, "hints", api_key).await?;
+        let cleaned = hints::cleaned(output.hints, answer);
+        if cleaned.len() < hints::MIN_HINTS {
+            return Err(RigError::SchemaValidation {
+                errors: vec![format!(
+                    "Only {} hints remain after dropping those that reveal the answer; at least {} are required",
+                    cleaned.len(),
+                    hints::MIN_HINTS
+                )],
+            }
+            .into());
+        }
+        Ok(cleaned)
+    }
+
+    /**
+     * Finds the jargon a note uses without defining and proposes a definition
+     * for each term. The output is validated against a JSON schema; terms the
+     * reader already knows are excluded.
+     *
+     * @param content The note to check
+     * @param known_terms Terms the reader knows, which are never reported
+     * @param api_key Optional API key to use for this specific request
+     * @return The undefined terms with suggested definitions
+     */
+    pub async fn detect_jargon(
         &self,
         content: &str,
-        title: Option<&str>,
+        known_terms: &[String],
         api_key: Option<&str>,
-    ) -> Result<(String, Vec<Flashcard>), Box<dyn Error>> {
-        let title_prompt = title.unwrap_or("this content");
+    ) -> Result<Vec<UndefinedTerm>, Box<dyn Error>> {
+        let schema_value = jargon::jargon_schema();
         
+        let known = if known_terms.is_empty() {
+            String::new()
+        } else {
+            format!(" Do not include these terms, which the reader already knows: {}.", known_terms.join(", "))
+        };
         let prompt = format!(
-            "Create flashcards for studying {}. Each flashcard should have a question on the front and the answer on the back. Return the response as a JSON object with a 'filename' field containing a suggested filename (without extension) and a 'flashcards' field containing an array of objects, each with 'front' and 'back' fields.\n\nContent: {}\n\nFlashcards:",
-            title_prompt, content
+            "List the technical terms, acronyms and jargon the following note uses without defining or explaining them. Skip terms the note defines itself and everyday words. For each term, suggest a one-sentence definition that fits how the note uses it.{} Return the response as JSON matching this JSON schema: {}\n\nContent: {}\n\nUndefined terms:",
+            known, schema_value, content
         );
         
-        // Get the response as a String
-        let response_str = self.generate_response(&prompt, None, api_key).await?;
+        let output: JargonOutput = self.generate_validated("jargon", &prompt, &schema_value, "jargon", api_key).await?;
+        Ok(jargon::excluding_known(output.undefined, known_terms))
+    }
+
+    /**
+     * Runs the same prompt against several models in parallel so their
+     * answers can be compared side by side. Each model succeeds or fails on
+     * its own; results keep the order the models were given in.
+     *
+     * @param content The prompt to send to every model
+     * @param models The models to compare, optionally as "provider/model"
+     * @param api_key Optional API key to use for this specific request
+     * @return One result per model, with its latency
+     */
+    pub async fn compare_models(
+        &self,
+        content: &str,
+        models: &[String],
+        api_key: Option<&str>,
+    ) -> Vec<ModelComparison> {
+        // Specs are owned so the stream's closure isn't tied to the slice's lifetime
+        stream::iter(models.to_vec())
+            .map(|spec| async move {
+                let started = Instant::now();
+                let result = match compare::parse_model_spec(&spec) {
+                    Ok(model) => {
+                        let options = PromptOptions { model: Some(model), ..Default::default() };
+                        self.generate_response_with_options(content, None, api_key, &options)
+                            .await
+                            .map_err(|e| e.to_string())
+                    }
+                    Err(e) => Err(e),
+                };
+                let latency_ms = started.elapsed().as_millis() as u64;
+                
+                match result {
+                    Ok(response) => ModelComparison { model: spec, response: Some(response), error: None, latency_ms },
+                    Err(e) => {
+                        tracing::error!("Error comparing model '{}': {}", spec, e);
+                        ModelComparison { model: spec, response: None, error: Some(e), latency_ms }
+                    }
+                }
+            })
+            .buffered(compare::COMPARE_CONCURRENCY)
+            .collect()
+            .await
+    }
+
+    /**
+     * Drafts a coherent note from a list of loose keywords, optionally in a
+     * given style (e.g., "lecture notes", "blog post").
+     *
+     * @param keywords The keywords to connect; must not be empty
+     * @param style Optional style for the draft
+     * @param api_key Optional API key to use for this specific request
+     * @return The drafted note
+     */
+    pub async fn generate_from_keywords(
+        &self,
+        keywords: Vec<String>,
+        style: Option<String>,
+        api_key: Option<&str>,
+    ) -> Result<String, Box<dyn Error>> {
+        let keywords: Vec<String> = keywords
+            .iter()
+            .map(|keyword| keyword.trim())
+            .filter(|keyword| !keyword.is_empty())
+            .map(|keyword| format!("- {}", keyword))
+            .collect();
+        if keywords.is_empty() {
+            return Err("At least one keyword is required".into());
+        }
         
-        // Parse the JSON response
-        let output: FlashcardsOutput = serde_json::from_str(&response_str)
-            .map_err(|e| format!("Failed to parse flashcards response: {}", e))?;
+        let style_instruction = style
+            .as_deref()
+            .map(str::trim)
+            .filter(|style| !style.is_empty())
+            .map(|style| format!(" Write it in the style of: {}.", style))
+            .unwrap_or_default();
+        let prompt = format!(
+            "Write a coherent note that connects the following keywords. Use every keyword, work out how they relate to one another, and organize the note with markdown headings or lists where they help. Don't invent specific facts, figures or quotes the keywords don't suggest.{}\n\nKeywords:\n{}\n\nNote:",
+            style_instruction,
+            keywords.join("\n")
+        );
+        
+        let draft = self.generate_endpoint_response("draft", &prompt, api_key).await?;
+        Ok(draft.trim().to_string())
+    }
+
+    /**
+     * Extracts structured data matching a user-supplied JSON schema.
+     * The output is validated against the schema; on failure the model is
+     * re-prompted once with the validation errors.
+     *
+     * @param content The text to extract data from
+     * @param json_schema The JSON schema the data must match
+     * @param api_key Optional API key to use for this specific request
+     * @return The extracted data
+     */
+    pub async fn extract(
+        &self,
+        content: &str,
+        json_schema: &serde_json::Value,
+        api_key: Option<&str>,
+    ) -> Result<serde_json::Value, Box<dyn Error>> {
+        let schema = extract::compile_schema(json_schema)
+            .map_err(|message| RigError::InvalidSchema { message })?;
+        
+        let prompt = format!(
+            "Extract data from the following content. Return only JSON matching this JSON schema, with no commentary: {}\n\nContent: {}\n\nJSON:",
+            json_schema, content
+        );
+        
+        let response_str = self.generate_endpoint_response("extract", &prompt, api_key).await?;
+        let errors = match extract::parse_json(&response_str) {
+            Ok(value) => {
+                let errors = extract::validate(&schema, &value);
+                if errors.is_empty() {
+                    return Ok(value);
+                }
+                errors
+            }
+            Err(e) => vec![e],
+        };
+        
+        // Re-prompt once, telling the model what was wrong with its first attempt
+        tracing::warn!("Extraction failed validation, retrying: {}", errors.join("; "));
+        let retry_prompt = format!(
+            "{}\n\nYour previous answer was:\n{}\n\nIt did not match the schema:\n- {}\n\nReturn corrected JSON only.\n\nJSON:",
+            prompt,
+            response_str,
+            errors.join("\n- ")
+        );
+        let response_str = self.generate_endpoint_response("extract", &retry_prompt, api_key).await?;
+        let value = extract::parse_json(&response_str)
+            .map_err(|e| RigError::SchemaValidation { errors: vec![e] })?;
+        let errors = extract::validate(&schema, &value);
+        if errors.is_empty() {
+            Ok(value)
+        } else {
+            Err(RigError::SchemaValidation { errors }.into())
+        }
+    }
+}
+
+/**
+ * Checks a configuration before it is applied, rejecting settings that
+ * would make every later request fail.
+ *
+ * @return The compiled blocked patterns, to cache alongside the configuration
+ */
+fn validate_config(config: &ModelConfig) -> Result<RegexSet, Box<dyn Error>> {
+    // Reject invalid patterns up front rather than failing every later request
+    let blocked = compile_blocked_patterns(config)?;
+    
+    if !(0.0..=1.0).contains(&config.question_dedupe_threshold) {
+        return Err("question_dedupe_threshold must be between 0 and 1".into());
+    }
+    
+    // Out-of-range defaults would make every request fail at the provider
+    config
+        .sampling
+        .validate()
+        .map_err(|e| format!("Invalid sampling: {}", e))?;
+    
+    if config.request_timeout_secs == 0 {
+        return Err("request_timeout_secs must be greater than 0".into());
+    }
+    
+    if config.max_response_chars == Some(0) {
+        return Err("max_response_chars must be greater than 0".into());
+    }
+    
+    if config.max_multinode_chars == Some(0) {
+        return Err("max_multinode_chars must be greater than 0".into());
+    }
+    
+    if config.max_concurrent_requests == 0 {
+        return Err("max_concurrent_requests must be greater than 0".into());
+    }
+    
+    if config.max_input_tokens == Some(0) {
+        return Err("max_input_tokens must be greater than 0".into());
+    }
+    
+    if let Some((model, _)) = config.context_limits.iter().find(|(_, limit)| **limit == 0) {
+        return Err(format!("context_limits for '{}' must be greater than 0", model).into());
+    }
+    
+    if let Some(ratio) = config.instruction_budget_ratio {
+        budget::validate_instruction_budget_ratio(ratio)?;
+    }
+    
+    chunk::validate_chunking(config.chunk_size_tokens, config.chunk_overlap_tokens)?;
+    config.validate_auto_model_rules()?;
+    
+    if let Some(language) = &config.instruction_language {
+        if instructions::lookup(language).is_none() {
+            return Err(format!(
+                "Unsupported instruction_language '{}'; expected one of: {}",
+                language,
+                instructions::supported_languages().join(", ")
+            )
+            .into());
+        }
+    }
+    
+    Ok(blocked)
+}
+
+/**
+ * Compiles a configuration's blocked patterns into one set.
+ */
+fn compile_blocked_patterns(config: &ModelConfig) -> Result<RegexSet, Box<dyn Error>> {
+    RegexSet::new(&config.blocked_patterns).map_err(|e| format!("Invalid blocked pattern: {}", e).into())
+}
+
+/**
+ * Keys a configuration's compiled blocked patterns in the service's cache.
+ */
+fn blocked_patterns_key(config: &ModelConfig) -> String {
+    let patterns: Vec<&str> = config.blocked_patterns.iter().map(String::as_str).collect();
+    cache::cache_key(&patterns)
+}
+
+/**
+ * Makes an error Send so it can cross a buffered stream of futures.
+ * A RigError keeps its variant, so handlers can still tell it apart;
+ * other errors keep their message.
+ */
+fn sendable(error: Box<dyn Error>) -> Box<dyn Error + Send + Sync> {
+    match error.downcast_ref::<RigError>() {
+        Some(error) => Box::new(error.clone()),
+        None => error.to_string().into(),
+    }
+}
+
+
+/**
+ * Parses a quiz response, checking it against the schema and then each
+ * question's choices and `correct_index`.
+ *
+ * @return The questions, or one message per problem
+ */
+fn quiz_questions(schema: &JSONSchema, response: &str) -> Result<Vec<QuizQuestion>, Vec<String>> {
+    let value = extract::parse_validated(schema, response)?;
+    let output: QuizOutput = serde_json::from_value(value).map_err(|e| vec![e.to_string()])?;
+    quiz::checked(output.questions)
+}
+
+/**
+ * Builds the prompt used by `generate_questions`.
+ */
+fn questions_prompt(instructions: &InstructionSet, content: &str, count: usize) -> String {
+    instructions::render(
+        instructions.questions,
+        &[("count", &count.to_string()), ("content", content)],
+    )
+}
+
+/**
+ * Builds the prompt used by `generate_hints`.
+ */
+fn hints_prompt(question: &str, answer: &str) -> String {
+    format!(
+        "Write {} to {} hints for the following question that help a learner recall the answer on their own. Start with a gentle nudge and make each hint more revealing than the last, with the final hint stopping just short of the answer. Never state the answer itself. Return the response as JSON matching this JSON schema: {}\n\nQuestion: {}\n\nAnswer: {}\n\nHints:",
+        hints::MIN_HINTS, hints::MAX_HINTS, hints::hints_schema(), question, answer
+    )
+}
+
+/**
+ * Builds the prompt used by `summarize`.
+ */
+fn summarize_prompt(content: &str, max_words: Option<usize>) -> String {
+    let length = match max_words {
+        Some(max_words) => format!(" Keep the summary under {} words.", max_words),
+        None => String::new(),
+    };
+    format!(
+        "Summarize the following content concisely, keeping its key points and conclusions.{} Return only the summary.\n\nContent: {}\n\nSummary:",
+        length, content
+    )
+}
+
+/**
+ * Builds the prompt used by `extract_tags`.
+ */
+fn tags_prompt(content: &str, count: usize) -> String {
+    format!(
+        "Suggest {} tags for organizing the following note in a knowledge base. Each tag should name a key topic in one or two words. Return the response as a JSON object with a 'tags' field containing an array of strings.\n\nContent: {}\n\nTags:",
+        count, content
+    )
+}
+
+/**
+ * Builds the prompt used by `generate_flashcards`.
+ */
+fn flashcards_prompt(instructions: &InstructionSet, content: &str, title: Option<&str>, with_examples: bool) -> String {
+    let title_prompt = title.unwrap_or(instructions.default_title);
+    
+    // Request example sentences in the same call to avoid extra round-trips
+    let card_fields = if with_examples {
+        instructions.card_fields_with_examples
+    } else {
+        instructions.card_fields
+    };
+    
+    instructions::render(
+        instructions.flashcards,
+        &[("title", title_prompt), ("fields", card_fields), ("content", content)],
+    )
+}
+
+/**
+ * Builds the prompt used by `generate_flashcards` for cloze cards.
+ */
+fn cloze_flashcards_prompt(instructions: &InstructionSet, content: &str, title: Option<&str>) -> String {
+    let title_prompt = title.unwrap_or(instructions.default_title);
+    instructions::render(instructions.flashcards_cloze, &[("title", title_prompt), ("content", content)])
+}
+
+/**
+ * Keeps the cloze cards that hide something, filling in a missing back
+ * with the full sentence.
+ *
+ * @param cards The cards returned by the model
+ * @return The usable cards, in the model's order
+ */
+fn cloze_cards(cards: Vec<Flashcard>) -> Vec<Flashcard> {
+    let count = cards.len();
+    let cards: Vec<Flashcard> = cards
+        .into_iter()
+        .filter(|card| cloze::has_deletion(&card.front))
+        .map(|card| Flashcard {
+            back: if card.back.trim().is_empty() { cloze::revealed(&card.front) } else { card.back },
+            ..card
+        })
+        .collect();
+    if cards.len() < count {
+        tracing::warn!("Dropped {} cloze cards without a deletion", count - cards.len());
+    }
+    cards
+}
+
+/**
+ * Prepares a prompt the way every provider call sends it: the content run
+ * through the configured transforms (e.g., stripping frontmatter) in order,
+ * and the system prompt with the date/time context when `include_datetime`
+ * is on.
+ *
+ * @param config The effective configuration for the request
+ * @param content The text to send to the AI model
+ * @param system_prompt The request's system prompt, if any
+ * @return The content and system prompt to send
+ */
+fn prepare_prompt(config: &ModelConfig, content: &str, system_prompt: Option<&str>) -> (String, Option<String>) {
+    let content = transform::run(&transform::pipeline(config), content.to_string());
+    (content, request_system_prompt(config, system_prompt))
+}
+
+/**
+ * The system prompt sent with a request, with the current date/time
+ * prepended when `include_datetime` is on.
+ *
+ * @param config The effective configuration for the request
+ * @param system_prompt The request's system prompt, if any
+ * @return The system prompt to send
+ */
+fn request_system_prompt(config: &ModelConfig, system_prompt: Option<&str>) -> Option<String> {
+    if !config.include_datetime {
+        return system_prompt.map(|s| s.to_string());
+    }
+    
+    let datetime = current_datetime_context(config.timezone.as_deref());
+    Some(match system_prompt {
+        Some(system_prompt) => format!("{}\n\n{}", datetime, system_prompt),
+        None => datetime,
+    })
+}
+
+/**
+ * Counts the input tokens of a prompt with the tokenizer of the configured model.
+ *
+ * @param config The effective configuration for the request
+ * @param system_prompt The system prompt as sent
+ * @param history The applied history, oldest first
+ * @param content The content as sent
+ * @return The number of input tokens
+ */
+fn prompt_tokens(config: &ModelConfig, system_prompt: Option<&str>, history: &[ChatTurn], content: &str) -> usize {
+    let model = config.resolve_model(&config.model_name);
+    tokens::count_prompt_tokens(&model, system_prompt, &history::transcript(history, content))
+}
+
+/**
+ * Formats the current date and time as a line of system context.
+ * Uses the given IANA timezone when valid, falling back to UTC.
+ *
+ * @param timezone Optional IANA timezone name (e.g., "Europe/Berlin")
+ * @return A sentence describing the current date and time
+ */
+fn current_datetime_context(timezone: Option<&str>) -> String {
+    let now = Utc::now();
+    let formatted = match timezone.map(|tz| tz.parse::<Tz>()) {
+        Some(Ok(tz)) => now.with_timezone(&tz).format("%A, %Y-%m-%d %H:%M %Z").to_string(),
+        Some(Err(_)) => {
+            tracing::warn!("Unknown timezone '{}', using UTC", timezone.unwrap_or_default());
+            now.format("%A, %Y-%m-%d %H:%M UTC").to_string()
+        }
+        None => now.format("%A, %Y-%m-%d %H:%M UTC").to_string(),
+    };
+    format!("The current date and time is {}.", formatted)
+}
+
+/**
+ * Today's date in the given IANA timezone, falling back to UTC.
+ */
+fn current_date(timezone: Option<&str>) -> NaiveDate {
+    let now = Utc::now();
+    match timezone.and_then(|tz| tz.parse::<Tz>().ok()) {
+        Some(tz) => now.with_timezone(&tz).date_naive(),
+        None => now.date_naive(),
+    }
+}
+
+/**
+ * Normalizes a tag for comparison: trimmed, without a leading '#', lowercase.
+ */
+fn tag_key(tag: &str) -> String {
+    tag.trim().trim_start_matches('#').to_lowercase()
+}
+
+/**
+ * Normalizes suggested tags with `tag_key`, dropping empty tags and
+ * duplicates and keeping at most `count`.
+ */
+fn normalized_tags(tags: Vec<String>, count: usize) -> Vec<String> {
+    let mut normalized: Vec<String> = Vec::new();
+    for tag in tags {
+        let tag = tag_key(&tag);
+        if !tag.is_empty() && !normalized.contains(&tag) {
+            normalized.push(tag);
+        }
+    }
+    normalized.truncate(count);
+    normalized
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        blocked_patterns_key, cloze_cards, flashcards_prompt, hints_prompt, normalized_tags, questions_prompt, sendable,
+        tags_prompt, Flashcard, FlashcardStyle, PromptOptions, ProsCons, RigService,
+    };
+    use crate::error::RigError;
+    use crate::fixtures::{FixtureMode, FixtureStore};
+    use crate::history::{ChatRole, ChatTurn};
+    use crate::instructions;
+    use crate::models::{AutoModelRule, ModelConfig};
+    use crate::transform::ContentTransformKind;
+    use std::collections::HashMap;
+
+    /// A fresh fixture directory for one test, handed to the service explicitly.
+    fn fixtures(name: &str) -> FixtureStore {
+        let dir = std::env::temp_dir().join(format!("rig-service-fixtures-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        FixtureStore::new(dir, FixtureMode::Replay)
+    }
+
+    #[tokio::test]
+    async fn parses_recorded_questions() {
+        let fixtures = fixtures("parses-recorded-questions");
+        let content = "Ownership moves values between bindings.";
+        fixtures
+            .save(&questions_prompt(instructions::for_language(None), content, 2), r#"{"questions": ["What is a move?", "Who owns a value?"]}"#)
+            .unwrap();
+        
+        let service = RigService::new().unwrap().with_fixtures(fixtures);
+        let questions = service.generate_questions(content, 2, Some("test-key")).await.unwrap();
+        assert_eq!(questions, vec!["What is a move?", "Who owns a value?"]);
+    }
+
+    #[tokio::test]
+    async fn parses_recorded_tags() {
+        let fixtures = fixtures("parses-recorded-tags");
+        let content = "Traits define shared behavior across types.";
+        fixtures
+            .save(&tags_prompt(content, 3), r##"{"tags": ["#Rust", "traits", "rust", " Generics ", "types"]}"##)
+            .unwrap();
+        
+        let service = RigService::new().unwrap().with_fixtures(fixtures);
+        let tags = service.extract_tags(content, 3, Some("test-key")).await.unwrap();
+        assert_eq!(tags, vec!["rust", "traits", "generics"]);
+    }
+
+    #[tokio::test]
+    async fn limits_free_text_responses() {
+        let fixtures = fixtures("limits-free-text-responses");
+        fixtures.save("Describe the fox.", "The quick brown fox jumps over the lazy dog").unwrap();
+        
+        let service = RigService::new().unwrap().with_fixtures(fixtures);
+        service
+            .update_config(ModelConfig { max_response_chars: Some(20), ..ModelConfig::default() }, false)
+            .unwrap();
+        let (response, _) = service
+            .generate_response_with_usage("Describe the fox.", None, Some("test-key"), &PromptOptions::default())
+            .await
+            .unwrap();
+        assert_eq!(response.text, "The quick brown fox…");
+        assert!(response.truncated);
+        
+        let options = PromptOptions { structured: true, ..PromptOptions::default() };
+        let response = service
+            .generate_response_with_options("Describe the fox.", None, Some("test-key"), &options)
+            .await
+            .unwrap();
+        assert_eq!(response, "The quick brown fox jumps over the lazy dog");
+    }
+
+    #[tokio::test]
+    async fn preprocesses_streamed_prompts() {
+        use futures::StreamExt;
+        
+        let fixtures = fixtures("preprocesses-streamed-prompts");
+        fixtures.save("Describe the fox.", "A quick brown fox").unwrap();
+        
+        let service = RigService::new().unwrap().with_fixtures(fixtures);
+        service
+            .update_config(ModelConfig { strip_frontmatter: true, ..ModelConfig::default() }, false)
+            .unwrap();
+        let chunks: Vec<String> = service
+            .stream_response("---\ntitle: Fox\n---\nDescribe the fox.", None, Some("test-key"))
+            .await
+            .unwrap()
+            .map(|chunk| chunk.unwrap())
+            .collect()
+            .await;
+        assert_eq!(chunks.concat(), "A quick brown fox");
+    }
+
+    #[tokio::test]
+    async fn refuses_streamed_prompts_over_the_input_limit() {
+        let fixtures = fixtures("refuses-streamed-prompts-over-the-input-limit");
+        fixtures.save("Describe the fox in great detail, please.", "A quick brown fox").unwrap();
+        
+        let service = RigService::new().unwrap().with_fixtures(fixtures);
+        service
+            .update_config(ModelConfig { max_input_tokens: Some(2), ..ModelConfig::default() }, false)
+            .unwrap();
+        let error = match service.stream_response("Describe the fox in great detail, please.", None, Some("test-key")).await {
+            Ok(_) => panic!("the prompt is over the limit"),
+            Err(error) => error,
+        };
+        assert!(matches!(error.downcast_ref::<RigError>(), Some(RigError::InputTooLarge { limit: 2, .. })));
+    }
+
+    #[test]
+    fn normalizes_tags() {
+        let tags = vec!["##Ownership".to_string(), "#".to_string(), "ownership".to_string(), "Borrowing".to_string()];
+        assert_eq!(normalized_tags(tags, 5), vec!["ownership", "borrowing"]);
+    }
+
+    #[tokio::test]
+    async fn parses_recorded_flashcards() {
+        let fixtures = fixtures("parses-recorded-flashcards");
+        let content = "Borrowing lends access without moving.";
+        fixtures
+            .save(
+                &flashcards_prompt(instructions::for_language(None), content, Some("Borrowing"), false),
+                r#"{"filename": "borrowing", "flashcards": [{"front": "What is borrowing?", "back": "Access without a move"}]}"#,
+            )
+            .unwrap();
+        
+        let service = RigService::new().unwrap().with_fixtures(fixtures);
+        let (filename, cards) = service
+            .generate_flashcards(content, Some("Borrowing"), false, FlashcardStyle::Basic, Some("test-key"))
+            .await
+            .unwrap();
+        assert_eq!(filename, "borrowing");
+        assert_eq!(cards.len(), 1);
+        assert_eq!(cards[0].front, "What is borrowing?");
+        assert_eq!(cards[0].example, None);
+    }
+
+    #[tokio::test]
+    async fn parses_fenced_questions_and_retries_unparseable_ones() {
+        let fixtures = fixtures("parses-fenced-questions-and-retries-unparseable-ones");
+        let content = "Slices borrow a contiguous range.";
+        fixtures
+            .save(
+                &questions_prompt(instructions::for_language(None), content, 1),
+                "Sure! Here they are:\n```json\n{\"questions\": [\"What is a slice?\"]}\n```",
+            )
+            .unwrap();
+        
+        let service = RigService::new().unwrap().with_fixtures(fixtures.clone());
+        let questions = service.generate_questions(content, 1, Some("test-key")).await.unwrap();
+        assert_eq!(questions, vec!["What is a slice?"]);
+        
+        let content = "Vectors grow on the heap.";
+        let prompt = questions_prompt(instructions::for_language(None), content, 1);
+        fixtures.save(&prompt, "I'd be happy to help with questions!").unwrap();
+        fixtures
+            .save(
+                &format!("{}\n\nYour previous answer could not be parsed (Response does not contain JSON). Return only valid JSON, with no code fences or commentary.", prompt),
+                r#"{"questions": ["Where do vectors store elements?"]}"#,
+            )
+            .unwrap();
+        let questions = service.generate_questions(content, 1, Some("test-key")).await.unwrap();
+        assert_eq!(questions, vec!["Where do vectors store elements?"]);
+    }
+
+    #[tokio::test]
+    async fn retries_hints_that_give_the_answer_away() {
+        let fixtures = fixtures("retries-hints-that-give-the-answer-away");
+        let (question, answer) = ("What powers the cell?", "Mitochondria");
+        let prompt = hints_prompt(question, answer);
+        fixtures
+            .save(&prompt, r#"{"hints": ["It's an organelle", "Mitochondria", "The mitochondria"]}"#)
+            .unwrap();
+        let retry_prompt = format!(
+            "{}\n\nSome of your previous hints stated the answer outright. Write hints that never contain the answer itself.",
+            prompt
+        );
+        fixtures
+            .save(&retry_prompt, r#"{"hints": ["It's an organelle", "It has its own DNA", "Called the powerhouse"]}"#)
+            .unwrap();
+        
+        let service = RigService::new().unwrap().with_fixtures(fixtures.clone());
+        let hints = service.generate_hints(question, answer, Some("test-key")).await.unwrap();
+        assert_eq!(hints, vec!["It's an organelle", "It has its own DNA", "Called the powerhouse"]);
+        
+        // Still too few after the retry
+        let question = "Where is most ATP made?";
+        let prompt = hints_prompt(question, answer);
+        let revealing = r#"{"hints": ["Mitochondria", "mitochondria", "It's an organelle"]}"#;
+        fixtures.save(&prompt, revealing).unwrap();
+        fixtures
+            .save(
+                &format!("{}\n\nSome of your previous hints stated the answer outright. Write hints that never contain the answer itself.", prompt),
+                revealing,
+            )
+            .unwrap();
+        let error = service.generate_hints(question, answer, Some("test-key")).await.unwrap_err();
+        assert!(matches!(error.downcast_ref::<RigError>(), Some(RigError::SchemaValidation { .. })));
+    }
+
+    #[tokio::test]
+    async fn parses_double_encoded_flashcards() {
+        let fixtures = fixtures("parses-double-encoded-flashcards");
+        let content = "Lifetimes bound how long references are valid.";
+        let flashcards = r#"{"filename": "lifetimes", "flashcards": [{"front": "What is a lifetime?", "back": "How long a reference is valid"}]}"#;
+        fixtures
+            .save(
+                &flashcards_prompt(instructions::for_language(None), content, Some("Lifetimes"), false),
+                &serde_json::to_string(flashcards).unwrap(),
+            )
+            .unwrap();
+        
+        let service = RigService::new().unwrap().with_fixtures(fixtures);
+        assert!(service
+            .generate_flashcards(content, Some("Lifetimes"), false, FlashcardStyle::Basic, Some("test-key"))
+            .await
+            .is_err());
+        
+        service
+            .update_config(ModelConfig {
+                unwrap_double_encoded_json: true,
+                ..ModelConfig::default()
+            }, false)
+            .unwrap();
+        let (filename, cards) = service
+            .generate_flashcards(content, Some("Lifetimes"), false, FlashcardStyle::Basic, Some("test-key"))
+            .await
+            .unwrap();
+        assert_eq!(filename, "lifetimes");
+        assert_eq!(cards[0].front, "What is a lifetime?");
+    }
+
+    #[test]
+    fn keeps_cloze_cards_with_deletions() {
+        let card = |front: &str, back: &str| Flashcard {
+            front: front.to_string(),
+            back: back.to_string(),
+            example: None,
+        };
+        let cards = cloze_cards(vec![
+            card("The {{c1::borrow checker}} enforces ownership.", ""),
+            card("What does the borrow checker do?", "Enforces ownership."),
+            card("{{c1::Rust}} has no garbage collector.", "Rust has no garbage collector."),
+        ]);
+        assert_eq!(cards.len(), 2);
+        assert_eq!(cards[0].back, "The borrow checker enforces ownership.");
+        assert_eq!(cards[1].back, "Rust has no garbage collector.");
+    }
+
+    #[test]
+    fn picks_feature_models() {
+        let config = ModelConfig {
+            flashcards_model: Some("gpt-4o-mini".to_string()),
+            summary_model: Some(" ".to_string()),
+            ..ModelConfig::default()
+        };
+        assert_eq!(config.feature_model("flashcards"), Some("gpt-4o-mini"));
+        // Blank overrides and features without one fall back to model_name
+        assert_eq!(config.feature_model("summarize"), None);
+        assert_eq!(config.feature_model("questions"), None);
+        assert_eq!(config.feature_model("tags"), None);
+    }
+
+    #[test]
+    fn stores_named_profiles() {
+        let service = RigService::new().unwrap();
+        let cheap = ModelConfig {
+            model_name: "gpt-4o-mini".to_string(),
+            ..ModelConfig::default()
+        };
+        service.upsert_profile("cheap", cheap).unwrap();
+        assert_eq!(service.profile("cheap").unwrap().unwrap().model_name, "gpt-4o-mini");
+        assert!(service.profile("strong").unwrap().is_none());
+        
+        // Profiles are checked like the default configuration
+        assert!(service.upsert_profile(" ", ModelConfig::default()).is_err());
+        let broken = ModelConfig {
+            request_timeout_secs: 0,
+            ..ModelConfig::default()
+        };
+        assert!(service.upsert_profile("broken", broken).is_err());
+        assert_eq!(service.list_profiles().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn blocks_content_matching_a_pattern() {
+        let service = RigService::new().unwrap();
+        let config = ModelConfig {
+            blocked_patterns: vec![r"\bpassword\b".to_string(), r"(?i)secret".to_string()],
+            ..ModelConfig::default()
+        };
+        service.update_config(config.clone(), false).unwrap();
+        assert!(service.blocked.get(&blocked_patterns_key(&config)).is_some());
         
-        Ok((output.filename, output.flashcards))
+        assert!(service.check_blocked_patterns(&config, "nothing to see").is_ok());
+        let error = service.check_blocked_patterns(&config, "my SECRET plan").unwrap_err();
+        assert_eq!(
+            error.downcast_ref::<RigError>(),
+            Some(&RigError::Blocked { pattern: "(?i)secret".to_string() })
+        );
+    }
+
+    #[tokio::test]
+    async fn blocks_patterns_in_conversation_history() {
+        let service = RigService::new().unwrap();
+        let config = ModelConfig {
+            blocked_patterns: vec![r"(?i)secret".to_string()],
+            ..ModelConfig::default()
+        };
+        service.update_config(config, false).unwrap();
+        
+        let options = PromptOptions {
+            history: vec![ChatTurn { role: ChatRole::User, content: "my secret plan".to_string() }],
+            ..PromptOptions::default()
+        };
+        let error = service
+            .generate_response_with_usage("what was my plan?", None, Some("test-key"), &options)
+            .await
+            .unwrap_err();
+        assert_eq!(
+            error.downcast_ref::<RigError>(),
+            Some(&RigError::Blocked { pattern: "(?i)secret".to_string() })
+        );
+    }
+
+    #[test]
+    fn sendable_errors_keep_their_rig_error() {
+        let error = sendable(Box::new(RigError::Timeout { secs: 5 }));
+        assert_eq!(error.downcast_ref::<RigError>(), Some(&RigError::Timeout { secs: 5 }));
+        assert_eq!(sendable("boom".into()).to_string(), "boom");
+    }
+
+    #[test]
+    fn response_keys_depend_on_the_endpoint() {
+        let service = RigService::new().unwrap();
+        let options = PromptOptions::default();
+        let openai = service.response_key("Hello", None, &options).unwrap();
+        
+        let config = ModelConfig {
+            base_url: Some("http://localhost:11434/v1".to_string()),
+            ..ModelConfig::default()
+        };
+        service.update_config(config, false).unwrap();
+        let local = service.response_key("Hello", None, &options).unwrap();
+        assert!(openai.is_some() && local.is_some());
+        assert_ne!(openai, local);
+    }
+
+    #[test]
+    fn response_keys_depend_on_the_content_transforms() {
+        let service = RigService::new().unwrap();
+        let options = PromptOptions::default();
+        let key = |transforms: Vec<ContentTransformKind>| {
+            let config = ModelConfig { content_transforms: transforms, ..ModelConfig::default() };
+            service.update_config(config, false).unwrap();
+            service.response_key("Hello", None, &options).unwrap()
+        };
+        
+        let plain = key(vec![]);
+        let delimited = key(vec![ContentTransformKind::Delimit]);
+        let reordered = key(vec![ContentTransformKind::Delimit, ContentTransformKind::StripFrontmatter]);
+        let stripped_first = key(vec![ContentTransformKind::StripFrontmatter, ContentTransformKind::Delimit]);
+        assert_ne!(plain, delimited);
+        assert_ne!(delimited, reordered);
+        assert_ne!(reordered, stripped_first);
+    }
+
+    #[test]
+    fn validates_auto_model_rule_models() {
+        let service = RigService::new().unwrap();
+        let rules = |models: &[&str]| {
+            models
+                .iter()
+                .enumerate()
+                .map(|(index, model)| AutoModelRule { max_chars: (index + 1) * 100, model: model.to_string() })
+                .collect()
+        };
+        let config = |models: &[&str]| ModelConfig {
+            auto_model_rules: rules(models),
+            model_aliases: HashMap::from([("fast".to_string(), "gpt-4o-mini".to_string())]),
+            ..ModelConfig::default()
+        };
+        
+        assert!(service.update_config(config(&["gpt-4o-mini", "fast", "o3-mini"]), false).is_ok());
+        assert!(service.update_config(config(&["gtp-4o"]), false).is_err());
+        assert!(service.update_config(config(&["llama3"]), false).is_err());
+        assert!(service.update_config(config(&["gpt 4o"]), false).is_err());
+        
+        // Models listed in context_limits are known too
+        let listed = ModelConfig {
+            context_limits: HashMap::from([("llama3".to_string(), 8_192)]),
+            ..config(&["llama3"])
+        };
+        assert!(service.update_config(listed, false).is_ok());
+        
+        // Local servers may serve models under any name
+        let local = ModelConfig {
+            base_url: Some("http://localhost:11434/v1".to_string()),
+            ..config(&["llama3"])
+        };
+        assert!(service.update_config(local, false).is_ok());
+    }
+
+    #[test]
+    fn rejects_invalid_blocked_patterns() {
+        let service = RigService::new().unwrap();
+        let config = ModelConfig {
+            blocked_patterns: vec!["(unclosed".to_string()],
+            ..ModelConfig::default()
+        };
+        assert!(service.update_config(config, false).is_err());
+    }
+
+    #[test]
+    fn parses_loosely_shaped_pros_cons() {
+        let value = serde_json::json!({
+            "Pros": ["Cheaper", {"point": "Faster"}, "  "],
+            "cons": "Less reliable",
+        });
+        assert_eq!(
+            ProsCons::from_value(&value),
+            Some(ProsCons {
+                pros: vec!["Cheaper".to_string(), "Faster".to_string()],
+                cons: vec!["Less reliable".to_string()],
+            })
+        );
+        assert_eq!(ProsCons::from_value(&serde_json::json!(["not", "an", "object"])), None);
     }
 }
@@ -0,0 +1,145 @@
+/**
+ * Sanitize module cleans model output before it reaches the plugin.
+ * Stray control characters in a response can break JSON serialization or
+ * corrupt the note they are pasted into, and suggested filenames need to be
+ * valid in the vault. Reasoning models may also leak their chain of thought
+ * into responses that are meant to be parsed.
+ */
+
+use crate::models::FilenameStyle;
+use regex::Regex;
+use std::sync::OnceLock;
+
+/// Characters Obsidian doesn't allow in file names (or which break links).
+const INVALID_FILENAME_CHARS: &[char] = &['\\', '/', ':', '*', '?', '"', '<', '>', '|', '#', '^', '[', ']'];
+
+/**
+ * Removes disallowed control characters from a model response while keeping
+ * the whitespace that matters for markdown. Newlines and tabs are preserved,
+ * CRLF line endings become LF, and byte-order marks are dropped.
+ *
+ * @param response The raw response text
+ * @return The sanitized response
+ */
+pub fn sanitize_response(response: &str) -> String {
+    response
+        .replace("\r\n", "\n")
+        .chars()
+        .filter(|&c| c == '\n' || c == '\t' || !(c.is_control() || c == '\u{feff}'))
+        .collect()
+}
+
+/**
+ * Removes reasoning a model leaked into its answer: `<think>`, `<thinking>`
+ * and `<reasoning>` blocks, plus any prose around the outermost JSON object
+ * when the answer contains one.
+ *
+ * @param response The response text
+ * @return The final answer only
+ */
+pub fn strip_reasoning(response: &str) -> String {
+    static REASONING_BLOCK: OnceLock<Regex> = OnceLock::new();
+    let block = REASONING_BLOCK.get_or_init(|| {
+        Regex::new(r"(?is)<(think|thinking|reasoning)>.*?</(think|thinking|reasoning)>").unwrap()
+    });
+    let stripped = block.replace_all(response, "");
+    
+    // Keep just the JSON object when the model surrounded it with commentary
+    let answer = match (stripped.find('{'), stripped.rfind('}')) {
+        (Some(start), Some(end)) if start < end => &stripped[start..=end],
+        _ => stripped.as_ref(),
+    };
+    answer.trim().to_string()
+}
+
+/**
+ * Cleans a suggested filename and applies the configured casing style.
+ * Invalid characters are always removed; falls back to "untitled" when
+ * nothing usable remains.
+ *
+ * @param name The suggested filename (without extension)
+ * @param style The casing convention to apply
+ * @return A filename safe to create in the vault
+ */
+pub fn sanitize_filename(name: &str, style: FilenameStyle) -> String {
+    let cleaned: String = name
+        .chars()
+        .map(|c| if INVALID_FILENAME_CHARS.contains(&c) || c.is_control() { ' ' } else { c })
+        .collect();
+    let words: Vec<&str> = cleaned
+        .split(|c: char| c.is_whitespace() || (style != FilenameStyle::Raw && (c == '-' || c == '_')))
+        .filter(|word| !word.is_empty())
+        .collect();
+
+    let formatted = match style {
+        FilenameStyle::Raw => words.join(" "),
+        FilenameStyle::Kebab => words.join("-").to_lowercase(),
+        FilenameStyle::Snake => words.join("_").to_lowercase(),
+        FilenameStyle::Title => words
+            .iter()
+            .map(|word| {
+                let mut chars = word.chars();
+                match chars.next() {
+                    Some(first) => first.to_uppercase().chain(chars.flat_map(char::to_lowercase)).collect(),
+                    None => String::new(),
+                }
+            })
+            .collect::<Vec<String>>()
+            .join(" "),
+    };
+
+    // Leading dots would hide the file
+    let formatted = formatted.trim_start_matches('.').trim().to_string();
+    if formatted.is_empty() {
+        "untitled".to_string()
+    } else {
+        formatted
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{sanitize_filename, sanitize_response, strip_reasoning};
+    use crate::models::FilenameStyle;
+
+    #[test]
+    fn keeps_newlines_and_tabs() {
+        assert_eq!(sanitize_response("a\n\tb\n"), "a\n\tb\n");
+    }
+
+    #[test]
+    fn strips_control_characters() {
+        assert_eq!(sanitize_response("he\u{0}ll\u{7}o\u{1b}[0m\u{85}"), "hello[0m");
+    }
+
+    #[test]
+    fn normalizes_line_endings_and_bom() {
+        assert_eq!(sanitize_response("\u{feff}line one\r\nline two\r"), "line one\nline two");
+    }
+
+    #[test]
+    fn leaves_unicode_text_alone() {
+        assert_eq!(sanitize_response("Café – 東京 🚀"), "Café – 東京 🚀");
+    }
+
+    #[test]
+    fn strips_leaked_reasoning() {
+        let response = "<think>The user wants JSON.\nLet me list {two} questions.</think>\nSure! Here they are:\n{\"questions\": [\"Why?\"]}\nHope this helps.";
+        assert_eq!(strip_reasoning(response), "{\"questions\": [\"Why?\"]}");
+        assert_eq!(strip_reasoning("  plain answer  "), "plain answer");
+    }
+
+    #[test]
+    fn formats_filenames_by_style() {
+        let name = "Photosynthesis: Light_Reactions";
+        assert_eq!(sanitize_filename(name, FilenameStyle::Raw), "Photosynthesis Light_Reactions");
+        assert_eq!(sanitize_filename(name, FilenameStyle::Kebab), "photosynthesis-light-reactions");
+        assert_eq!(sanitize_filename(name, FilenameStyle::Snake), "photosynthesis_light_reactions");
+        assert_eq!(sanitize_filename(name, FilenameStyle::Title), "Photosynthesis Light Reactions");
+    }
+
+    #[test]
+    fn falls_back_when_filename_is_empty() {
+        assert_eq!(sanitize_filename("  /?#  ", FilenameStyle::Kebab), "untitled");
+    }
+}
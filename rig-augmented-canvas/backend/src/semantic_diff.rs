@@ -0,0 +1,130 @@
+/**
+ * Semantic diff module compares two notes by meaning rather than wording.
+ * Each note is split into passages, the passages are embedded, and a
+ * passage counts as shared when the other note has one similar enough to
+ * it. Reworded content therefore matches where a textual diff would report
+ * every changed line.
+ */
+
+use crate::chunk::chunk_text;
+use serde::Serialize;
+
+/// Default cosine similarity at or above which two passages are treated as the same.
+pub const DEFAULT_SEMANTIC_DIFF_THRESHOLD: f64 = 0.85;
+
+/**
+ * Passages found in only one of the notes, and those found in both.
+ * Shared passages are given as written in the first note.
+ */
+#[derive(Serialize, Debug, Clone, PartialEq, Default)]
+pub struct SemanticDiff {
+    pub only_in_a: Vec<String>,
+    pub only_in_b: Vec<String>,
+    pub shared: Vec<String>,
+}
+
+/**
+ * Splits a note into passages for comparison: one per paragraph, with
+ * paragraphs longer than the chunk size split into overlapping chunks.
+ *
+ * @param text The note to split
+ * @param size_tokens The configured chunk size
+ * @param overlap_tokens The configured chunk overlap
+ * @return The passages, in order
+ */
+pub fn passages(text: &str, size_tokens: usize, overlap_tokens: usize) -> Vec<String> {
+    text.replace("\r\n", "\n")
+        .split("\n\n")
+        .flat_map(|paragraph| chunk_text(paragraph.trim(), size_tokens, overlap_tokens))
+        .collect()
+}
+
+/**
+ * Computes the cosine similarity of two vectors.
+ *
+ * @return The similarity, or 0.0 if either vector is zero or their lengths differ
+ */
+pub fn cosine_similarity(a: &[f64], b: &[f64]) -> f64 {
+    if a.len() != b.len() {
+        return 0.0;
+    }
+    let dot: f64 = a.iter().zip(b).map(|(x, y)| x * y).sum();
+    let norm_a = a.iter().map(|x| x * x).sum::<f64>().sqrt();
+    let norm_b = b.iter().map(|x| x * x).sum::<f64>().sqrt();
+    if norm_a == 0.0 || norm_b == 0.0 {
+        return 0.0;
+    }
+    dot / (norm_a * norm_b)
+}
+
+/**
+ * Matches the passages of two notes by embedding similarity.
+ *
+ * @param a The first note's passages with their embeddings
+ * @param b The second note's passages with their embeddings
+ * @param threshold The similarity at or above which passages match
+ * @return The passages only in a, only in b, and in both
+ */
+pub fn compare(a: &[(String, Vec<f64>)], b: &[(String, Vec<f64>)], threshold: f64) -> SemanticDiff {
+    let has_match = |vector: &[f64], other: &[(String, Vec<f64>)]| {
+        other.iter().any(|(_, candidate)| cosine_similarity(vector, candidate) >= threshold)
+    };
+
+    let mut diff = SemanticDiff::default();
+    for (passage, vector) in a {
+        if has_match(vector, b) {
+            diff.shared.push(passage.clone());
+        } else {
+            diff.only_in_a.push(passage.clone());
+        }
+    }
+    diff.only_in_b = b
+        .iter()
+        .filter(|(_, vector)| !has_match(vector, a))
+        .map(|(passage, _)| passage.clone())
+        .collect();
+    diff
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{compare, cosine_similarity, passages, SemanticDiff};
+
+    fn passage(text: &str, vector: &[f64]) -> (String, Vec<f64>) {
+        (text.to_string(), vector.to_vec())
+    }
+
+    #[test]
+    fn computes_cosine_similarity() {
+        assert!((cosine_similarity(&[1.0, 0.0], &[2.0, 0.0]) - 1.0).abs() < 1e-9);
+        assert!(cosine_similarity(&[1.0, 0.0], &[0.0, 1.0]).abs() < 1e-9);
+        assert_eq!(cosine_similarity(&[0.0, 0.0], &[1.0, 0.0]), 0.0);
+        assert_eq!(cosine_similarity(&[1.0], &[1.0, 0.0]), 0.0);
+    }
+
+    #[test]
+    fn splits_notes_into_paragraph_passages() {
+        let text = "First paragraph.\r\n\r\nSecond\nparagraph.\n\n\n\n  ";
+        assert_eq!(passages(text, 2000, 200), vec!["First paragraph.", "Second\nparagraph."]);
+    }
+
+    #[test]
+    fn matches_passages_by_similarity() {
+        let a = vec![
+            passage("Rust has ownership.", &[1.0, 0.0, 0.0]),
+            passage("Old section.", &[0.0, 1.0, 0.0]),
+        ];
+        let b = vec![
+            passage("Ownership is part of Rust.", &[0.95, 0.05, 0.0]),
+            passage("New section.", &[0.0, 0.0, 1.0]),
+        ];
+        assert_eq!(
+            compare(&a, &b, 0.85),
+            SemanticDiff {
+                only_in_a: vec!["Old section.".to_string()],
+                only_in_b: vec!["New section.".to_string()],
+                shared: vec!["Rust has ownership.".to_string()],
+            }
+        );
+    }
+}
@@ -0,0 +1,195 @@
+/**
+ * Split module breaks an oversized response into parts at markdown heading
+ * boundaries, so the plugin can create a set of linked notes instead of one
+ * giant node.
+ */
+
+use serde::Serialize;
+
+/// Default size (in characters) above which a response is split.
+pub const DEFAULT_SPLIT_THRESHOLD: usize = 6000;
+
+/**
+ * A suggested link between two parts, by index into `parts`.
+ */
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct PartLink {
+    pub from: usize,
+    pub to: usize,
+}
+
+/**
+ * A response split into parts, with a suggested title for each part's note
+ * and links chaining the parts in reading order.
+ */
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct SplitResponse {
+    pub parts: Vec<String>,
+    pub titles: Vec<String>,
+    pub links: Vec<PartLink>,
+}
+
+/**
+ * Splits text longer than `threshold` characters at heading boundaries.
+ * Sections under the shallowest heading level are packed into parts of at
+ * most `threshold` characters; a section that is still too large is split at
+ * its own subheadings. Headings inside fenced code blocks are ignored.
+ *
+ * @param text The response text
+ * @param threshold Size in characters above which the text is split
+ * @return The split response, or None if the text is small enough or has no headings to split at
+ */
+pub fn split_response(text: &str, threshold: usize) -> Option<SplitResponse> {
+    if text.chars().count() <= threshold {
+        return None;
+    }
+    
+    let parts: Vec<String> = split_sections(text, threshold.max(1), 1)
+        .into_iter()
+        .map(|part| part.trim().to_string())
+        .filter(|part| !part.is_empty())
+        .collect();
+    if parts.len() < 2 {
+        return None;
+    }
+    
+    let titles = parts
+        .iter()
+        .enumerate()
+        .map(|(i, part)| {
+            part.lines()
+                .find_map(|line| heading_level(line).map(|level| line[level..].trim().to_string()))
+                .unwrap_or_else(|| format!("Part {}", i + 1))
+        })
+        .collect();
+    let links = (1..parts.len()).map(|to| PartLink { from: to - 1, to }).collect();
+    
+    Some(SplitResponse { parts, titles, links })
+}
+
+/**
+ * Splits text at headings of the shallowest level at or below `min_level`,
+ * then packs the sections greedily up to `threshold` characters.
+ */
+fn split_sections(text: &str, threshold: usize, min_level: usize) -> Vec<String> {
+    let headings = heading_offsets(text);
+    let Some(level) = headings.iter().map(|(_, level)| *level).filter(|level| *level >= min_level).min() else {
+        return vec![text.to_string()];
+    };
+    
+    // Cut before every heading of the chosen level
+    let mut cuts: Vec<usize> = headings
+        .iter()
+        .filter(|(_, heading_level)| *heading_level == level)
+        .map(|(offset, _)| *offset)
+        .collect();
+    cuts.insert(0, 0);
+    cuts.push(text.len());
+    cuts.dedup();
+    
+    let mut parts: Vec<String> = Vec::new();
+    let mut current = String::new();
+    for bounds in cuts.windows(2) {
+        let section = &text[bounds[0]..bounds[1]];
+        let section_size = section.chars().count();
+        
+        if section_size > threshold {
+            // Too big on its own: flush what we have and split it one level deeper
+            if !current.is_empty() {
+                parts.push(std::mem::take(&mut current));
+            }
+            parts.extend(split_sections(section, threshold, level + 1));
+        } else if current.chars().count() + section_size > threshold {
+            parts.push(std::mem::replace(&mut current, section.to_string()));
+        } else {
+            current.push_str(section);
+        }
+    }
+    if !current.is_empty() {
+        parts.push(current);
+    }
+    
+    parts
+}
+
+/**
+ * Returns the byte offset and level of every heading line outside code fences.
+ */
+fn heading_offsets(text: &str) -> Vec<(usize, usize)> {
+    let mut headings = Vec::new();
+    let mut offset = 0;
+    let mut in_fence = false;
+    
+    for line in text.split_inclusive('\n') {
+        if line.trim_start().starts_with("```") {
+            in_fence = !in_fence;
+        } else if !in_fence {
+            if let Some(level) = heading_level(line) {
+                headings.push((offset, level));
+            }
+        }
+        offset += line.len();
+    }
+    
+    headings
+}
+
+/**
+ * Returns the level of an ATX heading line (`# Title` is level 1), if it is one.
+ */
+fn heading_level(line: &str) -> Option<usize> {
+    let level = line.chars().take_while(|c| *c == '#').count();
+    let rest = &line[level..];
+    if (1..=6).contains(&level) && (rest.starts_with(' ') || rest.trim().is_empty()) {
+        Some(level)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{split_response, PartLink};
+
+    #[test]
+    fn leaves_small_responses_alone() {
+        assert_eq!(split_response("# A\n\nshort", 100), None);
+    }
+
+    #[test]
+    fn leaves_responses_without_headings_alone() {
+        assert_eq!(split_response(&"word ".repeat(100), 50), None);
+    }
+
+    #[test]
+    fn splits_at_top_level_headings() {
+        let text = format!("# One\n{}\n# Two\n{}\n# Three\n{}\n", "a".repeat(40), "b".repeat(40), "c".repeat(40));
+        let split = split_response(&text, 60).unwrap();
+        assert_eq!(split.parts.len(), 3);
+        assert!(split.parts[1].starts_with("# Two"));
+        assert_eq!(split.titles, vec!["One", "Two", "Three"]);
+        assert_eq!(split.links, vec![PartLink { from: 0, to: 1 }, PartLink { from: 1, to: 2 }]);
+    }
+
+    #[test]
+    fn packs_small_sections_together() {
+        let text = format!("# One\n{}\n# Two\n{}\n# Three\n{}\n", "a".repeat(10), "b".repeat(10), "c".repeat(60));
+        let split = split_response(&text, 70).unwrap();
+        assert_eq!(split.parts.len(), 2);
+        assert!(split.parts[0].contains("# Two"));
+    }
+
+    #[test]
+    fn splits_oversized_sections_at_subheadings() {
+        let text = format!("# Big\n## A\n{}\n## B\n{}\n", "a".repeat(40), "b".repeat(40));
+        let split = split_response(&text, 60).unwrap();
+        assert_eq!(split.parts.len(), 2);
+        assert_eq!(split.titles, vec!["Big", "B"]);
+    }
+
+    #[test]
+    fn ignores_headings_in_code_fences() {
+        let text = format!("# One\n```\n# not a heading\n{}\n```\n", "a".repeat(80));
+        assert_eq!(split_response(&text, 50), None);
+    }
+}
@@ -0,0 +1,214 @@
+/**
+ * SRS module schedules flashcard reviews with the SM-2 spaced-repetition
+ * algorithm, so generated cards can be studied as a real review system.
+ * Scheduling is deterministic and runs entirely in Rust; no model is called.
+ *
+ * A review is graded 0-5 (or "again", "hard", "good", "easy"). Grades of 3
+ * and up count as recalled: the interval grows to 1 day, then 6 days, then
+ * the previous interval times the card's ease. Lower grades reset the card
+ * to a 1-day interval. Every graded review adjusts the ease, never below 1.3.
+ */
+
+use chrono::{Duration, NaiveDate};
+use serde::{Deserialize, Serialize};
+
+/// Ease of a card that hasn't been reviewed yet.
+pub const DEFAULT_EASE: f32 = 2.5;
+
+/// Lowest ease a card can reach, so hard cards still come back less often over time.
+pub const MIN_EASE: f32 = 1.3;
+
+/**
+ * A card as sent by the plugin, with its review history so far.
+ * `interval` is the card's previous interval in days; when it is missing
+ * it is reconstructed from `reps` and `ease`.
+ */
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct ReviewCard {
+    pub front: String,
+    pub back: String,
+    pub last_result: Option<String>,
+    pub reps: Option<u32>,
+    pub ease: Option<f32>,
+    pub interval: Option<u32>,
+}
+
+/**
+ * A card with its next review scheduled. `reps` counts consecutive
+ * successful reviews and `next_review` is `YYYY-MM-DD`.
+ */
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct ScheduledCard {
+    pub front: String,
+    pub back: String,
+    pub reps: u32,
+    pub ease: f32,
+    pub interval: u32,
+    pub next_review: String,
+}
+
+/**
+ * Parses a review result into an SM-2 grade.
+ *
+ * @param result "0" to "5", or "again" (1), "hard" (3), "good" (4), "easy" (5)
+ * @return The grade, or None if the result isn't recognized
+ */
+pub fn parse_grade(result: &str) -> Option<u8> {
+    match result.trim().to_lowercase().as_str() {
+        "again" => Some(1),
+        "hard" => Some(3),
+        "good" => Some(4),
+        "easy" => Some(5),
+        other => other.parse::<u8>().ok().filter(|grade| *grade <= 5),
+    }
+}
+
+/**
+ * The interval SM-2 would have reached after `reps` successful reviews at
+ * a constant ease, used when the client doesn't send the card's interval.
+ */
+fn reconstructed_interval(reps: u32, ease: f32) -> u32 {
+    match reps {
+        0 => 0,
+        1 => 1,
+        reps => (6.0 * ease.powi(reps as i32 - 2)).round() as u32,
+    }
+}
+
+/**
+ * Applies one review to a card's schedule.
+ *
+ * @param reps Consecutive successful reviews before this one
+ * @param ease The card's ease before this review
+ * @param interval The card's previous interval in days
+ * @param grade The SM-2 grade of this review (0-5)
+ * @return The new reps, ease and interval
+ */
+pub fn review(reps: u32, ease: f32, interval: u32, grade: u8) -> (u32, f32, u32) {
+    let (reps, interval) = if grade >= 3 {
+        let interval = match reps {
+            0 => 1,
+            1 => 6,
+            _ => (interval as f32 * ease).round() as u32,
+        };
+        (reps + 1, interval)
+    } else {
+        (0, 1)
+    };
+
+    let missed = f32::from(5 - grade);
+    let ease = (ease + 0.1 - missed * (0.08 + missed * 0.02)).max(MIN_EASE);
+    (reps, ease, interval)
+}
+
+/**
+ * Schedules a card's next review. A card without a `last_result` hasn't
+ * been reviewed since it was last scheduled and is due today.
+ *
+ * @param card The card and its review history
+ * @param today The date intervals are counted from
+ * @return The scheduled card, or an error for an unknown result or invalid ease
+ */
+pub fn schedule(card: ReviewCard, today: NaiveDate) -> Result<ScheduledCard, String> {
+    let ease = card.ease.unwrap_or(DEFAULT_EASE);
+    if !ease.is_finite() || ease <= 0.0 {
+        return Err(format!("Invalid ease {}: must be a positive number", ease));
+    }
+    let ease = ease.max(MIN_EASE);
+    let reps = card.reps.unwrap_or(0);
+    let interval = card.interval.unwrap_or_else(|| reconstructed_interval(reps, ease));
+
+    let (reps, ease, interval) = match card.last_result.as_deref() {
+        Some(result) => {
+            let grade = parse_grade(result).ok_or_else(|| {
+                format!("Unknown last_result '{}': expected 0-5, again, hard, good or easy", result)
+            })?;
+            review(reps, ease, interval, grade)
+        }
+        None => (reps, ease, 0),
+    };
+
+    Ok(ScheduledCard {
+        front: card.front,
+        back: card.back,
+        reps,
+        ease,
+        interval,
+        next_review: (today + Duration::days(i64::from(interval))).format("%Y-%m-%d").to_string(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_grade, review, schedule, ReviewCard, DEFAULT_EASE, MIN_EASE};
+    use chrono::NaiveDate;
+
+    fn card(last_result: Option<&str>, reps: Option<u32>, ease: Option<f32>, interval: Option<u32>) -> ReviewCard {
+        ReviewCard {
+            front: "Capital of France?".to_string(),
+            back: "Paris".to_string(),
+            last_result: last_result.map(str::to_string),
+            reps,
+            ease,
+            interval,
+        }
+    }
+
+    fn today() -> NaiveDate {
+        NaiveDate::from_ymd_opt(2024, 5, 15).unwrap()
+    }
+
+    #[test]
+    fn parses_grades() {
+        assert_eq!(parse_grade(" Good "), Some(4));
+        assert_eq!(parse_grade("0"), Some(0));
+        assert_eq!(parse_grade("6"), None);
+        assert_eq!(parse_grade("maybe"), None);
+    }
+
+    #[test]
+    fn new_cards_are_due_today() {
+        let scheduled = schedule(card(None, None, None, None), today()).unwrap();
+        assert_eq!((scheduled.reps, scheduled.interval), (0, 0));
+        assert_eq!(scheduled.ease, DEFAULT_EASE);
+        assert_eq!(scheduled.next_review, "2024-05-15");
+    }
+
+    #[test]
+    fn first_successful_reviews_use_fixed_intervals() {
+        let first = schedule(card(Some("good"), None, None, None), today()).unwrap();
+        assert_eq!((first.reps, first.interval, first.next_review.as_str()), (1, 1, "2024-05-16"));
+        // A grade of 4 leaves the ease unchanged
+        assert!((first.ease - DEFAULT_EASE).abs() < 1e-6);
+
+        let second = schedule(card(Some("good"), Some(1), Some(first.ease), Some(1)), today()).unwrap();
+        assert_eq!((second.reps, second.interval), (2, 6));
+
+        let third = schedule(card(Some("good"), Some(2), Some(second.ease), Some(6)), today()).unwrap();
+        assert_eq!((third.reps, third.interval, third.next_review.as_str()), (3, 15, "2024-05-30"));
+    }
+
+    #[test]
+    fn failed_cards_start_over_with_lower_ease() {
+        let scheduled = schedule(card(Some("again"), Some(5), Some(2.5), Some(40)), today()).unwrap();
+        assert_eq!((scheduled.reps, scheduled.interval), (0, 1));
+        assert!((scheduled.ease - 1.96).abs() < 1e-5);
+
+        // Ease never drops below the minimum
+        let (_, ease, _) = review(0, MIN_EASE, 1, 0);
+        assert_eq!(ease, MIN_EASE);
+    }
+
+    #[test]
+    fn reconstructs_missing_intervals() {
+        // Without an interval, three successful reviews at ease 2.0 imply 6 * 2.0 = 12 days
+        let scheduled = schedule(card(Some("5"), Some(3), Some(2.0), None), today()).unwrap();
+        assert_eq!((scheduled.reps, scheduled.interval), (4, 24));
+    }
+
+    #[test]
+    fn rejects_unknown_results_and_invalid_ease() {
+        assert!(schedule(card(Some("perfect"), None, None, None), today()).is_err());
+        assert!(schedule(card(Some("good"), None, Some(f32::NAN), None), today()).is_err());
+    }
+}
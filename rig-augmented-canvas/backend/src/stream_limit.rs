@@ -0,0 +1,151 @@
+/**
+ * Stream limit module caps the number of streaming connections open at once.
+ * SSE streams hold a connection for the whole generation, so without a cap
+ * a few clients could tie up the server. The cap is read from `max_streams`
+ * on every new stream, so configuration changes apply right away. A slot is
+ * shared by the connection and the task generating the stream, so a
+ * generation that outlives its client still counts against the cap.
+ */
+
+use axum::{
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+};
+use futures::stream::{Stream, StreamExt};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use crate::api_error::ApiError;
+
+/// Seconds a client is asked to wait before retrying when every stream slot is taken.
+pub const STREAM_RETRY_AFTER_SECS: u64 = 5;
+
+/**
+ * Counts the streams currently open.
+ */
+#[derive(Default)]
+pub struct StreamLimiter {
+    active: Arc<AtomicUsize>,
+}
+
+/**
+ * A stream slot, released when the permit and all its clones are dropped.
+ */
+#[derive(Clone)]
+pub struct StreamPermit {
+    _slot: Arc<Slot>,
+}
+
+struct Slot {
+    active: Arc<AtomicUsize>,
+}
+
+impl Drop for Slot {
+    fn drop(&mut self) {
+        self.active.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/**
+ * Rejection for a stream opened while every slot is taken, sent as
+ * `503 Service Unavailable` with `Retry-After`.
+ */
+#[derive(Debug)]
+pub struct TooManyStreams;
+
+impl IntoResponse for TooManyStreams {
+    fn into_response(self) -> Response {
+        (
+            [(header::RETRY_AFTER, STREAM_RETRY_AFTER_SECS.to_string())],
+            ApiError::new(
+                StatusCode::SERVICE_UNAVAILABLE,
+                "too_many_streams",
+                "Too many streams are open. Please retry shortly.",
+            ),
+        )
+            .into_response()
+    }
+}
+
+impl StreamLimiter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /**
+     * The number of streams currently open.
+     */
+    pub fn active(&self) -> usize {
+        self.active.load(Ordering::SeqCst)
+    }
+
+    /**
+     * Takes a stream slot if fewer than `max_streams` are open.
+     *
+     * @param max_streams The cap, or None for no limit
+     * @return The permit, or None if every slot is taken
+     */
+    pub fn try_acquire(&self, max_streams: Option<usize>) -> Option<StreamPermit> {
+        self.active
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |active| match max_streams {
+                Some(max_streams) if active >= max_streams => None,
+                _ => Some(active + 1),
+            })
+            .ok()
+            .map(|_| StreamPermit {
+                _slot: Arc::new(Slot {
+                    active: self.active.clone(),
+                }),
+            })
+    }
+}
+
+/**
+ * Ties a permit to a stream so the slot is released when the stream is
+ * dropped (finished or disconnected).
+ */
+pub fn hold<S: Stream>(stream: S, permit: StreamPermit) -> impl Stream<Item = S::Item> {
+    stream.map(move |item| {
+        let _held = &permit;
+        item
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::StreamLimiter;
+
+    #[test]
+    fn caps_open_streams() {
+        let limiter = StreamLimiter::new();
+        let first = limiter.try_acquire(Some(2)).unwrap();
+        let _second = limiter.try_acquire(Some(2)).unwrap();
+        assert!(limiter.try_acquire(Some(2)).is_none());
+        assert_eq!(limiter.active(), 2);
+
+        drop(first);
+        assert_eq!(limiter.active(), 1);
+        assert!(limiter.try_acquire(Some(2)).is_some());
+    }
+
+    #[test]
+    fn slot_is_held_until_every_clone_is_dropped() {
+        let limiter = StreamLimiter::new();
+        let connection = limiter.try_acquire(Some(1)).unwrap();
+        let producer = connection.clone();
+
+        // The client disconnecting doesn't free the slot while generation continues
+        drop(connection);
+        assert!(limiter.try_acquire(Some(1)).is_none());
+
+        drop(producer);
+        assert_eq!(limiter.active(), 0);
+    }
+
+    #[test]
+    fn unlimited_without_a_cap() {
+        let limiter = StreamLimiter::new();
+        let permits: Vec<_> = (0..50).filter_map(|_| limiter.try_acquire(None)).collect();
+        assert_eq!(permits.len(), 50);
+    }
+}
@@ -0,0 +1,191 @@
+/**
+ * Telemetry module records Prometheus metrics for long-running deployments:
+ * requests per endpoint, error responses by code, provider call latency and
+ * token usage by model. They are served in the Prometheus text format at
+ * `/metrics`. Until `install` is called, recording is a no-op.
+ */
+
+use futures::{Stream, StreamExt};
+use metrics_exporter_prometheus::{BuildError, Matcher, PrometheusBuilder, PrometheusHandle};
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+
+use crate::pricing::UsageReport;
+use crate::tokens;
+
+/// Requests received, labeled by `endpoint`.
+pub const REQUESTS_TOTAL: &str = "rig_requests_total";
+/// Error responses sent, labeled by `kind` (the error's `code`).
+pub const ERRORS_TOTAL: &str = "rig_errors_total";
+/// Time the provider took to answer, labeled by `model`.
+pub const PROVIDER_LATENCY: &str = "rig_provider_call_duration_seconds";
+/// Tokens reported by the provider, labeled by `model` and `kind` (`prompt` or `completion`).
+pub const TOKENS_TOTAL: &str = "rig_tokens_total";
+
+/// Histogram buckets for provider latency, in seconds.
+const LATENCY_BUCKETS: [f64; 10] = [0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 20.0, 30.0, 60.0, 120.0];
+
+/**
+ * Builds the Prometheus recorder without installing it.
+ */
+fn builder() -> Result<PrometheusBuilder, BuildError> {
+    PrometheusBuilder::new().set_buckets_for_metric(Matcher::Full(PROVIDER_LATENCY.to_string()), &LATENCY_BUCKETS)
+}
+
+/**
+ * Installs the Prometheus recorder for the whole process.
+ *
+ * @return The handle `/metrics` renders the metrics with
+ */
+pub fn install() -> Result<PrometheusHandle, BuildError> {
+    let handle = builder()?.install_recorder()?;
+    metrics::describe_counter!(REQUESTS_TOTAL, "Requests received, by endpoint");
+    metrics::describe_counter!(ERRORS_TOTAL, "Error responses sent, by error code");
+    metrics::describe_histogram!(PROVIDER_LATENCY, metrics::Unit::Seconds, "Model provider call latency, by model");
+    metrics::describe_counter!(TOKENS_TOTAL, "Tokens reported by the model provider, by model and kind");
+    Ok(handle)
+}
+
+/**
+ * Counts a request to an endpoint.
+ */
+pub fn record_request(endpoint: &str) {
+    metrics::counter!(REQUESTS_TOTAL, "endpoint" => endpoint.to_string()).increment(1);
+}
+
+/**
+ * Counts an error response by its code (e.g., `timeout`).
+ */
+pub fn record_error(kind: &str) {
+    metrics::counter!(ERRORS_TOTAL, "kind" => kind.to_string()).increment(1);
+}
+
+/**
+ * Records how long a provider call took, whether or not it succeeded.
+ */
+pub fn record_provider_call(model: &str, elapsed: Duration) {
+    metrics::histogram!(PROVIDER_LATENCY, "model" => model.to_string()).record(elapsed.as_secs_f64());
+}
+
+/**
+ * Adds the tokens a provider reported for a call to the model's totals.
+ */
+pub fn record_tokens(model: &str, usage: &UsageReport) {
+    metrics::counter!(TOKENS_TOTAL, "model" => model.to_string(), "kind" => "prompt")
+        .increment(u64::from(usage.prompt_tokens));
+    metrics::counter!(TOKENS_TOTAL, "model" => model.to_string(), "kind" => "completion")
+        .increment(u64::from(usage.completion_tokens));
+}
+
+/**
+ * A streamed response whose provider call is recorded like any other: the
+ * latency from the start of the call until the stream ends or is dropped,
+ * and the tokens of the prompt and of the text streamed. Streams don't
+ * report usage, so the tokens are counted locally.
+ */
+pub struct MeteredStream<S> {
+    chunks: S,
+    model: String,
+    prompt_tokens: u32,
+    completion: String, // Text streamed so far
+    started: Instant,
+}
+
+impl<S> MeteredStream<S> {
+    /**
+     * @param chunks The provider's response stream
+     * @param model The concrete model the call went to
+     * @param prompt_tokens The tokens sent in the prompt
+     * @param started When the provider call was made
+     */
+    pub fn new(chunks: S, model: String, prompt_tokens: usize, started: Instant) -> Self {
+        Self {
+            chunks,
+            model,
+            prompt_tokens: prompt_tokens as u32,
+            completion: String::new(),
+            started,
+        }
+    }
+}
+
+impl<S, E> Stream for MeteredStream<S>
+where
+    S: Stream<Item = Result<String, E>> + Unpin,
+{
+    type Item = S::Item;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let polled = self.chunks.poll_next_unpin(cx);
+        if let Poll::Ready(Some(Ok(chunk))) = &polled {
+            self.completion.push_str(chunk);
+        }
+        polled
+    }
+}
+
+impl<S> Drop for MeteredStream<S> {
+    fn drop(&mut self) {
+        record_provider_call(&self.model, self.started.elapsed());
+        let usage = UsageReport {
+            prompt_tokens: self.prompt_tokens,
+            completion_tokens: tokens::count_tokens(&self.model, &self.completion) as u32,
+            estimated_cost_usd: None,
+        };
+        record_tokens(&self.model, &usage);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{builder, record_error, record_provider_call, record_request, record_tokens, MeteredStream};
+    use crate::pricing::UsageReport;
+    use futures::{stream, StreamExt};
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn renders_recorded_metrics() {
+        let recorder = builder().unwrap().build_recorder();
+        let handle = recorder.handle();
+        metrics::with_local_recorder(&recorder, || {
+            record_request("/api/tags");
+            record_request("/api/tags");
+            record_error("timeout");
+            record_provider_call("o3-mini", Duration::from_millis(1500));
+            record_tokens(
+                "o3-mini",
+                &UsageReport { prompt_tokens: 120, completion_tokens: 30, estimated_cost_usd: None },
+            );
+        });
+
+        let rendered = handle.render();
+        assert!(rendered.contains("rig_requests_total{endpoint=\"/api/tags\"} 2"));
+        assert!(rendered.contains("rig_errors_total{kind=\"timeout\"} 1"));
+        assert!(rendered.contains("rig_provider_call_duration_seconds_bucket{model=\"o3-mini\",le=\"2.5\"} 1"));
+        assert!(rendered.contains("rig_provider_call_duration_seconds_bucket{model=\"o3-mini\",le=\"1\"} 0"));
+        assert!(rendered.contains("rig_tokens_total{model=\"o3-mini\",kind=\"prompt\"} 120"));
+        assert!(rendered.contains("rig_tokens_total{model=\"o3-mini\",kind=\"completion\"} 30"));
+    }
+
+    #[test]
+    fn records_streamed_calls_when_they_end() {
+        let recorder = builder().unwrap().build_recorder();
+        let handle = recorder.handle();
+        metrics::with_local_recorder(&recorder, || {
+            let chunks = stream::iter(vec![Ok::<_, String>("Hello".to_string()), Ok(" world".to_string())]);
+            let mut metered = MeteredStream::new(chunks, "o3-mini".to_string(), 12, Instant::now());
+            let streamed: Vec<_> = futures::executor::block_on((&mut metered).collect());
+            assert_eq!(streamed.len(), 2);
+            
+            // Nothing is recorded until the stream is done with
+            assert!(!handle.render().contains("rig_tokens_total"));
+            drop(metered);
+        });
+
+        let rendered = handle.render();
+        assert!(rendered.contains("rig_provider_call_duration_seconds_count{model=\"o3-mini\"} 1"));
+        assert!(rendered.contains("rig_tokens_total{model=\"o3-mini\",kind=\"prompt\"} 12"));
+        assert!(!rendered.contains("rig_tokens_total{model=\"o3-mini\",kind=\"completion\"} 0"));
+    }
+}
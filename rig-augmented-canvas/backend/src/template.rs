@@ -0,0 +1,80 @@
+/**
+ * Template module checks the markdown templates generated from example notes.
+ * Placeholders are written `{{name}}`, where the name is made of letters,
+ * digits and underscores and doesn't start with a digit.
+ */
+
+/**
+ * Validates the placeholders in a template.
+ *
+ * @param template The markdown template to check
+ * @return The distinct placeholder names in order of first use, or a description of the first malformed one
+ */
+pub fn validate_placeholders(template: &str) -> Result<Vec<String>, String> {
+    let mut names: Vec<String> = Vec::new();
+    let mut rest = template;
+    
+    while let Some(open) = rest.find("{{") {
+        // A closing brace pair before the next opening one has nothing to close
+        if rest[..open].contains("}}") {
+            return Err("unmatched '}}'".to_string());
+        }
+        
+        let after_open = &rest[open + 2..];
+        let close = after_open
+            .find("}}")
+            .ok_or_else(|| "unclosed '{{'".to_string())?;
+        let name = after_open[..close].trim();
+        
+        if !is_placeholder_name(name) {
+            return Err(format!("invalid placeholder '{{{{{}}}}}'", &after_open[..close]));
+        }
+        if !names.iter().any(|existing| existing == name) {
+            names.push(name.to_string());
+        }
+        
+        rest = &after_open[close + 2..];
+    }
+    
+    if rest.contains("}}") {
+        return Err("unmatched '}}'".to_string());
+    }
+    
+    Ok(names)
+}
+
+fn is_placeholder_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    match chars.next() {
+        Some(first) if first.is_ascii_alphabetic() || first == '_' => {
+            chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+        }
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::validate_placeholders;
+
+    #[test]
+    fn collects_distinct_names_in_order() {
+        let template = "# {{title}}\n\nDate: {{ date }}\n\n## Notes\n{{body}}\n\nSee {{title}}";
+        assert_eq!(validate_placeholders(template).unwrap(), vec!["title", "date", "body"]);
+    }
+
+    #[test]
+    fn accepts_template_without_placeholders() {
+        assert_eq!(validate_placeholders("# Heading\n\n- item").unwrap(), Vec::<String>::new());
+    }
+
+    #[test]
+    fn rejects_malformed_placeholders() {
+        assert!(validate_placeholders("# {{title").is_err());
+        assert!(validate_placeholders("# title}}").is_err());
+        assert!(validate_placeholders("{{}}").is_err());
+        assert!(validate_placeholders("{{2nd}}").is_err());
+        assert!(validate_placeholders("{{due date}}").is_err());
+        assert!(validate_placeholders("{{a{{b}}}}").is_err());
+    }
+}
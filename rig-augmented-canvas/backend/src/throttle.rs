@@ -0,0 +1,136 @@
+/**
+ * Throttle module smooths outgoing traffic to stay under a provider's
+ * tokens-per-minute (TPM) limit. Instead of firing requests and collecting
+ * 429s, callers wait until the rolling one-minute window has room. Each
+ * model has its own window; prompts are charged up front from an estimate,
+ * and completion tokens once the response reports them.
+ */
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+use std::time::Duration;
+use tokio::time::Instant;
+
+const WINDOW: Duration = Duration::from_secs(60);
+
+/**
+ * Roughly estimates the token count of a text (about four characters per token).
+ *
+ * @param text The text to estimate
+ * @return The estimated number of tokens
+ */
+pub fn estimate_tokens(text: &str) -> u32 {
+    (text.chars().count() as u32).div_ceil(4)
+}
+
+/**
+ * Sliding-window token budget tracked separately for each model.
+ */
+#[derive(Default)]
+pub struct TokenThrottle {
+    windows: Mutex<HashMap<String, VecDeque<(Instant, u32)>>>,
+}
+
+impl TokenThrottle {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn windows(&self) -> std::sync::MutexGuard<'_, HashMap<String, VecDeque<(Instant, u32)>>> {
+        match self.windows.lock() {
+            Ok(windows) => windows,
+            Err(poisoned) => poisoned.into_inner(),
+        }
+    }
+
+    /**
+     * Charges tokens used after the fact, such as a response's completion
+     * tokens, which only become known once the call returns. They count
+     * against the budget without waiting.
+     *
+     * @param model The model the request was sent to
+     * @param tokens The tokens to charge
+     */
+    pub fn record(&self, model: &str, tokens: u32) {
+        if tokens > 0 {
+            self.windows().entry(model.to_string()).or_default().push_back((Instant::now(), tokens));
+        }
+    }
+
+    /**
+     * Waits until `tokens` fit into the model's rolling per-minute budget and
+     * then records them. A request larger than the whole budget is let through
+     * once the window is empty so it can't wait forever.
+     *
+     * @param model The model the request is sent to
+     * @param tokens The estimated tokens of the request
+     * @param tpm_limit The tokens-per-minute budget for the model
+     */
+    pub async fn acquire(&self, model: &str, tokens: u32, tpm_limit: u32) {
+        loop {
+            let wait = {
+                let mut windows = self.windows();
+                let window = windows.entry(model.to_string()).or_default();
+                let now = Instant::now();
+                while window.front().is_some_and(|(sent, _)| now.duration_since(*sent) >= WINDOW) {
+                    window.pop_front();
+                }
+
+                let used: u32 = window.iter().map(|(_, tokens)| tokens).sum();
+                if window.is_empty() || used.saturating_add(tokens) <= tpm_limit {
+                    window.push_back((now, tokens));
+                    return;
+                }
+
+                // Wait until the oldest entry leaves the window, then re-check
+                window
+                    .front()
+                    .map(|(sent, _)| WINDOW.saturating_sub(now.duration_since(*sent)))
+                    .unwrap_or_default()
+            };
+
+            tracing::info!(
+                "Throttling request to '{}' for {}ms to respect the {} TPM limit",
+                model,
+                wait.as_millis(),
+                tpm_limit
+            );
+            tokio::time::sleep(wait.max(Duration::from_millis(10))).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::TokenThrottle;
+    use std::time::Duration;
+    use tokio::time::Instant;
+
+    #[tokio::test(start_paused = true)]
+    async fn waits_for_the_window_to_make_room() {
+        let throttle = TokenThrottle::new();
+        let start = Instant::now();
+        throttle.acquire("gpt-4o", 60, 100).await;
+        throttle.acquire("gpt-4o", 40, 100).await;
+        assert_eq!(start.elapsed(), Duration::ZERO);
+
+        // Another model has its own budget
+        throttle.acquire("o3-mini", 100, 100).await;
+        assert_eq!(start.elapsed(), Duration::ZERO);
+
+        // The budget is spent until the first requests leave the window
+        throttle.acquire("gpt-4o", 10, 100).await;
+        assert!(start.elapsed() >= Duration::from_secs(60));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn charges_recorded_tokens() {
+        let throttle = TokenThrottle::new();
+        let start = Instant::now();
+        throttle.acquire("gpt-4o", 30, 100).await;
+        throttle.record("gpt-4o", 60);
+
+        throttle.acquire("gpt-4o", 20, 100).await;
+        assert!(start.elapsed() >= Duration::from_secs(60));
+    }
+}
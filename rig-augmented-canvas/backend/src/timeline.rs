@@ -0,0 +1,171 @@
+/**
+ * Timeline module holds the dated events pulled out of a note, normalizes
+ * their dates to ISO-8601 where possible and sorts them chronologically.
+ */
+
+use chrono::{Datelike, NaiveDate};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::sync::OnceLock;
+
+/**
+ * An event on the timeline. `date` is `YYYY-MM-DD`, `YYYY-MM` or `YYYY` when
+ * it could be normalized, and the model's wording otherwise.
+ */
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct TimelineEvent {
+    pub date: String,
+    pub title: String,
+    #[serde(default)]
+    pub description: String,
+}
+
+/**
+ * JSON schema timeline output must match.
+ */
+pub fn timeline_schema() -> Value {
+    json!({
+        "type": "object",
+        "properties": {
+            "events": {
+                "type": "array",
+                "items": {
+                    "type": "object",
+                    "properties": {
+                        "date": { "type": "string", "minLength": 1 },
+                        "title": { "type": "string", "minLength": 1 },
+                        "description": { "type": "string" }
+                    },
+                    "required": ["date", "title"]
+                }
+            }
+        },
+        "required": ["events"]
+    })
+}
+
+/// Formats tried for dates with a day, after ordinal suffixes are removed.
+const DAY_FORMATS: [&str; 6] = ["%Y-%m-%d", "%Y/%m/%d", "%B %d, %Y", "%B %d %Y", "%d %B %Y", "%d %B, %Y"];
+
+/// Formats tried for month-and-year dates, with a leading day of 1 added.
+const MONTH_FORMATS: [&str; 3] = ["%d %B %Y", "%d %B, %Y", "%d %Y-%m"];
+
+/**
+ * Normalizes a date to ISO-8601 at the precision it was given: a full date
+ * becomes `YYYY-MM-DD`, a month `YYYY-MM` and a bare year `YYYY`. Dates in
+ * other forms (e.g., "early 1990s", "Q3 2021") are returned trimmed.
+ *
+ * @param date The date as written by the model
+ * @return The normalized date
+ */
+pub fn normalize_date(date: &str) -> String {
+    static ORDINAL: OnceLock<Regex> = OnceLock::new();
+    static YEAR: OnceLock<Regex> = OnceLock::new();
+    let ordinal = ORDINAL.get_or_init(|| Regex::new(r"(?i)\b(\d{1,2})(st|nd|rd|th)\b").unwrap());
+    let year = YEAR.get_or_init(|| Regex::new(r"^\d{1,4}$").unwrap());
+
+    let trimmed = date.trim();
+    let cleaned = ordinal.replace_all(trimmed, "$1");
+    let cleaned = cleaned.split_whitespace().collect::<Vec<&str>>().join(" ");
+
+    // Chrono reads short digit runs as years ("March 2020" as March 20, year 20), so require a full year
+    let parse = |text: &str, format: &str| {
+        NaiveDate::parse_from_str(text, format)
+            .ok()
+            .filter(|date| cleaned.contains(&format!("{:04}", date.year())))
+    };
+    
+    if let Some(day) = DAY_FORMATS.iter().find_map(|format| parse(&cleaned, format)) {
+        return day.format("%Y-%m-%d").to_string();
+    }
+    let first_of_month = format!("1 {}", cleaned);
+    if let Some(month) = MONTH_FORMATS.iter().find_map(|format| parse(&first_of_month, format)) {
+        return format!("{:04}-{:02}", month.year(), month.month());
+    }
+    if year.is_match(&cleaned) {
+        if let Ok(year) = cleaned.parse::<u32>() {
+            return format!("{:04}", year);
+        }
+    }
+    trimmed.to_string()
+}
+
+/**
+ * Orders a normalized date: year, month and day, with missing parts first
+ * so "2021" comes before "2021-03". Dates that aren't ISO-8601 have no key.
+ */
+fn sort_key(date: &str) -> Option<(i32, u32, u32)> {
+    let mut parts = date.split('-');
+    let year = parts.next().filter(|year| year.len() == 4)?.parse().ok()?;
+    let month = parts.next().map(str::parse).transpose().ok()?.unwrap_or(0);
+    let day = parts.next().map(str::parse).transpose().ok()?.unwrap_or(0);
+    if parts.next().is_some() {
+        return None;
+    }
+    Some((year, month, day))
+}
+
+/**
+ * Cleans up events and sorts them chronologically: dates are normalized,
+ * text is trimmed and events without a title are dropped. Events whose
+ * date couldn't be normalized keep their relative order after the rest.
+ *
+ * @param events The events returned by the model
+ * @return The sorted events
+ */
+pub fn sorted(events: Vec<TimelineEvent>) -> Vec<TimelineEvent> {
+    let mut events: Vec<TimelineEvent> = events
+        .into_iter()
+        .filter(|event| !event.title.trim().is_empty())
+        .map(|event| TimelineEvent {
+            date: normalize_date(&event.date),
+            title: event.title.trim().to_string(),
+            description: event.description.trim().to_string(),
+        })
+        .collect();
+
+    // Stable sort, so same-day events keep the note's order
+    events.sort_by_key(|event| match sort_key(&event.date) {
+        Some(key) => (false, key),
+        None => (true, (0, 0, 0)),
+    });
+    events
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{normalize_date, sorted, TimelineEvent};
+
+    fn event(date: &str, title: &str) -> TimelineEvent {
+        TimelineEvent {
+            date: date.to_string(),
+            title: title.to_string(),
+            description: String::new(),
+        }
+    }
+
+    #[test]
+    fn normalizes_dates_to_iso_8601() {
+        assert_eq!(normalize_date("July 20th, 1969"), "1969-07-20");
+        assert_eq!(normalize_date("4 Oct 1957"), "1957-10-04");
+        assert_eq!(normalize_date("1989/11/09"), "1989-11-09");
+        assert_eq!(normalize_date("March 2020"), "2020-03");
+        assert_eq!(normalize_date(" 1066 "), "1066");
+        assert_eq!(normalize_date("early 1990s"), "early 1990s");
+    }
+
+    #[test]
+    fn sorts_events_chronologically() {
+        let events = sorted(vec![
+            event("1969-07-20", "Moon landing"),
+            event("sometime later", "Undated"),
+            event("October 4, 1957", "Sputnik"),
+            event("1969", "Year of the landing"),
+            event("1969-07-20", "Same day, second"),
+            event("2000", "  "),
+        ]);
+        let titles: Vec<&str> = events.iter().map(|event| event.title.as_str()).collect();
+        assert_eq!(titles, vec!["Sputnik", "Year of the landing", "Moon landing", "Same day, second", "Undated"]);
+    }
+}
@@ -0,0 +1,108 @@
+/**
+ * Tokens module counts prompt tokens with the tokenizer of the model that
+ * will receive them, so clients can check what a prompt will cost before
+ * sending it and oversized prompts can be refused up front. Models whose
+ * tokenizer isn't known (e.g., local models) fall back to the rough
+ * four-characters-per-token estimate used elsewhere.
+ */
+
+use std::sync::OnceLock;
+use tiktoken_rs::CoreBPE;
+
+use crate::throttle::estimate_tokens;
+
+/**
+ * Byte-pair encodings used by OpenAI models.
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Encoding {
+    O200kBase,
+    Cl100kBase,
+}
+
+/**
+ * The encoding a model uses, by the prefix of its name. Names may carry an
+ * `openai/` provider prefix.
+ *
+ * @param model The concrete model name (aliases already resolved)
+ * @return The encoding, or None if the model's tokenizer isn't known
+ */
+pub fn encoding_for(model: &str) -> Option<Encoding> {
+    let model = model.trim().to_lowercase();
+    let model = model.strip_prefix("openai/").unwrap_or(&model);
+    let is_reasoning_model = model.starts_with('o') && model[1..].starts_with(|c: char| c.is_ascii_digit());
+    if is_reasoning_model || ["gpt-4o", "gpt-4.1", "gpt-4.5", "gpt-5", "chatgpt-4o"].iter().any(|prefix| model.starts_with(prefix)) {
+        Some(Encoding::O200kBase)
+    } else if ["gpt-4", "gpt-3.5", "gpt-35", "text-embedding-"].iter().any(|prefix| model.starts_with(prefix)) {
+        Some(Encoding::Cl100kBase)
+    } else {
+        None
+    }
+}
+
+/**
+ * The tokenizer for an encoding, loaded once and shared.
+ */
+fn tokenizer(encoding: Encoding) -> Option<&'static CoreBPE> {
+    static O200K_BASE: OnceLock<Option<CoreBPE>> = OnceLock::new();
+    static CL100K_BASE: OnceLock<Option<CoreBPE>> = OnceLock::new();
+    match encoding {
+        Encoding::O200kBase => O200K_BASE.get_or_init(|| tiktoken_rs::o200k_base().ok()),
+        Encoding::Cl100kBase => CL100K_BASE.get_or_init(|| tiktoken_rs::cl100k_base().ok()),
+    }
+    .as_ref()
+}
+
+/**
+ * Counts the tokens of a text for a model.
+ *
+ * @param model The concrete model name (aliases already resolved)
+ * @param text The text to count
+ * @return The token count, estimated if the model's tokenizer isn't known
+ */
+pub fn count_tokens(model: &str, text: &str) -> usize {
+    match encoding_for(model).and_then(tokenizer) {
+        Some(bpe) => bpe.encode_with_special_tokens(text).len(),
+        None => estimate_tokens(text) as usize,
+    }
+}
+
+/**
+ * Counts the tokens of a prompt: its system prompt and its content.
+ *
+ * @param model The concrete model name (aliases already resolved)
+ * @param system_prompt The system prompt sent with the content, if any
+ * @param content The content as sent to the model
+ * @return The token count
+ */
+pub fn count_prompt_tokens(model: &str, system_prompt: Option<&str>, content: &str) -> usize {
+    system_prompt.map_or(0, |system_prompt| count_tokens(model, system_prompt)) + count_tokens(model, content)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{count_prompt_tokens, count_tokens, encoding_for, Encoding};
+
+    #[test]
+    fn picks_encodings_by_model() {
+        assert_eq!(encoding_for("o3-mini"), Some(Encoding::O200kBase));
+        assert_eq!(encoding_for("openai/gpt-4o-mini"), Some(Encoding::O200kBase));
+        assert_eq!(encoding_for("gpt-4-turbo"), Some(Encoding::Cl100kBase));
+        assert_eq!(encoding_for("gpt-3.5-turbo-instruct"), Some(Encoding::Cl100kBase));
+        assert_eq!(encoding_for("llama3"), None);
+        assert_eq!(encoding_for("ollama"), None);
+    }
+
+    #[test]
+    fn estimates_unknown_models() {
+        assert_eq!(count_tokens("llama3", "twelve chars"), 3);
+        assert_eq!(count_prompt_tokens("llama3", Some("abcd"), "twelve chars"), 4);
+        assert_eq!(count_prompt_tokens("llama3", None, ""), 0);
+    }
+
+    #[test]
+    fn counts_known_models_with_their_tokenizer() {
+        assert!(count_tokens("gpt-4o", "Ownership rules in Rust.") > 0);
+        assert_eq!(count_tokens("gpt-4o", ""), 0);
+    }
+}
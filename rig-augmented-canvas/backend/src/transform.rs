@@ -0,0 +1,142 @@
+/**
+ * Transform module holds the pipeline that prepares prompt content before
+ * the model call. Each step is a `ContentTransform`; the pipeline is built
+ * from the configuration and applies its steps in order, so preprocessing
+ * stays composable and each step can be tested on its own.
+ */
+
+use serde::{Deserialize, Serialize};
+
+use crate::frontmatter::split_frontmatter;
+use crate::models::ModelConfig;
+
+/**
+ * A preprocessing step applied to prompt content.
+ */
+pub trait ContentTransform: Send + Sync {
+    /**
+     * Transforms the content.
+     *
+     * @param content The content produced by the previous step
+     * @return The content for the next step
+     */
+    fn apply(&self, content: String) -> String;
+}
+
+/**
+ * Transforms that can be listed in `content_transforms`.
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ContentTransformKind {
+    StripFrontmatter,
+    Delimit,
+}
+
+/**
+ * Removes a leading YAML frontmatter block.
+ */
+pub struct StripFrontmatter;
+
+impl ContentTransform for StripFrontmatter {
+    fn apply(&self, content: String) -> String {
+        match split_frontmatter(&content) {
+            (Some(_), body) => body.to_string(),
+            (None, _) => content,
+        }
+    }
+}
+
+/**
+ * Wraps content in `<content>` tags, so the model can tell the note apart
+ * from the instructions around it.
+ */
+pub struct Delimit;
+
+impl ContentTransform for Delimit {
+    fn apply(&self, content: String) -> String {
+        format!("<content>\n{}\n</content>", content)
+    }
+}
+
+impl ContentTransformKind {
+    fn build(self) -> Box<dyn ContentTransform> {
+        match self {
+            Self::StripFrontmatter => Box::new(StripFrontmatter),
+            Self::Delimit => Box::new(Delimit),
+        }
+    }
+}
+
+/**
+ * The ordered steps the configuration asks for: `content_transforms` as
+ * listed, with frontmatter stripped first when `strip_frontmatter` is set
+ * and the list doesn't place that step itself.
+ *
+ * @param config The effective configuration for the request
+ * @return The step kinds, in order
+ */
+pub fn configured_steps(config: &ModelConfig) -> Vec<ContentTransformKind> {
+    let mut steps = Vec::new();
+    if config.strip_frontmatter && !config.content_transforms.contains(&ContentTransformKind::StripFrontmatter) {
+        steps.push(ContentTransformKind::StripFrontmatter);
+    }
+    steps.extend(config.content_transforms.iter().copied());
+    steps
+}
+
+/**
+ * Builds the pipeline for a configuration.
+ *
+ * @param config The effective configuration for the request
+ * @return The transforms, in the order they run
+ */
+pub fn pipeline(config: &ModelConfig) -> Vec<Box<dyn ContentTransform>> {
+    configured_steps(config).into_iter().map(ContentTransformKind::build).collect()
+}
+
+/**
+ * Runs content through a pipeline.
+ *
+ * @param pipeline The transforms, in order
+ * @param content The original content
+ * @return The content to send to the model
+ */
+pub fn run(pipeline: &[Box<dyn ContentTransform>], content: String) -> String {
+    pipeline.iter().fold(content, |content, transform| transform.apply(content))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{configured_steps, run, ContentTransform, ContentTransformKind, Delimit, StripFrontmatter};
+    use crate::models::ModelConfig;
+
+    const NOTE: &str = "---\ntags: [rust]\n---\nOwnership rules.";
+
+    #[test]
+    fn applies_transforms_in_order() {
+        let strip_then_delimit: Vec<Box<dyn ContentTransform>> = vec![Box::new(StripFrontmatter), Box::new(Delimit)];
+        assert_eq!(run(&strip_then_delimit, NOTE.to_string()), "<content>\nOwnership rules.\n</content>");
+
+        // Once delimited, the frontmatter no longer starts the content and is kept
+        let delimit_then_strip: Vec<Box<dyn ContentTransform>> = vec![Box::new(Delimit), Box::new(StripFrontmatter)];
+        assert_eq!(run(&delimit_then_strip, NOTE.to_string()), format!("<content>\n{}\n</content>", NOTE));
+    }
+
+    #[test]
+    fn builds_steps_from_config() {
+        let mut config = ModelConfig::default();
+        assert!(configured_steps(&config).is_empty());
+
+        config.strip_frontmatter = true;
+        config.content_transforms = vec![ContentTransformKind::Delimit];
+        assert_eq!(
+            configured_steps(&config),
+            vec![ContentTransformKind::StripFrontmatter, ContentTransformKind::Delimit]
+        );
+
+        // An explicitly placed step isn't added twice
+        config.content_transforms = vec![ContentTransformKind::Delimit, ContentTransformKind::StripFrontmatter];
+        assert_eq!(configured_steps(&config), config.content_transforms);
+    }
+}
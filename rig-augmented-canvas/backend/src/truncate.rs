@@ -0,0 +1,164 @@
+/**
+ * Truncate module enforces `max_response_chars` after generation.
+ * `max_tokens` only bounds a response loosely, so free-text responses are
+ * cut at a word boundary to give canvas nodes a predictable size.
+ */
+
+use futures::{future, StreamExt};
+
+use crate::models::ResponseStream;
+
+/// Appended to a response that was cut short.
+const ELLIPSIS: char = '…';
+
+/**
+ * A free-text response after `max_response_chars` was applied.
+ */
+#[derive(Debug, Clone, PartialEq)]
+pub struct LimitedResponse {
+    pub text: String,
+    pub truncated: bool, // Whether the text was cut short
+}
+
+/**
+ * Cuts a response down to `max_chars` at a word boundary.
+ *
+ * @param text The generated response
+ * @param max_chars The configured `max_response_chars`, if any
+ * @return The response, and whether it was truncated
+ */
+pub fn limit_response(text: String, max_chars: Option<usize>) -> LimitedResponse {
+    match max_chars.and_then(|max_chars| truncate_at_word(&text, max_chars)) {
+        Some(truncated) => LimitedResponse { text: truncated, truncated: true },
+        None => LimitedResponse { text, truncated: false },
+    }
+}
+
+/**
+ * Ends a response stream once it reaches `max_chars` characters. Chunks
+ * already sent can't be taken back, so the chunk that crosses the limit is
+ * cut at its last word boundary (or mid-word if it has none) and followed
+ * by an ellipsis, and the rest of the stream is dropped.
+ *
+ * @param chunks The response stream
+ * @param max_chars The configured `max_response_chars`, if any
+ * @return The stream, ending at the limit
+ */
+pub fn limit_stream(chunks: ResponseStream, max_chars: Option<usize>) -> ResponseStream {
+    let Some(max_chars) = max_chars else {
+        return chunks;
+    };
+    let mut sent = 0;
+    Box::pin(chunks.scan(false, move |done, chunk| {
+        if *done {
+            return future::ready(None);
+        }
+        let chunk = match chunk {
+            Ok(text) => {
+                let remaining = max_chars - sent;
+                match truncate_at_word(&text, remaining) {
+                    None => {
+                        sent += text.chars().count();
+                        text
+                    }
+                    // Nothing fits once the limit is reached exactly, not even the ellipsis
+                    Some(_) if remaining == 0 => return future::ready(None),
+                    Some(truncated) => {
+                        *done = true;
+                        truncated
+                    }
+                }
+            }
+            Err(e) => return future::ready(Some(Err(e))),
+        };
+        future::ready(Some(Ok(chunk)))
+    }))
+}
+
+/**
+ * Shortens text longer than `max_chars` characters to the last whole word
+ * that fits, followed by an ellipsis. The result, ellipsis included, is at
+ * most `max_chars` characters; a single word longer than that is cut mid-word.
+ *
+ * @param text The response text
+ * @param max_chars The maximum length in characters
+ * @return The truncated text, or None if the text already fits
+ */
+pub fn truncate_at_word(text: &str, max_chars: usize) -> Option<String> {
+    if text.chars().count() <= max_chars {
+        return None;
+    }
+
+    // Leave room for the ellipsis
+    let budget = max_chars.saturating_sub(1);
+    let cut = text.char_indices().nth(budget).map(|(index, _)| index).unwrap_or(text.len());
+    let head = &text[..cut];
+
+    // Back up to the last word boundary unless the cut already falls on one
+    let next_is_space = text[cut..].starts_with(char::is_whitespace);
+    let head = match head.rfind(char::is_whitespace) {
+        Some(space) if !next_is_space => &head[..space],
+        _ => head,
+    };
+
+    Some(format!("{}{}", head.trim_end(), ELLIPSIS))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{limit_response, limit_stream, truncate_at_word};
+    use crate::models::ResponseStream;
+    use futures::{stream, StreamExt};
+
+    #[test]
+    fn leaves_short_text_alone() {
+        assert_eq!(truncate_at_word("fits exactly", 12), None);
+    }
+
+    #[test]
+    fn truncates_at_word_boundaries() {
+        let truncated = truncate_at_word("The quick brown fox jumps over the lazy dog", 20).unwrap();
+        assert_eq!(truncated, "The quick brown fox…");
+        assert!(truncated.chars().count() <= 20);
+
+        assert_eq!(truncate_at_word("The quick brown fox jumps", 18).unwrap(), "The quick brown…");
+        assert_eq!(truncate_at_word("Line one\n\nline two", 12).unwrap(), "Line one…");
+    }
+
+    #[test]
+    fn cuts_long_words_and_multibyte_text() {
+        assert_eq!(truncate_at_word("Donaudampfschifffahrt", 6).unwrap(), "Donau…");
+        assert_eq!(truncate_at_word("東京 大阪 京都", 6).unwrap(), "東京 大阪…");
+    }
+
+    #[test]
+    fn limits_responses_when_configured() {
+        let response = limit_response("The quick brown fox jumps".to_string(), Some(18));
+        assert_eq!((response.text.as_str(), response.truncated), ("The quick brown…", true));
+
+        let response = limit_response("The quick brown fox jumps".to_string(), None);
+        assert!(!response.truncated);
+    }
+
+    #[tokio::test]
+    async fn ends_streams_at_the_limit() {
+        let chunks = |parts: &[&str]| -> ResponseStream {
+            let parts: Vec<_> = parts.iter().map(|part| Ok(part.to_string())).collect();
+            Box::pin(stream::iter(parts))
+        };
+
+        let limited: Vec<String> = limit_stream(chunks(&["The quick ", "brown fox ", "jumps over"]), Some(18))
+            .map(|chunk| chunk.unwrap())
+            .collect()
+            .await;
+        assert_eq!(limited, vec!["The quick ", "brown…"]);
+
+        let limited: Vec<String> = limit_stream(chunks(&["The quick ", "brown fox"]), Some(10))
+            .map(|chunk| chunk.unwrap())
+            .collect()
+            .await;
+        assert_eq!(limited, vec!["The quick "]);
+
+        assert_eq!(limit_stream(chunks(&["a", "b"]), None).count().await, 2);
+    }
+}
@@ -0,0 +1,92 @@
+/**
+ * Usage module tracks the token usage reported on streaming endpoints.
+ * Streamed responses don't carry the provider's usage figures, so counts
+ * are estimated from the text sent and received (about four characters per
+ * token, as in `throttle::estimate_tokens`).
+ */
+
+use crate::throttle::estimate_tokens;
+use serde::Serialize;
+
+/**
+ * Token counts reported in a `usage` SSE event.
+ */
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Default)]
+pub struct UsageStats {
+    pub prompt_tokens: u32,
+    pub completion_tokens: u32,
+    pub total_tokens: u32,
+    /// Always true: counts are estimated from text, not reported by the provider.
+    pub estimated: bool,
+}
+
+/**
+ * Running token counts for one stream.
+ */
+#[derive(Debug, Default)]
+pub struct UsageMeter {
+    prompt_tokens: u32,
+    completion_text: String,
+    completion_tokens: u32,
+}
+
+impl UsageMeter {
+    /**
+     * Counts a prompt sent for the stream (e.g., each batch item's content).
+     */
+    pub fn add_prompt(&mut self, prompt: &str) {
+        self.prompt_tokens += estimate_tokens(prompt);
+    }
+
+    /**
+     * Counts a chunk of streamed output. Chunks of one response are
+     * estimated together, so splitting a word across chunks doesn't inflate
+     * the count.
+     */
+    pub fn add_chunk(&mut self, chunk: &str) {
+        self.completion_text.push_str(chunk);
+    }
+
+    /**
+     * Counts a complete response (e.g., a finished batch item).
+     */
+    pub fn add_response(&mut self, response: &str) {
+        self.completion_tokens += estimate_tokens(response);
+    }
+
+    /**
+     * The counts so far.
+     */
+    pub fn stats(&self) -> UsageStats {
+        let completion_tokens = self.completion_tokens + estimate_tokens(&self.completion_text);
+        UsageStats {
+            prompt_tokens: self.prompt_tokens,
+            completion_tokens,
+            total_tokens: self.prompt_tokens + completion_tokens,
+            estimated: true,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::UsageMeter;
+
+    #[test]
+    fn estimates_running_totals() {
+        let mut meter = UsageMeter::default();
+        meter.add_prompt("12345678");
+        assert_eq!(meter.stats().prompt_tokens, 2);
+        assert_eq!(meter.stats().completion_tokens, 0);
+
+        // Two chunks of one response are counted as one text
+        meter.add_chunk("ab");
+        meter.add_chunk("cd");
+        assert_eq!(meter.stats().completion_tokens, 1);
+
+        meter.add_prompt("1234");
+        meter.add_response("12345");
+        let stats = meter.stats();
+        assert_eq!((stats.prompt_tokens, stats.completion_tokens, stats.total_tokens), (3, 3, 6));
+    }
+}
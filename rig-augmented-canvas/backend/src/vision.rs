@@ -0,0 +1,235 @@
+/**
+ * Vision module sends images to a vision-capable model. Rig's agents only
+ * take text prompts, so images go straight to the OpenAI-compatible
+ * `/chat/completions` endpoint as `image_url` content parts. Inline images
+ * are checked before they're sent so one unreadable reference can be
+ * reported on its own instead of failing the provider call. Remote URLs are
+ * passed to the provider as-is; the backend never fetches them itself.
+ */
+
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+use crate::pricing::TokenUsage;
+
+/// Vision-capable model used when the configuration doesn't name one.
+pub const DEFAULT_VISION_MODEL: &str = "gpt-4o-mini";
+
+/// Image types accepted as base64 data.
+const IMAGE_MIME_TYPES: [&str; 4] = ["image/png", "image/jpeg", "image/gif", "image/webp"];
+
+/// Largest base64 payload accepted for one image (about 15 MB decoded).
+const MAX_IMAGE_DATA_CHARS: usize = 20 * 1024 * 1024;
+
+/// How long to wait for a connection to the provider.
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Longest a vision call may take, including uploading inline images.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(120);
+
+/**
+ * An image to describe: either a `url` (http(s) or a `data:` URL) or raw
+ * base64 `data` with its `mime_type` (PNG when omitted). `ref` is echoed
+ * back so the plugin can match descriptions to the embeds in a note.
+ */
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ImageInput {
+    #[serde(rename = "ref")]
+    pub reference: String,
+    #[serde(default)]
+    pub url: Option<String>,
+    #[serde(default)]
+    pub data: Option<String>,
+    #[serde(default)]
+    pub mime_type: Option<String>,
+}
+
+/**
+ * Alt text for one image, or the reason it couldn't be described.
+ */
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct AltText {
+    #[serde(rename = "ref")]
+    pub reference: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub alt: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/**
+ * Builds the client vision calls are sent with. It's meant to be built once
+ * and shared, so connections to the provider are reused.
+ */
+pub fn http_client() -> reqwest::Client {
+    reqwest::Client::builder()
+        .connect_timeout(CONNECT_TIMEOUT)
+        .timeout(REQUEST_TIMEOUT)
+        .build()
+        .expect("vision HTTP client configuration is valid")
+}
+
+/**
+ * Turns an image input into the URL sent to the model. Base64 data is
+ * checked and wrapped in a `data:` URL; http(s) URLs are sent unchanged for
+ * the provider to load.
+ *
+ * @param image The image to resolve
+ * @return The URL to send, or why the image can't be used
+ */
+pub fn resolve_image(image: &ImageInput) -> Result<String, String> {
+    match (&image.url, &image.data) {
+        (Some(_), Some(_)) => Err("Provide either url or data, not both".to_string()),
+        (None, Some(data)) => data_url(image.mime_type.as_deref().unwrap_or("image/png"), data),
+        (Some(url), None) if url.starts_with("data:") => {
+            let (mime_type, data) = url
+                .strip_prefix("data:")
+                .and_then(|rest| rest.split_once(";base64,"))
+                .ok_or_else(|| "Data URLs must be base64-encoded".to_string())?;
+            data_url(mime_type, data)
+        }
+        (Some(url), None) if url.starts_with("http://") || url.starts_with("https://") => Ok(url.clone()),
+        (Some(_), None) => Err("Image url must be http(s) or a data: URL".to_string()),
+        (None, None) => Err("Image has neither url nor data".to_string()),
+    }
+}
+
+/**
+ * Validates base64 image data and builds a `data:` URL from it.
+ */
+fn data_url(mime_type: &str, data: &str) -> Result<String, String> {
+    let mime_type = mime_type.trim().to_lowercase();
+    if !IMAGE_MIME_TYPES.contains(&mime_type.as_str()) {
+        return Err(format!("Unsupported image type '{}'", mime_type));
+    }
+
+    let data: String = data.chars().filter(|c| !c.is_whitespace()).collect();
+    if data.is_empty() {
+        return Err("Image data is empty".to_string());
+    }
+    if data.len() > MAX_IMAGE_DATA_CHARS {
+        return Err("Image data is too large".to_string());
+    }
+    let body = data.trim_end_matches('=');
+    let valid = data.len().is_multiple_of(4)
+        && data.len() - body.len() <= 2
+        && body.chars().all(|c| c.is_ascii_alphanumeric() || c == '+' || c == '/');
+    if !valid {
+        return Err("Image data is not valid base64".to_string());
+    }
+
+    Ok(format!("data:{};base64,{}", mime_type, data))
+}
+
+#[derive(Deserialize)]
+struct ChatResponse {
+    choices: Vec<ChatChoice>,
+    #[serde(default)]
+    usage: Option<TokenUsage>,
+}
+
+#[derive(Deserialize)]
+struct ChatChoice {
+    message: ChatMessage,
+}
+
+#[derive(Deserialize)]
+struct ChatMessage {
+    #[serde(default)]
+    content: Option<String>,
+}
+
+/**
+ * Sends a text prompt with one image to a vision-capable chat model.
+ *
+ * @param http The client to send the request with
+ * @param base_url The provider's API base URL
+ * @param api_key The API key to use
+ * @param model The vision-capable model
+ * @param prompt The instruction sent alongside the image
+ * @param image_url The image, as an http(s) or `data:` URL
+ * @return The model's reply, with the usage the provider reported
+ */
+pub async fn describe_image(
+    http: &reqwest::Client,
+    base_url: &str,
+    api_key: &str,
+    model: &str,
+    prompt: &str,
+    image_url: &str,
+) -> Result<(String, Option<TokenUsage>), String> {
+    let body = serde_json::json!({
+        "model": model,
+        "messages": [{
+            "role": "user",
+            "content": [
+                { "type": "text", "text": prompt },
+                { "type": "image_url", "image_url": { "url": image_url } },
+            ],
+        }],
+    });
+
+    let response = http
+        .post(format!("{}/chat/completions", base_url.trim_end_matches('/')))
+        .bearer_auth(api_key)
+        .json(&body)
+        .send()
+        .await
+        .and_then(|response| response.error_for_status())
+        .map_err(|e| format!("Vision request failed: {}", e))?
+        .json::<ChatResponse>()
+        .await
+        .map_err(|e| format!("Failed to parse vision response: {}", e))?;
+
+    let usage = response.usage;
+    response
+        .choices
+        .into_iter()
+        .next()
+        .and_then(|choice| choice.message.content)
+        .map(|content| content.trim().to_string())
+        .filter(|content| !content.is_empty())
+        .map(|content| (content, usage))
+        .ok_or_else(|| "Vision response contained no text".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{resolve_image, ImageInput};
+
+    fn image(url: Option<&str>, data: Option<&str>, mime_type: Option<&str>) -> ImageInput {
+        ImageInput {
+            reference: "diagram.png".to_string(),
+            url: url.map(str::to_string),
+            data: data.map(str::to_string),
+            mime_type: mime_type.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn wraps_base64_data_in_a_data_url() {
+        assert_eq!(
+            resolve_image(&image(None, Some("iVBO Rw0K\nGgo="), None)),
+            Ok("data:image/png;base64,iVBORw0KGgo=".to_string())
+        );
+        assert_eq!(
+            resolve_image(&image(Some("data:image/JPEG;base64,/9j/4A=="), None, None)),
+            Ok("data:image/jpeg;base64,/9j/4A==".to_string())
+        );
+    }
+
+    #[test]
+    fn passes_remote_urls_through_unfetched() {
+        let url = "https://example.com/diagram.png";
+        assert_eq!(resolve_image(&image(Some(url), None, None)), Ok(url.to_string()));
+    }
+
+    #[test]
+    fn rejects_unusable_images() {
+        assert!(resolve_image(&image(None, Some("not base64!"), None)).is_err());
+        assert!(resolve_image(&image(None, Some("iVBORw0KGgo="), Some("image/tiff"))).is_err());
+        assert!(resolve_image(&image(Some("data:image/png,raw"), None, None)).is_err());
+        assert!(resolve_image(&image(Some("file:///etc/passwd"), None, None)).is_err());
+        assert!(resolve_image(&image(None, None, None)).is_err());
+    }
+}
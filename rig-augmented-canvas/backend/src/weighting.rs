@@ -0,0 +1,102 @@
+/**
+ * Weighting module arranges the nodes of a combined multi-node prompt by
+ * their importance. Heavier nodes come first and are marked "primary", the
+ * rest "secondary"; when the nodes don't fit the configured budget, the
+ * lightest are dropped first. Nodes without a weight count as 1.0, so
+ * requests without weights are sent exactly as before.
+ */
+
+/// Weight of a node that doesn't set one.
+pub const DEFAULT_NODE_WEIGHT: f32 = 1.0;
+
+/**
+ * A node's place in the combined prompt.
+ */
+#[derive(Debug, Clone, PartialEq)]
+pub struct ArrangedNode {
+    /// Position of the node in the request.
+    pub index: usize,
+    /// "primary" or "secondary" when the nodes' weights differ.
+    pub emphasis: Option<&'static str>,
+}
+
+/**
+ * Checks that a weight is a finite, non-negative number.
+ */
+pub fn is_valid_weight(weight: f32) -> bool {
+    weight.is_finite() && weight >= 0.0
+}
+
+/**
+ * Orders nodes by weight, heaviest first, and drops the lightest until the
+ * total content length fits `max_chars`. Nodes of equal weight keep their
+ * request order, and among them later nodes are dropped first. The heaviest
+ * node is always kept.
+ *
+ * @param weights Each node's weight, if set
+ * @param lengths Each node's content length in characters
+ * @param max_chars The budget for node content, or None for no limit
+ * @return The nodes to send, in prompt order
+ */
+pub fn arrange(weights: &[Option<f32>], lengths: &[usize], max_chars: Option<usize>) -> Vec<ArrangedNode> {
+    let weight = |index: usize| weights[index].unwrap_or(DEFAULT_NODE_WEIGHT);
+
+    // Stable sort, so equal weights keep the request order
+    let mut order: Vec<usize> = (0..weights.len()).collect();
+    order.sort_by(|a, b| weight(*b).total_cmp(&weight(*a)));
+
+    if let Some(max_chars) = max_chars {
+        let mut total: usize = order.iter().map(|index| lengths[*index]).sum();
+        while total > max_chars && order.len() > 1 {
+            if let Some(dropped) = order.pop() {
+                total -= lengths[dropped];
+            }
+        }
+    }
+
+    let heaviest = order.first().map(|index| weight(*index));
+    let weighted = order.iter().any(|index| Some(weight(*index)) != heaviest);
+    order
+        .into_iter()
+        .map(|index| ArrangedNode {
+            index,
+            emphasis: weighted.then(|| if Some(weight(index)) == heaviest { "primary" } else { "secondary" }),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{arrange, ArrangedNode};
+
+    fn indices(nodes: &[ArrangedNode]) -> Vec<usize> {
+        nodes.iter().map(|node| node.index).collect()
+    }
+
+    #[test]
+    fn keeps_order_without_weights() {
+        let nodes = arrange(&[None, None, Some(1.0)], &[10, 10, 10], None);
+        assert_eq!(indices(&nodes), vec![0, 1, 2]);
+        assert!(nodes.iter().all(|node| node.emphasis.is_none()));
+    }
+
+    #[test]
+    fn orders_and_labels_by_weight() {
+        let nodes = arrange(&[Some(0.5), None, Some(3.0), Some(3.0)], &[10, 10, 10, 10], None);
+        assert_eq!(indices(&nodes), vec![2, 3, 1, 0]);
+        let emphasis: Vec<Option<&str>> = nodes.iter().map(|node| node.emphasis).collect();
+        assert_eq!(emphasis, vec![Some("primary"), Some("primary"), Some("secondary"), Some("secondary")]);
+    }
+
+    #[test]
+    fn drops_lightest_nodes_to_fit() {
+        let nodes = arrange(&[Some(0.5), None, Some(2.0)], &[100, 100, 100], Some(250));
+        assert_eq!(indices(&nodes), vec![2, 1]);
+
+        let nodes = arrange(&[None, None], &[100, 100], Some(150));
+        assert_eq!(indices(&nodes), vec![0]);
+
+        let nodes = arrange(&[Some(2.0), None], &[500, 10], Some(100));
+        assert_eq!(indices(&nodes), vec![0]);
+    }
+}
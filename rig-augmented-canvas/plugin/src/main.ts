@@ -47,9 +47,6 @@ export default class RigAugmentedCanvasPlugin extends Plugin {
             // Initialize the WebAssembly module
             await wasm.default(Promise.resolve(wasmbin));
             
-            // Register the onload function with the plugin
-            wasm.onload(this);
-            
             console.log("WebAssembly module initialized");
         } catch (error) {
             console.error("Failed to initialize WebAssembly module:", error);
@@ -57,6 +54,13 @@ export default class RigAugmentedCanvasPlugin extends Plugin {
         
         await this.loadSettings();
         
+        // Register the WebAssembly commands (prompt, summarize, flashcards, questions)
+        try {
+            wasm.onload(this, { apiKey: this.settings.openaiApiKey });
+        } catch (error) {
+            console.error("Failed to register WebAssembly commands:", error);
+        }
+        
         // Initialize API client for WebAssembly communication
         this.apiClient = new RigApiClient(this.settings);
         
@@ -0,0 +1,73 @@
+/**
+ * Cache module keeps built agents for reuse across prompts in the WASM
+ * worker, so processing a whole canvas doesn't build a new client and agent
+ * for every node. The worker is single-threaded, so a `RefCell` is enough.
+ */
+use std::cell::RefCell;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::VecDeque;
+use std::hash::{Hash, Hasher};
+use std::rc::Rc;
+
+/// Number of built agents kept before the least recently used one is evicted.
+pub const AGENT_CACHE_SIZE: usize = 16;
+
+/**
+ * Hashes the given parts into a cache key, so API keys and system prompts
+ * are never stored as-is. Parts are length-prefixed so ("ab", "c") and
+ * ("a", "bc") differ.
+ *
+ * @param parts The values that determine the cached value
+ * @return The key
+ */
+pub fn cache_key(parts: &[&str]) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    for part in parts {
+        part.len().hash(&mut hasher);
+        part.hash(&mut hasher);
+    }
+    hasher.finish()
+}
+
+/**
+ * LRU cache from hashed keys to shared values, most recently used last.
+ */
+pub struct BoundedCache<V> {
+    capacity: usize,
+    entries: RefCell<VecDeque<(u64, Rc<V>)>>,
+}
+
+impl<V> BoundedCache<V> {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity: capacity.max(1),
+            entries: RefCell::new(VecDeque::new()),
+        }
+    }
+
+    /**
+     * Returns the value for a key, building and caching it when missing.
+     * Build errors are returned without caching anything.
+     *
+     * @param key The hashed key
+     * @param build Builds the value on a miss
+     * @return The shared value
+     */
+    pub fn get_or_try_insert<E>(&self, key: u64, build: impl FnOnce() -> Result<V, E>) -> Result<Rc<V>, E> {
+        let mut entries = self.entries.borrow_mut();
+        if let Some(position) = entries.iter().position(|(entry_key, _)| *entry_key == key) {
+            // Move the hit to the back so it is evicted last
+            let entry = entries.remove(position).expect("position is in range");
+            let value = entry.1.clone();
+            entries.push_back(entry);
+            return Ok(value);
+        }
+
+        let value = Rc::new(build()?);
+        if entries.len() == self.capacity {
+            entries.pop_front();
+        }
+        entries.push_back((key, value.clone()));
+        Ok(value)
+    }
+}
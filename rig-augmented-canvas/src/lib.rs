@@ -1,10 +1,13 @@
+mod cache;
 mod obsidian;
 mod models;
 mod utils;
 
+use cache::{BoundedCache, AGENT_CACHE_SIZE};
 use js_sys::JsString;
 use serde::{Deserialize, Serialize};
 use serde_json;
+use std::rc::Rc;
 use wasm_bindgen::prelude::*;
 
 // Re-export models for use in JavaScript
@@ -23,12 +26,166 @@ pub struct WasmFlashcard {
 #[derive(Serialize)]
 pub struct PromptResponse {
     response: String,
+    truncated: bool,
 }
 
 #[wasm_bindgen]
 pub struct RigCommand {
     id: JsString,
     name: JsString,
+    action: CommandAction,
+    app: obsidian::App,
+    api_key: Option<String>,
+}
+
+/**
+ * What a registered command does with the active note.
+ */
+#[derive(Deserialize, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+enum CommandAction {
+    Prompt,
+    Summarize,
+    Flashcards,
+    Questions,
+}
+
+/**
+ * A command to register, as passed to `onload`.
+ */
+#[derive(Deserialize)]
+struct CommandConfig {
+    id: String,
+    name: String,
+    action: CommandAction,
+}
+
+/**
+ * Configuration passed to `onload`. Without `commands`, the default set
+ * (prompt, summarize, flashcards, questions) is registered.
+ */
+#[derive(Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+struct OnloadConfig {
+    #[serde(default)]
+    commands: Option<Vec<CommandConfig>>,
+    #[serde(default)]
+    api_key: Option<String>,
+}
+
+fn default_commands() -> Vec<CommandConfig> {
+    [
+        ("rig-prompt", "Send active note to AI", CommandAction::Prompt),
+        ("rig-summarize", "Summarize active note", CommandAction::Summarize),
+        ("rig-flashcards", "Generate flashcards from active note", CommandAction::Flashcards),
+        ("rig-questions", "Generate questions from active note", CommandAction::Questions),
+    ]
+    .into_iter()
+    .map(|(id, name, action)| CommandConfig {
+        id: id.to_string(),
+        name: name.to_string(),
+        action,
+    })
+    .collect()
+}
+
+/**
+ * Builds a JavaScript error whose `name` identifies the kind of failure
+ * (`MissingApiKey`, `Timeout`, `ProviderError` or `ParseError`, as in the
+ * backend's `RigError`), so callers can branch on `error.name` instead of
+ * matching on the message.
+ */
+fn rig_error(name: &str, message: &str) -> js_sys::Error {
+    let error = js_sys::Error::new(message);
+    error.set_name(name);
+    error
+}
+
+/**
+ * Extracts a readable message from a JavaScript error value.
+ */
+fn js_error_message(value: JsValue) -> String {
+    value
+        .dyn_ref::<js_sys::Error>()
+        .map(|error| String::from(error.message()))
+        .or_else(|| value.as_string())
+        .unwrap_or_else(|| format!("{:?}", value))
+}
+
+/**
+ * Cuts a prompt response down to `max_response_chars` at a word boundary.
+ * Structured responses (flashcards, questions) are never cut, since that
+ * would leave their JSON unparsable.
+ */
+fn limit_response(config: &ModelConfig, response: String) -> PromptResponse {
+    match config
+        .max_response_chars
+        .and_then(|max_chars| utils::truncate_at_word(&response, max_chars))
+    {
+        Some(response) => PromptResponse { response, truncated: true },
+        None => PromptResponse { response, truncated: false },
+    }
+}
+
+/**
+ * Runs a command's action on the active note.
+ *
+ * @param action What to do with the note
+ * @param app The Obsidian app, used to find and read the active note
+ * @param api_key Optional API key to use for the request
+ * @return The text to show the user
+ */
+async fn run_command(action: CommandAction, app: &obsidian::App, api_key: Option<String>) -> Result<String, String> {
+    let file = app.workspace().getActiveFile();
+    if file.is_null() || file.is_undefined() {
+        return Err("No active note".to_string());
+    }
+    
+    let content = wasm_bindgen_futures::JsFuture::from(app.vault().read(&file))
+        .await
+        .map_err(js_error_message)?
+        .as_string()
+        .ok_or_else(|| "Could not read the active note".to_string())?;
+    let title = js_sys::Reflect::get(&file, &JsValue::from_str("basename"))
+        .ok()
+        .and_then(|value| value.as_string());
+    
+    let service = WasmRigService::new().map_err(|e| js_error_message(e.into()))?;
+    match action {
+        CommandAction::Prompt => service
+            .generate_response(content, None, api_key)
+            .await
+            .map_err(|e| js_error_message(e.into())),
+        CommandAction::Summarize => service
+            .generate_summary(content, None, api_key)
+            .await
+            .map_err(js_error_message),
+        CommandAction::Questions => {
+            let questions = service
+                .generate_questions(content, 5, api_key)
+                .await
+                .map_err(js_error_message)?;
+            Ok(questions
+                .iter()
+                .enumerate()
+                .map(|(i, question)| format!("{}. {}", i + 1, question))
+                .collect::<Vec<_>>()
+                .join("\n"))
+        }
+        CommandAction::Flashcards => {
+            let value = service
+                .generate_flashcards(&content, title, api_key)
+                .await
+                .map_err(js_error_message)?;
+            let output: FlashcardsResult = serde_wasm_bindgen::from_value(value).map_err(|e| e.to_string())?;
+            Ok(output
+                .flashcards
+                .iter()
+                .map(|card| format!("Q: {}\nA: {}", card.front, card.back))
+                .collect::<Vec<_>>()
+                .join("\n\n"))
+        }
+    }
 }
 
 #[wasm_bindgen]
@@ -54,7 +211,16 @@ impl RigCommand {
     }
 
     pub fn callback(&self) {
-        obsidian::Notice::new("Hello from Rig Augmented Canvas WASM!");
+        let action = self.action;
+        let app = self.app.clone();
+        let api_key = self.api_key.clone();
+        
+        wasm_bindgen_futures::spawn_local(async move {
+            match run_command(action, &app, api_key).await {
+                Ok(message) => obsidian::Notice::new(&message),
+                Err(e) => obsidian::Notice::new(&format!("Rig: {}", e)),
+            };
+        });
     }
 }
 
@@ -62,6 +228,7 @@ impl RigCommand {
 pub struct WasmRigService {
     agent: Option<AgentWrapper>,
     config: ModelConfig,
+    agents: BoundedCache<AgentWrapper>, // Agents built for per-request keys or system prompts
 }
 
 #[wasm_bindgen]
@@ -92,7 +259,11 @@ impl WasmRigService {
             None
         };
 
-        Ok(Self { agent, config })
+        Ok(Self {
+            agent,
+            config,
+            agents: BoundedCache::new(AGENT_CACHE_SIZE),
+        })
     }
 
     // Get model configuration
@@ -104,6 +275,7 @@ impl WasmRigService {
     pub fn update_model_config(&mut self, config_json: String) -> Result<(), JsValue> {
         let config: ModelConfig = serde_json::from_str(config_json.clone().as_ref())
             .map_err(|e| JsValue::from_str(&e.to_string()))?;
+        config.validate().map_err(|e| JsValue::from_str(&e))?;
 
         self.config = config;
         Ok(())
@@ -127,7 +299,7 @@ impl WasmRigService {
                     .generate_response(content, system_prompt, api_key)
                     .await
                 {
-                    Ok(response) => Ok(PromptResponse { response }),
+                    Ok(response) => Ok(limit_response(&self.config, response)),
                     Err(e) => {
                         return Err(e);
                     }
@@ -141,9 +313,30 @@ impl WasmRigService {
                 // Combine all node contents with the prompt
                 let mut combined_content = String::new();
 
-                // Add each node's content
-                for (i, node) in nodes.iter().enumerate() {
-                    combined_content.push_str(&format!("Node {}: {}\n\n", i + 1, node.content));
+                // Heavier nodes come first; equal weights keep their order
+                let weight = |node: &NodeContent| node.weight.unwrap_or(1.0);
+                let mut ordered: Vec<&NodeContent> = nodes.iter().collect();
+                ordered.sort_by(|a, b| weight(b).total_cmp(&weight(a)));
+                let heaviest = ordered.first().map(|node| weight(node));
+                let weighted = ordered.iter().any(|node| Some(weight(node)) != heaviest);
+
+                // Add each node's content, labelled with its emphasis and instruction
+                for (i, node) in ordered.into_iter().enumerate() {
+                    let emphasis = weighted.then(|| {
+                        if Some(weight(node)) == heaviest { "primary" } else { "secondary" }.to_string()
+                    });
+                    let instruction = node
+                        .instruction
+                        .as_deref()
+                        .map(str::trim)
+                        .filter(|instruction| !instruction.is_empty())
+                        .map(|instruction| format!("instruction: {}", instruction));
+                    let labels: Vec<String> = emphasis.into_iter().chain(instruction).collect();
+                    if labels.is_empty() {
+                        combined_content.push_str(&format!("Node {}: {}\n\n", i + 1, node.content));
+                    } else {
+                        combined_content.push_str(&format!("Node {} ({}): {}\n\n", i + 1, labels.join("; "), node.content));
+                    }
                 }
 
                 // Add the user's prompt
@@ -154,7 +347,7 @@ impl WasmRigService {
                     .generate_response(combined_content, system_prompt, api_key)
                     .await
                 {
-                    Ok(response) => Ok(PromptResponse { response }),
+                    Ok(response) => Ok(limit_response(&self.config, response)),
                     Err(e) => {
                         return Err(e);
                     }
@@ -177,66 +370,47 @@ impl WasmRigService {
         system_prompt: Option<String>,
         api_key: Option<String>,
     ) -> Result<String, js_sys::Error> {
-        // Get the response based on whether we have a system prompt and/or API key
+        // Use the default agent unless this request needs its own key or system prompt
+        let api_key = api_key.filter(|key| !key.is_empty());
         let response = match (system_prompt, api_key) {
-            // Both system prompt and API key provided
-            (Some(system_prompt), Some(api_key)) => {
-                let config = self.get_config();
-                let temp_agent =
-                    create_agent_with_system_prompt(&config, &system_prompt, Some(&api_key))
-                        .map_err(|e| js_sys::Error::new(&e.to_string()))?;
-                temp_agent
-                    .prompt(&content)
-                    .await
-                    .map_err(|e| js_sys::Error::new(&e.to_string()))?
-            }
-            // Only system prompt provided
-            (Some(system_prompt), None) => {
-                let config = self.get_config();
-                let temp_agent = create_agent_with_system_prompt(&config, &system_prompt, None)
-                    .map_err(|x| js_sys::Error::new(&x.to_string()))?;
-                temp_agent
-                    .prompt(&content)
-                    .await
-                    .map_err(|x| js_sys::Error::new(&x.to_string()))?
-            }
-            // Only API key provided
-            (None, Some(api_key)) => {
-                if !api_key.is_empty() {
-                    let config = self.get_config();
-                    let temp_agent = create_agent(&config, Some(&api_key))
-                        .map_err(|x| js_sys::Error::new(&x.to_string()))?;
-                    temp_agent
-                        .prompt(&content)
-                        .await
-                        .map_err(|x| js_sys::Error::new(&x.to_string()))?
-                } else {
-                    // Empty API key, check if we have an existing agent
-                    if let Some(agent) = &self.agent {
-                        agent
-                            .prompt(&content)
-                            .await
-                            .map_err(|e| js_sys::Error::new(&e.to_string()))?
-                    } else {
-                        return Err(js_sys::Error::new("No API key provided and no default agent available. Please provide an OpenAI API key in the plugin settings."));
-                    }
-                }
-            }
-            // Neither system prompt nor API key provided
             (None, None) => {
                 // Check if we have an existing agent
                 if let Some(agent) = &self.agent {
-                    agent
-                        .prompt(&content)
-                        .await
-                        .map_err(|e| js_sys::Error::new(&e.to_string()))?
+                    self.prompt_with_timeout(agent, &content).await?
                 } else {
-                    return Err(js_sys::Error::new("No API key provided and no default agent available. Please provide an OpenAI API key in the plugin settings."));
+                    return Err(rig_error("MissingApiKey", "No API key provided and no default agent available. Please provide an OpenAI API key in the plugin settings."));
                 }
             }
+            (system_prompt, api_key) => {
+                let agent = self.agent_for(system_prompt.as_deref(), api_key.as_deref())?;
+                self.prompt_with_timeout(&agent, &content).await?
+            }
+        };
+
+        if self.config.sanitize_responses {
+            Ok(utils::sanitize_response(&response))
+        } else {
+            Ok(response)
+        }
+    }
+
+    pub async fn generate_summary(
+        &self,
+        content: String,
+        max_words: Option<usize>,
+        api_key: Option<String>,
+    ) -> Result<String, JsValue> {
+        let length = match max_words.filter(|max_words| *max_words > 0) {
+            Some(max_words) => format!(" Keep the summary under {} words.", max_words),
+            None => String::new(),
         };
+        let prompt = format!(
+            "Summarize the following content concisely, keeping its key points and conclusions.{} Return only the summary.\n\nContent: {}\n\nSummary:",
+            length, content
+        );
 
-        Ok(response)
+        let summary = self.generate_response(prompt, None, api_key).await.map_err(JsValue::from)?;
+        Ok(summary.trim().to_string())
     }
 
     pub async fn generate_questions(
@@ -258,7 +432,7 @@ impl WasmRigService {
 
         // Parse the JSON response
         let output: QuestionsOutput = serde_json::from_str(&response_str)
-            .map_err(|e| JsValue::from_str(&format!("Failed to parse questions response: {}", e)))?;
+            .map_err(|e| rig_error("ParseError", &format!("Failed to parse questions response: {}", e)))?;
 
         Ok(output.questions)
     }
@@ -285,20 +459,152 @@ impl WasmRigService {
         // Parse the JSON response
         let output: FlashcardsResult = match serde_json::from_str(&response_str) {
             Ok(output) => output,
-            Err(e) => return Err(JsValue::from_str(&format!("Failed to parse flashcards response: {}", e))),
+            Err(e) => return Err(rig_error("ParseError", &format!("Failed to parse flashcards response: {}", e)).into()),
         };
         
         Ok(serde_wasm_bindgen::to_value(&output).unwrap())
     }
+
+    pub async fn generate_from_keywords(
+        &self,
+        keywords: Vec<String>,
+        style: Option<String>,
+        api_key: Option<String>,
+    ) -> Result<String, JsValue> {
+        let keywords: Vec<String> = keywords
+            .iter()
+            .map(|keyword| keyword.trim())
+            .filter(|keyword| !keyword.is_empty())
+            .map(|keyword| format!("- {}", keyword))
+            .collect();
+        if keywords.is_empty() {
+            return Err(JsValue::from_str("At least one keyword is required"));
+        }
+        
+        let style_instruction = style
+            .as_deref()
+            .map(str::trim)
+            .filter(|style| !style.is_empty())
+            .map(|style| format!(" Write it in the style of: {}.", style))
+            .unwrap_or_default();
+        let prompt = format!(
+            "Write a coherent note that connects the following keywords. Use every keyword, work out how they relate to one another, and organize the note with markdown headings or lists where they help. Don't invent specific facts, figures or quotes the keywords don't suggest.{}\n\nKeywords:\n{}\n\nNote:",
+            style_instruction,
+            keywords.join("\n")
+        );
+        
+        match self.generate_response(prompt, None, api_key).await {
+            Ok(draft) => Ok(draft.trim().to_string()),
+            Err(e) => Err(JsValue::from(e)),
+        }
+    }
+
+    pub async fn analyze_tone(
+        &self,
+        content: &str,
+        api_key: Option<String>,
+    ) -> Result<JsValue, JsValue> {
+        let prompt = format!(
+            "Analyze the emotional content of the following note. Give the overall sentiment as one of \"positive\", \"negative\", \"neutral\" or \"mixed\", up to five single-word tones (for example \"hopeful\", \"anxious\", \"reflective\"), and a sentiment score from -1.0 (very negative) to 1.0 (very positive). Return the response as JSON matching this schema: {{\"sentiment\": string, \"tone\": [string], \"score\": number}}.\n\nContent: {}\n\nAnalysis:",
+            content
+        );
+        
+        // Get the response as a String
+        let response_str = match self.generate_response(prompt, None, api_key).await {
+            Ok(response) => response,
+            Err(e) => return Err(JsValue::from(e)),
+        };
+        
+        // Parse the JSON response
+        let mut output: ToneOutput = match serde_json::from_str(&response_str) {
+            Ok(output) => output,
+            Err(e) => return Err(rig_error("ParseError", &format!("Failed to parse tone response: {}", e)).into()),
+        };
+        
+        output.sentiment = output.sentiment.trim().to_lowercase();
+        output.score = if output.score.is_finite() {
+            output.score.clamp(-1.0, 1.0)
+        } else {
+            0.0
+        };
+        
+        Ok(serde_wasm_bindgen::to_value(&output).unwrap())
+    }
+}
+
+impl WasmRigService {
+    /**
+     * Prompts an agent, giving up after `request_timeout_secs` so a hung
+     * provider connection doesn't leave the UI waiting forever.
+     *
+     * @param agent The agent to prompt
+     * @param content The text to send to the AI model
+     * @return The response, or an error naming the timeout
+     */
+    async fn prompt_with_timeout(&self, agent: &AgentWrapper, content: &str) -> Result<String, js_sys::Error> {
+        let secs = self.config.request_timeout_secs;
+        match utils::with_timeout(secs, agent.prompt(content)).await {
+            Some(response) => response.map_err(|e| rig_error("ProviderError", &e.to_string())),
+            None => Err(rig_error("Timeout", &format!("Request timed out after {} seconds", secs))),
+        }
+    }
+
+    /**
+     * Returns an agent for the given system prompt and key, reusing one built
+     * earlier for the same model, endpoint, sampling settings, system prompt
+     * and key. Keys and system prompts are only kept hashed.
+     *
+     * @param system_prompt Optional system prompt for the agent
+     * @param api_key Optional non-empty API key provided with the request
+     * @return The shared agent
+     */
+    fn agent_for(&self, system_prompt: Option<&str>, api_key: Option<&str>) -> Result<Rc<AgentWrapper>, js_sys::Error> {
+        let config = &self.config;
+        let temperature = config.temperature.map(|temperature| temperature.to_string()).unwrap_or_default();
+        let max_tokens = config.max_tokens.map(|max_tokens| max_tokens.to_string()).unwrap_or_default();
+        let key = cache::cache_key(&[
+            &config.model_name,
+            config.base_url.as_deref().unwrap_or_default(),
+            &temperature,
+            &max_tokens,
+            if system_prompt.is_some() { "preamble" } else { "no-preamble" },
+            system_prompt.unwrap_or_default(),
+            if api_key.is_some() { "key" } else { "env" },
+            api_key.unwrap_or_default(),
+        ]);
+        
+        self.agents
+            .get_or_try_insert(key, || match system_prompt {
+                Some(system_prompt) => create_agent_with_system_prompt(config, system_prompt, api_key),
+                None => create_agent(config, api_key),
+            })
+            .map_err(|e| js_sys::Error::new(&e.to_string()))
+    }
 }
 
 #[wasm_bindgen]
-pub fn onload(plugin: &obsidian::Plugin) {
-    let cmd = RigCommand {
-        id: JsString::from("rig-example"),
-        name: JsString::from("Rig Example Command"),
+pub fn onload(plugin: &obsidian::Plugin, config: JsValue) {
+    let config: OnloadConfig = if config.is_undefined() || config.is_null() {
+        OnloadConfig::default()
+    } else {
+        serde_wasm_bindgen::from_value(config).unwrap_or_else(|e| {
+            obsidian::Notice::new(&format!("Rig: invalid command configuration, using defaults: {}", e));
+            OnloadConfig::default()
+        })
     };
-    plugin.addCommand(JsValue::from(cmd))
+    
+    let api_key = config.api_key.filter(|key| !key.is_empty());
+    let app = plugin.app();
+    for command in config.commands.unwrap_or_else(default_commands) {
+        let cmd = RigCommand {
+            id: JsString::from(command.id.as_str()),
+            name: JsString::from(command.name.as_str()),
+            action: command.action,
+            app: app.clone(),
+            api_key: api_key.clone(),
+        };
+        plugin.addCommand(JsValue::from(cmd));
+    }
 }
 
 #[derive(Deserialize)]
@@ -322,6 +628,10 @@ struct NodeContent {
     #[allow(dead_code)]
     id: String,
     content: String,
+    #[serde(default)]
+    instruction: Option<String>, // Optional directive describing the node's role
+    #[serde(default)]
+    weight: Option<f32>, // Optional importance; heavier nodes are listed first
 }
 
 /**
@@ -337,3 +647,11 @@ struct FlashcardsResult {
     filename: String,
     flashcards: Vec<WasmFlashcard>,
 }
+
+#[derive(Serialize, Deserialize)]
+struct ToneOutput {
+    sentiment: String,
+    #[serde(default)]
+    tone: Vec<String>,
+    score: f32,
+}
@@ -2,7 +2,10 @@
  * Models module defines the AI model configuration and agent creation.
  * It provides a wrapper around the Rig library's Agent type.
  */
-use rig::{agent::Agent, providers::openai};
+use rig::{
+    agent::{Agent, AgentBuilder},
+    providers::openai,
+};
 use serde::{Deserialize, Serialize};
 use std::error::Error;
 use wasm_bindgen::prelude::*;
@@ -21,6 +24,24 @@ pub struct ModelConfig {
     pub api_key_env: Option<String>, // Environment variable name for the API key
     #[wasm_bindgen(getter_with_clone)]
     pub base_url: Option<String>, // Optional custom API endpoint
+    #[serde(default = "default_true")]
+    pub sanitize_responses: bool, // Strip stray control characters from model responses
+    #[serde(default)]
+    pub max_response_chars: Option<usize>, // Prompt responses longer than this are cut at a word boundary
+    #[serde(default)]
+    pub temperature: Option<f64>, // Sampling temperature (0.0-2.0); provider default when unset
+    #[serde(default)]
+    pub max_tokens: Option<u64>, // Maximum tokens to generate; provider default when unset
+    #[serde(default = "default_request_timeout_secs")]
+    pub request_timeout_secs: u64, // Prompts waiting longer than this for the model fail with a timeout
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_request_timeout_secs() -> u64 {
+    60
 }
 
 #[wasm_bindgen]
@@ -32,7 +53,37 @@ impl ModelConfig {
             model_name: "o3-mini".to_string(),
             api_key_env: Some("OPENAI_API_KEY".to_string()),
             base_url: None,
+            sanitize_responses: true,
+            max_response_chars: None,
+            temperature: None,
+            max_tokens: None,
+            request_timeout_secs: default_request_timeout_secs(),
+        }
+    }
+}
+
+/// Range of sampling temperatures accepted by OpenAI.
+const TEMPERATURE_RANGE: std::ops::RangeInclusive<f64> = 0.0..=2.0;
+
+impl ModelConfig {
+    /**
+     * Checks that the sampling settings are within the ranges the provider accepts.
+     *
+     * @return An error describing the first invalid field, if any
+     */
+    pub fn validate(&self) -> Result<(), String> {
+        if let Some(temperature) = self.temperature {
+            if !TEMPERATURE_RANGE.contains(&temperature) {
+                return Err(format!("temperature must be between 0 and 2, got {}", temperature));
+            }
+        }
+        if self.max_tokens == Some(0) {
+            return Err("max_tokens must be greater than 0".to_string());
         }
+        if self.request_timeout_secs == 0 {
+            return Err("request_timeout_secs must be greater than 0".to_string());
+        }
+        Ok(())
     }
 }
 
@@ -71,7 +122,41 @@ pub fn has_api_key(config: &ModelConfig, direct_api_key: Option<&str>) -> bool {
         }
     }
 
-    false
+    // Custom endpoints (e.g., a local Ollama server) can be used without a key
+    uses_custom_endpoint(config)
+}
+
+/// OpenAI's API endpoint, used when no `base_url` is configured.
+const DEFAULT_OPENAI_BASE_URL: &str = "https://api.openai.com/v1";
+
+/**
+ * Tells whether requests go to a custom OpenAI-compatible endpoint rather
+ * than OpenAI itself. Local servers often need no API key.
+ *
+ * @param config The model configuration
+ * @return true if a base URL other than OpenAI's is configured
+ */
+fn uses_custom_endpoint(config: &ModelConfig) -> bool {
+    config
+        .base_url
+        .as_deref()
+        .map(|url| url.trim().trim_end_matches('/'))
+        .is_some_and(|url| !url.is_empty() && url != DEFAULT_OPENAI_BASE_URL)
+}
+
+/**
+ * Builds an OpenAI client, pointed at `base_url` when one is configured
+ * (e.g., a local Ollama server at `http://localhost:11434/v1`).
+ *
+ * @param config The model configuration
+ * @param api_key The API key, possibly empty for local servers
+ * @return The client
+ */
+fn openai_client(config: &ModelConfig, api_key: &str) -> openai::Client {
+    match config.base_url.as_deref().filter(|url| !url.trim().is_empty()) {
+        Some(base_url) => openai::Client::from_url(api_key, base_url),
+        None => openai::Client::new(api_key),
+    }
 }
 
 /**
@@ -113,17 +198,17 @@ pub fn create_agent(
                     key.to_string()
                 } else {
                     // If direct key is empty, fall back to environment variable
-                    get_api_key_from_env(config)?
+                    get_api_key_or_keyless(config)?
                 }
             } else {
                 // No direct key, use environment variable
-                get_api_key_from_env(config)?
+                get_api_key_or_keyless(config)?
             };
 
-            let client = openai::Client::new(&api_key);
+            let client = openai_client(config, &api_key);
 
             // Create the agent
-            let agent = client.agent(&config.model_name).build();
+            let agent = with_sampling(client.agent(&config.model_name), config).build();
 
             Ok(AgentWrapper(agent))
         }
@@ -152,18 +237,17 @@ pub fn create_agent_with_system_prompt(
                     key.to_string()
                 } else {
                     // If direct key is empty, fall back to environment variable
-                    get_api_key_from_env(config)?
+                    get_api_key_or_keyless(config)?
                 }
             } else {
                 // No direct key, use environment variable
-                get_api_key_from_env(config)?
+                get_api_key_or_keyless(config)?
             };
 
-            let client = openai::Client::new(&api_key);
+            let client = openai_client(config, &api_key);
 
             // Create the agent with system prompt
-            let agent = client
-                .agent(&config.model_name)
+            let agent = with_sampling(client.agent(&config.model_name), config)
                 .preamble(system_prompt)
                 .build();
 
@@ -172,6 +256,42 @@ pub fn create_agent_with_system_prompt(
     }
 }
 
+/**
+ * Applies the configured temperature and token limit to an agent builder.
+ * The temperature is clamped to the valid range in case the config was
+ * changed without validation (e.g., through a field setter).
+ *
+ * @param builder The agent builder
+ * @param config The model configuration
+ * @return The builder with sampling settings applied
+ */
+fn with_sampling(
+    mut builder: AgentBuilder<openai::CompletionModel>,
+    config: &ModelConfig,
+) -> AgentBuilder<openai::CompletionModel> {
+    if let Some(temperature) = config.temperature {
+        builder = builder.temperature(temperature.clamp(*TEMPERATURE_RANGE.start(), *TEMPERATURE_RANGE.end()));
+    }
+    if let Some(max_tokens) = config.max_tokens {
+        builder = builder.max_tokens(max_tokens);
+    }
+    builder
+}
+
+/**
+ * Gets the API key from the environment, or an empty key when a custom
+ * endpoint is configured and no key is set.
+ *
+ * @param config The model configuration
+ * @return The API key as a string
+ */
+fn get_api_key_or_keyless(config: &ModelConfig) -> Result<String, Box<dyn Error>> {
+    match get_api_key_from_env(config) {
+        Err(_) if uses_custom_endpoint(config) => Ok(String::new()),
+        result => result,
+    }
+}
+
 /**
  * Helper function to get API key from environment variable.
  *
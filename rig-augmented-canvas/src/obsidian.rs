@@ -4,6 +4,9 @@ use wasm_bindgen::prelude::*;
 extern "C" {
     pub type Plugin;
 
+    #[wasm_bindgen(structural, method, getter)]
+    pub fn app(this: &Plugin) -> App;
+
     #[wasm_bindgen(structural, method)]
     pub fn addCommand(this: &Plugin, command: JsValue);
 
@@ -27,13 +30,25 @@ extern "C" {
     #[wasm_bindgen(constructor)]
     pub fn new(message: &str) -> Notice;
 
+    #[derive(Clone)]
     pub type App;
 
     pub type Workspace;
 
-    #[wasm_bindgen(structural, getter)]
+    #[wasm_bindgen(structural, method, getter)]
     pub fn workspace(this: &App) -> Workspace;
 
     #[wasm_bindgen(structural, method, catch)]
     pub fn getActiveViewOfType(this: &Workspace, view_type: &str) -> Result<JsValue, JsValue>;
+
+    #[wasm_bindgen(structural, method)]
+    pub fn getActiveFile(this: &Workspace) -> JsValue;
+
+    pub type Vault;
+
+    #[wasm_bindgen(structural, method, getter)]
+    pub fn vault(this: &App) -> Vault;
+
+    #[wasm_bindgen(structural, method)]
+    pub fn read(this: &Vault, file: &JsValue) -> js_sys::Promise;
 }
@@ -1,3 +1,8 @@
+use std::future::{poll_fn, Future};
+use std::pin::pin;
+use std::task::Poll;
+use wasm_bindgen::{JsCast, JsValue};
+
 pub fn set_panic_hook() {
     // When the `console_error_panic_hook` feature is enabled, we can call the
     // `set_panic_hook` function at least once during initialization, and then
@@ -8,3 +13,75 @@ pub fn set_panic_hook() {
     #[cfg(feature = "console_error_panic_hook")]
     console_error_panic_hook::set_once();
 }
+
+/**
+ * Removes disallowed control characters from a model response while keeping
+ * newlines and tabs. CRLF line endings become LF and byte-order marks are dropped.
+ */
+pub fn sanitize_response(response: &str) -> String {
+    response
+        .replace("\r\n", "\n")
+        .chars()
+        .filter(|&c| c == '\n' || c == '\t' || !(c.is_control() || c == '\u{feff}'))
+        .collect()
+}
+
+/**
+ * Shortens text longer than `max_chars` characters to the last whole word
+ * that fits, followed by an ellipsis, keeping the result within `max_chars`.
+ * Returns None if the text already fits.
+ */
+pub fn truncate_at_word(text: &str, max_chars: usize) -> Option<String> {
+    if text.chars().count() <= max_chars {
+        return None;
+    }
+
+    let budget = max_chars.saturating_sub(1);
+    let cut = text.char_indices().nth(budget).map(|(index, _)| index).unwrap_or(text.len());
+    let head = &text[..cut];
+    let next_is_space = text[cut..].starts_with(char::is_whitespace);
+    let head = match head.rfind(char::is_whitespace) {
+        Some(space) if !next_is_space => &head[..space],
+        _ => head,
+    };
+
+    Some(format!("{}…", head.trim_end()))
+}
+
+/**
+ * Resolves after `ms` milliseconds using the JavaScript `setTimeout`, which
+ * works in both the plugin and worker contexts where tokio isn't available.
+ */
+async fn sleep_ms(ms: i32) {
+    let promise = js_sys::Promise::new(&mut |resolve, _reject| {
+        let global = js_sys::global();
+        let set_timeout = js_sys::Reflect::get(&global, &JsValue::from_str("setTimeout"))
+            .ok()
+            .and_then(|set_timeout| set_timeout.dyn_into::<js_sys::Function>().ok());
+        if let Some(set_timeout) = set_timeout {
+            let _ = set_timeout.call2(&global, &resolve, &JsValue::from(ms));
+        }
+    });
+    let _ = wasm_bindgen_futures::JsFuture::from(promise).await;
+}
+
+/**
+ * Runs a future, giving up once `secs` seconds have passed.
+ * Returns None on timeout; the abandoned future is dropped.
+ */
+pub async fn with_timeout<F: Future>(secs: u64, future: F) -> Option<F::Output> {
+    // setTimeout delays are 32-bit signed; longer ones would fire immediately
+    let ms = i32::try_from(secs.saturating_mul(1000)).unwrap_or(i32::MAX);
+    let mut future = pin!(future);
+    let mut timer = pin!(sleep_ms(ms));
+    poll_fn(|cx| {
+        if let Poll::Ready(output) = future.as_mut().poll(cx) {
+            return Poll::Ready(Some(output));
+        }
+        if timer.as_mut().poll(cx).is_ready() {
+            return Poll::Ready(None);
+        }
+        Poll::Pending
+    })
+    .await
+}